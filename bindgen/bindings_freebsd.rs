@@ -65,6 +65,7 @@ pub const ACL_FLAGS_BITS: u32 = 191;
 pub const ACL_TEXT_VERBOSE: u32 = 1;
 pub const ACL_TEXT_NUMERIC_IDS: u32 = 2;
 pub const ACL_TEXT_APPEND_ID: u32 = 4;
+pub const _PC_ACL_EXTENDED: u32 = 59;
 pub const _PC_ACL_NFS4: u32 = 64;
 pub type __uint32_t = ::std::os::raw::c_uint;
 pub type __int64_t = ::std::os::raw::c_long;
@@ -17,6 +17,11 @@ pub const ACL_TYPE_ACCESS: u32 = 32768;
 pub const ACL_TYPE_DEFAULT: u32 = 16384;
 pub const ACL_FIRST_ENTRY: u32 = 0;
 pub const ACL_NEXT_ENTRY: u32 = 1;
+pub const TEXT_SOME_EFFECTIVE: u32 = 1;
+pub const TEXT_ALL_EFFECTIVE: u32 = 2;
+pub const TEXT_SMART_INDENT: u32 = 4;
+pub const TEXT_NUMERIC_IDS: u32 = 8;
+pub const TEXT_ABBREVIATE: u32 = 16;
 pub const ACL_MULTI_ERROR: u32 = 4096;
 pub const ACL_DUPLICATE_ERROR: u32 = 8192;
 pub const ACL_MISS_ERROR: u32 = 12288;
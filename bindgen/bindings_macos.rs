@@ -7,6 +7,7 @@ pub const ACL_MAX_ENTRIES: u32 = 128;
 pub const O_SYMLINK: u32 = 2097152;
 pub const ID_TYPE_UID: u32 = 0;
 pub const ID_TYPE_GID: u32 = 1;
+pub const _PC_EXTENDED_SECURITY_NP: u32 = 13;
 pub type __uint32_t = ::std::os::raw::c_uint;
 pub type __darwin_time_t = ::std::os::raw::c_long;
 pub type u_int64_t = ::std::os::raw::c_ulonglong;
@@ -308,6 +309,13 @@ extern "C" {
         id_type: *mut ::std::os::raw::c_int,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn mbr_check_membership(
+        user: *mut ::std::os::raw::c_uchar,
+        group: *mut ::std::os::raw::c_uchar,
+        ismember: *mut ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct group {
@@ -369,3 +377,9 @@ extern "C" {
 extern "C" {
     pub fn close(arg1: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn pathconf(
+        arg1: *const ::std::os::raw::c_char,
+        arg2: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_long;
+}
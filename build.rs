@@ -16,23 +16,56 @@ fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
     let out_path = Path::new(&out_dir).join("bindings.rs");
     let wrapper = "bindgen/wrapper.h";
+    let target = env::var("CARGO_CFG_TARGET_OS").unwrap();
+
+    // Permissions and flags beyond Posix.1e exist on macOS, FreeBSD and the
+    // portable core, unless the `strict_posix` feature leaves them out.
+    // `exacl_extended` covers the extended permissions and inheritance flags;
+    // `exacl_nfs4` covers the NFSv4 permissions.
+    println!("cargo:rustc-check-cfg=cfg(exacl_extended, exacl_nfs4)");
+    // On Linux, they are only used for NFSv4 mounts, with the `nfs4` feature.
+    let extended = target != "linux" || env::var_os("CARGO_FEATURE_NFS4").is_some();
+    if env::var_os("CARGO_FEATURE_STRICT_POSIX").is_none() && extended {
+        println!("cargo:rustc-cfg=exacl_extended");
+        if target != "macos" {
+            println!("cargo:rustc-cfg=exacl_nfs4");
+        }
+    }
+
+    // Older libacl builds lack `acl_get_perm`, a libacl extension. Set
+    // EXACL_NO_ACL_GET_PERM when building for such a system to read entry
+    // permissions from the ACL text instead.
+    println!("cargo:rustc-check-cfg=cfg(exacl_no_acl_get_perm)");
+    println!("cargo:rerun-if-env-changed=EXACL_NO_ACL_GET_PERM");
+    if target == "linux" && env::var_os("EXACL_NO_ACL_GET_PERM").is_some() {
+        println!("cargo:rustc-cfg=exacl_no_acl_get_perm");
+    }
+
+    // On targets without a native ACL API, only build the portable core types.
+    println!("cargo:rustc-check-cfg=cfg(exacl_core)");
+    if !matches!(target.as_str(), "macos" | "linux" | "freebsd") {
+        println!("cargo:rustc-cfg=exacl_core");
+        return;
+    }
 
-    // Tell cargo to tell rustc to link libacl.so, only on Linux.
-    #[cfg(target_os = "linux")]
-    println!("cargo:rustc-link-lib=acl");
+    // Tell cargo to tell rustc to link libacl.so, only on Linux. Check the
+    // target rather than the host, so cross-compiling works.
+    if target == "linux" {
+        println!("cargo:rustc-link-lib=acl");
+    }
 
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed={wrapper}");
 
     #[cfg(feature = "buildtime_bindgen")]
-    bindgen_bindings(wrapper, &out_path);
+    bindgen_bindings(&target, wrapper, &out_path);
 
     #[cfg(not(feature = "buildtime_bindgen"))]
-    prebuilt_bindings(&out_path);
+    prebuilt_bindings(&target, &out_path);
 }
 
 #[cfg(feature = "buildtime_bindgen")]
-fn bindgen_bindings(wrapper: &str, out_path: &Path) {
+fn bindgen_bindings(target: &str, wrapper: &str, out_path: &Path) {
     // Build bindings for "wrapper.h". Tell cargo to invalidate the built
     // crate when any included header file changes.
     let mut builder = bindgen::Builder::default()
@@ -41,30 +74,33 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
         .disable_header_comment()
         .layout_tests(false); // no layout tests for passwd/group structs.
 
-    if cfg!(target_os = "macos") {
-        // Pass output of `xcrun --sdk macosx --show-sdk-path`.
-        builder = builder.clang_arg("-isysroot/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk");
+    // When cross-compiling, tell clang which target to parse headers for.
+    let target_triple = env::var("TARGET").unwrap();
+    if target_triple != env::var("HOST").unwrap() {
+        builder = builder.clang_arg(format!("--target={target_triple}"));
+    }
+
+    if target == "macos" {
+        // Use $SDKROOT if set (e.g. by osxcross), otherwise the output of
+        // `xcrun --sdk macosx --show-sdk-path`.
+        println!("cargo:rerun-if-env-changed=SDKROOT");
+        let sdk = env::var("SDKROOT")
+            .unwrap_or_else(|_| "/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk".to_string());
+        builder = builder.clang_arg(format!("-isysroot{sdk}"));
     }
 
     // Specify the types, functions, and constants we want to include.
     let types = ["acl_.*", "uid_t", "gid_t"];
-    let funcs = [
+    let mut funcs = vec![
         "acl_.*",
         "getpw(nam|uid)_r",
         "getgr(nam|gid)_r",
         "mbr_uid_to_uuid",
         "mbr_gid_to_uuid",
         "mbr_uuid_to_id",
-        #[cfg(target_os = "macos")]
-        "open",
-        #[cfg(target_os = "macos")]
-        "close",
-        #[cfg(target_os = "freebsd")]
-        "pathconf",
-        #[cfg(target_os = "freebsd")]
-        "lpathconf",
+        "mbr_check_membership",
     ];
-    let vars = [
+    let mut vars = vec![
         "ACL_.*",
         ".*_ACL_NFS4",
         "ENOENT",
@@ -72,12 +108,23 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
         "EINVAL",
         "ENOMEM",
         "ERANGE",
-        #[cfg(target_os = "macos")]
-        "O_SYMLINK",
         "ID_TYPE_UID",
         "ID_TYPE_GID",
     ];
 
+    match target {
+        "macos" => {
+            funcs.extend(["open", "close", "pathconf"]);
+            vars.extend(["O_SYMLINK", "_PC_EXTENDED_SECURITY_NP"]);
+        }
+        "freebsd" => {
+            funcs.extend(["pathconf", "lpathconf"]);
+            vars.push("_PC_ACL_EXTENDED");
+        }
+        "linux" => vars.push("TEXT_.*"),
+        _ => (),
+    }
+
     for type_ in &types {
         builder = builder.allowlist_type(type_);
     }
@@ -100,15 +147,7 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
 }
 
 #[cfg(not(feature = "buildtime_bindgen"))]
-fn prebuilt_bindings(out_path: &Path) {
-    let target = env::var("CARGO_CFG_TARGET_OS").unwrap();
-
-    // Untrusted input check.
-    match target.as_str() {
-        "macos" | "linux" | "freebsd" => (),
-        s => panic!("Unsupported target OS: {}", s),
-    };
-
+fn prebuilt_bindings(target: &str, out_path: &Path) {
     let bindings_path = format!("bindgen/bindings_{target}.rs");
     if let Err(err) = std::fs::copy(&bindings_path, out_path) {
         panic!("Can't copy {:?} to {:?}: {}", bindings_path, out_path, err);
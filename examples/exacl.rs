@@ -11,146 +11,161 @@
 //!
 //! To get/set the default ACL (on Linux), use the -d option.
 
-use exacl::{getfacl, setfacl, AclEntry, AclOption};
-use std::io;
-use std::path::{Path, PathBuf};
-use std::process;
-
-use clap::Parser;
-
-#[derive(clap::Parser)]
-#[command(name = "exacl", about = "Read or write a file's ACL.")]
-#[allow(clippy::struct_excessive_bools)]
-struct Opt {
-    /// Set file's ACL.
-    #[arg(long)]
-    set: bool,
-
-    /// Get or set the access ACL.
-    #[arg(short = 'a', long)]
-    access: bool,
-
-    /// Get or set the default ACL.
-    #[arg(short = 'd', long)]
-    default: bool,
-
-    /// Get or set the ACL of a symlink itself.
-    #[arg(short = 's', long)]
-    symlink: bool,
-
-    /// Format of input or output.
-    #[arg(value_enum, short = 'f', long, default_value = "json")]
-    format: Format,
-
-    /// Input files
-    #[arg()]
-    files: Vec<PathBuf>,
-}
-
-#[derive(Copy, Clone, Debug, clap::ValueEnum)]
-#[value(rename_all = "lower")]
-enum Format {
-    Json,
-    Std,
+#[cfg(not(exacl_core))]
+fn main() {
+    cli::main();
 }
 
-const EXIT_SUCCESS: i32 = 0;
-const EXIT_FAILURE: i32 = 1;
-
+/// ACL's can't be read or written on targets without a native ACL API.
+#[cfg(exacl_core)]
 fn main() {
-    env_logger::init();
-
-    let opt = Opt::parse();
+    eprintln!("exacl: ACL's are not supported on this platform");
+    std::process::exit(1);
+}
 
-    let mut options = AclOption::empty();
-    if opt.access {
-        options |= AclOption::ACCESS_ACL;
-    }
-    if opt.default {
-        options |= AclOption::DEFAULT_ACL;
+#[cfg(not(exacl_core))]
+mod cli {
+    use exacl::{getfacl, setfacl, AclEntry, AclOption};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    use clap::Parser;
+
+    #[derive(clap::Parser)]
+    #[command(name = "exacl", about = "Read or write a file's ACL.")]
+    #[allow(clippy::struct_excessive_bools)]
+    struct Opt {
+        /// Set file's ACL.
+        #[arg(long)]
+        set: bool,
+
+        /// Get or set the access ACL.
+        #[arg(short = 'a', long)]
+        access: bool,
+
+        /// Get or set the default ACL.
+        #[arg(short = 'd', long)]
+        default: bool,
+
+        /// Get or set the ACL of a symlink itself.
+        #[arg(short = 's', long)]
+        symlink: bool,
+
+        /// Format of input or output.
+        #[arg(value_enum, short = 'f', long, default_value = "json")]
+        format: Format,
+
+        /// Input files
+        #[arg()]
+        files: Vec<PathBuf>,
     }
-    if opt.symlink {
-        options |= AclOption::SYMLINK_ACL;
+
+    #[derive(Copy, Clone, Debug, clap::ValueEnum)]
+    #[value(rename_all = "lower")]
+    enum Format {
+        Json,
+        Std,
     }
 
-    let exit_code = if opt.set {
-        set_acl(&opt.files, options, opt.format)
-    } else {
-        get_acl(&opt.files, options, opt.format)
-    };
+    const EXIT_SUCCESS: i32 = 0;
+    const EXIT_FAILURE: i32 = 1;
 
-    process::exit(exit_code);
-}
+    pub fn main() {
+        env_logger::init();
 
-fn get_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
-    for path in paths {
-        if let Err(err) = dump_acl(path, options, format) {
-            eprintln!("{err}");
-            return EXIT_FAILURE;
-        }
-    }
+        let opt = Opt::parse();
 
-    EXIT_SUCCESS
-}
+        let mut options = AclOption::empty();
+        if opt.access {
+            options |= AclOption::ACCESS_ACL;
+        }
+        if opt.default {
+            options |= AclOption::DEFAULT_ACL;
+        }
+        if opt.symlink {
+            options |= AclOption::SYMLINK_ACL;
+        }
 
-fn set_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
-    let Some(entries) = read_input(format) else {
-        return EXIT_FAILURE;
-    };
+        let exit_code = if opt.set {
+            set_acl(&opt.files, options, opt.format)
+        } else {
+            get_acl(&opt.files, options, opt.format)
+        };
 
-    if let Err(err) = setfacl(paths, &entries, options) {
-        eprintln!("{err}");
-        return EXIT_FAILURE;
+        process::exit(exit_code);
     }
 
-    EXIT_SUCCESS
-}
+    fn get_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
+        for path in paths {
+            if let Err(err) = dump_acl(path, options, format) {
+                eprintln!("{err}");
+                return EXIT_FAILURE;
+            }
+        }
 
-fn dump_acl(path: &Path, options: AclOption, format: Format) -> io::Result<()> {
-    let entries = getfacl(path, options)?;
+        EXIT_SUCCESS
+    }
 
-    match format {
-        #[cfg(feature = "serde")]
-        Format::Json => {
-            serde_json::to_writer(io::stdout(), &entries)?;
-            println!(); // add newline
-        }
-        #[cfg(not(feature = "serde"))]
-        Format::Json => {
-            panic!("serde not supported");
+    fn set_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
+        let Some(entries) = read_input(format) else {
+            return EXIT_FAILURE;
+        };
+
+        if let Err(err) = setfacl(paths, &entries, options) {
+            eprintln!("{err}");
+            return EXIT_FAILURE;
         }
-        Format::Std => exacl::to_writer(io::stdout(), &entries)?,
-    };
 
-    Ok(())
-}
+        EXIT_SUCCESS
+    }
 
-fn read_input(format: Format) -> Option<Vec<AclEntry>> {
-    let reader = io::BufReader::new(io::stdin());
-
-    let entries: Vec<AclEntry> = match format {
-        // Read JSON format.
-        #[cfg(feature = "serde")]
-        Format::Json => match serde_json::from_reader(reader) {
-            Ok(entries) => entries,
-            Err(err) => {
-                eprintln!("JSON parser error: {err}");
-                return None;
+    fn dump_acl(path: &Path, options: AclOption, format: Format) -> io::Result<()> {
+        let entries = getfacl(path, options)?;
+
+        match format {
+            #[cfg(feature = "serde")]
+            Format::Json => {
+                serde_json::to_writer(io::stdout(), &entries)?;
+                println!(); // add newline
             }
-        },
-        #[cfg(not(feature = "serde"))]
-        Format::Json => {
-            panic!("serde not supported");
-        }
-        // Read Std format.
-        Format::Std => match exacl::from_reader(reader) {
-            Ok(entries) => entries,
-            Err(err) => {
-                eprintln!("Std parser error: {err}");
-                return None;
+            #[cfg(not(feature = "serde"))]
+            Format::Json => {
+                panic!("serde not supported");
             }
-        },
-    };
+            Format::Std => exacl::to_writer(io::stdout(), &entries)?,
+        };
 
-    Some(entries)
+        Ok(())
+    }
+
+    fn read_input(format: Format) -> Option<Vec<AclEntry>> {
+        let reader = io::BufReader::new(io::stdin());
+
+        let entries: Vec<AclEntry> = match format {
+            // Read JSON format.
+            #[cfg(feature = "serde")]
+            Format::Json => match serde_json::from_reader(reader) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    eprintln!("JSON parser error: {err}");
+                    return None;
+                }
+            },
+            #[cfg(not(feature = "serde"))]
+            Format::Json => {
+                panic!("serde not supported");
+            }
+            // Read Std format.
+            Format::Std => match exacl::from_reader(reader) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    eprintln!("Std parser error: {err}");
+                    return None;
+                }
+            },
+        };
+
+        Some(entries)
+    }
 }
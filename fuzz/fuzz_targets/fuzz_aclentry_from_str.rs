@@ -0,0 +1,18 @@
+//! Fuzz target for `AclEntry::from_str`.
+//!
+//! Any entry that parses successfully must format to text that parses back
+//! to the same entry.
+
+#![no_main]
+
+use exacl::AclEntry;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    if let Ok(entry) = data.parse::<AclEntry>() {
+        assert_eq!(
+            entry.to_string().parse::<AclEntry>().expect("reparse"),
+            entry
+        );
+    }
+});
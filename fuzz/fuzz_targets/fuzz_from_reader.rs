@@ -0,0 +1,13 @@
+//! Fuzz target for `exacl::from_reader`.
+//!
+//! The input is arbitrary bytes, including invalid UTF-8 and embedded NUL's.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entries) = exacl::from_reader(data) {
+        let _ = exacl::to_string(&entries);
+    }
+});
@@ -0,0 +1,28 @@
+//! Fuzz target for `exacl::from_xattr_bytes` (Linux only).
+//!
+//! The input is arbitrary bytes. Decoding must not panic. Encoding sorts the
+//! entries and adds a mask, so a successful re-encoding is canonical: it must
+//! decode to entries that encode to the same bytes. An input that is already
+//! canonical must round-trip exactly.
+
+#![no_main]
+
+use exacl::XattrKind;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(entries) = exacl::from_xattr_bytes(data) else {
+        return;
+    };
+    let Ok(bytes) = exacl::to_xattr_bytes(&entries, XattrKind::Access) else {
+        return;
+    };
+
+    let decoded = exacl::from_xattr_bytes(&bytes).expect("canonical value decodes");
+    let encoded =
+        exacl::to_xattr_bytes(&decoded, XattrKind::Access).expect("decoded entries encode");
+    assert_eq!(encoded, bytes);
+    if bytes == data {
+        assert_eq!(decoded, entries);
+    }
+});
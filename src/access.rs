@@ -0,0 +1,564 @@
+//! Simulates access checks against an ACL, and implements `rights_matrix`,
+//! which shows the effective permissions of several principals on one path,
+//! and `check_access`, which explains the result for one principal.
+
+use crate::acl::{Acl, AclOption};
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::unix::{self, gid_t, uid_t};
+
+use std::fmt;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// A user or group whose effective permissions are shown by
+/// [`rights_matrix`].
+///
+/// A principal made by [`Principal::user`] is a member of the user's primary
+/// group. On Linux and `FreeBSD`, supplementary groups are not looked up; add
+/// them with [`Principal::with_groups`]. On macOS, a user's groups, including
+/// nested groups, are resolved with the membership service, as the kernel
+/// does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    label: String,
+    owner: bool,
+    uid: Option<uid_t>,
+    gids: Vec<gid_t>,
+}
+
+impl Principal {
+    /// The owner of the file, whoever that is.
+    #[must_use]
+    pub fn owner() -> Principal {
+        Principal {
+            label: "owner".to_string(),
+            owner: true,
+            uid: None,
+            gids: Vec::new(),
+        }
+    }
+
+    /// A user who is not the owner, is in no group, and has no named entry.
+    #[must_use]
+    pub fn everyone() -> Principal {
+        Principal {
+            label: "everyone".to_string(),
+            owner: false,
+            uid: None,
+            gids: Vec::new(),
+        }
+    }
+
+    /// A user, and a member of the user's primary group. The name may be a
+    /// decimal uid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the name can't be resolved.
+    pub fn user(name: &str) -> io::Result<Principal> {
+        let (uid, gids) = match unix::lookup_user(name)? {
+            Some((uid, gid)) => (uid, vec![gid]),
+            None => (unix::name_to_uid(name)?, Vec::new()),
+        };
+
+        Ok(Principal {
+            label: format!("user:{name}"),
+            owner: false,
+            uid: Some(uid),
+            gids,
+        })
+    }
+
+    /// A member of a group, who is not the owner and has no named user
+    /// entry. The name may be a decimal gid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the name can't be resolved.
+    pub fn group(name: &str) -> io::Result<Principal> {
+        Ok(Principal {
+            label: format!("group:{name}"),
+            owner: false,
+            uid: None,
+            gids: vec![unix::name_to_gid(name)?],
+        })
+    }
+
+    /// Add memberships in more groups.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a group name can't be resolved.
+    pub fn with_groups<S: AsRef<str>>(mut self, names: &[S]) -> io::Result<Principal> {
+        for name in names {
+            self.gids.push(unix::name_to_gid(name.as_ref())?);
+        }
+        Ok(self)
+    }
+}
+
+impl fmt::Display for Principal {
+    /// Write the principal as `owner`, `everyone`, `user:<name>` or
+    /// `group:<name>`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+/// Return the effective permissions of each principal on a file or
+/// directory, in the order given.
+///
+/// The permissions are computed from the access ACL the way the kernel checks
+/// access. On Linux and `FreeBSD` with Posix.1e ACL's, the owner gets the
+/// `user::` entry; a named user gets its entry, limited by the mask; a member
+/// of the owning group or of named groups gets the union of their entries,
+/// limited by the mask; and everyone else gets the `other::` entry. With
+/// `NFSv4` and macOS ACL's, entries are checked in order, and the first entry
+/// that allows or denies a permission decides it; `ONLY_INHERIT` entries are
+/// skipped. On macOS, read, write and execute permissions that no entry
+/// decides fall back to the file mode. Privileges such as root's are not
+/// simulated.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{rights_matrix, Principal};
+///
+/// let principals = [
+///     Principal::owner(),
+///     Principal::user("bob")?,
+///     Principal::group("auditors")?,
+///     Principal::everyone(),
+/// ];
+/// for (principal, perms) in rights_matrix("./tmp/report.txt", &principals)? {
+///     println!("{principal}: {perms}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the path's metadata or ACL can't be read.
+pub fn rights_matrix<P: AsRef<Path>>(
+    path: P,
+    principals: &[Principal],
+) -> io::Result<Vec<(Principal, Perm)>> {
+    let object = Object::read(path.as_ref())?;
+
+    Ok(principals
+        .iter()
+        .map(|principal| (principal.clone(), object.effective_perms(principal)))
+        .collect())
+}
+
+/// Result of [`check_access`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AccessCheck {
+    /// True if every requested permission is granted.
+    pub granted: bool,
+
+    /// All permissions the principal has.
+    pub effective: Perm,
+
+    /// Entries that decided the permissions, in the order they were checked.
+    /// With Posix.1e ACL's, this is the entry for the principal's class, or
+    /// the entries of all its groups, followed by the mask if it applies.
+    /// With ordered ACL's, these are the entries that allowed or denied a
+    /// permission first. Empty if only the macOS file mode decided.
+    pub entries: Vec<AclEntry>,
+}
+
+/// Check whether a user with the given uid and groups would be granted the
+/// requested permissions on a file or directory, and explain why.
+///
+/// The check is simulated the same way as in [`rights_matrix`]: the owner,
+/// named user, group and other classes and the mask for Posix.1e ACL's, and
+/// the order of allow and deny entries for `NFSv4` and macOS ACL's.
+/// On macOS, the uid's groups are also resolved with the membership service,
+/// including nested groups, so `gids` only needs to list extra groups.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{check_access, Perm};
+///
+/// let check = check_access("./tmp/report.txt", 1001, &[1001, 27], Perm::WRITE)?;
+/// if check.granted {
+///     for entry in &check.entries {
+///         println!("granted by {entry}");
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the path's metadata or ACL can't be read.
+pub fn check_access<P: AsRef<Path>>(
+    path: P,
+    uid: uid_t,
+    gids: &[gid_t],
+    perms: Perm,
+) -> io::Result<AccessCheck> {
+    let object = Object::read(path.as_ref())?;
+    let principal = Principal {
+        label: format!("uid:{uid}"),
+        owner: false,
+        uid: Some(uid),
+        gids: gids.to_vec(),
+    };
+
+    let (effective, entries) = object.explain(&principal);
+    Ok(AccessCheck {
+        granted: effective.contains(perms),
+        effective,
+        entries: entries.into_iter().cloned().collect(),
+    })
+}
+
+/// A file's owner, mode and access ACL entries, for access checks.
+struct Object {
+    uid: uid_t,
+    gid: gid_t,
+    #[cfg(target_os = "macos")]
+    mode: u32,
+    posix: bool,
+    entries: Vec<AclEntry>,
+}
+
+impl Object {
+    /// Read the owner, mode and access ACL of a path.
+    fn read(path: &Path) -> io::Result<Object> {
+        let metadata = std::fs::metadata(path).map_err(|err| crate::failx::path_err(path, &err))?;
+        let acl = Acl::read(path, AclOption::empty())?;
+
+        Ok(Object {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            #[cfg(target_os = "macos")]
+            mode: metadata.mode(),
+            posix: acl.is_posix(),
+            entries: acl
+                .entries()?
+                .into_iter()
+                .filter(AclEntry::matches)
+                .collect(),
+        })
+    }
+
+    /// Return the permissions the principal has.
+    fn effective_perms(&self, principal: &Principal) -> Perm {
+        self.explain(principal).0
+    }
+
+    /// Return the permissions the principal has, and the entries that
+    /// decided them.
+    fn explain(&self, principal: &Principal) -> (Perm, Vec<&AclEntry>) {
+        if self.posix {
+            self.posix_perms(principal)
+        } else {
+            self.ordered_perms(principal)
+        }
+    }
+
+    /// Check a Posix.1e ACL: the first class the principal belongs to
+    /// decides, and the mask limits named entries and the group class.
+    fn posix_perms(&self, principal: &Principal) -> (Perm, Vec<&AclEntry>) {
+        let find = |kind: AclEntryKind| {
+            self.entries
+                .iter()
+                .find(|entry| entry.kind == kind && entry.name.is_empty())
+        };
+        let mask = self
+            .entries
+            .iter()
+            .find(|entry| entry.kind == AclEntryKind::Mask);
+        if self.is_owner(principal) {
+            return find(AclEntryKind::User)
+                .map_or((Perm::empty(), vec![]), |e| (e.perms, vec![e]));
+        }
+
+        let named_user = self
+            .entries
+            .iter()
+            .find(|entry| entry.kind == AclEntryKind::User && self.applies(entry, principal));
+        if let Some(entry) = named_user {
+            return with_mask(entry.perms, vec![entry], mask);
+        }
+
+        let groups = self
+            .entries
+            .iter()
+            .filter(|entry| entry.kind == AclEntryKind::Group && self.applies(entry, principal))
+            .collect::<Vec<_>>();
+        if !groups.is_empty() {
+            let perms = groups.iter().fold(Perm::empty(), |acc, e| acc | e.perms);
+            return with_mask(perms, groups, mask);
+        }
+
+        find(AclEntryKind::Other).map_or((Perm::empty(), vec![]), |e| (e.perms, vec![e]))
+    }
+
+    /// Check an ordered ACL: the first entry that allows or denies a
+    /// permission decides it.
+    fn ordered_perms(&self, principal: &Principal) -> (Perm, Vec<&AclEntry>) {
+        let mut allowed = Perm::empty();
+        let mut decided = Perm::empty();
+        let mut deciding = Vec::new();
+
+        for entry in &self.entries {
+            if !self.applies(entry, principal) {
+                continue;
+            }
+            if !(entry.perms - decided).is_empty() {
+                deciding.push(entry);
+            }
+            if entry.allow {
+                allowed |= entry.perms - decided;
+            }
+            decided |= entry.perms;
+        }
+
+        // macOS checks the file mode for anything the ACL leaves open.
+        #[cfg(target_os = "macos")]
+        {
+            let bits = if self.is_owner(principal) {
+                self.mode >> 6
+            } else if is_member(principal, self.gid) {
+                self.mode >> 3
+            } else {
+                self.mode
+            };
+            allowed |= mode_perms(bits) - decided;
+        }
+
+        (allowed, deciding)
+    }
+
+    /// Return true if the principal is the owner of the file.
+    fn is_owner(&self, principal: &Principal) -> bool {
+        principal.owner || principal.uid == Some(self.uid)
+    }
+
+    /// Return true if the entry applies to the principal. Entries for the
+    /// owner and owning group have an empty name.
+    fn applies(&self, entry: &AclEntry, principal: &Principal) -> bool {
+        if entry.is_everyone() {
+            return true;
+        }
+
+        match entry.kind {
+            AclEntryKind::User if entry.name.is_empty() => self.is_owner(principal),
+            AclEntryKind::Group if entry.name.is_empty() => is_member(principal, self.gid),
+            AclEntryKind::User | AclEntryKind::Group => match entry.qualifier(false) {
+                Ok(Qualifier::User(uid)) => principal.uid == Some(uid),
+                Ok(Qualifier::Group(gid)) => is_member(principal, gid),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Return true if the principal is a member of the group.
+///
+/// On macOS, the kernel also asks the membership service, which knows a
+/// user's supplementary and nested groups, so the groups of a principal with
+/// a uid are looked up the same way.
+fn is_member(principal: &Principal, gid: gid_t) -> bool {
+    if principal.gids.contains(&gid) {
+        return true;
+    }
+
+    #[cfg(target_os = "macos")]
+    if let Some(uid) = principal.uid {
+        return unix::check_membership(uid, gid).unwrap_or(false);
+    }
+
+    false
+}
+
+/// Limit the permissions of a named entry or the group class by the mask, if
+/// there is one, and add the mask to the deciding entries.
+fn with_mask<'a>(
+    perms: Perm,
+    mut deciding: Vec<&'a AclEntry>,
+    mask: Option<&'a AclEntry>,
+) -> (Perm, Vec<&'a AclEntry>) {
+    match mask {
+        Some(mask) => {
+            deciding.push(mask);
+            (perms & mask.perms, deciding)
+        }
+        None => (perms, deciding),
+    }
+}
+
+/// Return the read, write and execute permissions in the low 3 bits of a
+/// mode.
+#[cfg(target_os = "macos")]
+fn mode_perms(bits: u32) -> Perm {
+    let mut perms = Perm::empty();
+    if bits & 0o4 != 0 {
+        perms |= Perm::READ;
+    }
+    if bits & 0o2 != 0 {
+        perms |= Perm::WRITE;
+    }
+    if bits & 0o1 != 0 {
+        perms |= Perm::EXECUTE;
+    }
+    perms
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod access_tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_rights_matrix() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let uid = file.as_file().metadata()?.uid();
+        let gid = file.as_file().metadata()?.gid();
+
+        let mut entries = crate::from_mode(0o644);
+        entries.push(AclEntry::allow_user(
+            "11501",
+            Perm::READ | Perm::WRITE,
+            None,
+        ));
+        entries.push(AclEntry::allow_group("11502", Perm::EXECUTE, None));
+        entries.push(AclEntry::allow_mask(Perm::READ | Perm::EXECUTE, None));
+        crate::setfacl(&[&file], &entries, None)?;
+
+        let principals = [
+            Principal::owner(),
+            Principal::user(&uid.to_string())?,
+            Principal::user("11501")?,
+            Principal::group("11502")?,
+            Principal::group(&gid.to_string())?.with_groups(&["11502"])?,
+            Principal::everyone(),
+        ];
+        let matrix = rights_matrix(&file, &principals)?;
+
+        let labels = matrix
+            .iter()
+            .map(|(p, _)| p.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(labels[0], "owner");
+        assert_eq!(labels[2], "user:11501");
+        assert_eq!(labels[3], "group:11502");
+
+        let rw = Perm::READ | Perm::WRITE;
+        let perms = matrix.iter().map(|(_, perms)| *perms).collect::<Vec<_>>();
+        assert_eq!(
+            perms,
+            [
+                rw,                         // owner: user::
+                rw,                         // owner by uid
+                Perm::READ,                 // named user, limited by mask
+                Perm::EXECUTE,              // named group
+                Perm::READ | Perm::EXECUTE, // union of group entries
+                Perm::READ,                 // other::
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_check_access() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let uid = file.as_file().metadata()?.uid();
+
+        let mut entries = crate::from_mode(0o640);
+        entries.push(AclEntry::allow_user(
+            "11501",
+            Perm::READ | Perm::WRITE,
+            None,
+        ));
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        crate::setfacl(&[&file], &entries, None)?;
+
+        // The mask takes away the named user's write permission.
+        let check = check_access(&file, 11501, &[], Perm::WRITE)?;
+        assert!(!check.granted);
+        assert_eq!(check.effective, Perm::READ);
+        let kinds = check.entries.iter().map(|e| e.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [AclEntryKind::User, AclEntryKind::Mask]);
+        assert_eq!(check.entries[0].name, "11501");
+        assert!(check_access(&file, 11501, &[], Perm::READ)?.granted);
+
+        // The owner is not limited by the mask.
+        let check = check_access(&file, uid, &[], Perm::READ | Perm::WRITE)?;
+        assert!(check.granted);
+        assert_eq!(
+            check.entries,
+            [AclEntry::allow_user("", Perm::READ | Perm::WRITE, None)]
+        );
+
+        let check = check_access(&file, 11502, &[11503], Perm::READ)?;
+        assert!(!check.granted);
+        assert_eq!(check.entries, [AclEntry::allow_other(Perm::empty(), None)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ordered_perms() {
+        let user = Principal::user("11501")
+            .unwrap()
+            .with_groups(&["11502"])
+            .unwrap();
+        let object = Object {
+            uid: 0,
+            gid: 0,
+            #[cfg(target_os = "macos")]
+            mode: 0o600,
+            posix: false,
+            entries: vec![
+                AclEntry {
+                    allow: false,
+                    ..AclEntry::allow_user("11501", Perm::WRITE, None)
+                },
+                AclEntry::allow_group("11502", Perm::READ | Perm::WRITE, None),
+                AclEntry::allow_group("11503", Perm::EXECUTE, None),
+            ],
+        };
+
+        // The earlier deny wins over the later allow.
+        assert_eq!(object.effective_perms(&user), Perm::READ);
+        let (_, entries) = object.explain(&user);
+        assert_eq!(entries, [&object.entries[0], &object.entries[1]]);
+        assert_eq!(
+            object.effective_perms(&Principal::everyone()),
+            Perm::empty()
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_ordered_perms_membership() {
+        // _spotlight (uid 89) is in `everyone` (gid 12) without listing it.
+        let user = Principal {
+            label: "uid:89".to_string(),
+            owner: false,
+            uid: Some(89),
+            gids: Vec::new(),
+        };
+        let object = Object {
+            uid: 0,
+            gid: 0,
+            mode: 0o600,
+            posix: false,
+            entries: vec![AclEntry::allow_group("12", Perm::WRITE, None)],
+        };
+        assert_eq!(object.effective_perms(&user), Perm::WRITE);
+    }
+}
@@ -1,18 +1,30 @@
 //! Provides `Acl` and `AclOption` implementation.
 
-use crate::aclentry::AclEntry;
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::aclentry::AclEntryKind;
+use crate::aclentry::{AclEntry, AclEntryRef};
 use crate::failx::{fail_custom, path_err};
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::flag::Flag;
-#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::format::{self, syntax::LIST_SEPARATOR};
+#[cfg(any(target_os = "linux", target_os = "freebsd", feature = "raw"))]
 use crate::perm::Perm;
+use crate::resolve::UnresolvedPrincipal;
+#[cfg(any(
+    docsrs,
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "macos"
+))]
+use crate::sys::sg;
 use crate::util::*;
 
 use bitflags::bitflags;
 use scopeguard::{self, ScopeGuard};
+use std::borrow::Borrow;
+use std::fmt;
 use std::io;
+use std::os::fd::AsFd;
 use std::path::Path;
 
 bitflags! {
@@ -28,9 +40,257 @@ bitflags! {
         /// Get/set the ACL of the symlink itself (macOS only).
         const SYMLINK_ACL = 0b0100;
 
+        /// Fail if a user/group name is not in the user/group database, rather
+        /// than treating a decimal name as a numeric id (or a GUID on macOS).
+        /// The error lists every unresolved name. Entries marked `numeric` are
+        /// still accepted.
+        const STRICT_PRINCIPALS = 0b100000;
+
+        /// Remove the `INHERITED` flag from entries written by `setfacl`, such
+        /// as entries copied from a parent directory. Each entry changed is
+        /// logged as a warning (macOS and FreeBSD only; ignored with the
+        /// `strict_posix` feature).
+        const STRIP_INHERITED = 0b100_0000;
+
+        /// In tree operations such as [`stats`](crate::stats), follow
+        /// symbolic links to directories whose target is inside the root. The
+        /// directory is walked again under the link's path. Links to files,
+        /// links that lead outside the root, and links back to a directory
+        /// being walked are still skipped, so no file's ACL is read or changed
+        /// through a link and a walk always ends.
+        const FOLLOW_DIR_SYMLINKS = 0b1000_0000;
+
+        /// After [`setfacl`](crate::setfacl) writes every path, `fsync` each
+        /// file so its new ACL survives a crash once `setfacl` returns. A
+        /// symlink written with `SYMLINK_ACL` is not opened; use `SYNC_DIRS`.
+        const SYNC_FILES = 0b1_0000_0000;
+
+        /// After [`setfacl`](crate::setfacl) writes every path, `fsync` the
+        /// parent directory of each path, once per directory. Use it with
+        /// `SYNC_FILES` after restoring files that were just created, so their
+        /// directory entries are durable too.
+        const SYNC_DIRS = 0b10_0000_0000;
+
+        /// In tree operations such as [`stats`](crate::stats), skip a path
+        /// whose ACL can't be read, or a directory that can't be listed,
+        /// because permission is denied, instead of failing the whole walk,
+        /// like `getfacl -R`. Each skipped path is logged as a warning, and
+        /// [`stats`](crate::stats) lists them in
+        /// [`TreeStats::access_denied`](crate::TreeStats::access_denied).
+        const SKIP_ACCESS_DENIED = 0b100_0000_0000;
+
+        /// Let [`setfacl`](crate::setfacl) fill in the owner, group and other
+        /// entries that the given entries lack, copied from the current ACL
+        /// of each path, so the caller doesn't have to supply them. Like
+        /// `setfacl -m`, base entries that aren't given are kept, and they
+        /// match the file's mode unless it has an extended ACL. Missing
+        /// default entries are copied from the current default ACL, or from
+        /// the access ACL if there is none. An ACL with no entries given is
+        /// left alone (Linux and FreeBSD only; ignored for `NFSv4` ACL's).
+        const FILL_REQUIRED = 0b1000_0000_0000;
+
+        /// Let [`getfacl`](crate::getfacl) and
+        /// [`getfacl_map`](crate::getfacl_map) read a path's ACL again if its
+        /// change time moved while it was read, so the access and default
+        /// entries come from the same version of the ACL. The read fails
+        /// after 5 attempts if the ACL keeps changing.
+        const CONSISTENT_READ = 0b100_0000_0000_0000;
+
+        /// Let [`getfacl`](crate::getfacl) name each user and group entry by
+        /// its decimal uid or gid, with `numeric` set, without looking it up
+        /// in the user/group database. Use it in containers and chroots
+        /// where the database doesn't match the ids in the file system.
+        const NUMERIC_IDS = 0b1000_0000_0000_0000;
+
         /// Ignore expected error when using DEFAULT_ACL on a file.
         #[doc(hidden)]
         const IGNORE_EXPECTED_FILE_ERR = 0b10000;
+
+        /// The caller knows the path is a directory, so it isn't checked again.
+        #[doc(hidden)]
+        const KNOWN_DIR = 0b1_0000_0000_0000;
+
+        /// The caller knows the path is not a directory, so it isn't checked
+        /// again.
+        #[doc(hidden)]
+        const KNOWN_NON_DIR = 0b10_0000_0000_0000;
+    }
+}
+
+impl AclOption {
+    /// Return the hidden option that records whether a path is a directory,
+    /// for a caller that already has its file type.
+    pub(crate) const fn known_kind(is_dir: bool) -> AclOption {
+        if is_dir {
+            AclOption::KNOWN_DIR
+        } else {
+            AclOption::KNOWN_NON_DIR
+        }
+    }
+}
+
+/// Names of the options, as written by `Display` and read by `FromStr`.
+const OPTION_NAMES: [(AclOption, &str); 12] = [
+    (AclOption::ACCESS_ACL, "access_acl"),
+    (AclOption::DEFAULT_ACL, "default_acl"),
+    (AclOption::SYMLINK_ACL, "symlink_acl"),
+    (AclOption::STRICT_PRINCIPALS, "strict_principals"),
+    (AclOption::STRIP_INHERITED, "strip_inherited"),
+    (AclOption::FOLLOW_DIR_SYMLINKS, "follow_dir_symlinks"),
+    (AclOption::SYNC_FILES, "sync_files"),
+    (AclOption::SYNC_DIRS, "sync_dirs"),
+    (AclOption::SKIP_ACCESS_DENIED, "skip_access_denied"),
+    (AclOption::FILL_REQUIRED, "fill_required"),
+    (AclOption::CONSISTENT_READ, "consistent_read"),
+    (AclOption::NUMERIC_IDS, "numeric_ids"),
+];
+
+impl fmt::Display for AclOption {
+    /// Write a comma-separated list of option names, e.g.
+    /// "access_acl,strict_principals". Hidden options are left out.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = OPTION_NAMES
+            .iter()
+            .filter(|(option, _)| self.contains(*option))
+            .map(|(_, name)| *name);
+
+        for (i, name) in names.enumerate() {
+            if i > 0 {
+                write!(f, "{LIST_SEPARATOR}")?;
+            }
+            write!(f, "{name}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for AclOption {
+    type Err = format::Error;
+
+    /// Parse a comma-separated list of option names, e.g. "default_acl". An
+    /// empty string is no options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = AclOption::empty();
+
+        for item in s.split(LIST_SEPARATOR) {
+            let word = item.trim();
+            if word.is_empty() {
+                continue;
+            }
+            match OPTION_NAMES.iter().find(|(_, name)| *name == word) {
+                Some((option, _)) => result |= *option,
+                None => {
+                    let expected = OPTION_NAMES
+                        .iter()
+                        .map(|(_, name)| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    return Err(format::Error::Message(format!(
+                        "unknown option `{word}`, expected one of {expected}"
+                    )));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Lets command line tools accept options with `clap`, e.g.
+/// `#[arg(long, value_enum, value_delimiter = ',')] options: Vec<AclOption>`.
+/// Each value is a single option, named as in `Display`.
+#[cfg(feature = "cli")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cli")))]
+impl clap::ValueEnum for AclOption {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            AclOption::ACCESS_ACL,
+            AclOption::DEFAULT_ACL,
+            AclOption::SYMLINK_ACL,
+            AclOption::STRICT_PRINCIPALS,
+            AclOption::STRIP_INHERITED,
+            AclOption::FOLLOW_DIR_SYMLINKS,
+            AclOption::SYNC_FILES,
+            AclOption::SYNC_DIRS,
+            AclOption::SKIP_ACCESS_DENIED,
+            AclOption::FILL_REQUIRED,
+            AclOption::CONSISTENT_READ,
+            AclOption::NUMERIC_IDS,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        OPTION_NAMES
+            .iter()
+            .find(|(option, _)| option == self)
+            .map(|(_, name)| clap::builder::PossibleValue::new(*name))
+    }
+}
+
+/// Kind of native ACL, returned by [`Acl::brand`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AclBrand {
+    /// Posix.1e ACL with owner, group, mask and other entries (Linux and
+    /// `FreeBSD`).
+    Posix1e,
+
+    /// `NFSv4` ACL with ordered allow and deny entries (`FreeBSD`).
+    Nfs4,
+
+    /// Extended ACL with ordered allow and deny entries (macOS).
+    Extended,
+
+    /// ACL has no entries that determine its kind yet (`FreeBSD`).
+    Unknown,
+}
+
+/// Options for [`Acl::to_any_text`] (Linux only).
+///
+/// The default options produce the same long text form as `acl_to_text`, one
+/// entry per line.
+#[cfg(any(docsrs, target_os = "linux"))]
+#[cfg_attr(docsrs, doc(cfg(target_os = "linux")))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct TextOptions {
+    /// Text to prepend to each entry, e.g. "default:". Must not contain NUL.
+    pub prefix: String,
+
+    /// Character used to separate entries. Must be a non-NUL ASCII character.
+    pub separator: char,
+
+    /// Show numeric user and group IDs instead of names (`TEXT_NUMERIC_IDS`).
+    pub numeric_ids: bool,
+
+    /// Abbreviate entry tags, e.g. `u::rwx` (`TEXT_ABBREVIATE`).
+    pub abbreviate: bool,
+
+    /// Append an "#effective:" comment to entries whose permissions are
+    /// limited by the mask (`TEXT_SOME_EFFECTIVE`).
+    pub some_effective: bool,
+
+    /// Append an "#effective:" comment to every entry affected by the mask
+    /// (`TEXT_ALL_EFFECTIVE`).
+    pub all_effective: bool,
+
+    /// Align "#effective:" comments to column 32 (`TEXT_SMART_INDENT`).
+    pub smart_indent: bool,
+}
+
+#[cfg(any(docsrs, target_os = "linux"))]
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            prefix: String::new(),
+            separator: '\n',
+            numeric_ids: false,
+            abbreviate: false,
+            some_effective: false,
+            all_effective: false,
+            smart_indent: false,
+        }
     }
 }
 
@@ -39,6 +299,8 @@ bitflags! {
 /// Each [`Acl`] is immutable once constructed. To manipulate its contents, you
 /// can retrieve a mutable vector of [`AclEntry`], modify the vector's contents,
 /// then create a new [`Acl`].
+///
+/// An [`Acl`] is `Send` but not `Sync`.
 pub struct Acl {
     /// Native acl.
     acl: acl_t,
@@ -51,10 +313,10 @@ pub struct Acl {
 }
 
 impl Acl {
-    /// Convenience function to construct an `Acl`.
+    /// Convenience function to construct an `Acl`. The native functions that
+    /// return an `acl_t` fail rather than return null.
     #[allow(unused_variables)]
     fn new(acl: acl_t, default_acl: bool) -> Acl {
-        assert!(!acl.is_null());
         Acl {
             acl,
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
@@ -68,9 +330,17 @@ impl Acl {
     ///
     /// Returns an [`io::Error`] on failure.
     pub fn read(path: &Path, options: AclOption) -> io::Result<Acl> {
+        crate::deadline::enter(path);
         let symlink_acl = options.contains(AclOption::SYMLINK_ACL);
         let default_acl = options.contains(AclOption::DEFAULT_ACL);
 
+        // A non-directory has no default ACL, so don't ask for it.
+        if default_acl
+            && options.contains(AclOption::IGNORE_EXPECTED_FILE_ERR | AclOption::KNOWN_NON_DIR)
+        {
+            return Ok(Acl::new(xacl_init(1)?, default_acl));
+        }
+
         let result = xacl_get_file(path, symlink_acl, default_acl);
         match result {
             Ok(acl) => Ok(Acl::new(acl, default_acl)),
@@ -84,7 +354,7 @@ impl Acl {
                     && (err.kind() == io::ErrorKind::PermissionDenied
                         || err.kind() == io::ErrorKind::InvalidInput)
                     && options.contains(AclOption::IGNORE_EXPECTED_FILE_ERR)
-                    && is_non_directory(path, symlink_acl)
+                    && is_non_directory(path, options)
                 {
                     // Return an empty acl.
                     Ok(Acl::new(xacl_init(1)?, default_acl))
@@ -101,107 +371,173 @@ impl Acl {
     ///
     /// Returns an [`io::Error`] on failure.
     pub fn write(&self, path: &Path, options: AclOption) -> io::Result<()> {
+        crate::deadline::enter(path);
         let symlink_acl = options.contains(AclOption::SYMLINK_ACL);
         let default_acl = options.contains(AclOption::DEFAULT_ACL);
 
         // If we're writing a default ACL to a non-directory, and we
         // specify the `IGNORE_EXPECTED_FILE_ERR` option, this function is a
         // no-op if the ACL is empty.
-        if default_acl && is_non_directory(path, symlink_acl) {
+        if default_acl && is_non_directory(path, options) {
             if self.is_empty() && options.contains(AclOption::IGNORE_EXPECTED_FILE_ERR) {
                 return Ok(());
             }
 
-            return fail_custom(&format!(
-                "File {path:?}: Non-directory does not have default ACL"
-            ));
+            let err = io::Error::new(
+                io::ErrorKind::Other,
+                "Non-directory does not have default ACL",
+            );
+            return Err(path_err(path, &err));
         }
 
         if let Err(err) = xacl_set_file(path, self.acl, symlink_acl, default_acl) {
+            // Report when System Integrity Protection is the cause of EPERM.
+            #[cfg(target_os = "macos")]
+            if err.kind() == io::ErrorKind::PermissionDenied {
+                let err = path_err(path, &err);
+                return Err(crate::macos::check_sip(path, symlink_acl, err));
+            }
+
             return Err(path_err(path, &err));
         }
 
         Ok(())
     }
 
-    /// Compute mask.
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    fn compute_mask_perms(entries: &[AclEntry], filter: (Flag, Flag)) -> Option<Perm> {
-        let mut perms = Perm::empty();
-        let mut need_mask = false;
+    /// Read ACL for an open file, e.g. a [`std::fs::File`].
+    ///
+    /// Use `DEFAULT_ACL` to read the default ACL of a directory (`FreeBSD`
+    /// only). On `FreeBSD`, an `NFSv4` ACL is returned if the file system does
+    /// not support Posix.1e ACL's. On macOS, this reads the extended ACL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn read_fd<F: AsFd>(fd: F, options: AclOption) -> io::Result<Acl> {
+        let default_acl = options.contains(AclOption::DEFAULT_ACL);
+        let acl = xacl_get_fd(fd.as_fd(), default_acl)?;
+        Ok(Acl::new(acl, default_acl))
+    }
 
-        for entry in entries {
-            // Skip over undesired entries in a unified ACL.
-            if (entry.flags & filter.1) != filter.0 {
-                continue;
-            }
+    /// Write ACL for an open file, e.g. a [`std::fs::File`].
+    ///
+    /// Use `DEFAULT_ACL` to write the default ACL of a directory (`FreeBSD`
+    /// only). On `FreeBSD`, an `NFSv4` ACL is written as `ACL_TYPE_NFS4`. On
+    /// macOS, this writes the extended ACL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn write_fd<F: AsFd>(&self, fd: F, options: AclOption) -> io::Result<()> {
+        let default_acl = options.contains(AclOption::DEFAULT_ACL);
+        xacl_set_fd(fd.as_fd(), self.acl, default_acl)
+    }
 
-            match entry.kind {
-                AclEntryKind::Mask => return None,
-                AclEntryKind::User | AclEntryKind::Group if !entry.name.is_empty() => {
-                    perms |= entry.perms;
-                    need_mask = true;
-                }
-                AclEntryKind::Group => perms |= entry.perms,
-                _ => (),
-            }
-        }
+    /// Parse an ACL in the text form of `acl_from_text` (Linux only).
+    #[cfg(target_os = "linux")]
+    pub(crate) fn from_text(text: &str, default_acl: bool) -> io::Result<Acl> {
+        Ok(Acl::new(xacl_from_text(text)?, default_acl))
+    }
 
-        if !need_mask {
-            return None;
-        }
+    /// Return an empty ACL, for entries added by `add_raw_entry`.
+    #[cfg(feature = "raw")]
+    pub(crate) fn empty() -> io::Result<Acl> {
+        Ok(Acl::new(xacl_init(1)?, false))
+    }
 
-        Some(perms)
+    /// Append an entry with a raw tag value. Neither the tag nor the ACL as a
+    /// whole is checked.
+    #[cfg(feature = "raw")]
+    pub(crate) fn add_raw_entry(
+        &mut self,
+        tag: u32,
+        id: Option<u32>,
+        perms: Perm,
+    ) -> io::Result<()> {
+        xacl_add_raw_entry(&mut self.acl, tag, id, perms)?;
+        Ok(())
     }
 
-    /// Check for required entries that are missing.
-    ///
-    /// It is valid for there to be zero entries.
+    /// Return an ACL with only the owner, group and other entries that
+    /// correspond to the file mode (Linux and `FreeBSD`).
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    fn find_missing_entries(entries: &[AclEntry], filter: (Flag, Flag)) -> Option<AclEntryKind> {
-        let mut miss_user = true;
-        let mut miss_group = true;
-        let mut miss_other = true;
-        let mut is_empty = true;
-
-        for entry in entries {
-            // Skip over undesired entries in a unified ACL.
-            if (entry.flags & filter.1) != filter.0 {
-                continue;
-            }
+    pub(crate) fn strip(&self) -> io::Result<Acl> {
+        #[cfg(target_os = "freebsd")]
+        return Ok(Acl::new(xacl_strip(self.acl)?, self.default_acl));
 
-            is_empty = false;
-            match entry.kind {
-                AclEntryKind::User if entry.name.is_empty() => miss_user = false,
-                AclEntryKind::Group if entry.name.is_empty() => miss_group = false,
-                AclEntryKind::Other => miss_other = false,
-                _ => (),
-            }
+        #[cfg(target_os = "linux")]
+        Acl::from_entries(self.entries()?.into_iter().filter(|entry| {
+            entry.name.is_empty()
+                && matches!(
+                    entry.kind,
+                    AclEntryKind::User | AclEntryKind::Group | AclEntryKind::Other
+                )
+        }))
+    }
+
+    /// Return the permission bits of the file mode equivalent to the ACL
+    /// (Linux and `FreeBSD`).
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    pub(crate) fn equiv_mode(&self) -> io::Result<u32> {
+        #[cfg(target_os = "linux")]
+        return xacl_equiv_mode(self.acl);
+
+        #[cfg(target_os = "freebsd")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            Ok(crate::as_permissions(&self.entries()?).mode())
         }
+    }
 
-        if is_empty {
-            None
-        } else if miss_user {
-            Some(AclEntryKind::User)
-        } else if miss_group {
-            Some(AclEntryKind::Group)
-        } else if miss_other {
-            Some(AclEntryKind::Other)
-        } else {
-            None
+    /// Return initial capacity for a native ACL, given the lower bound of an
+    /// iterator's size. On Linux and `FreeBSD`, leave room for a computed mask
+    /// entry.
+    fn initial_capacity(size_hint: usize) -> usize {
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        if i32::try_from(size_hint).is_ok_and(|size| size < sg::ACL_MAX_ENTRIES) {
+            return size_hint + 1;
         }
+
+        size_hint
     }
 
-    /// Return an ACL from a slice of [`AclEntry`].
+    /// Return an ACL from a sequence of [`AclEntry`].
+    ///
+    /// The entries may be any iterable of `AclEntry` or `&AclEntry`, so they
+    /// can be generated on the fly without collecting them into a `Vec`.
     ///
     /// On Linux, if there is no mask `AclEntry`, one will be computed and
     /// added, if needed.
     ///
+    /// The native ACL is allocated once with room for every entry, including
+    /// the computed mask, when the iterator reports its length.
+    ///
     /// # Errors
     ///
-    /// Returns an [`io::Error`] on failure.
-    pub fn from_entries(entries: &[AclEntry]) -> io::Result<Acl> {
-        let new_acl = xacl_init(entries.len())?;
+    /// Returns an [`io::Error`] on failure. On macOS, an ACL holds at most
+    /// 128 entries (see [`VolumeCapabilities::max_entries`]); the error for a
+    /// longer list suggests granting a group instead of many users.
+    ///
+    /// [`VolumeCapabilities::max_entries`]: crate::VolumeCapabilities::max_entries
+    pub fn from_entries<I>(entries: I) -> io::Result<Acl>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<AclEntry>,
+    {
+        Acl::from_entries_strict(entries, false)
+    }
+
+    /// Return an ACL from a sequence of [`AclEntry`]. If `strict` is true,
+    /// user/group names must be in the user/group database.
+    pub(crate) fn from_entries_strict<I>(entries: I, strict: bool) -> io::Result<Acl>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<AclEntry>,
+    {
+        let entries = entries.into_iter();
+        #[cfg(target_os = "macos")]
+        check_max_entries(entries.size_hint().0)?;
+        let new_acl = xacl_init(Acl::initial_capacity(entries.size_hint().0))?;
 
         // Use the smart pointer form of scopeguard; `acl_p` can change value
         // when we create entries in it.
@@ -209,33 +545,55 @@ impl Acl {
             xacl_free(a);
         });
 
-        for (i, entry) in entries.iter().enumerate() {
-            if let Err(err) = entry.add_to_acl(&mut acl_p) {
-                return fail_custom(&format!("entry {i}: {err}"));
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let mut required = RequiredEntries::default();
+
+        #[cfg(target_os = "linux")]
+        let mut input = InputIndex::default();
+
+        let mut unresolved = Unresolved::default();
+
+        for (i, entry) in entries.enumerate() {
+            #[cfg(target_os = "macos")]
+            check_max_entries(i + 1)?;
+            let entry = entry.borrow();
+            let result = entry.add_to_acl(&mut acl_p, strict);
+            #[cfg(target_os = "linux")]
+            let result = result.map(|handle| input.add(handle, i));
+            if let Err(err) = unresolved.check(i, entry, result) {
+                return fail_entry(i, entry, &err);
             }
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            required.add(i, entry);
         }
 
+        unresolved.into_result()?;
+
         // Check for missing required entries.
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-        if let Some(kind) = Acl::find_missing_entries(entries, (Flag::empty(), Flag::empty())) {
-            return fail_custom(&format!("missing required entry \"{kind}\""));
+        if let Some(problem) = required.describe_missing("access") {
+            return fail_custom(&problem);
         }
 
         // Check if we need to add a mask entry.
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-        if let Some(mask_perms) = Acl::compute_mask_perms(entries, (Flag::empty(), Flag::empty())) {
+        if let Some(mask_perms) = required.mask_perms() {
             let mask = AclEntry::allow_mask(mask_perms, None);
-            if let Err(err) = mask.add_to_acl(&mut acl_p) {
-                return fail_custom(&format!("entry -1: {err}"));
+            if let Err(err) = mask.add_to_acl(&mut acl_p, strict) {
+                return fail_custom(&format!("mask entry: {err}"));
             }
         }
 
+        #[cfg(target_os = "linux")]
+        input.check(*acl_p, false)?;
+
         Ok(Acl::new(ScopeGuard::into_inner(acl_p), false))
     }
 
-    /// Return pair of ACL's from slice of [`AclEntry`]. This method separates
-    /// regular access entries from default entries and returns two ACL's, an
-    /// access ACL and default ACL. Either may be empty.
+    /// Return pair of ACL's from a sequence of [`AclEntry`]. This method
+    /// separates regular access entries from default entries and returns two
+    /// ACL's, an access ACL and default ACL. Either may be empty.
     ///
     /// If there is no mask `AclEntry` in an ACL, one will be computed and
     /// added, if needed.
@@ -245,9 +603,26 @@ impl Acl {
     /// Returns an [`io::Error`] on failure.
     #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
     #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
-    pub fn from_unified_entries(entries: &[AclEntry]) -> io::Result<(Acl, Acl)> {
-        let new_access = xacl_init(entries.len())?;
-        let new_default = xacl_init(entries.len())?;
+    pub fn from_unified_entries<I>(entries: I) -> io::Result<(Acl, Acl)>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<AclEntry>,
+    {
+        Acl::from_unified_entries_strict(entries, false)
+    }
+
+    /// Return pair of ACL's from a sequence of [`AclEntry`]. If `strict` is
+    /// true, user/group names must be in the user/group database.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    pub(crate) fn from_unified_entries_strict<I>(entries: I, strict: bool) -> io::Result<(Acl, Acl)>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<AclEntry>,
+    {
+        let entries = entries.into_iter();
+        let capacity = Acl::initial_capacity(entries.size_hint().0);
+        let new_access = xacl_init(capacity)?;
+        let new_default = xacl_init(capacity)?;
 
         // Use the smart pointer form of scopeguard; acls can change value when
         // we create entries in them.
@@ -259,47 +634,66 @@ impl Acl {
             xacl_free(a);
         });
 
-        for (i, entry) in entries.iter().enumerate() {
+        let mut access_required = RequiredEntries::default();
+        let mut default_required = RequiredEntries::default();
+
+        #[cfg(target_os = "linux")]
+        let mut input = InputIndex::default();
+
+        let mut unresolved = Unresolved::default();
+
+        for (i, entry) in entries.enumerate() {
+            let entry = entry.borrow();
             let result = if entry.flags.contains(Flag::DEFAULT) {
-                entry.add_to_acl(&mut default_p)
+                default_required.add(i, entry);
+                entry.add_to_acl(&mut default_p, strict)
             } else {
-                entry.add_to_acl(&mut access_p)
+                access_required.add(i, entry);
+                entry.add_to_acl(&mut access_p, strict)
             };
-            if let Err(err) = result {
-                return fail_custom(&format!("entry {i}: {err}"));
+            #[cfg(target_os = "linux")]
+            let result = result.map(|handle| input.add(handle, i));
+            if let Err(err) = unresolved.check(i, entry, result) {
+                return fail_entry(i, entry, &err);
             }
         }
 
-        if xacl_is_posix(*access_p) {
-            // Check for missing entries in both access and default entries.
-            if let Some(kind) = Acl::find_missing_entries(entries, (Flag::empty(), Flag::DEFAULT)) {
-                return fail_custom(&format!("missing required entry \"{kind}\""));
-            }
+        unresolved.into_result()?;
 
-            if let Some(kind) = Acl::find_missing_entries(entries, (Flag::DEFAULT, Flag::DEFAULT)) {
-                return fail_custom(&format!("missing required default entry \"{kind}\""));
+        if xacl_is_posix(*access_p) {
+            // Check for missing entries in both access and default entries,
+            // and report both at once.
+            let problems = [
+                access_required.describe_missing("access"),
+                default_required.describe_missing("default"),
+            ];
+            let problems = problems.into_iter().flatten().collect::<Vec<_>>();
+            if !problems.is_empty() {
+                return fail_custom(&problems.join("; "));
             }
 
             // Check if we need to add a mask entry.
-            if let Some(mask_perms) =
-                Acl::compute_mask_perms(entries, (Flag::empty(), Flag::DEFAULT))
-            {
+            if let Some(mask_perms) = access_required.mask_perms() {
                 let mask = AclEntry::allow_mask(mask_perms, None);
-                if let Err(err) = mask.add_to_acl(&mut access_p) {
+                if let Err(err) = mask.add_to_acl(&mut access_p, strict) {
                     return fail_custom(&format!("mask entry: {err}"));
                 }
             }
 
-            if let Some(mask_perms) =
-                Acl::compute_mask_perms(entries, (Flag::DEFAULT, Flag::DEFAULT))
-            {
+            if let Some(mask_perms) = default_required.mask_perms() {
                 let mask = AclEntry::allow_mask(mask_perms, Flag::DEFAULT);
-                if let Err(err) = mask.add_to_acl(&mut default_p) {
+                if let Err(err) = mask.add_to_acl(&mut default_p, strict) {
                     return fail_custom(&format!("default mask entry: {err}"));
                 }
             }
         }
 
+        #[cfg(target_os = "linux")]
+        {
+            input.check(*access_p, false)?;
+            input.check(*default_p, true)?;
+        }
+
         let access_acl = ScopeGuard::into_inner(access_p);
         let default_acl = ScopeGuard::into_inner(default_p);
 
@@ -312,23 +706,67 @@ impl Acl {
     ///
     /// Returns an [`io::Error`] on failure.
     pub fn entries(&self) -> io::Result<Vec<AclEntry>> {
-        let mut entries = Vec::<AclEntry>::with_capacity(8);
+        self.entries_with(AclOption::empty())
+    }
+
+    /// Return ACL as a vector of [`AclEntry`]. With `NUMERIC_IDS`, users and
+    /// groups are named by id.
+    pub(crate) fn entries_with(&self, options: AclOption) -> io::Result<Vec<AclEntry>> {
+        let numeric_ids = options.contains(AclOption::NUMERIC_IDS);
+        let mut entries = Vec::<AclEntry>::with_capacity(self.len());
 
         xacl_foreach(self.acl, |entry_p| {
-            let entry = AclEntry::from_raw(entry_p, self.acl)?;
-            entries.push(entry);
+            let entry = AclEntryRef::from_raw(entry_p, self.acl, self.is_default())?;
+            if numeric_ids {
+                entries.push(entry.to_numeric_entry()?);
+            } else {
+                entries.push(entry.to_entry()?);
+            }
             Ok(())
         })?;
 
-        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-        if self.default_acl {
-            // Set DEFAULT flag on each entry.
-            for entry in &mut entries {
-                entry.flags |= Flag::DEFAULT;
-            }
+        Ok(entries)
+    }
+
+    /// Return an iterator over the entries of the ACL.
+    ///
+    /// Unlike [`Acl::entries`], this doesn't build a vector or look up user
+    /// and group names; a name is looked up when [`AclEntryRef::name`] is
+    /// called. This makes it cheap to scan many ACL's for a uid or gid:
+    ///
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// use exacl::{Acl, AclOption};
+    /// use std::path::Path;
+    ///
+    /// let mut acl = Acl::read(Path::new("./foo"), AclOption::empty())?;
+    /// for entry in acl.iter() {
+    ///     if entry?.id() == Some(1000) {
+    ///         println!("found uid 1000");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The native ACL keeps the position of the iterator, so the iterator
+    /// borrows the ACL mutably.
+    pub fn iter(&mut self) -> AclIter<'_> {
+        AclIter {
+            acl: self,
+            first: true,
+            done: false,
         }
+    }
 
-        Ok(entries)
+    /// Return true if the ACL was read as the default ACL of a directory.
+    #[allow(clippy::missing_const_for_fn)]
+    fn is_default(&self) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        return self.default_acl;
+
+        #[cfg(target_os = "macos")]
+        return false;
     }
 
     /// Return ACL as a string.
@@ -348,6 +786,50 @@ impl Acl {
         String::from_utf8(buf).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
     }
 
+    /// Return ACL in the platform's native text format (Linux only).
+    ///
+    /// This method calls `acl_to_any_text` from libacl. Use [`TextOptions`]
+    /// to control the prefix, separator and formatting flags. Unlike
+    /// [`AclEntry`]'s text format, the output does not include `DEFAULT`
+    /// flags; use a prefix of "default:" for a default ACL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    #[cfg(any(docsrs, target_os = "linux"))]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "linux")))]
+    pub fn to_any_text(&self, options: &TextOptions) -> io::Result<String> {
+        let separator = match u8::try_from(options.separator) {
+            Ok(sep) if sep.is_ascii() && sep != 0 => sep,
+            _ => return fail_custom(&format!("invalid separator: {:?}", options.separator)),
+        };
+
+        let mut flags = 0;
+        if options.numeric_ids {
+            flags |= sg::TEXT_NUMERIC_IDS;
+        }
+        if options.abbreviate {
+            flags |= sg::TEXT_ABBREVIATE;
+        }
+        if options.some_effective {
+            flags |= sg::TEXT_SOME_EFFECTIVE;
+        }
+        if options.all_effective {
+            flags |= sg::TEXT_ALL_EFFECTIVE;
+        }
+        if options.smart_indent {
+            flags |= sg::TEXT_SMART_INDENT;
+        }
+
+        xacl_to_any_text(self.acl, &options.prefix, separator, flags)
+    }
+
+    /// Return number of entries in the ACL.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        xacl_entry_count(self.acl)
+    }
+
     /// Return true if ACL is empty.
     #[must_use]
     pub fn is_empty(&self) -> bool {
@@ -355,12 +837,37 @@ impl Acl {
     }
 
     /// Return true if ACL is a Posix.1e ACL on Linux or `FreeBSD`.
+    ///
+    /// An ACL with an unknown brand is treated as Posix.1e. Use
+    /// [`Acl::brand`] to distinguish the two.
     #[must_use]
     #[allow(clippy::missing_const_for_fn, dead_code)]
     pub fn is_posix(&self) -> bool {
         xacl_is_posix(self.acl)
     }
 
+    /// Return the kind of native ACL.
+    ///
+    /// On Linux, this is always [`AclBrand::Posix1e`]. On macOS, this is always
+    /// [`AclBrand::Extended`]. On `FreeBSD`, an empty ACL may be
+    /// [`AclBrand::Unknown`] until entries are added.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn brand(&self) -> AclBrand {
+        #[cfg(target_os = "linux")]
+        return AclBrand::Posix1e;
+
+        #[cfg(target_os = "macos")]
+        return AclBrand::Extended;
+
+        #[cfg(target_os = "freebsd")]
+        return match xacl_get_brand(self.acl) {
+            Ok(sg::ACL_BRAND_POSIX) => AclBrand::Posix1e,
+            Ok(sg::ACL_BRAND_NFS4) => AclBrand::Nfs4,
+            _ => AclBrand::Unknown,
+        };
+    }
+
     /// Return true if file uses an `NFSv4` ACL (`FreeBSD` only).
     ///
     /// Only used in testing.
@@ -382,9 +889,266 @@ impl Drop for Acl {
     }
 }
 
-/// Return true if path exists and it's not a directory.
-fn is_non_directory(path: &Path, symlink: bool) -> bool {
-    let result = if symlink {
+/// Iterator over the entries of an [`Acl`], returned by [`Acl::iter`].
+pub struct AclIter<'a> {
+    acl: &'a mut Acl,
+    first: bool,
+    done: bool,
+}
+
+impl<'a> Iterator for AclIter<'a> {
+    type Item = io::Result<AclEntryRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let acl = self.acl.acl;
+        let result = xacl_next_entry(acl, self.first).and_then(|entry| {
+            entry
+                .map(|entry_p| AclEntryRef::from_raw(entry_p, acl, self.acl.is_default()))
+                .transpose()
+        });
+        self.first = false;
+
+        // Stop after the last entry or an error.
+        let result = result.transpose();
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
+// The native acl is owned exclusively by `Acl`, so it may move to another
+// thread. `Acl` is not `Sync`: reading entries uses the native acl's internal
+// iterator, even through a shared reference.
+unsafe impl Send for Acl {}
+
+/// Collects the entries whose user/group name is not in the database, so they
+/// can be reported together instead of failing on the first one.
+#[derive(Default)]
+struct Unresolved(Vec<UnresolvedPrincipal>);
+
+impl Unresolved {
+    /// Record entry `i` if `result` is an unknown name; otherwise, return it.
+    fn check<T>(&mut self, i: usize, entry: &AclEntry, result: io::Result<T>) -> io::Result<()> {
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.0.push(UnresolvedPrincipal::new(i, entry));
+                Ok(())
+            }
+            Err(err) => Err(err),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Return an error listing all unknown names, if any.
+    fn into_result(self) -> io::Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        let names = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        fail_custom(&format!("unresolved principals: {}", names.join(", ")))
+    }
+}
+
+/// Return an error for entry `i` of the caller's input, showing the entry.
+fn fail_entry<U>(i: usize, entry: &AclEntry, msg: &dyn fmt::Display) -> io::Result<U> {
+    fail_custom(&format!("entry {i} ({entry}): {msg}"))
+}
+
+/// Fail if `count` entries won't fit in a macOS ACL. Users who hit the limit
+/// can usually put the principals in a group and grant the group instead.
+#[cfg(target_os = "macos")]
+fn check_max_entries(count: usize) -> io::Result<()> {
+    if i32::try_from(count).is_ok_and(|count| count <= sg::ACL_MAX_ENTRIES) {
+        return Ok(());
+    }
+
+    fail_custom(&format!(
+        "too many ACL entries: macOS allows at most {} entries; \
+         consolidate entries with the same permissions into a group entry",
+        sg::ACL_MAX_ENTRIES
+    ))
+}
+
+/// Maps native entries to their index in the caller's input, so an error from
+/// `acl_check` can name the entry. libacl keeps entries sorted, so the position
+/// of an entry in the ACL is not its input index.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+struct InputIndex(Vec<(acl_entry_t, usize)>);
+
+#[cfg(target_os = "linux")]
+impl InputIndex {
+    /// Record that native `entry` was added for input entry `i`.
+    fn add(&mut self, entry: acl_entry_t, i: usize) {
+        self.0.push((entry, i));
+    }
+
+    /// Check `acl` with `acl_check`. On failure, return libacl's description
+    /// of the error, prefixed with the index of the input entry at fault. Set
+    /// `default_acl` if `acl` holds the default entries.
+    fn check(&self, acl: acl_t, default_acl: bool) -> io::Result<()> {
+        if xacl_is_empty(acl) {
+            return Ok(());
+        }
+        let Some((msg, last)) = xacl_check(acl)? else {
+            return Ok(());
+        };
+
+        let mut pos = 0;
+        let mut bad_entry = None;
+        xacl_foreach(acl, |entry| {
+            if pos == last {
+                bad_entry = Some(entry);
+            }
+            pos += 1;
+            Ok(())
+        })?;
+
+        // A computed mask entry has no input index.
+        match bad_entry.and_then(|bad| self.0.iter().find(|(entry, _)| *entry == bad)) {
+            Some((bad, i)) => {
+                let mut entry = AclEntry::from_raw(*bad, acl)?;
+                entry.flags.set(Flag::DEFAULT, default_acl);
+                fail_entry(*i, &entry, &msg)
+            }
+            None => fail_custom(&msg),
+        }
+    }
+}
+
+/// Tracks the required entries of a Posix.1e ACL as entries are added.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct RequiredEntries {
+    /// Input indices of the entries added.
+    indices: Vec<usize>,
+    has_entries: bool,
+    has_user: bool,
+    has_group: bool,
+    has_other: bool,
+    has_mask: bool,
+    need_mask: bool,
+    mask_perms: Perm,
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+impl RequiredEntries {
+    /// Record entry `i` of the input.
+    fn add(&mut self, i: usize, entry: &AclEntry) {
+        self.indices.push(i);
+        self.has_entries = true;
+        match entry.kind {
+            AclEntryKind::User | AclEntryKind::Group if !entry.name.is_empty() => {
+                self.mask_perms |= entry.perms;
+                self.need_mask = true;
+            }
+            AclEntryKind::User => self.has_user = true,
+            AclEntryKind::Group => {
+                self.mask_perms |= entry.perms;
+                self.has_group = true;
+            }
+            AclEntryKind::Other => self.has_other = true,
+            AclEntryKind::Mask => self.has_mask = true,
+            _ => (),
+        }
+    }
+
+    /// Return all required entries that are missing.
+    ///
+    /// It is valid for there to be zero entries.
+    fn missing_all(&self) -> Vec<AclEntryKind> {
+        if !self.has_entries {
+            return Vec::new();
+        }
+
+        [
+            (AclEntryKind::User, self.has_user),
+            (AclEntryKind::Group, self.has_group),
+            (AclEntryKind::Other, self.has_other),
+        ]
+        .into_iter()
+        .filter_map(|(kind, present)| (!present).then_some(kind))
+        .collect()
+    }
+
+    /// Describe the missing required entries of the `acl_name` ACL, with the
+    /// input indices of its entries, e.g. `default ACL (entries 3-5): missing
+    /// required entries "group", "other"`.
+    fn describe_missing(&self, acl_name: &str) -> Option<String> {
+        let missing = self.missing_all();
+        let noun = match missing.len() {
+            0 => return None,
+            1 => "entry",
+            _ => "entries",
+        };
+        let kinds = missing
+            .iter()
+            .map(|kind| format!("\"{kind}\""))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Some(format!(
+            "{acl_name} ACL ({}): missing required {noun} {kinds}",
+            describe_indices(&self.indices)
+        ))
+    }
+
+    /// Return permissions for a computed mask entry, if one is needed.
+    const fn mask_perms(&self) -> Option<Perm> {
+        if self.has_mask || !self.need_mask {
+            None
+        } else {
+            Some(self.mask_perms)
+        }
+    }
+}
+
+/// Describe a sorted list of input indices, collapsing runs, e.g. `entries
+/// 0-2, 5` or `entry 4`.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn describe_indices(indices: &[usize]) -> String {
+    let mut runs = Vec::<(usize, usize)>::new();
+    for &i in indices {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == i => *end = i,
+            _ => runs.push((i, i)),
+        }
+    }
+
+    let runs = runs
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if indices.len() == 1 {
+        format!("entry {runs}")
+    } else {
+        format!("entries {runs}")
+    }
+}
+
+/// Return true if path exists and it's not a directory. Use the file type in
+/// `options` if the caller knows it.
+fn is_non_directory(path: &Path, options: AclOption) -> bool {
+    if options.contains(AclOption::KNOWN_NON_DIR) {
+        return true;
+    } else if options.contains(AclOption::KNOWN_DIR) {
+        return false;
+    }
+
+    let result = if options.contains(AclOption::SYMLINK_ACL) {
         path.symlink_metadata()
     } else {
         path.metadata()
@@ -421,8 +1185,89 @@ mod acl_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_iter() -> io::Result<()> {
+        let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+        let mut entries = vec![
+            AclEntry::allow_user("11501", rwx, None),
+            AclEntry::allow_group("11502", Perm::READ, None),
+        ];
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        entries.extend([
+            AclEntry::allow_user("", rwx, None),
+            AclEntry::allow_group("", rwx, None),
+            AclEntry::allow_mask(rwx, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ]);
+
+        let mut acl = Acl::from_entries(&entries)?;
+        let expected = acl.entries()?;
+        let actual = acl
+            .iter()
+            .map(|entry| entry?.to_entry())
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(actual, expected);
+
+        let ids = acl
+            .iter()
+            .filter_map(|entry| entry.map(|entry| entry.id()).transpose())
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(ids, vec![11501, 11502]);
+
+        let entry = acl
+            .iter()
+            .find(|entry| entry.as_ref().is_ok_and(|entry| entry.id() == Some(11501)))
+            .unwrap()?;
+        assert_eq!(entry.kind(), AclEntryKind::User);
+        assert_eq!(entry.name()?, "11501");
+        assert_eq!(entry.perms(), rwx);
+        assert!(entry.allow());
+
+        // The iterator starts over each time.
+        assert_eq!(acl.iter().count(), entries.len());
+        assert_eq!(Acl::from_entries(&[])?.iter().count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_iter_default() -> io::Result<()> {
+        let mut acl = Acl::from_entries(&[
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            AclEntry::allow_other(Perm::READ, None),
+        ])?;
+        acl.default_acl = true;
+
+        for entry in acl.iter() {
+            assert_eq!(entry?.flags(), Flag::DEFAULT);
+        }
+        Ok(())
+    }
+
     #[test]
     #[cfg(target_os = "macos")]
+    fn test_iter_guid() -> io::Result<()> {
+        // A GUID that doesn't map to a uid/gid is kept as the entry's name.
+        let guid = uuid::Uuid::parse_str("abcdefab-cdef-abcd-efab-cdef00000001").unwrap();
+        let entries = vec![
+            AclEntry::allow_user("11501", Perm::READ, None),
+            AclEntry::allow_user(&guid.to_string(), Perm::READ, None),
+        ];
+
+        let mut acl = Acl::from_entries(&entries)?;
+        let guids = acl
+            .iter()
+            .map(|entry| entry.map(|entry| entry.guid()))
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(guids, vec![Some(entries[0].guid()?), Some(guid)]);
+        assert_eq!(entries[1].guid()?, guid);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(target_os = "macos", exacl_extended))]
     fn test_write_acl_macos() -> io::Result<()> {
         let mut entries = Vec::<AclEntry>::new();
         let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
@@ -442,8 +1287,8 @@ mod acl_tests {
         assert!(!acl.is_empty());
         acl.write(file.as_ref(), AclOption::empty())?;
 
-        // Even though the last entry is a group, the `acl_to_text` representation
-        // displays it as `user`.
+        // macOS `acl_to_text` may display the last entry as `user`. Our text
+        // format takes the entry kind from `mbr_uuid_to_id`, so it stays a group.
         assert_eq!(
             acl.to_string()?,
             r#"allow::group:_spotlight:read,write,execute
@@ -514,6 +1359,43 @@ allow::other::read,write,execute
         Ok(())
     }
 
+    #[test]
+    fn test_read_write_fd() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let fd = file.as_file();
+
+        let mut entries = Acl::read_fd(fd, AclOption::empty())?.entries()?;
+        entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+
+        // Skip writing if file uses NFSv4 ACL.
+        #[cfg(target_os = "freebsd")]
+        if Acl::is_nfs4(file.as_ref(), AclOption::empty())? {
+            return Ok(());
+        }
+
+        Acl::from_entries(&entries)?.write_fd(fd, AclOption::empty())?;
+
+        let acl = Acl::read(file.as_ref(), AclOption::empty())?;
+        assert!(acl.entries()?.contains(&entries[entries.len() - 1]));
+        assert_eq!(
+            Acl::read_fd(fd, AclOption::empty())?.entries()?,
+            acl.entries()?
+        );
+
+        #[cfg(target_os = "linux")]
+        {
+            let err = Acl::read_fd(fd, AclOption::DEFAULT_ACL).err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                "Linux does not support default ACL's with file descriptors"
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(target_os = "macos")]
     fn test_write_acl_big() -> io::Result<()> {
@@ -546,8 +1428,16 @@ allow::other::read,write,execute
             entries.push(AclEntry::allow_user("11501", rwx, None));
         }
 
+        let expected = "too many ACL entries: macOS allows at most 128 entries; \
+             consolidate entries with the same permissions into a group entry";
         let err = Acl::from_entries(&entries).err().unwrap();
-        assert_eq!(err.to_string(), "Too many ACL entries");
+        assert_eq!(err.to_string(), expected);
+
+        // The iterator's size is not known in advance.
+        let err = Acl::from_entries(entries.iter().filter(|_| true))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), expected);
     }
 
     #[test]
@@ -627,7 +1517,10 @@ allow::other::read,write,execute
             ];
 
             let err = Acl::from_entries(&entries).err().unwrap();
-            assert_eq!(err.to_string(), "missing required entry \"user\"");
+            assert_eq!(
+                err.to_string(),
+                "access ACL (entries 0-2): missing required entry \"user\""
+            );
 
             entries.push(AclEntry::allow_user("", Perm::READ, None));
             let acl = Acl::from_entries(&entries).unwrap();
@@ -642,10 +1535,51 @@ allow::other::read,write,execute
 
             entries.push(AclEntry::allow_group("", Perm::WRITE, None));
             let err = Acl::from_entries(&entries).err().unwrap();
-            assert_eq!(err.to_string(), "entry 4: duplicate entry for \"group\"");
+            assert_eq!(
+                err.to_string(),
+                "entry 4 (allow::group::write): duplicate entry for \"group\""
+            );
         }
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_from_entries_acl_check() {
+        // uid 4294967295 is ACL_UNDEFINED_ID, which `acl_check` rejects.
+        let undefined = AclEntry {
+            numeric: true,
+            ..AclEntry::allow_user("4294967295", Perm::READ, None)
+        };
+        let mut entries = vec![
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            undefined.clone(),
+            AclEntry::allow_other(Perm::READ, None),
+        ];
+
+        // The index is the position in `entries`, not in the sorted ACL.
+        let err = Acl::from_entries(&entries).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "entry 2 (allow::user:4294967295:read): Missing or wrong entry (acl_check returned 12288)"
+        );
+
+        entries.remove(2);
+        entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
+        entries.push(AclEntry {
+            flags: Flag::DEFAULT,
+            ..undefined
+        });
+        entries.push(AclEntry::allow_group("", Perm::READ, Flag::DEFAULT));
+        entries.push(AclEntry::allow_other(Perm::READ, Flag::DEFAULT));
+
+        let err = Acl::from_unified_entries(&entries).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "entry 4 (allow:default:user:4294967295:read): Duplicate entries (acl_check returned 8192)"
+        );
+    }
+
     #[test]
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     fn test_from_unified_entries() {
@@ -659,9 +1593,13 @@ allow::other::read,write,execute
             AclEntry::allow_user("501", Perm::EXECUTE, Flag::DEFAULT),
         ];
 
-        // Missing required entries.
+        // Missing required entries are reported for both ACL's.
         let err = Acl::from_unified_entries(&entries).err().unwrap();
-        assert_eq!(err.to_string(), "missing required entry \"user\"");
+        assert_eq!(
+            err.to_string(),
+            "access ACL (entry 0): missing required entries \"user\", \"group\", \"other\"; \
+             default ACL (entry 1): missing required entries \"user\", \"group\", \"other\""
+        );
 
         entries.push(AclEntry::allow_group("", Perm::WRITE, None));
         entries.push(AclEntry::allow_user("", Perm::READ, None));
@@ -669,10 +1607,20 @@ allow::other::read,write,execute
 
         // Missing required default entries.
         let err = Acl::from_unified_entries(&entries).err().unwrap();
-        assert_eq!(err.to_string(), "missing required default entry \"user\"");
+        assert_eq!(
+            err.to_string(),
+            "default ACL (entry 1): missing required entries \"user\", \"group\", \"other\""
+        );
 
         entries.push(AclEntry::allow_group("", Perm::WRITE, Flag::DEFAULT));
         entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
+
+        let err = Acl::from_unified_entries(&entries).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "default ACL (entries 1, 5-6): missing required entry \"other\""
+        );
+
         entries.push(AclEntry::allow_other(Perm::empty(), Flag::DEFAULT));
 
         let (a, d) = Acl::from_unified_entries(&entries).unwrap();
@@ -694,14 +1642,174 @@ allow::other::read,write,execute
         let err = Acl::from_unified_entries(&entries).err().unwrap();
         assert_eq!(
             err.to_string(),
-            "entry 8: duplicate default entry for \"group\""
+            "entry 8 (allow:default:group::write): duplicate default entry for \"group\""
         );
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_to_any_text() -> io::Result<()> {
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("0", Perm::READ | Perm::EXECUTE, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ];
+        let acl = Acl::from_entries(&entries)?;
+
+        let text = acl.to_any_text(&TextOptions::default())?;
+        assert_eq!(
+            text,
+            "user::rw-\nuser:root:r-x\ngroup::r--\nmask::r-x\nother::---"
+        );
+
+        let options = TextOptions {
+            prefix: "default:".to_string(),
+            separator: ',',
+            numeric_ids: true,
+            abbreviate: true,
+            ..TextOptions::default()
+        };
+        let text = acl.to_any_text(&options)?;
+        assert_eq!(
+            text,
+            "default:u::rw-,default:u:0:r-x,default:g::r--,default:m::r-x,default:o::---"
+        );
+
+        let options = TextOptions {
+            separator: '\u{e9}',
+            ..TextOptions::default()
+        };
+        let err = acl.to_any_text(&options).unwrap_err();
+        assert_eq!(err.to_string(), "invalid separator: '\u{e9}'");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_len() -> io::Result<()> {
+        let mut entries = Vec::<AclEntry>::new();
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        entries.extend(crate::from_mode(0o640));
+
+        for i in 0..100 {
+            entries.push(AclEntry::allow_user(
+                &(20000 + i).to_string(),
+                Perm::READ,
+                None,
+            ));
+        }
+
+        let acl = Acl::from_entries(&entries)?;
+
+        // A mask entry is added on Linux and FreeBSD.
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        assert_eq!(acl.len(), entries.len() + 1);
+        #[cfg(target_os = "macos")]
+        assert_eq!(acl.len(), entries.len());
+
+        let actual = acl.entries()?;
+        assert_eq!(actual.len(), acl.len());
+        assert!(!acl.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_brand() -> io::Result<()> {
+        let acl = Acl::from_entries(&[])?;
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(acl.brand(), AclBrand::Posix1e);
+
+        #[cfg(target_os = "macos")]
+        assert_eq!(acl.brand(), AclBrand::Extended);
+
+        #[cfg(target_os = "freebsd")]
+        {
+            assert_eq!(acl.brand(), AclBrand::Unknown);
+            let entries = crate::from_mode(0o644);
+            assert_eq!(Acl::from_entries(&entries)?.brand(), AclBrand::Posix1e);
+        }
+
+        assert_eq!(acl.is_posix(), acl.brand() != AclBrand::Extended);
+
+        Ok(())
+    }
+
     #[test]
     fn test_empty_acl() -> io::Result<()> {
         let acl = Acl::from_entries(&[])?;
         assert!(acl.is_empty());
+        assert_eq!(acl.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_acloption_string() {
+        let options = AclOption::DEFAULT_ACL | AclOption::SYNC_FILES;
+        assert_eq!(options.to_string(), "default_acl,sync_files");
+        assert_eq!(
+            "default_acl, sync_files".parse::<AclOption>().unwrap(),
+            options
+        );
+        assert_eq!("".parse::<AclOption>().unwrap(), AclOption::empty());
+        assert_eq!(AclOption::IGNORE_EXPECTED_FILE_ERR.to_string(), "");
+
+        let all = OPTION_NAMES
+            .iter()
+            .fold(AclOption::empty(), |acc, (option, _)| acc | *option);
+        assert_eq!(all.to_string().parse::<AclOption>().unwrap(), all);
+
+        let err = "access_acl,bogus".parse::<AclOption>().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unknown option `bogus`, expected one of `access_acl`"));
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_acloption_clap() {
+        use clap::ValueEnum;
+
+        assert_eq!(AclOption::value_variants().len(), OPTION_NAMES.len());
+        for option in AclOption::value_variants() {
+            let value = option.to_possible_value().unwrap();
+            assert_eq!(value.get_name(), option.to_string());
+            assert_eq!(
+                AclOption::from_str(value.get_name(), false).unwrap(),
+                *option
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_describe_indices() {
+        assert_eq!(describe_indices(&[4]), "entry 4");
+        assert_eq!(describe_indices(&[0, 1, 2, 5]), "entries 0-2, 5");
+        assert_eq!(describe_indices(&[1, 3, 4]), "entries 1, 3-4");
+    }
+
+    #[test]
+    fn test_known_kind() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let missing = dir.path().join("missing");
+
+        // The file type in the options is trusted without a `stat`.
+        assert!(!is_non_directory(&missing, AclOption::empty()));
+        assert!(is_non_directory(&missing, AclOption::known_kind(false)));
+        assert!(!is_non_directory(dir.path(), AclOption::known_kind(true)));
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        {
+            let options = AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR;
+            assert!(Acl::read(&missing, options).is_err());
+            let acl = Acl::read(&missing, options | AclOption::known_kind(false))?;
+            assert!(acl.is_empty());
+        }
+
         Ok(())
     }
 }
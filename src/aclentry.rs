@@ -1,19 +1,40 @@
 //! Provides `AclEntry` implementation.
 
+#[cfg(not(exacl_core))]
 use crate::failx::fail_custom;
 use crate::flag::Flag;
-use crate::format;
+use crate::format::syntax::{
+    ALLOW, COMMENT, DENY, ESCAPE, FIELD_SEPARATOR, NUMERIC_GROUP, NUMERIC_USER,
+};
+use crate::format::{self, FormatVersion};
 use crate::perm::Perm;
+#[cfg(not(exacl_core))]
 use crate::qualifier::Qualifier;
+#[cfg(not(exacl_core))]
 use crate::util::*;
 
+#[cfg(not(exacl_core))]
+use crate::acl::AclBrand;
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::fmt;
+#[cfg(not(exacl_core))]
 use std::io;
+#[cfg(not(exacl_core))]
+use std::marker::PhantomData;
+#[cfg(target_os = "macos")]
+use uuid::Uuid;
+
+/// Name of the macOS group that contains every user.
+const EVERYONE_GROUP: &str = "everyone";
 
-/// Kind of ACL entry (User, Group, Mask, Other, or Unknown).
+/// Kind of ACL entry (User, Group, Mask, Other, Everyone, or Unknown).
+///
+/// Every kind exists on every platform, so ACL's can be read and written as
+/// text or with serde anywhere. Writing an entry whose kind is not supported
+/// by the platform fails.
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
@@ -26,29 +47,39 @@ pub enum AclEntryKind {
     /// Entry represents a group.
     Group,
 
-    /// Entry represents a Posix.1e "mask" entry.
-    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
-    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    /// Entry represents a Posix.1e "mask" entry (Linux and `FreeBSD` only).
     Mask,
 
-    /// Entry represents a Posix.1e "other" entry.
-    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
-    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    /// Entry represents a Posix.1e "other" entry (Linux and `FreeBSD` only).
     Other,
 
-    /// Entry represents a NFS "everyone" entry.
-    #[cfg(any(docsrs, target_os = "freebsd"))]
-    #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
+    /// Entry represents a NFS "everyone" entry (`FreeBSD` only).
     Everyone,
 
     /// Entry represents a possibly corrupt ACL entry, caused by an unknown tag.
     Unknown,
 }
 
+/// Kind of file system object an entry is checked against, used by
+/// [`AclEntry::matches`] and [`AclEntry::inherited_by`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathKind {
+    /// A regular file, symlink or other non-directory.
+    File,
+
+    /// A directory.
+    Directory,
+}
+
 /// ACL entry with allow/deny semantics.
 ///
 /// ACL entries are ordered so sorting will automatically put the ACL in
-/// canonical order.
+/// canonical order: entries without flags come first, then deny entries
+/// before allow entries, then by kind (user, group, mask, other, everyone,
+/// unknown), then by name. Any remaining ties are broken by the `numeric`
+/// field, flags and perms.
+/// This ordering is total, consistent with `Eq`, and will not change across
+/// crate versions.
 ///
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -72,6 +103,15 @@ pub struct AclEntry {
     /// allow=true.
     #[cfg_attr(feature = "serde", serde(default = "default_allow"))]
     pub allow: bool,
+
+    /// True if `name` is an explicit decimal uid/gid. A numeric name is never
+    /// looked up in the user/group database, so it stays unambiguous even if a
+    /// user or group is named "1000". Written as `uid`/`gid` in the text format.
+    /// [`getfacl`](crate::getfacl) sets this only with
+    /// [`AclOption::NUMERIC_IDS`](crate::AclOption::NUMERIC_IDS); otherwise a
+    /// uid/gid that has no name is named by its id, but isn't numeric.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_false"))]
+    pub numeric: bool,
 }
 
 // Default value of allow; used for serde.
@@ -80,6 +120,13 @@ const fn default_allow() -> bool {
     true
 }
 
+// Omit numeric when false; used for serde.
+#[cfg(feature = "serde")]
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 impl Ord for AclEntry {
     fn cmp(&self, other: &Self) -> Ordering {
         // Entries with flags last.
@@ -101,8 +148,18 @@ impl Ord for AclEntry {
             return ret;
         }
 
-        // Lastly, order by name.
-        self.name.cmp(&other.name)
+        // Order by name.
+        let ret = self.name.cmp(&other.name);
+        if ret != Ordering::Equal {
+            return ret;
+        }
+
+        // Lastly, break ties using numeric, flags and perms so the order is
+        // total.
+        self.numeric
+            .cmp(&other.numeric)
+            .then_with(|| self.flags.cmp(&other.flags))
+            .then_with(|| self.perms.cmp(&other.perms))
     }
 }
 
@@ -128,6 +185,7 @@ impl AclEntry {
             perms,
             flags: flags.unwrap_or_default(),
             allow,
+            numeric: false,
         }
     }
 
@@ -150,7 +208,7 @@ impl AclEntry {
     }
 
     /// Construct an ALLOW access control entry for mask.
-    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core))]
     #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
     #[must_use]
     pub fn allow_mask<F>(perms: Perm, flags: F) -> AclEntry
@@ -161,7 +219,7 @@ impl AclEntry {
     }
 
     /// Construct an ALLOW access control entry for other.
-    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core))]
     #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
     #[must_use]
     pub fn allow_other<F>(perms: Perm, flags: F) -> AclEntry
@@ -172,8 +230,17 @@ impl AclEntry {
     }
 
     /// Construct a DENY access control entry for a user.
-    #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
-    #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
+    #[cfg(any(
+        docsrs,
+        target_os = "macos",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "nfs4"
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(any(target_os = "macos", target_os = "freebsd", feature = "nfs4")))
+    )]
     #[must_use]
     pub fn deny_user<F>(name: &str, perms: Perm, flags: F) -> AclEntry
     where
@@ -183,8 +250,17 @@ impl AclEntry {
     }
 
     /// Construct a DENY access control entry for a group.
-    #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
-    #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
+    #[cfg(any(
+        docsrs,
+        target_os = "macos",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "nfs4"
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(any(target_os = "macos", target_os = "freebsd", feature = "nfs4")))
+    )]
     #[must_use]
     pub fn deny_group<F>(name: &str, perms: Perm, flags: F) -> AclEntry
     where
@@ -193,63 +269,217 @@ impl AclEntry {
         AclEntry::new(AclEntryKind::Group, name, perms, flags.into(), false)
     }
 
-    /// Return an `AclEntry` constructed from a native `acl_entry_t`.
-    pub(crate) fn from_raw(entry: acl_entry_t, acl: acl_t) -> io::Result<AclEntry> {
-        let (allow, qualifier, perms, flags) = xacl_get_entry(acl, entry)?;
+    /// Construct an ALLOW access control entry for NFSv4 `everyone@`.
+    #[cfg(any(docsrs, exacl_nfs4))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "freebsd", feature = "nfs4"))))]
+    #[must_use]
+    pub fn allow_everyone<F>(perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        AclEntry::new(AclEntryKind::Everyone, "", perms, flags.into(), true)
+    }
 
-        let (kind, name) = match qualifier {
-            Qualifier::Unknown(s) => (AclEntryKind::Unknown, s),
+    /// Construct a DENY access control entry for NFSv4 `everyone@`.
+    #[cfg(any(docsrs, exacl_nfs4))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "freebsd", feature = "nfs4"))))]
+    #[must_use]
+    pub fn deny_everyone<F>(perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        AclEntry::new(AclEntryKind::Everyone, "", perms, flags.into(), false)
+    }
 
-            #[cfg(target_os = "macos")]
-            Qualifier::User(_) | Qualifier::Guid(_) => (AclEntryKind::User, qualifier.name()?),
+    /// Construct an ALLOW access control entry for everyone.
+    ///
+    /// The entry uses the platform's representation: an `Other` entry on
+    /// Linux, an `Everyone` (`everyone@`) entry on `FreeBSD`, and an entry for
+    /// the `everyone` group on macOS. A Posix.1e ACL on `FreeBSD` has no
+    /// `everyone@` entry; use [`AclEntry::everyone_for`] with the ACL's brand.
+    #[must_use]
+    pub fn everyone<F>(perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        #[cfg(target_os = "macos")]
+        let kind = AclEntryKind::Group;
+        #[cfg(target_os = "freebsd")]
+        let kind = AclEntryKind::Everyone;
+        #[cfg(any(target_os = "linux", exacl_core))]
+        let kind = AclEntryKind::Other;
 
-            #[cfg(target_os = "macos")]
-            Qualifier::Group(_) => (AclEntryKind::Group, qualifier.name()?),
+        AclEntry::everyone_of_kind(kind, perms, flags.into())
+    }
 
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::User(_) | Qualifier::UserObj => (AclEntryKind::User, qualifier.name()?),
+    /// Construct an ALLOW access control entry for everyone, as represented
+    /// in an ACL of the given brand: an `Other` entry for
+    /// [`AclBrand::Posix1e`], an `Everyone` entry for [`AclBrand::Nfs4`], and
+    /// an entry for the `everyone` group for [`AclBrand::Extended`]. An
+    /// unknown brand is the same as [`AclEntry::everyone`].
+    #[cfg(not(exacl_core))]
+    #[must_use]
+    pub fn everyone_for<F>(brand: AclBrand, perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        let kind = match brand {
+            AclBrand::Posix1e => AclEntryKind::Other,
+            AclBrand::Nfs4 => AclEntryKind::Everyone,
+            AclBrand::Extended => AclEntryKind::Group,
+            AclBrand::Unknown => return AclEntry::everyone(perms, flags),
+        };
 
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Group(_) | Qualifier::GroupObj => (AclEntryKind::Group, qualifier.name()?),
+        AclEntry::everyone_of_kind(kind, perms, flags.into())
+    }
 
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Mask => (AclEntryKind::Mask, qualifier.name()?),
+    /// Construct an ALLOW entry for everyone of the given kind.
+    fn everyone_of_kind(kind: AclEntryKind, perms: Perm, flags: Option<Flag>) -> AclEntry {
+        let name = if kind == AclEntryKind::Group {
+            EVERYONE_GROUP
+        } else {
+            ""
+        };
+        AclEntry::new(kind, name, perms, flags, true)
+    }
 
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Other => (AclEntryKind::Other, qualifier.name()?),
+    /// Return true if the entry applies to everyone: an `Other` or
+    /// `Everyone` entry, or an entry for the `everyone` group on macOS.
+    #[must_use]
+    pub fn is_everyone(&self) -> bool {
+        match self.kind {
+            AclEntryKind::Other | AclEntryKind::Everyone => true,
+            AclEntryKind::Group => cfg!(target_os = "macos") && self.name == EVERYONE_GROUP,
+            _ => false,
+        }
+    }
 
-            #[cfg(target_os = "freebsd")]
-            Qualifier::Everyone => (AclEntryKind::Everyone, qualifier.name()?),
+    /// Return true if the entry belongs to a default ACL.
+    #[allow(clippy::missing_const_for_fn)]
+    pub(crate) fn is_default(&self) -> bool {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            exacl_core,
+            feature = "strict_posix"
+        ))]
+        return self.flags.contains(Flag::DEFAULT);
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            exacl_core,
+            feature = "strict_posix"
+        )))]
+        return false;
+    }
+
+    /// Return true if the entry was inherited. Inherited entries are
+    /// evaluated after explicit entries.
+    #[allow(clippy::missing_const_for_fn)]
+    pub(crate) fn is_inherited(&self) -> bool {
+        #[cfg(exacl_extended)]
+        return self.flags.contains(Flag::INHERITED);
+
+        #[cfg(not(exacl_extended))]
+        return false;
+    }
+
+    /// Return true if both entries refer to the same user or group.
+    pub(crate) fn is_same_principal(&self, other: &AclEntry) -> bool {
+        self.kind == other.kind && self.name == other.name
+    }
+
+    /// Return the GUID of the entry's user or group.
+    ///
+    /// The name is looked up in the user/group database, or parsed as a
+    /// GUID. To get the exact GUID of an entry read from a file, use
+    /// [`AclEntryRef::guid`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the name can't be resolved.
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    pub fn guid(&self) -> io::Result<Uuid> {
+        self.qualifier(false)?.guid()
+    }
+
+    /// Return true if the entry applies to access checks on the file or
+    /// directory itself.
+    ///
+    /// Default entries (Linux and `FreeBSD`) and `ONLY_INHERIT` entries never
+    /// apply to the object itself; they only matter as templates for new
+    /// files in a directory. See [`AclEntry::inherited_by`].
+    #[must_use]
+    pub fn matches(&self) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+        if self.flags.contains(Flag::DEFAULT) {
+            return false;
+        }
+
+        #[cfg(exacl_extended)]
+        if self.flags.contains(Flag::ONLY_INHERIT) {
+            return false;
+        }
+
+        true
+    }
+
+    /// Return true if a new object of the given kind, created in a directory
+    /// with this entry, inherits the entry.
+    ///
+    /// Default entries are inherited by both files and directories. Otherwise,
+    /// `FILE_INHERIT` entries are inherited by files and `DIRECTORY_INHERIT`
+    /// entries by directories. Entries on a file are never inherited.
+    #[must_use]
+    #[allow(unused_variables)]
+    pub fn inherited_by(&self, path_kind: PathKind) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+        if self.flags.contains(Flag::DEFAULT) {
+            return true;
+        }
+
+        #[cfg(exacl_extended)]
+        return match path_kind {
+            PathKind::File => self.flags.contains(Flag::FILE_INHERIT),
+            PathKind::Directory => self.flags.contains(Flag::DIRECTORY_INHERIT),
         };
 
-        Ok(AclEntry {
-            kind,
-            name,
-            perms,
-            flags,
-            allow,
-        })
+        #[cfg(not(exacl_extended))]
+        false
     }
 
-    pub(crate) fn add_to_acl(&self, acl: &mut acl_t) -> io::Result<()> {
-        let qualifier = self.qualifier()?;
-        xacl_add_entry(acl, self.allow, &qualifier, self.perms, self.flags)?;
+    /// Return an `AclEntry` constructed from a native `acl_entry_t`.
+    #[cfg(not(exacl_core))]
+    pub(crate) fn from_raw(entry: acl_entry_t, acl: acl_t) -> io::Result<AclEntry> {
+        AclEntryRef::from_raw(entry, acl, false)?.to_entry()
+    }
 
-        Ok(())
+    /// Add entry to a native ACL. If `strict` is true, user/group names must
+    /// be in the database; a numeric entry is still accepted. Return the new
+    /// native entry.
+    #[cfg(not(exacl_core))]
+    pub(crate) fn add_to_acl(&self, acl: &mut acl_t, strict: bool) -> io::Result<acl_entry_t> {
+        let qualifier = self.qualifier(strict)?;
+        xacl_add_entry(acl, self.allow, &qualifier, self.perms, self.flags)
     }
 
-    fn qualifier(&self) -> io::Result<Qualifier> {
+    #[cfg(not(exacl_core))]
+    pub(crate) fn qualifier(&self, strict: bool) -> io::Result<Qualifier> {
         let qualifier = match self.kind {
-            AclEntryKind::User => Qualifier::user_named(&self.name)?,
-            AclEntryKind::Group => Qualifier::group_named(&self.name)?,
+            AclEntryKind::User if self.numeric => Qualifier::User(parse_id(&self.name)?),
+            AclEntryKind::Group if self.numeric => Qualifier::Group(parse_id(&self.name)?),
+            AclEntryKind::User => Qualifier::user_named(&self.name, strict)?,
+            AclEntryKind::Group => Qualifier::group_named(&self.name, strict)?,
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
             AclEntryKind::Mask => Qualifier::mask_named(&self.name)?,
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
             AclEntryKind::Other => Qualifier::other_named(&self.name)?,
             #[cfg(target_os = "freebsd")]
             AclEntryKind::Everyone => Qualifier::everyone_named(&self.name)?,
-            AclEntryKind::Unknown => {
-                return fail_custom("unsupported kind: \"unknown\"");
+            kind => {
+                return fail_custom(&format!("unsupported kind: \"{kind}\""));
             }
         };
 
@@ -257,6 +487,228 @@ impl AclEntry {
     }
 }
 
+/// An entry read from a native [`Acl`](crate::Acl) by
+/// [`Acl::iter`](crate::Acl::iter).
+///
+/// The user or group name is not looked up until [`AclEntryRef::name`] is
+/// called. Use [`AclEntryRef::id`] to match entries by uid or gid without a
+/// lookup.
+#[cfg(not(exacl_core))]
+#[derive(Debug)]
+pub struct AclEntryRef<'a> {
+    qualifier: Qualifier,
+    #[cfg(target_os = "macos")]
+    guid: Option<Uuid>,
+    perms: Perm,
+    flags: Flag,
+    allow: bool,
+    _acl: PhantomData<&'a ()>,
+}
+
+#[cfg(not(exacl_core))]
+impl<'a> AclEntryRef<'a> {
+    /// Read a native entry. If `default_acl` is true, set the `DEFAULT` flag.
+    pub(crate) fn from_raw(
+        entry: acl_entry_t,
+        acl: acl_t,
+        default_acl: bool,
+    ) -> io::Result<AclEntryRef<'a>> {
+        let (allow, qualifier, perms, mut flags) = xacl_get_entry(acl, entry)?;
+        if default_acl {
+            flags |= Flag::DEFAULT;
+        }
+
+        // Keep the GUID, since resolving it to a uid/gid loses it.
+        #[cfg(target_os = "macos")]
+        let guid = match qualifier {
+            Qualifier::Unknown(_) => None,
+            _ => Some(xacl_get_guid(entry)?),
+        };
+
+        Ok(AclEntryRef {
+            qualifier,
+            #[cfg(target_os = "macos")]
+            guid,
+            perms,
+            flags,
+            allow,
+            _acl: PhantomData,
+        })
+    }
+
+    /// Return the kind of entry.
+    #[must_use]
+    pub const fn kind(&self) -> AclEntryKind {
+        match self.qualifier {
+            Qualifier::Unknown(_) => AclEntryKind::Unknown,
+
+            #[cfg(target_os = "macos")]
+            Qualifier::User(_) | Qualifier::Guid(_) => AclEntryKind::User,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::User(_) | Qualifier::UserObj => AclEntryKind::User,
+
+            #[cfg(target_os = "macos")]
+            Qualifier::Group(_) => AclEntryKind::Group,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Group(_) | Qualifier::GroupObj => AclEntryKind::Group,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Mask => AclEntryKind::Mask,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Other => AclEntryKind::Other,
+
+            #[cfg(target_os = "freebsd")]
+            Qualifier::Everyone => AclEntryKind::Everyone,
+        }
+    }
+
+    /// Return the uid or gid of a named user or group entry.
+    ///
+    /// Return None for other entries, such as the owner's entries.
+    #[must_use]
+    pub const fn id(&self) -> Option<u32> {
+        match self.qualifier {
+            Qualifier::User(id) | Qualifier::Group(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Return the GUID that identifies the principal, as stored in the ACL.
+    ///
+    /// Unlike [`AclEntryRef::name`], this doesn't depend on how the GUID maps
+    /// to a uid/gid on this system. Return None for an entry with an unknown
+    /// tag.
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    #[must_use]
+    pub const fn guid(&self) -> Option<Uuid> {
+        self.guid
+    }
+
+    /// Return the name of the principal, as in [`AclEntry::name`].
+    ///
+    /// This looks up the user or group name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    pub fn name(&self) -> io::Result<String> {
+        self.qualifier.name()
+    }
+
+    /// Return the permissions of the entry.
+    #[must_use]
+    pub const fn perms(&self) -> Perm {
+        self.perms
+    }
+
+    /// Return the flags of the entry.
+    #[must_use]
+    pub const fn flags(&self) -> Flag {
+        self.flags
+    }
+
+    /// Return true if the entry allows access; false if it denies access.
+    #[must_use]
+    pub const fn allow(&self) -> bool {
+        self.allow
+    }
+
+    /// Return the entry as an [`AclEntry`], looking up its name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    ///
+    /// A user or group without a name in the database is named by its decimal
+    /// id, but isn't marked `numeric`, so its text is still `user:<id>` or
+    /// `group:<id>`. Use [`AclEntryRef::to_numeric_entry`] for numeric
+    /// entries.
+    pub fn to_entry(&self) -> io::Result<AclEntry> {
+        Ok(self.with_name(self.name()?, false))
+    }
+
+    /// Return the entry as an [`AclEntry`], naming a user or group by its
+    /// decimal id without a lookup.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the entry has no id and its name can't be
+    /// found.
+    pub fn to_numeric_entry(&self) -> io::Result<AclEntry> {
+        match self.id() {
+            Some(id) => Ok(self.with_name(id.to_string(), true)),
+            None => Ok(self.with_name(self.name()?, false)),
+        }
+    }
+
+    fn with_name(&self, name: String, numeric: bool) -> AclEntry {
+        AclEntry {
+            kind: self.kind(),
+            name,
+            perms: self.perms,
+            flags: self.flags,
+            allow: self.allow,
+            numeric,
+        }
+    }
+}
+
+/// Parse the name of a numeric entry as a uid/gid.
+#[cfg(not(exacl_core))]
+fn parse_id(name: &str) -> io::Result<u32> {
+    match name.parse::<u32>() {
+        Ok(id) => Ok(id),
+        Err(_) => fail_custom(&format!("invalid numeric id: {name:?}")),
+    }
+}
+
+impl AclEntryKind {
+    /// Return a stable integer code for the kind, for use in FFI or binary
+    /// formats.
+    ///
+    /// | Code | Kind       |
+    /// |------|------------|
+    /// | 0    | `Unknown`  |
+    /// | 1    | `User`     |
+    /// | 2    | `Group`    |
+    /// | 3    | `Mask`     |
+    /// | 4    | `Other`    |
+    /// | 5    | `Everyone` |
+    ///
+    /// These values will not change.
+    #[must_use]
+    pub const fn as_u8(self) -> u8 {
+        match self {
+            AclEntryKind::Unknown => 0,
+            AclEntryKind::User => 1,
+            AclEntryKind::Group => 2,
+            AclEntryKind::Mask => 3,
+            AclEntryKind::Other => 4,
+            AclEntryKind::Everyone => 5,
+        }
+    }
+
+    /// Return the kind for a code returned by [`AclEntryKind::as_u8`].
+    ///
+    /// Returns None if the code is not assigned.
+    #[must_use]
+    pub const fn from_u8(code: u8) -> Option<AclEntryKind> {
+        match code {
+            0 => Some(AclEntryKind::Unknown),
+            1 => Some(AclEntryKind::User),
+            2 => Some(AclEntryKind::Group),
+            3 => Some(AclEntryKind::Mask),
+            4 => Some(AclEntryKind::Other),
+            5 => Some(AclEntryKind::Everyone),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for AclEntryKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         format::write_aclentrykind(f, *self)
@@ -270,10 +722,9 @@ impl std::str::FromStr for AclEntryKind {
         match s {
             "u" => Ok(AclEntryKind::User),
             "g" => Ok(AclEntryKind::Group),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
             "o" => Ok(AclEntryKind::Other),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
             "m" => Ok(AclEntryKind::Mask),
+            "e" => Ok(AclEntryKind::Everyone),
             _ => format::read_aclentrykind(s),
         }
     }
@@ -283,66 +734,251 @@ impl fmt::Display for AclEntry {
     /// Format an `AclEntry` 5-tuple:
     ///   `<allow>:<flags>:<kind>:<name>:<perms>`
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let allow = if self.allow { "allow" } else { "deny" };
-        write!(
-            f,
-            "{}:{}:{}:{}:{}",
-            allow, self.flags, self.kind, self.name, self.perms
-        )
+        self.versioned(FormatVersion::LATEST).fmt(f)
     }
 }
 
+/// Displays an `AclEntry` using a specific text format version.
+pub(crate) struct VersionedEntry<'a> {
+    entry: &'a AclEntry,
+    version: FormatVersion,
+}
+
+impl AclEntry {
+    /// Return a wrapper that displays the entry using the given format version.
+    pub(crate) const fn versioned(&self, version: FormatVersion) -> VersionedEntry<'_> {
+        VersionedEntry {
+            entry: self,
+            version,
+        }
+    }
+}
+
+impl fmt::Display for VersionedEntry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entry = self.entry;
+        let allow = if entry.allow { ALLOW } else { DENY };
+        let sep = FIELD_SEPARATOR;
+        match self.version {
+            // Version 1 has no raw token for unknown flag bits.
+            FormatVersion::V1 => write!(f, "{allow}{sep}{}{sep}", entry.flags & Flag::all())?,
+            FormatVersion::V2 => write!(f, "{allow}{sep}{}{sep}", entry.flags)?,
+        }
+        match (self.version, entry.numeric, entry.kind) {
+            (FormatVersion::V2, true, AclEntryKind::User) => write!(f, "{NUMERIC_USER}{sep}")?,
+            (FormatVersion::V2, true, AclEntryKind::Group) => write!(f, "{NUMERIC_GROUP}{sep}")?,
+            _ => write!(f, "{}{sep}", entry.kind)?,
+        }
+        match self.version {
+            FormatVersion::V1 => write!(f, "{}", entry.name)?,
+            FormatVersion::V2 => write_escaped_name(f, &entry.name)?,
+        }
+        write!(f, "{sep}{}", entry.perms)
+    }
+}
+
+/// Displays a list of entries, one per line, e.g. for snapshot tests.
+///
+/// Each entry is written in text format version 2, so the output is the same
+/// across crate versions and can be read back with [`from_str`](crate::from_str).
+/// `Debug` output is the same as `Display`, so a `DisplayList` can be passed
+/// to `insta::assert_debug_snapshot!` as well as `assert_snapshot!`.
+///
+/// ```
+/// use exacl::{AclEntry, DisplayList, Perm};
+///
+/// let entries = vec![
+///     AclEntry::allow_group("staff", Perm::READ | Perm::WRITE, None),
+///     AclEntry::allow_user("bob", Perm::READ, None),
+/// ];
+///
+/// assert_eq!(
+///     DisplayList::new(&entries).sorted().to_string(),
+///     "allow::user:bob:read\nallow::group:staff:read,write\n"
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct DisplayList<'a> {
+    entries: &'a [AclEntry],
+    sorted: bool,
+}
+
+impl<'a> DisplayList<'a> {
+    /// Construct a wrapper that displays the entries in the order given.
+    #[must_use]
+    pub const fn new(entries: &'a [AclEntry]) -> DisplayList<'a> {
+        DisplayList {
+            entries,
+            sorted: false,
+        }
+    }
+
+    /// Display the entries in sorted order instead, so the output doesn't
+    /// depend on the order the entries were read in.
+    #[must_use]
+    pub const fn sorted(self) -> DisplayList<'a> {
+        DisplayList {
+            sorted: true,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for DisplayList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        if self.sorted {
+            entries.sort();
+        }
+
+        for entry in entries {
+            writeln!(f, "{}", entry.versioned(FormatVersion::V2))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for DisplayList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Return true if a character in a name must be escaped in the text format.
+fn needs_escape(ch: char) -> bool {
+    ch == ESCAPE || ch == FIELD_SEPARATOR || ch == COMMENT || ch.is_whitespace() || ch.is_control()
+}
+
+/// Write a name, escaping separators, comments and white space.
+///
+/// Escaped characters are written as a backslash followed by three octal
+/// digits for each UTF-8 byte, e.g. `:` is written as `\072`. This is the
+/// same convention used by the Linux `getfacl` tool.
+fn write_escaped_name(f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+    for ch in name.chars() {
+        if needs_escape(ch) {
+            let mut buf = [0; 4];
+            for byte in ch.encode_utf8(&mut buf).bytes() {
+                write!(f, "\\{byte:03o}")?;
+            }
+        } else {
+            write!(f, "{ch}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a name written by `write_escaped_name`.
+///
+/// A backslash that doesn't start an escape sequence is kept, so names such
+/// as `DOMAIN\user` can be written by hand.
+fn parse_escaped_name(value: &str) -> Result<String, format::Error> {
+    if !value.contains(ESCAPE) {
+        return Ok(value.to_string());
+    }
+
+    let mut bytes = Vec::<u8>::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        match parse_octal_escape(byte, tail) {
+            Some(code) => {
+                bytes.push(code);
+                rest = &tail[3..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8(bytes)
+        .map_err(|_| format::Error::Message(format!("Invalid escape sequence in name: `{value}`")))
+}
+
+/// Return the byte escaped by a backslash (`byte`) and three octal digits at
+/// the start of `tail`, if there is such an escape.
+fn parse_octal_escape(byte: u8, tail: &[u8]) -> Option<u8> {
+    if char::from(byte) != ESCAPE {
+        return None;
+    }
+
+    let mut code = 0u32;
+    for digit in tail.get(..3)? {
+        match digit {
+            b'0'..=b'7' => code = code * 8 + u32::from(digit - b'0'),
+            _ => return None,
+        }
+    }
+    u8::try_from(code).ok()
+}
+
 fn parse_allow(value: &str) -> Result<bool, format::Error> {
     let result = match value {
-        "allow" => true,
-        "deny" => false,
+        ALLOW => true,
+        DENY => false,
         s => {
             return Err(format::Error::Message(format!(
-                "Unknown variant `{s}`, expected one of `allow`, `deny`"
+                "Unknown variant `{s}`, expected one of `{ALLOW}`, `{DENY}`"
             )))
         }
     };
     Ok(result)
 }
 
+/// Parse the kind field. The `uid` and `gid` kinds mark a numeric name.
+fn parse_kind(value: &str) -> Result<(AclEntryKind, bool), format::Error> {
+    match value {
+        NUMERIC_USER => Ok((AclEntryKind::User, true)),
+        NUMERIC_GROUP => Ok((AclEntryKind::Group, true)),
+        s => Ok((s.parse::<AclEntryKind>()?, false)),
+    }
+}
+
 impl std::str::FromStr for AclEntry {
     type Err = format::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let fields = s.splitn(5, ':').map(str::trim).collect::<Vec<&str>>();
-
-        let entry = match fields.len() {
-            5 => {
-                // <allow>:<flags>:<kind>:<name>:<perms>
-                let allow = parse_allow(fields[0])?;
-                let flags = fields[1].parse::<Flag>()?;
-                let kind = fields[2].parse::<AclEntryKind>()?;
-                let name = fields[3];
-                let perms = fields[4].parse::<Perm>()?;
-                AclEntry::new(kind, name, perms, Some(flags), allow)
-            }
-            4 => {
-                // <flags>:<kind>:<name>:<perms>
-                let allow = true;
-                let flags = fields[0].parse::<Flag>()?;
-                let kind = fields[1].parse::<AclEntryKind>()?;
-                let name = fields[2];
-                let perms = fields[3].parse::<Perm>()?;
-                AclEntry::new(kind, name, perms, Some(flags), allow)
-            }
-            3 => {
-                // <kind>:<name>:<perms>
-                let allow = true;
-                let flags = Flag::empty();
-                let kind = fields[0].parse::<AclEntryKind>()?;
-                let name = fields[1];
-                let perms = fields[2].parse::<Perm>()?;
-                AclEntry::new(kind, name, perms, Some(flags), allow)
-            }
+        let fields = s
+            .splitn(5, FIELD_SEPARATOR)
+            .map(str::trim)
+            .collect::<Vec<&str>>();
+
+        let (allow, flags, fields) = match fields.len() {
+            // <allow>:<flags>:<kind>:<name>:<perms>
+            5 => (
+                parse_allow(fields[0])?,
+                fields[1].parse::<Flag>()?,
+                &fields[2..],
+            ),
+            // <flags>:<kind>:<name>:<perms>
+            4 => (true, fields[0].parse::<Flag>()?, &fields[1..]),
+            // <kind>:<name>:<perms>
+            3 => (true, Flag::empty(), &fields[..]),
             _ => return Err(format::Error::Message(format!("Unknown ACL format: `{s}`"))),
         };
 
-        Ok(entry)
+        let (kind, numeric) = parse_kind(fields[0])?;
+        let name = parse_escaped_name(fields[1])?;
+        let perms = fields[2].parse::<Perm>()?;
+
+        if numeric && name.parse::<u32>().is_err() {
+            return Err(format::Error::Message(format!(
+                "Invalid numeric id: `{name}`"
+            )));
+        }
+
+        Ok(AclEntry {
+            kind,
+            name,
+            perms,
+            flags,
+            allow,
+            numeric,
+        })
     }
 }
 
@@ -360,23 +996,23 @@ mod aclentry_tests {
             AclEntry::allow_group("d", Perm::EXECUTE, None),
             AclEntry::allow_user("z", Perm::READ, None),
             AclEntry::allow_group("z", Perm::READ, None),
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             AclEntry::deny_user("a", Perm::READ, Flag::FILE_INHERIT),
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             AclEntry::deny_user("c", Perm::READ, None),
         ];
 
         acl.sort();
 
         let acl_sorted = vec![
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             AclEntry::deny_user("c", Perm::READ, None),
             AclEntry::allow_user("f", Perm::WRITE, None),
             AclEntry::allow_user("z", Perm::READ, None),
             AclEntry::allow_group("3", Perm::EXECUTE, None),
             AclEntry::allow_group("d", Perm::EXECUTE, None),
             AclEntry::allow_group("z", Perm::READ, None),
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             AclEntry::deny_user("a", Perm::READ, Flag::FILE_INHERIT),
         ];
 
@@ -384,7 +1020,7 @@ mod aclentry_tests {
     }
 
     #[test]
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
     fn test_ordering_linux() {
         let mut acl = vec![
             AclEntry::allow_user("f", Perm::WRITE, None),
@@ -417,6 +1053,36 @@ mod aclentry_tests {
         assert_eq!(acl, acl_sorted);
     }
 
+    #[test]
+    fn test_matches() {
+        let entry = AclEntry::allow_user("a", Perm::READ, None);
+        assert!(entry.matches());
+        assert!(!entry.inherited_by(PathKind::File));
+        assert!(!entry.inherited_by(PathKind::Directory));
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        {
+            let entry = AclEntry::allow_user("a", Perm::READ, Flag::DEFAULT);
+            assert!(!entry.matches());
+            assert!(entry.inherited_by(PathKind::File));
+            assert!(entry.inherited_by(PathKind::Directory));
+        }
+
+        #[cfg(exacl_extended)]
+        {
+            let entry = AclEntry::allow_user("a", Perm::READ, Flag::FILE_INHERIT);
+            assert!(entry.matches());
+            assert!(entry.inherited_by(PathKind::File));
+            assert!(!entry.inherited_by(PathKind::Directory));
+
+            let flags = Flag::DIRECTORY_INHERIT | Flag::ONLY_INHERIT;
+            let entry = AclEntry::allow_user("a", Perm::READ, flags);
+            assert!(!entry.matches());
+            assert!(!entry.inherited_by(PathKind::File));
+            assert!(entry.inherited_by(PathKind::Directory));
+        }
+    }
+
     #[test]
     fn test_display_kind() {
         assert_eq!(format!("{}", AclEntryKind::User), "user");
@@ -424,7 +1090,7 @@ mod aclentry_tests {
     }
 
     #[test]
-    #[cfg(target_os = "macos")]
+    #[cfg(all(target_os = "macos", exacl_extended))]
     fn test_display_entry() {
         let perms = Perm::READ | Perm::EXECUTE;
         let flags = Flag::INHERITED | Flag::FILE_INHERIT;
@@ -437,7 +1103,12 @@ mod aclentry_tests {
     }
 
     #[test]
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    ))]
     fn test_display_entry() {
         let perms = Perm::READ | Perm::EXECUTE;
         let flags = Flag::DEFAULT;
@@ -450,13 +1121,48 @@ mod aclentry_tests {
     fn test_display_entry_name() {
         let perms = Perm::READ;
 
-        // FIXME: Need to have colons in user names escaped on output!
         let entry = AclEntry::allow_user("x:y", perms, None);
-        assert_eq!(format!("{entry}"), "allow::user:x:y:read");
+        assert_eq!(format!("{entry}"), "allow::user:x\\072y:read");
+
+        let entry = AclEntry::allow_user(" a\\b#\u{a0}", perms, None);
+        assert_eq!(
+            format!("{entry}"),
+            "allow::user:\\040a\\134b\\043\\302\\240:read"
+        );
     }
 
     #[test]
-    #[cfg(target_os = "macos")]
+    fn test_entry_fromstr_escaped_name() {
+        let entry = "user:x\\072y:read".parse::<AclEntry>().unwrap();
+        assert_eq!(entry.name, "x:y");
+
+        let entry = "user:\\040a\\134b\\043\\302\\240:read"
+            .parse::<AclEntry>()
+            .unwrap();
+        assert_eq!(entry.name, " a\\b#\u{a0}");
+
+        // A backslash that doesn't start an escape sequence is kept.
+        for (input, name) in [
+            ("user:DOMAIN\\user:read", "DOMAIN\\user"),
+            ("user:x\\07:read", "x\\07"),
+            ("user:x\\08a:read", "x\\08a"),
+            ("user:\\777:read", "\\777"),
+            ("user:x\\:read", "x\\"),
+        ] {
+            let entry = input.parse::<AclEntry>().unwrap();
+            assert_eq!(entry.name, name);
+            assert_eq!(entry.to_string().parse::<AclEntry>().unwrap(), entry);
+        }
+
+        // An escape sequence must produce valid UTF-8.
+        let err = "user:\\377:read".parse::<AclEntry>().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid escape sequence in name"));
+    }
+
+    #[test]
+    #[cfg(all(target_os = "macos", exacl_extended))]
     fn test_entry_fromstr() {
         let entry = "allow:inherited:user:x:read".parse::<AclEntry>().unwrap();
         assert_eq!(entry.to_string(), "allow:inherited:user:x:read");
@@ -477,7 +1183,7 @@ mod aclentry_tests {
     }
 
     #[test]
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
     fn test_entry_fromstr() {
         let entry = "allow:default:user:x:read".parse::<AclEntry>().unwrap();
         assert_eq!(entry.to_string(), "allow:default:user:x:read");
@@ -517,9 +1223,9 @@ mod aclentry_tests {
             ("user:a:read", "allow::user:a:read"),
             ("group:b:write", "allow::group:b:write"),
             ("unknown:c:execute", "allow::unknown:c:execute"),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
             ("other:d:execute", "allow::other:d:execute"),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
             ("mask:e:write,read", "allow::mask:e:read,write"),
         ];
 
@@ -529,14 +1235,87 @@ mod aclentry_tests {
         }
     }
 
+    #[test]
+    fn test_kind_codes() {
+        let kinds = [
+            (AclEntryKind::Unknown, 0),
+            (AclEntryKind::User, 1),
+            (AclEntryKind::Group, 2),
+            (AclEntryKind::Mask, 3),
+            (AclEntryKind::Other, 4),
+            (AclEntryKind::Everyone, 5),
+        ];
+
+        for (kind, code) in kinds {
+            assert_eq!(kind.as_u8(), code);
+            assert_eq!(AclEntryKind::from_u8(code), Some(kind));
+        }
+
+        assert_eq!(AclEntryKind::from_u8(6), None);
+        assert_eq!(AclEntryKind::from_u8(255), None);
+    }
+
+    #[test]
+    fn test_entry_raw_flags() {
+        let entry = "allow:raw=0x4000:user:a:read".parse::<AclEntry>().unwrap();
+        assert_eq!(entry.flags, Flag::from_bits_retain(0x4000));
+        assert_eq!(entry.to_string(), "allow:raw=0x4000:user:a:read");
+        assert_eq!(
+            entry.versioned(FormatVersion::V1).to_string(),
+            "allow::user:a:read"
+        );
+    }
+
+    #[test]
+    fn test_entry_v1_unescaped_names() {
+        // V1 writes names verbatim, so some names don't read back.
+        for name in ["a:b", "a#b", " a "] {
+            let entry = AclEntry::allow_user(name, Perm::READ, None);
+            let text = format!("{}\n", entry.versioned(FormatVersion::V1));
+            assert_eq!(text, format!("allow::user:{name}:read\n"));
+            assert_ne!(crate::from_str(&text).ok(), Some(vec![entry.clone()]));
+
+            let text = format!("{entry}\n");
+            assert_eq!(crate::from_str(&text).unwrap(), vec![entry]);
+        }
+    }
+
+    #[test]
+    fn test_entry_fromstr_numeric() {
+        let entry = "uid:1000:read".parse::<AclEntry>().unwrap();
+        assert_eq!(entry.kind, AclEntryKind::User);
+        assert_eq!(entry.name, "1000");
+        assert!(entry.numeric);
+        assert_eq!(entry.to_string(), "allow::uid:1000:read");
+        assert_eq!(
+            entry.versioned(FormatVersion::V1).to_string(),
+            "allow::user:1000:read"
+        );
+
+        let entry = "deny::gid:20:write".parse::<AclEntry>().unwrap();
+        assert_eq!(entry.kind, AclEntryKind::Group);
+        assert!(entry.numeric);
+        assert_eq!(entry.to_string(), "deny::gid:20:write");
+
+        // A numeric entry is not the same as a user named "1000".
+        let named = "user:1000:read".parse::<AclEntry>().unwrap();
+        assert!(!named.numeric);
+        assert_ne!(named, "uid:1000:read".parse::<AclEntry>().unwrap());
+
+        for input in ["uid:bob:read", "gid::read", "uid:-1:read"] {
+            let err = input.parse::<AclEntry>().unwrap_err();
+            assert!(err.to_string().starts_with("Invalid numeric id"));
+        }
+    }
+
     #[test]
     fn test_entry_fromstr_examples() {
         let values = [
             ("u:admin:rwx", "allow::user:admin:read,write,execute"),
             ("g::rw", "allow::group::read,write"),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
             ("default:user:admin:r", "allow:default:user:admin:read"),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
             ("d:group:admin:w", "allow:default:group:admin:write"),
             ("deny::u:self:x", "deny::user:self:execute"),
         ];
@@ -559,25 +1338,86 @@ mod aclentry_tests {
             "unknown".parse::<AclEntryKind>().unwrap()
         );
 
-        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         assert_eq!(AclEntryKind::Mask, "mask".parse::<AclEntryKind>().unwrap());
+        assert_eq!(
+            AclEntryKind::Everyone,
+            "everyone".parse::<AclEntryKind>().unwrap()
+        );
+        assert_eq!(AclEntryKind::Everyone, "e".parse::<AclEntryKind>().unwrap());
 
-        #[cfg(target_os = "macos")]
         assert_eq!(
-            "unknown variant `x`, expected one of `user`, `group`, `unknown`",
+            "unknown variant `x`, expected one of `user`, `group`, `mask`, `other`, `everyone`, `unknown`",
             "x".parse::<AclEntryKind>().unwrap_err().to_string()
         );
+    }
+
+    #[test]
+    fn test_display_list() {
+        let entries = vec![
+            AclEntry::allow_group("a", Perm::WRITE, None),
+            AclEntry::allow_user("b c", Perm::READ, None),
+        ];
 
-        #[cfg(target_os = "linux")]
+        let list = DisplayList::new(&entries);
         assert_eq!(
-            "unknown variant `x`, expected one of `user`, `group`, `mask`, `other`, `unknown`",
-            "x".parse::<AclEntryKind>().unwrap_err().to_string()
+            list.to_string(),
+            "allow::group:a:write\nallow::user:b\\040c:read\n"
         );
+        assert_eq!(format!("{list:?}"), list.to_string());
+        assert_eq!(
+            list.sorted().to_string(),
+            "allow::user:b\\040c:read\nallow::group:a:write\n"
+        );
+        assert_eq!(crate::from_str(&list.to_string()).unwrap(), entries);
+        assert_eq!(DisplayList::new(&[]).to_string(), "");
+    }
 
+    #[test]
+    fn test_everyone() {
+        let entry = AclEntry::everyone(Perm::READ, None);
+        assert!(entry.allow);
+        assert!(entry.is_everyone());
+        assert_eq!(entry.perms, Perm::READ);
+
+        #[cfg(any(target_os = "linux", exacl_core))]
+        assert_eq!(entry, AclEntry::allow_other(Perm::READ, None));
+        #[cfg(target_os = "macos")]
+        assert_eq!(entry, AclEntry::allow_group("everyone", Perm::READ, None));
         #[cfg(target_os = "freebsd")]
+        assert_eq!(entry.kind, AclEntryKind::Everyone);
+
+        assert!(!AclEntry::allow_user("", Perm::READ, None).is_everyone());
         assert_eq!(
-            "unknown variant `x`, expected one of `user`, `group`, `mask`, `other`, `everyone`, `unknown`",
-            "x".parse::<AclEntryKind>().unwrap_err().to_string()
+            AclEntry::allow_group("everyone", Perm::READ, None).is_everyone(),
+            cfg!(target_os = "macos")
+        );
+    }
+
+    #[test]
+    #[cfg(exacl_nfs4)]
+    fn test_allow_deny_everyone() {
+        let entry = AclEntry::deny_everyone(Perm::WRITE, Flag::FILE_INHERIT);
+        assert_eq!(entry.kind, AclEntryKind::Everyone);
+        assert!(!entry.allow);
+        assert!(entry.is_everyone());
+        assert_eq!(entry.to_string(), "deny:file_inherit:everyone::write");
+        assert_eq!(
+            "deny:file_inherit:e::write".parse::<AclEntry>().unwrap(),
+            entry
+        );
+        assert!(AclEntry::allow_everyone(Perm::READ, None).allow);
+    }
+
+    #[test]
+    #[cfg(not(exacl_core))]
+    fn test_everyone_for() {
+        let kind = |brand| AclEntry::everyone_for(brand, Perm::READ, None).kind;
+        assert_eq!(kind(AclBrand::Posix1e), AclEntryKind::Other);
+        assert_eq!(kind(AclBrand::Nfs4), AclEntryKind::Everyone);
+        assert_eq!(kind(AclBrand::Extended), AclEntryKind::Group);
+        assert_eq!(
+            AclEntry::everyone_for(AclBrand::Unknown, Perm::READ, None),
+            AclEntry::everyone(Perm::READ, None)
         );
     }
 }
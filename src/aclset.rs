@@ -0,0 +1,289 @@
+//! Implements `AclSet`, a collection of ACL entries with set operations.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+use std::collections::BTreeMap;
+
+/// Fields that identify an entry, apart from its permissions.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct AclKey {
+    kind: AclEntryKind,
+    name: String,
+    numeric: bool,
+    allow: bool,
+    flags: Flag,
+}
+
+/// Collection of ACL entries with set algebra.
+///
+/// Entries are keyed by kind, name, allow/deny and flags. Entries with the
+/// same key are merged by combining their permissions, so each key appears at
+/// most once.
+///
+/// ```
+/// use exacl::{AclEntry, AclSet, Perm};
+///
+/// let team_a = AclSet::from(vec![
+///     AclEntry::allow_user("alice", Perm::READ | Perm::WRITE, None),
+///     AclEntry::allow_group("team_a", Perm::READ | Perm::WRITE, None),
+/// ]);
+/// let auditors = AclSet::from(vec![AclEntry::allow_group("auditors", Perm::READ, None)]);
+///
+/// let policy = team_a.union(&auditors);
+/// assert_eq!(policy.len(), 3);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AclSet {
+    entries: BTreeMap<AclKey, Perm>,
+}
+
+impl AclSet {
+    /// Construct an empty set.
+    #[must_use]
+    pub fn new() -> AclSet {
+        AclSet::default()
+    }
+
+    /// Return number of entries in the set.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if the set has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add an entry. If the set already has an entry with the same key, the
+    /// permissions are merged.
+    pub fn insert(&mut self, entry: AclEntry) {
+        let (key, perms) = split_entry(entry);
+        *self.entries.entry(key).or_default() |= perms;
+    }
+
+    /// Return the permissions of the entry with the same key as `entry`.
+    ///
+    /// The permissions of `entry` itself are ignored.
+    #[must_use]
+    pub fn get(&self, entry: &AclEntry) -> Option<Perm> {
+        self.entries.get(&entry_key(entry)).copied()
+    }
+
+    /// Return entries in either set. Permissions of entries in both sets are
+    /// merged.
+    #[must_use]
+    pub fn union(&self, other: &AclSet) -> AclSet {
+        let mut result = self.clone();
+        for (key, perms) in &other.entries {
+            *result.entries.entry(key.clone()).or_default() |= *perms;
+        }
+        result
+    }
+
+    /// Return entries in both sets, with the permissions they have in common.
+    ///
+    /// An entry whose common permissions are empty is kept, so required
+    /// entries like `user::` are not lost.
+    #[must_use]
+    pub fn intersection(&self, other: &AclSet) -> AclSet {
+        let entries = self
+            .entries
+            .iter()
+            .filter_map(|(key, perms)| {
+                let other_perms = other.entries.get(key)?;
+                Some((key.clone(), *perms & *other_perms))
+            })
+            .collect();
+
+        AclSet { entries }
+    }
+
+    /// Return entries in this set with the permissions in `other` removed.
+    ///
+    /// An entry is dropped when all of its permissions are removed.
+    #[must_use]
+    pub fn difference(&self, other: &AclSet) -> AclSet {
+        let entries = self
+            .entries
+            .iter()
+            .filter_map(|(key, perms)| match other.entries.get(key) {
+                Some(other_perms) if perms.difference(*other_perms).is_empty() => None,
+                Some(other_perms) => Some((key.clone(), perms.difference(*other_perms))),
+                None => Some((key.clone(), *perms)),
+            })
+            .collect();
+
+        AclSet { entries }
+    }
+
+    /// Return the entries in the set, sorted.
+    #[must_use]
+    pub fn to_vec(&self) -> Vec<AclEntry> {
+        let mut result = self
+            .entries
+            .iter()
+            .map(|(key, perms)| join_entry(key.clone(), *perms))
+            .collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+}
+
+impl From<Vec<AclEntry>> for AclSet {
+    fn from(entries: Vec<AclEntry>) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl From<AclSet> for Vec<AclEntry> {
+    fn from(set: AclSet) -> Self {
+        set.to_vec()
+    }
+}
+
+impl FromIterator<AclEntry> for AclSet {
+    fn from_iter<I: IntoIterator<Item = AclEntry>>(iter: I) -> Self {
+        let mut result = AclSet::new();
+        result.extend(iter);
+        result
+    }
+}
+
+impl Extend<AclEntry> for AclSet {
+    fn extend<I: IntoIterator<Item = AclEntry>>(&mut self, iter: I) {
+        for entry in iter {
+            self.insert(entry);
+        }
+    }
+}
+
+/// Return the key of an entry.
+fn entry_key(entry: &AclEntry) -> AclKey {
+    AclKey {
+        kind: entry.kind,
+        name: entry.name.clone(),
+        numeric: entry.numeric,
+        allow: entry.allow,
+        flags: entry.flags,
+    }
+}
+
+/// Split an entry into its key and permissions.
+fn split_entry(entry: AclEntry) -> (AclKey, Perm) {
+    let key = AclKey {
+        kind: entry.kind,
+        name: entry.name,
+        numeric: entry.numeric,
+        allow: entry.allow,
+        flags: entry.flags,
+    };
+    (key, entry.perms)
+}
+
+/// Rebuild an entry from its key and permissions.
+fn join_entry(key: AclKey, perms: Perm) -> AclEntry {
+    AclEntry {
+        kind: key.kind,
+        name: key.name,
+        perms,
+        flags: key.flags,
+        allow: key.allow,
+        numeric: key.numeric,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod aclset_tests {
+    use super::*;
+
+    fn sample_a() -> AclSet {
+        AclSet::from(vec![
+            AclEntry::allow_user("a", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("b", Perm::READ, None),
+            AclEntry::allow_user("a", Perm::EXECUTE, None),
+        ])
+    }
+
+    fn sample_b() -> AclSet {
+        AclSet::from(vec![
+            AclEntry::allow_user("a", Perm::WRITE, None),
+            AclEntry::allow_group("c", Perm::READ, None),
+        ])
+    }
+
+    #[test]
+    fn test_insert() {
+        let set = sample_a();
+        assert_eq!(set.len(), 2);
+        assert!(!set.is_empty());
+        assert!(AclSet::new().is_empty());
+
+        let user_a = AclEntry::allow_user("a", Perm::empty(), None);
+        assert_eq!(
+            set.get(&user_a),
+            Some(Perm::READ | Perm::WRITE | Perm::EXECUTE)
+        );
+        assert_eq!(set.get(&AclEntry::allow_user("b", Perm::READ, None)), None);
+    }
+
+    #[test]
+    fn test_union() {
+        let set = sample_a().union(&sample_b());
+        assert_eq!(
+            crate::to_string(&set.to_vec()).unwrap(),
+            "allow::user:a:read,write,execute\nallow::group:b:read\nallow::group:c:read\n"
+        );
+    }
+
+    #[test]
+    fn test_intersection() {
+        let set = sample_a().intersection(&sample_b());
+        assert_eq!(
+            Vec::from(set),
+            vec![AclEntry::allow_user("a", Perm::WRITE, None)]
+        );
+
+        let set = sample_b().intersection(&AclSet::from(vec![AclEntry::allow_group(
+            "c",
+            Perm::WRITE,
+            None,
+        )]));
+        assert_eq!(
+            set.to_vec(),
+            vec![AclEntry::allow_group("c", Perm::empty(), None)]
+        );
+    }
+
+    #[test]
+    fn test_difference() {
+        let set = sample_a().difference(&sample_b());
+        assert_eq!(
+            crate::to_string(&set.to_vec()).unwrap(),
+            "allow::user:a:read,execute\nallow::group:b:read\n"
+        );
+
+        let set = sample_b().difference(&sample_a());
+        assert_eq!(
+            set.to_vec(),
+            vec![AclEntry::allow_group("c", Perm::READ, None)]
+        );
+    }
+
+    #[test]
+    fn test_keys() {
+        // Entries differing in allow, flags or numeric are kept apart.
+        let mut set = AclSet::new();
+        set.insert(AclEntry::allow_user("1", Perm::READ, None));
+        let mut numeric = AclEntry::allow_user("1", Perm::WRITE, None);
+        numeric.numeric = true;
+        set.insert(numeric);
+        set.insert(AclEntry::allow_user("1", Perm::WRITE, Flag::all()));
+        assert_eq!(set.len(), 3);
+    }
+}
@@ -0,0 +1,126 @@
+//! Implements `AclText`, which serializes entries as one string.
+
+use crate::aclentry::AclEntry;
+
+use serde::{de, ser};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// List of ACL entries that serializes to a single string in the text format.
+///
+/// By default, a `Vec<AclEntry>` serializes to an array of structs. Wrap it in
+/// `AclText` to embed an ACL in a config file as one string instead, with one
+/// entry per line. See [`to_writer`](crate::to_writer) for the format.
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::AclText;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     acl: AclText,
+/// }
+///
+/// let config: Config = serde_json::from_str(r#"{"acl": "u::rw\ng::r\no::"}"#)?;
+/// assert_eq!(config.acl.len(), 3);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AclText(pub Vec<AclEntry>);
+
+impl From<Vec<AclEntry>> for AclText {
+    fn from(entries: Vec<AclEntry>) -> Self {
+        AclText(entries)
+    }
+}
+
+impl From<AclText> for Vec<AclEntry> {
+    fn from(text: AclText) -> Self {
+        text.0
+    }
+}
+
+impl Deref for AclText {
+    type Target = Vec<AclEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for AclText {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl ser::Serialize for AclText {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let text = crate::to_string(&self.0).map_err(ser::Error::custom)?;
+        serializer.serialize_str(&text)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for AclText {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct AclTextVisitor;
+
+        impl<'de> de::Visitor<'de> for AclTextVisitor {
+            type Value = AclText;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("ACL entries in text format")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                crate::from_str(value).map(AclText).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(AclTextVisitor)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod acltext_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_acltext_json() {
+        let text = AclText(vec![
+            AclEntry::allow_user("a:b", Perm::READ, None),
+            AclEntry::allow_group("c", Perm::READ | Perm::WRITE, None),
+        ]);
+
+        let json = serde_json::to_string(&text).unwrap();
+        assert_eq!(
+            json,
+            r#""allow::user:a\\072b:read\nallow::group:c:read,write\n""#
+        );
+
+        let text2: AclText = serde_json::from_str(&json).unwrap();
+        assert_eq!(text2, text);
+
+        let text3: AclText = serde_json::from_str(r#""g:c:rw  # comment\n""#).unwrap();
+        assert_eq!(text3[..], text[1..]);
+
+        let err = serde_json::from_str::<AclText>(r#""x:y""#).unwrap_err();
+        assert!(err.to_string().contains("Unknown"), "{err}");
+
+        let err = serde_json::from_str::<AclText>("[]").unwrap_err();
+        assert!(err.to_string().contains("ACL entries in text format"));
+    }
+}
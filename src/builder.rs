@@ -0,0 +1,180 @@
+//! Implements `AclBuilder`, which changes a file's ACL entry by entry.
+
+use crate::acl::AclOption;
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::{getfacl, setfacl};
+
+use std::io;
+use std::path::Path;
+
+/// Reads a file's ACL, changes individual entries, and writes it back.
+///
+/// Entries are matched by principal: their kind and name, whether they allow
+/// or deny, and whether they belong to the default ACL. The entries are only
+/// checked when they are written, so a sequence of changes may pass through
+/// an invalid ACL.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclBuilder, AclEntry, AclEntryKind, Perm};
+///
+/// let mut builder = AclBuilder::read("./tmp/foo", None)?;
+/// builder.upsert(AclEntry::allow_user("alice", Perm::READ, None));
+/// builder.remove_entry(AclEntryKind::User, "bob");
+/// builder.retain(|entry| entry.kind != AclEntryKind::Unknown);
+/// builder.write("./tmp/foo", None)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AclBuilder {
+    entries: Vec<AclEntry>,
+}
+
+impl AclBuilder {
+    /// Construct a builder for the given entries.
+    #[must_use]
+    pub const fn new(entries: Vec<AclEntry>) -> AclBuilder {
+        AclBuilder { entries }
+    }
+
+    /// Construct a builder for the ACL of a file or directory, as returned by
+    /// [`getfacl`](crate::getfacl).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the ACL can't be read.
+    pub fn read<P, O>(path: P, options: O) -> io::Result<AclBuilder>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        Ok(AclBuilder::new(getfacl(path, options)?))
+    }
+
+    /// Return the current entries.
+    #[must_use]
+    pub fn entries(&self) -> &[AclEntry] {
+        &self.entries
+    }
+
+    /// Replace the entry for the same principal, or add the entry at the end
+    /// if there is none. Return the replaced entry.
+    pub fn upsert(&mut self, entry: AclEntry) -> Option<AclEntry> {
+        match self.entries.iter_mut().find(|e| is_same_slot(e, &entry)) {
+            Some(existing) => Some(std::mem::replace(existing, entry)),
+            None => {
+                self.entries.push(entry);
+                None
+            }
+        }
+    }
+
+    /// Remove every entry with the given kind and name, in both the access
+    /// and default ACL's. Return the number of entries removed.
+    pub fn remove_entry(&mut self, kind: AclEntryKind, name: &str) -> usize {
+        let len = self.entries.len();
+        self.entries
+            .retain(|entry| entry.kind != kind || entry.name != name);
+        len - self.entries.len()
+    }
+
+    /// Keep only the entries for which `pred` returns true.
+    pub fn retain<F: FnMut(&AclEntry) -> bool>(&mut self, pred: F) {
+        self.entries.retain(pred);
+    }
+
+    /// Write the entries to a file or directory with
+    /// [`setfacl`](crate::setfacl).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the entries are not a valid ACL, or if the
+    /// ACL can't be written.
+    pub fn write<P, O>(&self, path: P, options: O) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        setfacl(&[path], &self.entries, options)
+    }
+
+    /// Return the entries.
+    #[must_use]
+    pub fn into_entries(self) -> Vec<AclEntry> {
+        self.entries
+    }
+}
+
+/// Return true if both entries are for the same principal, with the same
+/// allow/deny type, in the same ACL.
+fn is_same_slot(a: &AclEntry, b: &AclEntry) -> bool {
+    a.is_same_principal(b) && a.allow == b.allow && a.is_default() == b.is_default()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_upsert_remove() {
+        let mut builder = AclBuilder::default();
+        assert_eq!(
+            builder.upsert(AclEntry::allow_user("11501", Perm::READ, None)),
+            None
+        );
+        assert_eq!(
+            builder.upsert(AclEntry::allow_user("11501", Perm::WRITE, None)),
+            Some(AclEntry::allow_user("11501", Perm::READ, None))
+        );
+        builder.upsert(AclEntry::allow_group("11501", Perm::READ, None));
+        assert_eq!(
+            builder.entries(),
+            [
+                AclEntry::allow_user("11501", Perm::WRITE, None),
+                AclEntry::allow_group("11501", Perm::READ, None),
+            ]
+        );
+
+        assert_eq!(builder.remove_entry(AclEntryKind::User, "11501"), 1);
+        assert_eq!(builder.remove_entry(AclEntryKind::User, "11501"), 0);
+        builder.retain(|entry| entry.perms != Perm::READ);
+        assert!(builder.into_entries().is_empty());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_upsert_default() {
+        let mut builder = AclBuilder::new(vec![AclEntry::allow_user("11501", Perm::READ, None)]);
+        builder.upsert(AclEntry::allow_user(
+            "11501",
+            Perm::WRITE,
+            crate::Flag::DEFAULT,
+        ));
+        assert_eq!(builder.entries().len(), 2);
+        assert_eq!(builder.remove_entry(AclEntryKind::User, "11501"), 2);
+    }
+
+    #[test]
+    fn test_read_write() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+
+        let mut builder = AclBuilder::read(&file, None)?;
+        builder.upsert(AclEntry::allow_user("11501", Perm::READ, None));
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        builder.upsert(AclEntry::allow_mask(Perm::READ, None));
+        builder.write(&file, None)?;
+
+        let mut builder = AclBuilder::read(&file, None)?;
+        assert!(builder.entries().iter().any(|entry| entry.name == "11501"));
+        builder.remove_entry(AclEntryKind::User, "11501");
+        builder.write(&file, None)?;
+        assert!(!getfacl(&file, None)?
+            .iter()
+            .any(|entry| entry.name == "11501"));
+        Ok(())
+    }
+}
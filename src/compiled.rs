@@ -0,0 +1,157 @@
+//! Implements `CompiledAcl`, which converts entries to a native ACL once so
+//! it can be written to many open files.
+
+use crate::acl::{Acl, AclOption};
+use crate::aclentry::AclEntry;
+use crate::failx::{custom_err, fail_custom};
+
+use std::borrow::Borrow;
+use std::io;
+use std::os::fd::AsFd;
+
+/// Entries converted to a native access ACL once, for writing to many files.
+///
+/// [`setfacl`](crate::setfacl) resolves user and group names and builds a
+/// native ACL on every call. A `CompiledAcl` does that work up front, and
+/// [`CompiledAcl::apply_fd`] writes the result to an open file without any
+/// path resolution. This suits servers that create many files with the same
+/// ACL.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclEntry, CompiledAcl, Perm};
+///
+/// let mut entries = exacl::from_mode(0o640);
+/// entries.push(AclEntry::allow_group("auditors", Perm::READ, None));
+/// let acl = CompiledAcl::new(&entries, None)?;
+///
+/// for name in ["a.log", "b.log"] {
+///     let file = std::fs::File::create(name)?;
+///     acl.apply_fd(&file)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Like [`Acl`], a `CompiledAcl` is `Send` but not `Sync`.
+pub struct CompiledAcl {
+    acl: Acl,
+}
+
+impl CompiledAcl {
+    /// Convert entries to a native access ACL.
+    ///
+    /// Only [`AclOption::STRICT_PRINCIPALS`] and
+    /// [`AclOption::STRIP_INHERITED`] are used; other options are ignored.
+    /// Default entries are rejected, since only the access ACL is written
+    /// through a file descriptor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the entries don't form a valid ACL, or if
+    /// a name can't be resolved.
+    pub fn new<E, O>(entries: E, options: O) -> io::Result<CompiledAcl>
+    where
+        E: IntoIterator,
+        E::Item: Borrow<AclEntry>,
+        O: Into<Option<AclOption>>,
+    {
+        let options = options.into().unwrap_or_default();
+        let strict = options.contains(AclOption::STRICT_PRINCIPALS);
+
+        let mut has_default = false;
+        let entries = entries.into_iter().inspect(|entry| {
+            has_default |= entry.borrow().is_default();
+        });
+
+        #[cfg(all(any(target_os = "macos", target_os = "freebsd"), exacl_extended))]
+        let acl = if options.contains(AclOption::STRIP_INHERITED) {
+            Acl::from_entries_strict(crate::strip_inherited(entries), strict)
+        } else {
+            Acl::from_entries_strict(entries, strict)
+        };
+
+        #[cfg(not(all(any(target_os = "macos", target_os = "freebsd"), exacl_extended)))]
+        let acl = Acl::from_entries_strict(entries, strict);
+
+        let acl = acl.map_err(|err| custom_err("Invalid ACL", &err))?;
+        if has_default {
+            return fail_custom(
+                "Invalid ACL: default entries can't be written to a file descriptor",
+            );
+        }
+
+        Ok(CompiledAcl { acl })
+    }
+
+    /// Write the ACL to an open file, e.g. a [`std::fs::File`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn apply_fd<F: AsFd>(&self, fd: F) -> io::Result<()> {
+        self.acl.write_fd(fd, AclOption::empty())
+    }
+
+    /// Return the entries of the compiled ACL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn entries(&self) -> io::Result<Vec<AclEntry>> {
+        self.acl.entries()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod compiled_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_apply_fd() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let mut entries = crate::from_mode(0o640);
+        #[cfg(target_os = "macos")]
+        let mut entries = Vec::new();
+        entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+        let acl = CompiledAcl::new(&entries, None)?;
+
+        for name in ["a", "b", "c"] {
+            let path = dir.path().join(name);
+            let file = std::fs::File::create(&path)?;
+            acl.apply_fd(&file)?;
+            let entries = crate::getfacl(&path, None)?;
+            assert!(entries.iter().any(|entry| entry.name == "11501"));
+        }
+
+        assert!(acl.entries()?.iter().any(|entry| entry.name == "11501"));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_compiled_errors() {
+        let mut entries = crate::from_mode(0o640);
+        entries.push(AclEntry::allow_user(
+            "11501",
+            Perm::READ,
+            crate::Flag::DEFAULT,
+        ));
+        let err = CompiledAcl::new(&entries, None).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Invalid ACL: default entries can't be written to a file descriptor"
+        );
+
+        let entries = vec![AclEntry::allow_user("11501", Perm::READ, None)];
+        let err = CompiledAcl::new(&entries, AclOption::STRICT_PRINCIPALS)
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("Invalid ACL: "), "{err}");
+    }
+}
@@ -0,0 +1,187 @@
+//! Corpus of real-world ACL text dumps, for testing parsers (requires the
+//! `conformance` feature).
+//!
+//! The corpus holds anonymized output of Linux `getfacl`, macOS `ls -le` and
+//! `FreeBSD` `getfacl -q`, for both `POSIX.1e` and `NFSv4` ACL's. Only the ACL
+//! lines of `ls -le` are kept; the file listing line is left out. The dumps
+//! are built into the library, so a downstream parser can be tested against
+//! the same inputs as exacl's own.
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::conformance;
+//!
+//! for fixture in conformance::corpus() {
+//!     println!("{}: {} bytes", fixture.name, fixture.text.len());
+//! }
+//!
+//! // Every fixture this platform supports is accepted by `from_reader_detect`.
+//! let parsed = conformance::parse_all()?;
+//! assert!(!parsed.is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::aclentry::AclEntry;
+use crate::dialect::Dialect;
+
+use std::io;
+
+/// Permissions and flags a fixture needs in order to be parsed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Requires {
+    /// `POSIX.1e` entries, including default entries.
+    Posix,
+
+    /// Extended permissions and inheritance flags (macOS and `FreeBSD`).
+    Extended,
+}
+
+/// An ACL text dump in the corpus.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Fixture {
+    /// Path of the dump relative to the corpus, e.g. `linux/getfacl_dir.txt`.
+    pub name: &'static str,
+
+    /// Dialect the dump is written in.
+    pub dialect: Dialect,
+
+    /// Contents of the dump.
+    pub text: &'static str,
+
+    requires: Requires,
+}
+
+impl Fixture {
+    /// Return true if the dump can be parsed on this platform.
+    ///
+    /// Default entries can't be parsed on macOS, and extended permissions
+    /// can't be parsed on Linux or with the `strict_posix` feature.
+    #[must_use]
+    pub fn is_supported(&self) -> bool {
+        match self.requires {
+            Requires::Posix => cfg!(any(
+                target_os = "linux",
+                target_os = "freebsd",
+                exacl_core,
+                feature = "strict_posix"
+            )),
+            Requires::Extended => cfg!(exacl_extended),
+        }
+    }
+
+    /// Parse the dump with [`from_reader_detect`](crate::from_reader_detect).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the dump can't be parsed, or with kind
+    /// `InvalidData` if it is detected as a different dialect. The message
+    /// starts with the fixture's name.
+    pub fn parse(&self) -> io::Result<Vec<AclEntry>> {
+        let (entries, dialect) = crate::from_reader_detect(self.text.as_bytes())
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", self.name)))?;
+
+        if dialect != self.dialect {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: detected dialect {dialect:?}, expected {:?}",
+                    self.name, self.dialect
+                ),
+            ));
+        }
+
+        Ok(entries)
+    }
+}
+
+macro_rules! fixture {
+    ($name:literal, $dialect:ident, $requires:ident) => {
+        Fixture {
+            name: $name,
+            dialect: Dialect::$dialect,
+            text: include_str!(concat!("../tests/corpus/", $name)),
+            requires: Requires::$requires,
+        }
+    };
+}
+
+/// Dumps in the corpus, loaded from `tests/corpus`.
+static CORPUS: &[Fixture] = &[
+    fixture!("linux/getfacl_file.txt", Getfacl, Posix),
+    fixture!("linux/getfacl_dir.txt", Getfacl, Posix),
+    fixture!("macos/ls_le_file.txt", Chmod, Extended),
+    fixture!("macos/ls_le_dir.txt", Chmod, Extended),
+    fixture!("freebsd/getfacl_posix.txt", Getfacl, Posix),
+    fixture!("freebsd/getfacl_nfs4_file.txt", Nfs4, Extended),
+    fixture!("freebsd/getfacl_nfs4_dir.txt", Nfs4, Extended),
+];
+
+/// Return every dump in the corpus, including those this platform can't
+/// parse.
+#[must_use]
+pub fn corpus() -> &'static [Fixture] {
+    CORPUS
+}
+
+/// Parse every dump in the corpus that this platform supports.
+///
+/// Returns the name and entries of each parsed dump.
+///
+/// # Errors
+///
+/// Returns the error of the first dump that fails to parse.
+pub fn parse_all() -> io::Result<Vec<(&'static str, Vec<AclEntry>)>> {
+    CORPUS
+        .iter()
+        .filter(|fixture| fixture.is_supported())
+        .map(|fixture| Ok((fixture.name, fixture.parse()?)))
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod conformance_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_all() {
+        let parsed = parse_all().unwrap();
+        let supported = corpus().iter().filter(|f| f.is_supported()).count();
+        assert_eq!(parsed.len(), supported);
+        assert!(parsed.iter().all(|(_, entries)| !entries.is_empty()));
+
+        // Linux dumps have 6 access entries, plus 6 default entries for the
+        // directory.
+        #[cfg(all(target_os = "linux", not(exacl_extended)))]
+        {
+            let counts = parsed.iter().map(|(_, e)| e.len()).collect::<Vec<_>>();
+            assert_eq!(counts, [6, 12, 6]);
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_parse_unsupported() {
+        for fixture in corpus().iter().filter(|f| !f.is_supported()) {
+            let err = fixture.parse().unwrap_err();
+            assert!(err.to_string().starts_with(fixture.name), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_parse_wrong_dialect() {
+        let fixture = Fixture {
+            dialect: Dialect::Nfs4,
+            ..CORPUS[0].clone()
+        };
+        let err = fixture.parse().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "linux/getfacl_file.txt: detected dialect Getfacl, expected Nfs4"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Implements `consolidate`, which finds user entries that can be merged into
+//! a single group entry.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+use std::collections::BTreeMap;
+
+/// A set of named user entries with identical permissions and flags, which
+/// could be replaced by one group entry.
+///
+/// Returned by [`consolidate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Consolidation {
+    /// Indices of the user entries, in order.
+    pub indices: Vec<usize>,
+
+    /// Names of the users, in entry order.
+    pub users: Vec<String>,
+
+    /// Permissions shared by the entries.
+    pub perms: Perm,
+
+    /// Flags shared by the entries.
+    pub flags: Flag,
+
+    /// True if the entries allow access; false if they deny access.
+    pub allow: bool,
+}
+
+impl Consolidation {
+    /// Return the group entry that replaces the user entries.
+    #[must_use]
+    pub fn group_entry(&self, group_name: &str) -> AclEntry {
+        AclEntry {
+            allow: self.allow,
+            ..AclEntry::allow_group(group_name, self.perms, self.flags)
+        }
+    }
+}
+
+/// Find sets of at least `threshold` named user entries that share the same
+/// permissions, flags and allow/deny, and could be replaced by one group
+/// entry.
+///
+/// This helps stay under a platform's entry limit, e.g. 128 entries on macOS.
+/// Sets with fewer than two entries are never reported. Results are ordered by
+/// the index of their first entry.
+#[must_use]
+pub fn consolidate(entries: &[AclEntry], threshold: usize) -> Vec<Consolidation> {
+    let mut sets = BTreeMap::<(Perm, Flag, bool), Consolidation>::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.kind != AclEntryKind::User || entry.name.is_empty() {
+            continue;
+        }
+
+        let set = sets
+            .entry((entry.perms, entry.flags, entry.allow))
+            .or_insert_with(|| Consolidation {
+                indices: Vec::new(),
+                users: Vec::new(),
+                perms: entry.perms,
+                flags: entry.flags,
+                allow: entry.allow,
+            });
+        set.indices.push(i);
+        set.users.push(entry.name.clone());
+    }
+
+    let mut result = sets
+        .into_values()
+        .filter(|set| set.indices.len() >= threshold.max(2))
+        .collect::<Vec<_>>();
+    result.sort_by_key(|set| set.indices[0]);
+    result
+}
+
+/// Replace sets of user entries found by [`consolidate`] with group entries.
+///
+/// `group_for` is called for each set and returns the name of a group whose
+/// members are exactly those users, or `None` to keep the user entries. The
+/// group entry takes the place of the first user entry in the set; the other
+/// user entries are removed. Other entries are unchanged.
+///
+/// ```
+/// use exacl::{consolidate_with, AclEntry, Perm};
+///
+/// let entries = vec![
+///     AclEntry::allow_user("alice", Perm::READ, None),
+///     AclEntry::allow_user("bob", Perm::READ, None),
+///     AclEntry::allow_user("carol", Perm::WRITE, None),
+/// ];
+///
+/// let merged = consolidate_with(&entries, 2, |set| {
+///     (set.users == ["alice", "bob"]).then(|| "readers".to_string())
+/// });
+/// assert_eq!(
+///     merged,
+///     [
+///         AclEntry::allow_group("readers", Perm::READ, None),
+///         AclEntry::allow_user("carol", Perm::WRITE, None),
+///     ]
+/// );
+/// ```
+pub fn consolidate_with<F>(
+    entries: &[AclEntry],
+    threshold: usize,
+    mut group_for: F,
+) -> Vec<AclEntry>
+where
+    F: FnMut(&Consolidation) -> Option<String>,
+{
+    let mut replace = BTreeMap::<usize, Option<AclEntry>>::new();
+
+    for set in consolidate(entries, threshold) {
+        let Some(group_name) = group_for(&set) else {
+            continue;
+        };
+        replace.insert(set.indices[0], Some(set.group_entry(&group_name)));
+        for &i in &set.indices[1..] {
+            replace.insert(i, None);
+        }
+    }
+
+    entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| match replace.remove(&i) {
+            Some(group_entry) => group_entry,
+            None => Some(entry.clone()),
+        })
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod consolidate_tests {
+    use super::*;
+
+    fn sample() -> Vec<AclEntry> {
+        let rw = Perm::READ | Perm::WRITE;
+        vec![
+            AclEntry::allow_user("", rw, None),
+            AclEntry::allow_user("a", Perm::READ, None),
+            AclEntry::allow_user("b", rw, None),
+            AclEntry::allow_user("c", Perm::READ, None),
+            AclEntry::allow_group("g", Perm::READ, None),
+            AclEntry::allow_user("d", Perm::READ, None),
+            AclEntry {
+                allow: false,
+                ..AclEntry::allow_user("e", Perm::READ, None)
+            },
+            AclEntry::allow_user("f", rw, None),
+        ]
+    }
+
+    #[test]
+    fn test_consolidate() {
+        let entries = sample();
+        let sets = consolidate(&entries, 2);
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].indices, [1, 3, 5]);
+        assert_eq!(sets[0].users, ["a", "c", "d"]);
+        assert_eq!(sets[0].perms, Perm::READ);
+        assert!(sets[0].allow);
+        assert_eq!(sets[1].users, ["b", "f"]);
+
+        assert_eq!(consolidate(&entries, 3).len(), 1);
+        assert!(consolidate(&entries, 4).is_empty());
+        assert_eq!(consolidate(&entries, 0), sets);
+    }
+
+    #[test]
+    fn test_consolidate_with() {
+        let entries = sample();
+        let merged = consolidate_with(&entries, 2, |set| {
+            (set.perms == Perm::READ).then(|| "readers".to_string())
+        });
+
+        assert_eq!(
+            merged,
+            [
+                entries[0].clone(),
+                AclEntry::allow_group("readers", Perm::READ, None),
+                entries[2].clone(),
+                entries[4].clone(),
+                entries[6].clone(),
+                entries[7].clone(),
+            ]
+        );
+
+        assert_eq!(consolidate_with(&entries, 2, |_| None), entries);
+    }
+
+    #[test]
+    fn test_group_entry() {
+        let set = Consolidation {
+            indices: vec![0, 1],
+            users: vec!["a".to_string(), "b".to_string()],
+            perms: Perm::WRITE,
+            flags: Flag::default(),
+            allow: false,
+        };
+        let entry = set.group_entry("g");
+        assert_eq!(entry.kind, AclEntryKind::Group);
+        assert_eq!(entry.name, "g");
+        assert_eq!(entry.perms, Perm::WRITE);
+        assert!(!entry.allow);
+    }
+}
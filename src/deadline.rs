@@ -0,0 +1,209 @@
+//! Implements `with_timeout`, which runs ACL calls on a worker thread and
+//! gives up on a path that takes too long, e.g. on a dead network mount.
+
+use crate::failx::path_err;
+
+use std::cell::RefCell;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Progress of a call made by `with_timeout`: when the worker started on its
+/// current path, and the path.
+struct Progress {
+    state: Mutex<(Instant, Option<PathBuf>)>,
+}
+
+impl Progress {
+    fn snapshot(&self) -> (Instant, Option<PathBuf>) {
+        self.state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+}
+
+thread_local! {
+    /// Progress reported by this thread, if it is a worker of `with_timeout`.
+    static PROGRESS: RefCell<Option<Arc<Progress>>> = const { RefCell::new(None) };
+}
+
+/// Note that the current thread is starting work on `path`, which restarts
+/// the deadline of `with_timeout`. Does nothing on other threads.
+pub(crate) fn enter(path: &Path) {
+    PROGRESS.with(|progress| {
+        if let Some(progress) = &*progress.borrow() {
+            let mut state = progress.state.lock().unwrap_or_else(|err| err.into_inner());
+            *state = (Instant::now(), Some(path.to_path_buf()));
+        }
+    });
+}
+
+/// Run `f` on a worker thread, and fail if any one path takes longer than
+/// `timeout`.
+///
+/// ACL calls on a dead network mount can hang forever. Wrap a call such as
+/// [`getfacl`](crate::getfacl), [`setfacl`](crate::setfacl) or a tree
+/// operation like [`stats`](crate::stats) to make sure it returns. The
+/// deadline restarts each time the call moves on to another path, so a walk
+/// of a large tree succeeds as long as every path is quick.
+///
+/// If the deadline passes, the error has kind `TimedOut` and wraps a
+/// [`PathError`](crate::PathError) with the path that was stuck. The worker
+/// thread can't be interrupted: it is left to finish, or to stay blocked in the
+/// kernel, and its result is dropped. Each call uses a new worker, so one
+/// stuck mount doesn't hold up calls for other paths.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use std::time::Duration;
+///
+/// let stats = exacl::with_timeout(Duration::from_secs(5), || {
+///     exacl::stats("/mnt/nfs", None)
+/// })?;
+/// println!("{} paths", stats.paths);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns the result of `f`, an [`io::Error`] with kind `TimedOut` if the
+/// deadline passes, or an [`io::Error`] if the worker thread can't be started.
+///
+/// # Panics
+///
+/// Resumes the panic if `f` panics.
+pub fn with_timeout<T, F>(timeout: Duration, f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    let progress = Arc::new(Progress {
+        state: Mutex::new((Instant::now(), None)),
+    });
+    let (sender, receiver) = mpsc::channel();
+
+    let worker = Arc::clone(&progress);
+    thread::Builder::new()
+        .name("exacl-timeout".to_string())
+        .spawn(move || {
+            PROGRESS.with(|progress| *progress.borrow_mut() = Some(worker));
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            // The caller may have given up already.
+            let _ = sender.send(result);
+        })?;
+
+    loop {
+        let (started, path) = progress.snapshot();
+        let wait = (started + timeout).saturating_duration_since(Instant::now());
+
+        match receiver.recv_timeout(wait) {
+            Ok(Ok(result)) => return result,
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                // Keep waiting if the worker has moved on to another path.
+                if progress.snapshot().0 != started {
+                    continue;
+                }
+                let err = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out after {timeout:?}"),
+                );
+                return Err(match path {
+                    Some(path) => path_err(&path, &err),
+                    None => err,
+                });
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "worker exited without a result",
+                ));
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod deadline_tests {
+    use super::*;
+    use crate::failx::PathError;
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn test_with_timeout() -> io::Result<()> {
+        assert_eq!(with_timeout(TIMEOUT, || Ok(5))?, 5);
+
+        let err = with_timeout(TIMEOUT, || -> io::Result<()> {
+            Err(io::ErrorKind::NotFound.into())
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // Outside a worker, `enter` does nothing.
+        enter(Path::new("/tmp"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_timeout_stuck() {
+        let err = with_timeout(TIMEOUT, || {
+            enter(Path::new("/mnt/a"));
+            enter(Path::new("/mnt/b"));
+            thread::sleep(TIMEOUT * 10);
+            Ok(())
+        })
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let inner = err.get_ref().unwrap().downcast_ref::<PathError>().unwrap();
+        assert_eq!(inner.path(), Path::new("/mnt/b"));
+
+        let err = with_timeout(TIMEOUT, || {
+            thread::sleep(TIMEOUT * 10);
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.get_ref().unwrap().downcast_ref::<PathError>().is_none());
+    }
+
+    #[test]
+    fn test_with_timeout_progress() -> io::Result<()> {
+        // Every path is quick, though the whole call takes longer than the
+        // timeout.
+        let count = with_timeout(TIMEOUT, || {
+            for i in 0..6 {
+                enter(Path::new(&format!("/mnt/{i}")));
+                thread::sleep(TIMEOUT / 4);
+            }
+            Ok(6)
+        })?;
+        assert_eq!(count, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_timeout_tree() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a"), "")?;
+        let root = dir.path().to_path_buf();
+
+        let stats = with_timeout(Duration::from_secs(10), move || crate::stats(root, None))?;
+        assert_eq!(stats.paths, 2);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "oops")]
+    fn test_with_timeout_panic() {
+        let _ = with_timeout(TIMEOUT, || -> io::Result<()> { panic!("oops") });
+    }
+}
@@ -0,0 +1,380 @@
+//! Detects and parses other platforms' ACL text formats.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::format;
+use crate::perm::Perm;
+
+/// ACL text format detected by [`from_reader_detect`](crate::from_reader_detect).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// exacl's own format, written by [`to_writer`](crate::to_writer).
+    Native,
+
+    /// Output of Linux `getfacl`, e.g. `user:bob:r-x` and `default:group::r-x`.
+    Getfacl,
+
+    /// macOS `chmod +a` rules or `ls -le` output, e.g.
+    /// `user:bob allow read,write,file_inherit`.
+    Chmod,
+
+    /// `FreeBSD` `setfacl`/`getfacl` `NFSv4` entries, e.g.
+    /// `user:bob:rw-p--aARWcCos:fd-----:allow`.
+    Nfs4,
+}
+
+/// Return true if `line` is a header written by Linux `getfacl`.
+fn is_getfacl_header(line: &str) -> bool {
+    ["# file:", "# owner:", "# group:", "# flags:"]
+        .iter()
+        .any(|header| line.starts_with(header))
+}
+
+/// Return true if `value` is an allow/deny type in `NFSv4` or `chmod` syntax.
+fn is_entry_type(value: &str) -> bool {
+    matches!(value, "allow" | "deny" | "audit" | "alarm")
+}
+
+/// Return the dialect of a single line, or None if the line is blank.
+fn classify(line: &str) -> Option<Dialect> {
+    if is_getfacl_header(line) {
+        return Some(Dialect::Getfacl);
+    }
+
+    let line = crate::trim_comment(line).trim();
+    if line.is_empty() {
+        return None;
+    }
+
+    let mut words = strip_index(line).split_whitespace();
+    if words.next().is_some_and(|word| word.contains(':'))
+        && words
+            .next()
+            .is_some_and(|word| word == "inherited" || is_entry_type(word))
+    {
+        return Some(Dialect::Chmod);
+    }
+
+    let fields = line.split(':').map(str::trim).collect::<Vec<_>>();
+    if (4..=5).contains(&fields.len()) && is_entry_type(fields[fields.len() - 1]) {
+        return Some(Dialect::Nfs4);
+    }
+
+    let perms = fields[fields.len() - 1].as_bytes();
+    if perms.len() == 3 && perms.contains(&b'-') && perms.iter().all(|ch| b"rwx-".contains(ch)) {
+        return Some(Dialect::Getfacl);
+    }
+
+    Some(Dialect::Native)
+}
+
+/// Return the dialect of a list of lines.
+///
+/// The first line that isn't in the native format decides the dialect. Linux
+/// `getfacl` output is a subset of the native format.
+pub(crate) fn detect<S: AsRef<str>>(lines: &[S]) -> Dialect {
+    lines
+        .iter()
+        .filter_map(|line| classify(line.as_ref()))
+        .find(|dialect| *dialect != Dialect::Native)
+        .unwrap_or(Dialect::Native)
+}
+
+/// Parse one line in the given dialect. Blank lines and comments must already
+/// be removed.
+pub(crate) fn parse_line(line: &str, dialect: Dialect) -> Result<AclEntry, format::Error> {
+    match dialect {
+        Dialect::Native | Dialect::Getfacl => line.parse::<AclEntry>(),
+        Dialect::Chmod => parse_chmod(line),
+        Dialect::Nfs4 => parse_nfs4(line),
+    }
+}
+
+fn error(msg: String) -> format::Error {
+    format::Error::Message(msg)
+}
+
+/// Remove the entry number from a line of `ls -le` output, e.g. " 0: ".
+fn strip_index(line: &str) -> &str {
+    match line.split_once(':') {
+        Some((index, rest)) if !index.is_empty() && index.bytes().all(|ch| ch.is_ascii_digit()) => {
+            rest.trim_start()
+        }
+        _ => line,
+    }
+}
+
+/// Parse the `<kind>:<name>` field used by `chmod` and `NFSv4` entries.
+fn parse_principal(kind: &str, name: &str) -> Result<AclEntryKind, format::Error> {
+    match kind {
+        "user" => Ok(AclEntryKind::User),
+        "group" => Ok(AclEntryKind::Group),
+        _ => Err(error(format!(
+            "Unknown variant `{kind}`, expected one of `user`, `group` for `{name}`"
+        ))),
+    }
+}
+
+/// Parse a macOS `chmod +a` rule: `<kind>:<name> [inherited] <allow|deny> <perms,flags>`.
+fn parse_chmod(line: &str) -> Result<AclEntry, format::Error> {
+    let unknown = || error(format!("Unknown chmod ACL format: `{line}`"));
+
+    let line = strip_index(line.trim_start());
+    let (principal, rest) = line.split_once(char::is_whitespace).ok_or_else(unknown)?;
+    let (kind, name) = principal.split_once(':').ok_or_else(unknown)?;
+    let kind = parse_principal(kind, name)?;
+
+    let mut flags = Flag::empty();
+    let mut rest = rest.trim_start();
+    if let Some(after) = rest.strip_prefix("inherited") {
+        flags |= chmod_flag("inherited")?;
+        rest = after.trim_start();
+    }
+
+    let (allow, rest) = if let Some(after) = rest.strip_prefix("allow") {
+        (true, after)
+    } else if let Some(after) = rest.strip_prefix("deny") {
+        (false, after)
+    } else {
+        return Err(unknown());
+    };
+
+    let mut perms = Perm::empty();
+    for word in rest
+        .split(',')
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+    {
+        let name = match word {
+            "list" => "read",
+            "search" => "execute",
+            "add_file" => "write",
+            "add_subdirectory" => "append",
+            _ => word,
+        };
+        match name.parse::<Perm>() {
+            Ok(perm) => perms |= perm,
+            Err(_) => flags |= chmod_flag(name)?,
+        }
+    }
+
+    Ok(AclEntry {
+        kind,
+        name: name.to_string(),
+        perms,
+        flags,
+        allow,
+        numeric: false,
+    })
+}
+
+/// Parse a flag name used by `chmod`.
+fn chmod_flag(name: &str) -> Result<Flag, format::Error> {
+    name.parse::<Flag>().map_err(|_| {
+        error(format!(
+            "Unknown permission or flag `{name}` for this platform"
+        ))
+    })
+}
+
+/// Parse a `FreeBSD` `NFSv4` entry: `<tag>[:<name>]:<perms>:<flags>:<type>`.
+fn parse_nfs4(line: &str) -> Result<AclEntry, format::Error> {
+    let fields = line.split(':').map(str::trim).collect::<Vec<_>>();
+
+    let (kind, name, fields) = match fields.len() {
+        5 => (
+            parse_principal(fields[0], fields[1])?,
+            fields[1],
+            &fields[2..],
+        ),
+        4 => (nfs4_special(fields[0])?, "", &fields[1..]),
+        _ => return Err(error(format!("Unknown NFSv4 ACL format: `{line}`"))),
+    };
+
+    let allow = match fields[2] {
+        "allow" => true,
+        "deny" => false,
+        s => {
+            return Err(error(format!(
+                "Unsupported entry type `{s}`, expected one of `allow`, `deny`"
+            )))
+        }
+    };
+
+    let mut perms = Perm::empty();
+    for ch in fields[0].chars().filter(|ch| *ch != '-') {
+        perms |= nfs4_perm(ch)?.parse::<Perm>()?;
+    }
+
+    let mut flags = Flag::empty();
+    for ch in fields[1].chars().filter(|ch| *ch != '-') {
+        flags |= nfs4_flag(ch)?.parse::<Flag>()?;
+    }
+
+    Ok(AclEntry {
+        kind,
+        name: name.to_string(),
+        perms,
+        flags,
+        allow,
+        numeric: false,
+    })
+}
+
+/// Parse `owner@`, `group@` and `everyone@`.
+fn nfs4_special(tag: &str) -> Result<AclEntryKind, format::Error> {
+    match tag {
+        "owner@" => Ok(AclEntryKind::User),
+        "group@" => Ok(AclEntryKind::Group),
+        "everyone@" => Ok(AclEntryKind::Everyone),
+        _ => Err(error(format!("Unsupported NFSv4 tag `{tag}`"))),
+    }
+}
+
+/// Return the exacl name of an `NFSv4` permission letter.
+fn nfs4_perm(ch: char) -> Result<&'static str, format::Error> {
+    let name = match ch {
+        #[cfg(exacl_nfs4)]
+        'r' => "read_data",
+        #[cfg(exacl_nfs4)]
+        'w' => "write_data",
+        #[cfg(not(exacl_nfs4))]
+        'r' => "read",
+        #[cfg(not(exacl_nfs4))]
+        'w' => "write",
+        'x' => "execute",
+        'p' => "append",
+        'D' => "delete_child",
+        'd' => "delete",
+        'a' => "readattr",
+        'A' => "writeattr",
+        'R' => "readextattr",
+        'W' => "writeextattr",
+        'c' => "readsecurity",
+        'C' => "writesecurity",
+        'o' => "chown",
+        's' => "sync",
+        _ => return Err(error(format!("Unknown NFSv4 permission `{ch}`"))),
+    };
+
+    Ok(name)
+}
+
+/// Return the exacl name of an `NFSv4` flag letter.
+fn nfs4_flag(ch: char) -> Result<&'static str, format::Error> {
+    let name = match ch {
+        'f' => "file_inherit",
+        'd' => "directory_inherit",
+        'i' => "only_inherit",
+        'n' => "limit_inherit",
+        'I' => "inherited",
+        _ => return Err(error(format!("Unsupported NFSv4 flag `{ch}`"))),
+    };
+
+    Ok(name)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod dialect_tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect::<&str>(&[]), Dialect::Native);
+        assert_eq!(detect(&["", "allow::user:a:read"]), Dialect::Native);
+        assert_eq!(detect(&["u:a:rw", "g:b:r-x"]), Dialect::Getfacl);
+        assert_eq!(detect(&["# file: foo", "user::rwx"]), Dialect::Getfacl);
+        assert_eq!(detect(&["user:a allow read"]), Dialect::Chmod);
+        assert_eq!(
+            detect(&[" 0: group:b inherited deny write"]),
+            Dialect::Chmod
+        );
+        assert_eq!(
+            detect(&["owner@:rw-p--aARWcCos:-------:allow"]),
+            Dialect::Nfs4
+        );
+        assert_eq!(
+            detect(&["user:a:r-----a-R-c---:fd-----:deny"]),
+            Dialect::Nfs4
+        );
+    }
+
+    #[test]
+    fn test_parse_chmod() {
+        let entry = parse_line("user:a allow read,list,search", Dialect::Chmod).unwrap();
+        assert_eq!(
+            entry,
+            AclEntry::allow_user("a", Perm::READ | Perm::EXECUTE, None)
+        );
+
+        let err = parse_line("other:a allow read", Dialect::Chmod).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown variant `other`, expected one of `user`, `group` for `a`"
+        );
+
+        let err = parse_line("user:a maybe read", Dialect::Chmod).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown chmod ACL format: `user:a maybe read`"
+        );
+
+        #[cfg(exacl_extended)]
+        {
+            let line = " 1: group:b inherited deny write,delete,file_inherit";
+            let entry = parse_line(line, Dialect::Chmod).unwrap();
+            assert_eq!(
+                entry,
+                AclEntry::deny_group(
+                    "b",
+                    Perm::WRITE | Perm::DELETE,
+                    Flag::INHERITED | Flag::FILE_INHERIT
+                )
+            );
+        }
+
+        #[cfg(not(exacl_extended))]
+        {
+            let err = parse_line("user:a allow delete", Dialect::Chmod).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Unknown permission or flag `delete` for this platform"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_nfs4() {
+        let entry = parse_line("group@:--x-----------:-------:allow", Dialect::Nfs4).unwrap();
+        assert_eq!(entry, AclEntry::allow_group("", Perm::EXECUTE, None));
+
+        let err = parse_line("user:a:r:-:audit", Dialect::Nfs4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported entry type `audit`, expected one of `allow`, `deny`"
+        );
+
+        let err = parse_line("user:a:rq:-:allow", Dialect::Nfs4).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown NFSv4 permission `q`");
+
+        #[cfg(exacl_nfs4)]
+        {
+            let line = "user:a:rw-p--aARWcCos:fd-----:deny";
+            let entry = parse_line(line, Dialect::Nfs4).unwrap();
+            assert_eq!(entry.to_string(), "deny:file_inherit,directory_inherit:user:a:read_data,write_data,append,readextattr,writeextattr,readattr,writeattr,readsecurity,writesecurity,chown,sync");
+
+            let entry = parse_line("everyone@:r:S:allow", Dialect::Nfs4).unwrap_err();
+            assert_eq!(entry.to_string(), "Unsupported NFSv4 flag `S`");
+        }
+
+        let entry = parse_line("everyone@:--x-----------:-------:deny", Dialect::Nfs4).unwrap();
+        assert_eq!(entry.kind, AclEntryKind::Everyone);
+        assert_eq!((entry.perms, entry.allow), (Perm::EXECUTE, false));
+
+        let err = parse_line("nobody@:r::allow", Dialect::Nfs4).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported NFSv4 tag `nobody@`");
+    }
+}
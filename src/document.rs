@@ -0,0 +1,217 @@
+//! Implements `AclDocument` and the document-level readers and writers for
+//! structured formats.
+
+use crate::aclentry::AclEntry;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Current version of [`AclDocument`].
+#[cfg(feature = "serde")]
+const DOCUMENT_VERSION: u32 = 1;
+
+/// Format of a whole list of entries, for [`to_writer_format`] and
+/// [`from_reader_format`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    /// The text format of [`to_writer`](crate::to_writer), one entry per line.
+    #[default]
+    Text,
+
+    /// An [`AclDocument`] in JSON (requires the `json` feature).
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    Json,
+}
+
+/// A versioned list of ACL entries, for structured formats (requires the
+/// `serde` feature).
+///
+/// Serializes as `{"version": 1, "entries": [...]}`, where each entry is an
+/// [`AclEntry`] struct. Deserializing a document with a newer version fails,
+/// so an old backup tool won't misread a newer document. Use this with any
+/// `serde` format; [`to_json`] and [`from_json`] handle JSON directly.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawDocument")]
+pub struct AclDocument {
+    version: u32,
+
+    /// Entries of the ACL.
+    pub entries: Vec<AclEntry>,
+}
+
+#[cfg(feature = "serde")]
+impl AclDocument {
+    /// Construct a document with the current version.
+    #[must_use]
+    pub const fn new(entries: Vec<AclEntry>) -> AclDocument {
+        AclDocument {
+            version: DOCUMENT_VERSION,
+            entries,
+        }
+    }
+
+    /// Return the version of the document.
+    #[must_use]
+    pub const fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Document as read, before its version is checked.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDocument {
+    version: u32,
+    entries: Vec<AclEntry>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawDocument> for AclDocument {
+    type Error = String;
+
+    fn try_from(raw: RawDocument) -> Result<Self, Self::Error> {
+        if raw.version == 0 || raw.version > DOCUMENT_VERSION {
+            return Err(format!("unsupported document version: {}", raw.version));
+        }
+
+        Ok(AclDocument {
+            version: raw.version,
+            entries: raw.entries,
+        })
+    }
+}
+
+/// Write ACL entries as a JSON [`AclDocument`] (requires the `json`
+/// feature).
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclEntry, Perm};
+///
+/// let entries = vec![AclEntry::allow_user("bob", Perm::READ, None)];
+/// let mut json = Vec::new();
+/// exacl::to_json(&mut json, &entries)?;
+/// assert_eq!(exacl::from_json(json.as_slice())?, entries);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if writing fails.
+#[cfg(feature = "json")]
+pub fn to_json<W: io::Write>(writer: W, entries: &[AclEntry]) -> io::Result<()> {
+    #[derive(Serialize)]
+    struct DocumentRef<'a> {
+        version: u32,
+        entries: &'a [AclEntry],
+    }
+
+    let document = DocumentRef {
+        version: DOCUMENT_VERSION,
+        entries,
+    };
+    serde_json::to_writer_pretty(writer, &document).map_err(io::Error::from)
+}
+
+/// Read ACL entries from a JSON [`AclDocument`] (requires the `json`
+/// feature).
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if reading fails, or with kind `InvalidData` if
+/// the JSON is not a document of a supported version.
+#[cfg(feature = "json")]
+pub fn from_json<R: io::Read>(reader: R) -> io::Result<Vec<AclEntry>> {
+    let document: AclDocument = serde_json::from_reader(reader).map_err(io::Error::from)?;
+    Ok(document.entries)
+}
+
+/// Write ACL entries in the given format.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if writing fails.
+pub fn to_writer_format<W: io::Write>(
+    writer: W,
+    entries: &[AclEntry],
+    format: Format,
+) -> io::Result<()> {
+    match format {
+        Format::Text => crate::to_writer(writer, entries),
+        #[cfg(feature = "json")]
+        Format::Json => to_json(writer, entries),
+    }
+}
+
+/// Read ACL entries in the given format.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if reading fails or the input can't be parsed.
+pub fn from_reader_format<R: io::Read>(reader: R, format: Format) -> io::Result<Vec<AclEntry>> {
+    match format {
+        Format::Text => crate::from_reader(reader),
+        #[cfg(feature = "json")]
+        Format::Json => from_json(reader),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod document_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    fn sample() -> Vec<AclEntry> {
+        vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("x:y", Perm::READ, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ]
+    }
+
+    #[test]
+    fn test_format_text() -> io::Result<()> {
+        let mut buf = Vec::new();
+        to_writer_format(&mut buf, &sample(), Format::Text)?;
+        assert_eq!(buf, crate::to_string(&sample())?.as_bytes());
+        assert_eq!(from_reader_format(buf.as_slice(), Format::Text)?, sample());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_format_json() -> io::Result<()> {
+        let mut buf = Vec::new();
+        to_writer_format(&mut buf, &sample(), Format::Json)?;
+        assert_eq!(from_reader_format(buf.as_slice(), Format::Json)?, sample());
+
+        let document: AclDocument = serde_json::from_slice(&buf)?;
+        assert_eq!(document, AclDocument::new(sample()));
+        assert_eq!(document.version(), 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_from_json_errors() {
+        let err = from_json(&br#"{"version": 2, "entries": []}"#[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err
+            .to_string()
+            .starts_with("unsupported document version: 2"));
+
+        let err = from_json(&br#"{"entries": []}"#[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = from_json(&br#"{"version": 1, "entries": [], "x": 0}"#[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
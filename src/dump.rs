@@ -0,0 +1,310 @@
+//! Implements reading and writing ACL dumps of many files, one record at a
+//! time.
+
+use crate::aclentry::AclEntry;
+use crate::format::FormatVersion;
+use crate::redact::Redactor;
+
+use std::ffi::OsString;
+use std::io::{self, BufRead};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+/// Header line that starts the record of each file in a dump.
+const FILE_HEADER: &[u8] = b"# file: ";
+
+/// Write the ACL of one file to a dump, like `getfacl -R`.
+///
+/// The record is a `# file: <path>` header, the entries in the format of
+/// [`to_writer`](crate::to_writer), and a blank line. Backslashes, spaces and
+/// control characters in the path are escaped as a backslash and three octal
+/// digits, so any path can be read back by [`dump_from_reader`].
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if writing fails.
+pub fn write_dump<W: io::Write>(
+    mut writer: W,
+    path: &Path,
+    entries: &[AclEntry],
+) -> io::Result<()> {
+    writer.write_all(FILE_HEADER)?;
+    writer.write_all(&escape_path(path))?;
+    writer.write_all(b"\n")?;
+    crate::to_writer_with_version(&mut writer, entries, FormatVersion::LATEST)?;
+    writer.write_all(b"\n")
+}
+
+/// Write the ACL of one file to a dump, with user and group names replaced by
+/// pseudonyms.
+///
+/// Like [`write_dump`], but each entry is passed through the [`Redactor`]
+/// first. Use the same redactor for every file, so a principal gets the same
+/// pseudonym throughout the dump. The path is written as is.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if writing fails.
+pub fn write_dump_redacted<W: io::Write>(
+    mut writer: W,
+    path: &Path,
+    entries: &[AclEntry],
+    redactor: &Redactor,
+) -> io::Result<()> {
+    writer.write_all(FILE_HEADER)?;
+    writer.write_all(&escape_path(path))?;
+    writer.write_all(b"\n")?;
+    crate::to_writer_redacted(&mut writer, entries, redactor)?;
+    writer.write_all(b"\n")
+}
+
+/// Read a dump of many files, one file at a time.
+///
+/// Each item is the path and entries of one file, as written by
+/// [`write_dump`]. Linux `getfacl -R` output is also accepted. Only one record
+/// is held in memory at a time, so a dump may be larger than memory. Other
+/// comments and blank lines are ignored.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{dump_from_reader, write_dump, AclEntry, Perm};
+/// use std::path::Path;
+///
+/// let entries = vec![AclEntry::allow_user("bob", Perm::READ, None)];
+/// let mut dump = Vec::new();
+/// write_dump(&mut dump, Path::new("/tmp/a b"), &entries)?;
+/// write_dump(&mut dump, Path::new("/tmp/c"), &entries)?;
+///
+/// for record in dump_from_reader(dump.as_slice()) {
+///     let (path, entries) = record?;
+///     println!("{}: {} entries", path.display(), entries.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// An item is an [`io::Error`] if reading fails, or with kind `InvalidInput`
+/// and the line number if a line can't be parsed.
+pub fn dump_from_reader<R: io::Read>(
+    reader: R,
+) -> impl Iterator<Item = io::Result<(PathBuf, Vec<AclEntry>)>> {
+    DumpReader {
+        reader: io::BufReader::new(reader),
+        lineno: 0,
+        path: None,
+        done: false,
+    }
+}
+
+/// Iterator returned by `dump_from_reader`.
+struct DumpReader<R> {
+    reader: io::BufReader<R>,
+    lineno: usize,
+
+    /// Path from the header of the record being read.
+    path: Option<PathBuf>,
+    done: bool,
+}
+
+impl<R: io::Read> DumpReader<R> {
+    /// Read lines until the next header or the end of input, and return the
+    /// record they complete.
+    fn read_record(&mut self) -> io::Result<Option<(PathBuf, Vec<AclEntry>)>> {
+        let mut entries = Vec::new();
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.done = true;
+                return Ok(self.path.take().map(|path| (path, entries)));
+            }
+            self.lineno += 1;
+
+            if let Some(value) = line.strip_prefix(FILE_HEADER) {
+                let value = value.strip_suffix(b"\n").unwrap_or(value);
+                let path = unescape_path(value).ok_or_else(|| self.error("Invalid path"))?;
+                match self.path.replace(path) {
+                    Some(prev) => return Ok(Some((prev, entries))),
+                    None => continue,
+                }
+            }
+
+            let text = std::str::from_utf8(&line).map_err(|_| self.error("Invalid UTF-8"))?;
+            let text = crate::trim_comment(text).trim();
+            if text.is_empty() {
+                continue;
+            }
+            if self.path.is_none() {
+                return Err(self.error("Entry before `# file:` header"));
+            }
+            let entry = text
+                .parse::<AclEntry>()
+                .map_err(|err| self.error(&err.to_string()))?;
+            entries.push(entry);
+        }
+    }
+
+    fn error(&self, msg: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("line {}: {msg}", self.lineno),
+        )
+    }
+}
+
+impl<R: io::Read> Iterator for DumpReader<R> {
+    type Item = io::Result<(PathBuf, Vec<AclEntry>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_record();
+        if result.is_err() {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+/// Escape backslashes, spaces and control characters in a path.
+pub(crate) fn escape_path(path: &Path) -> Vec<u8> {
+    let mut result = Vec::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte == b'\\' || byte == b' ' || byte.is_ascii_control() {
+            result.extend_from_slice(format!("\\{byte:03o}").as_bytes());
+        } else {
+            result.push(byte);
+        }
+    }
+
+    result
+}
+
+/// Parse a path written by `escape_path`.
+pub(crate) fn unescape_path(value: &[u8]) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'\\' {
+            let digits = tail.get(..3)?;
+            if !digits.iter().all(|ch| (b'0'..=b'7').contains(ch)) {
+                return None;
+            }
+            bytes.push(u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok()?);
+            rest = &tail[3..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod dump_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_escape_path() {
+        let path = Path::new("a\tb\\c\n\u{e9} d");
+        let escaped = escape_path(path);
+        assert_eq!(escaped, b"a\\011b\\134c\\012\xc3\xa9\\040d");
+        assert_eq!(unescape_path(&escaped).unwrap(), path);
+        assert_eq!(unescape_path(b"a\\01"), None);
+        assert_eq!(unescape_path(b"a\\019"), None);
+        assert_eq!(unescape_path(b"a\\+12"), None);
+        assert_eq!(unescape_path(b"a\\400"), None);
+    }
+
+    #[test]
+    fn test_dump_roundtrip() -> io::Result<()> {
+        let a = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("bob", Perm::READ, None),
+        ];
+        let b = vec![AclEntry::allow_group("x y", Perm::EXECUTE, None)];
+        let empty = Vec::new();
+        let odd = Path::new(std::ffi::OsStr::from_bytes(b"/tmp/caf\xe9 \\"));
+
+        let mut dump = Vec::new();
+        write_dump(&mut dump, Path::new("/tmp/a"), &a)?;
+        write_dump(&mut dump, odd, &b)?;
+        write_dump(&mut dump, Path::new("/tmp/empty"), &empty)?;
+
+        let records = dump_from_reader(dump.as_slice()).collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(
+            records,
+            [
+                (PathBuf::from("/tmp/a"), a),
+                (odd.to_path_buf(), b),
+                (PathBuf::from("/tmp/empty"), empty),
+            ]
+        );
+
+        assert_eq!(dump_from_reader(&b""[..]).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_redacted() -> io::Result<()> {
+        let a = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("bob", Perm::READ, None),
+        ];
+        let b = vec![AclEntry::allow_user("bob", Perm::EXECUTE, None)];
+        let redactor = Redactor::new(b"salt");
+
+        let mut dump = Vec::new();
+        write_dump_redacted(&mut dump, Path::new("/tmp/a"), &a, &redactor)?;
+        write_dump_redacted(&mut dump, Path::new("/tmp/b"), &b, &redactor)?;
+
+        // The same principal gets the same pseudonym in every record.
+        let records = dump_from_reader(dump.as_slice()).collect::<io::Result<Vec<_>>>()?;
+        let pseudonym = redactor.pseudonym(&a[1]);
+        assert_eq!(records[0].1[0], a[0]);
+        assert_eq!(records[0].1[1].name, pseudonym);
+        assert_eq!(records[1].1[0].name, pseudonym);
+        assert_eq!(records[1].0, PathBuf::from("/tmp/b"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_getfacl() -> io::Result<()> {
+        let input = "# file: a\n# owner: root\nuser::rw-\ngroup::r--\nother::---\n\n\
+                     # file: b\\040c\nuser::rwx\n";
+        let records = dump_from_reader(input.as_bytes()).collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, Path::new("a"));
+        assert_eq!(records[0].1.len(), 3);
+        assert_eq!(records[1].0, Path::new("b c"));
+        assert_eq!(records[1].1.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_errors() {
+        let mut records =
+            dump_from_reader("# file: a\nuser::rw-\n# file: b\nbad\nuser::r\n".as_bytes());
+        assert!(records.next().unwrap().is_ok());
+        let err = records.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().starts_with("line 4: "), "{err}");
+        assert!(records.next().is_none());
+
+        let err = dump_from_reader("user::rw-\n".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 1: Entry before `# file:` header");
+    }
+}
@@ -0,0 +1,251 @@
+//! Implements `Editor`, which modifies Posix.1e ACL entries like `setfacl -m`.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+/// Modifies a list of Posix.1e ACL entries, maintaining the mask like
+/// `setfacl`.
+///
+/// When a group class entry (a named user, the owning group or a named group)
+/// is added, changed or removed, [`Editor::finish`] recalculates the mask as
+/// the union of the group class permissions. The access and default ACL's are
+/// tracked separately. The mask is left alone if nothing in the group class
+/// changed, if it was set explicitly with [`Editor::set_mask`] (or by setting a
+/// mask entry), or if recalculation is turned off with
+/// [`Editor::set_recalc_mask`], like `setfacl -n`.
+///
+/// ```
+/// use exacl::{AclEntry, Editor, Perm};
+///
+/// let mut editor = Editor::new(vec![
+///     AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+///     AclEntry::allow_group("", Perm::READ, None),
+///     AclEntry::allow_other(Perm::empty(), None),
+/// ]);
+/// editor.set(AclEntry::allow_user("bob", Perm::READ | Perm::EXECUTE, None));
+///
+/// let entries = editor.finish();
+/// assert_eq!(entries[4], AclEntry::allow_mask(Perm::READ | Perm::EXECUTE, None));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Editor {
+    entries: Vec<AclEntry>,
+    recalc_mask: bool,
+    /// Indexed by `scope`: true if a group class entry changed.
+    changed: [bool; 2],
+    /// Indexed by `scope`: true if the mask was set explicitly.
+    explicit_mask: [bool; 2],
+}
+
+impl Editor {
+    /// Construct an editor for the given entries.
+    #[must_use]
+    pub const fn new(entries: Vec<AclEntry>) -> Editor {
+        Editor {
+            entries,
+            recalc_mask: true,
+            changed: [false; 2],
+            explicit_mask: [false; 2],
+        }
+    }
+
+    /// Return the current entries. The mask is not recalculated until
+    /// [`Editor::finish`].
+    #[must_use]
+    pub fn entries(&self) -> &[AclEntry] {
+        &self.entries
+    }
+
+    /// Set whether the mask is recalculated when group class entries change.
+    /// The default is true; false is like `setfacl -n`.
+    pub fn set_recalc_mask(&mut self, recalc: bool) {
+        self.recalc_mask = recalc;
+    }
+
+    /// Add an entry, or replace the permissions of an existing entry with the
+    /// same kind, name and flags.
+    ///
+    /// Setting a mask entry is the same as calling [`Editor::set_mask`].
+    pub fn set(&mut self, entry: AclEntry) {
+        let scope = scope(&entry);
+        if entry.kind == AclEntryKind::Mask {
+            self.explicit_mask[scope] = true;
+        } else if is_group_class(&entry) {
+            self.changed[scope] = true;
+        }
+
+        match self.entries.iter_mut().find(|e| is_same_entry(e, &entry)) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Remove the entry with the same kind, name and flags as `entry`. Return
+    /// true if an entry was removed.
+    pub fn remove(&mut self, entry: &AclEntry) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|e| !is_same_entry(e, entry));
+        let removed = self.entries.len() != len;
+
+        if removed && is_group_class(entry) {
+            self.changed[scope(entry)] = true;
+        }
+
+        removed
+    }
+
+    /// Set the permissions of the access ACL's mask entry, like
+    /// `setfacl -m m::<perms>`. The mask is not recalculated.
+    pub fn set_mask(&mut self, perms: Perm) {
+        self.set(AclEntry::allow_mask(perms, None));
+    }
+
+    /// Return the modified entries, with the mask recalculated if needed.
+    #[must_use]
+    pub fn finish(mut self) -> Vec<AclEntry> {
+        if self.recalc_mask {
+            for default in [false, true] {
+                let scope = usize::from(default);
+                if self.changed[scope] && !self.explicit_mask[scope] {
+                    self.recalc(default);
+                }
+            }
+        }
+
+        self.entries
+    }
+
+    /// Recalculate the mask of the access or default ACL. A mask is only
+    /// added if the ACL has named entries.
+    fn recalc(&mut self, default: bool) {
+        let in_scope = |entry: &&AclEntry| scope(entry) == usize::from(default);
+
+        let perms = self
+            .entries
+            .iter()
+            .filter(in_scope)
+            .filter(|entry| is_group_class(entry))
+            .fold(Perm::empty(), |acc, entry| acc | entry.perms);
+        let has_named = self
+            .entries
+            .iter()
+            .filter(in_scope)
+            .any(|entry| is_group_class(entry) && !entry.name.is_empty());
+
+        let flags = if default {
+            Flag::DEFAULT
+        } else {
+            Flag::empty()
+        };
+        let mask = AclEntry::allow_mask(perms, flags);
+        match self.entries.iter_mut().find(|e| is_same_entry(e, &mask)) {
+            Some(existing) => *existing = mask,
+            None if has_named => self.entries.push(mask),
+            None => (),
+        }
+    }
+}
+
+/// Return 1 if entry belongs to the default ACL, 0 for the access ACL.
+fn scope(entry: &AclEntry) -> usize {
+    usize::from(entry.flags.contains(Flag::DEFAULT))
+}
+
+/// Return true if the entry is limited by the mask.
+fn is_group_class(entry: &AclEntry) -> bool {
+    match entry.kind {
+        AclEntryKind::User => !entry.name.is_empty(),
+        AclEntryKind::Group => true,
+        _ => false,
+    }
+}
+
+/// Return true if both entries have the same kind, name and flags.
+fn is_same_entry(a: &AclEntry, b: &AclEntry) -> bool {
+    a.kind == b.kind && a.name == b.name && a.flags == b.flags
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod editor_tests {
+    use super::*;
+
+    fn sample() -> Vec<AclEntry> {
+        vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("a", Perm::READ, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            AclEntry::allow_mask(Perm::READ, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ]
+    }
+
+    #[test]
+    fn test_recalc() {
+        let mut editor = Editor::new(sample());
+        editor.set(AclEntry::allow_group("b", Perm::WRITE, None));
+        let entries = editor.finish();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[3],
+            AclEntry::allow_mask(Perm::READ | Perm::WRITE, None)
+        );
+
+        // Changing the owner or other doesn't touch the mask.
+        let mut editor = Editor::new(sample());
+        editor.set(AclEntry::allow_user("", Perm::all(), None));
+        editor.set(AclEntry::allow_other(Perm::READ, None));
+        assert_eq!(editor.entries().len(), 5);
+        let entries = editor.finish();
+        assert_eq!(entries[3], AclEntry::allow_mask(Perm::READ, None));
+        assert_eq!(entries[4], AclEntry::allow_other(Perm::READ, None));
+    }
+
+    #[test]
+    fn test_no_recalc() {
+        let mut editor = Editor::new(sample());
+        editor.set_recalc_mask(false);
+        editor.set(AclEntry::allow_user("a", Perm::all(), None));
+        assert_eq!(editor.finish()[3], AclEntry::allow_mask(Perm::READ, None));
+
+        let mut editor = Editor::new(sample());
+        editor.set(AclEntry::allow_user("a", Perm::all(), None));
+        editor.set_mask(Perm::EXECUTE);
+        assert_eq!(
+            editor.finish()[3],
+            AclEntry::allow_mask(Perm::EXECUTE, None)
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut editor = Editor::new(sample());
+        assert!(editor.remove(&AclEntry::allow_user("a", Perm::empty(), None)));
+        assert!(!editor.remove(&AclEntry::allow_user("z", Perm::empty(), None)));
+        editor.set(AclEntry::allow_group("", Perm::EXECUTE, None));
+        let entries = editor.finish();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[2], AclEntry::allow_mask(Perm::EXECUTE, None));
+    }
+
+    #[test]
+    fn test_default_scope() {
+        let mut entries = sample();
+        entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
+        entries.push(AclEntry::allow_group("", Perm::READ, Flag::DEFAULT));
+        entries.push(AclEntry::allow_other(Perm::READ, Flag::DEFAULT));
+
+        let mut editor = Editor::new(entries);
+        editor.set(AclEntry::allow_group("b", Perm::WRITE, Flag::DEFAULT));
+        let entries = editor.finish();
+
+        // Access mask is unchanged; default mask is added.
+        assert_eq!(entries[3], AclEntry::allow_mask(Perm::READ, None));
+        assert_eq!(
+            entries.last().unwrap(),
+            &AclEntry::allow_mask(Perm::READ | Perm::WRITE, Flag::DEFAULT)
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Implements `ensure_default_acl`, which keeps the default ACL of a directory
+//! in line with a template, and helpers to read, write and simulate the
+//! inheritance of default ACL's.
+
+use crate::acl::AclOption;
+use crate::aclentry::{AclEntry, AclEntryKind, PathKind};
+use crate::aclset::AclSet;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::{getfacl, setfacl};
+
+use std::borrow::Borrow;
+use std::io;
+use std::path::Path;
+
+/// Result of [`ensure_default_acl`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DefaultAclStatus {
+    /// The directory's default ACL already matches the template.
+    Matched,
+
+    /// The directory had no default ACL, and the template was written.
+    Applied,
+
+    /// The directory's default ACL differs from the template. It was left
+    /// unchanged.
+    Drift {
+        /// Template permissions missing from the default ACL.
+        missing: Vec<AclEntry>,
+
+        /// Permissions in the default ACL that are not in the template.
+        extra: Vec<AclEntry>,
+    },
+}
+
+/// Make sure a directory's default ACL matches a template.
+///
+/// If the directory has no default ACL, the template is written as its default
+/// ACL. If it has one, it is compared with the template but never changed; any
+/// difference is reported as [`DefaultAclStatus::Drift`], so the caller can
+/// decide whether to overwrite it. Template entries may have the `DEFAULT`
+/// flag or not. The entries returned in `Drift` have the `DEFAULT` flag, like
+/// the entries read by [`getfacl`](crate::getfacl).
+///
+/// The template must be a complete ACL, including the owner, group, other
+/// and, if there are named entries, mask entries.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{ensure_default_acl, AclEntry, DefaultAclStatus, Perm};
+///
+/// let mut template = exacl::from_mode(0o750);
+/// template.push(AclEntry::allow_group("staff", Perm::READ | Perm::EXECUTE, None));
+/// template.push(AclEntry::allow_mask(Perm::READ | Perm::EXECUTE, None));
+///
+/// let status = ensure_default_acl("./tmp/shared", &template)?;
+/// if let DefaultAclStatus::Drift { missing, extra } = status {
+///     println!("missing: {missing:?}, extra: {extra:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the path is not a directory, if the template is
+/// not a valid ACL, or if reading or writing the ACL fails.
+pub fn ensure_default_acl<P, E>(dir: P, entries: E) -> io::Result<DefaultAclStatus>
+where
+    P: AsRef<Path>,
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
+{
+    let dir = dir.as_ref();
+    let template = entries
+        .into_iter()
+        .map(|entry| normalize(entry.borrow()))
+        .collect::<Vec<_>>();
+
+    let current = getfacl(dir, AclOption::DEFAULT_ACL)?;
+    if current.is_empty() {
+        setfacl(&[dir], &template, AclOption::DEFAULT_ACL)?;
+        return Ok(DefaultAclStatus::Applied);
+    }
+
+    let current = current.iter().map(normalize).collect::<AclSet>();
+    let template = template.into_iter().collect::<AclSet>();
+    if current == template {
+        return Ok(DefaultAclStatus::Matched);
+    }
+
+    Ok(DefaultAclStatus::Drift {
+        missing: with_default_flag(template.difference(&current)),
+        extra: with_default_flag(current.difference(&template)),
+    })
+}
+
+/// Return the default ACL of a directory, without the `DEFAULT` flag on its
+/// entries.
+///
+/// The entries can be passed to [`setfacl`](crate::setfacl) as an access ACL,
+/// or back to [`set_default_acl`]. Returns an empty list if the directory has
+/// no default ACL.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the path is not a directory or the ACL can't be
+/// read.
+pub fn get_default_acl<P: AsRef<Path>>(dir: P) -> io::Result<Vec<AclEntry>> {
+    let mut entries = getfacl(dir, AclOption::DEFAULT_ACL)?;
+    for entry in &mut entries {
+        entry.flags.remove(Flag::DEFAULT);
+    }
+    Ok(entries)
+}
+
+/// Replace the default ACL of a directory. Entries may have the `DEFAULT`
+/// flag or not.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the path is not a directory, if the entries are
+/// not a valid ACL, or if the ACL can't be written.
+pub fn set_default_acl<P, E>(dir: P, entries: E) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
+{
+    let entries = entries
+        .into_iter()
+        .map(|entry| {
+            let mut entry = entry.borrow().clone();
+            entry.flags.remove(Flag::DEFAULT);
+            entry
+        })
+        .collect::<Vec<_>>();
+    setfacl(&[dir.as_ref()], &entries, AclOption::DEFAULT_ACL)
+}
+
+/// Return the ACL that a new file or directory created in `dir` would get from
+/// the directory's default ACL, or None if there is no default ACL.
+///
+/// `mode` is the mode passed to `open` or `mkdir`, usually `0o666` for files
+/// and `0o777` for directories. Like the kernel, this limits the `user::` and
+/// `other::` entries by the mode's owner and other bits, and limits the mask
+/// by the group bits, or the `group::` entry if there is no mask. The umask
+/// does not apply when there is a default ACL. A new directory also gets the
+/// default ACL itself; those entries are returned with the `DEFAULT` flag.
+///
+/// Without a default ACL, the new object's permissions come from the mode and
+/// the umask instead.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{propagate_default, PathKind};
+///
+/// if let Some(entries) = propagate_default("./tmp/shared", PathKind::File, 0o666)? {
+///     print!("{}", exacl::to_string(&entries)?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the path is not a directory or the ACL can't be
+/// read.
+pub fn propagate_default<P: AsRef<Path>>(
+    dir: P,
+    path_kind: PathKind,
+    mode: u32,
+) -> io::Result<Option<Vec<AclEntry>>> {
+    let defaults = get_default_acl(dir)?;
+    if defaults.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(inherit_default(&defaults, path_kind, mode)))
+}
+
+/// Compute the ACL of a new object from its directory's default entries,
+/// which have no `DEFAULT` flag.
+fn inherit_default(defaults: &[AclEntry], path_kind: PathKind, mode: u32) -> Vec<AclEntry> {
+    let bits = |shift: u32| Perm::from_bits_truncate((mode >> shift) & 7);
+    let has_mask = defaults
+        .iter()
+        .any(|entry| entry.kind == AclEntryKind::Mask);
+
+    let mut entries = defaults
+        .iter()
+        .map(|entry| {
+            let mut entry = entry.clone();
+            let limit = match entry.kind {
+                AclEntryKind::User if entry.name.is_empty() => bits(6),
+                AclEntryKind::Group if entry.name.is_empty() && !has_mask => bits(3),
+                AclEntryKind::Mask => bits(3),
+                AclEntryKind::Other => bits(0),
+                _ => Perm::all(),
+            };
+            entry.perms &= limit;
+            entry
+        })
+        .collect::<Vec<_>>();
+
+    if path_kind == PathKind::Directory {
+        entries.extend(defaults.iter().map(|entry| {
+            let mut entry = entry.clone();
+            entry.flags |= Flag::DEFAULT;
+            entry
+        }));
+    }
+
+    entries
+}
+
+/// Return a copy of the entry without the `DEFAULT` flag. A name read back as
+/// an unresolved id compares equal to the same decimal name in a template.
+fn normalize(entry: &AclEntry) -> AclEntry {
+    let mut entry = entry.clone();
+    entry.flags.remove(Flag::DEFAULT);
+    entry.numeric = false;
+    entry
+}
+
+/// Return the entries of the set with the `DEFAULT` flag added.
+fn with_default_flag(set: AclSet) -> Vec<AclEntry> {
+    let mut entries = set.to_vec();
+    for entry in &mut entries {
+        entry.flags |= Flag::DEFAULT;
+    }
+    entries
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod ensure_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    fn template() -> Vec<AclEntry> {
+        let mut entries = crate::from_mode(0o750);
+        entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+        entries.push(AclEntry::allow_mask(Perm::READ | Perm::EXECUTE, None));
+        entries
+    }
+
+    #[test]
+    fn test_ensure_default_acl() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+
+        let status = ensure_default_acl(&dir, template())?;
+        assert_eq!(status, DefaultAclStatus::Applied);
+        let defaults = getfacl(&dir, AclOption::DEFAULT_ACL)?;
+        assert!(defaults.iter().any(|entry| entry.name == "11501"));
+
+        assert_eq!(
+            ensure_default_acl(&dir, template())?,
+            DefaultAclStatus::Matched
+        );
+
+        // Template entries with the DEFAULT flag match too.
+        let flagged = template().into_iter().map(|mut entry| {
+            entry.flags = Flag::DEFAULT;
+            entry
+        });
+        assert_eq!(
+            ensure_default_acl(&dir, flagged)?,
+            DefaultAclStatus::Matched
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_default_acl_drift() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        ensure_default_acl(&dir, template())?;
+
+        let mut changed = template();
+        changed[3] = AclEntry::allow_user("11502", Perm::READ, None);
+        let status = ensure_default_acl(&dir, &changed)?;
+
+        let DefaultAclStatus::Drift { missing, extra } = status else {
+            panic!("expected drift: {status:?}");
+        };
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].name, "11502");
+        assert_eq!(missing[0].flags, Flag::DEFAULT);
+        assert_eq!(extra.len(), 1);
+        assert_eq!(extra[0].name, "11501");
+
+        // The default ACL was left unchanged.
+        let defaults = getfacl(&dir, AclOption::DEFAULT_ACL)?;
+        assert!(defaults.iter().any(|entry| entry.name == "11501"));
+        assert!(!defaults.iter().any(|entry| entry.name == "11502"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_set_default_acl() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        assert!(get_default_acl(&dir)?.is_empty());
+        assert_eq!(propagate_default(&dir, PathKind::File, 0o666)?, None);
+
+        set_default_acl(&dir, template())?;
+        let defaults = get_default_acl(&dir)?;
+        assert_eq!(defaults.len(), 5);
+        assert!(defaults.iter().all(|entry| entry.flags.is_empty()));
+
+        // Entries with the DEFAULT flag are accepted too.
+        set_default_acl(&dir, getfacl(&dir, AclOption::DEFAULT_ACL)?)?;
+        assert_eq!(get_default_acl(&dir)?, defaults);
+
+        // The simulated ACL matches the ACL of a new file.
+        let expected = propagate_default(&dir, PathKind::File, 0o666)?.unwrap();
+        let file = dir.path().join("file");
+        std::fs::File::create(&file)?;
+        assert_eq!(getfacl(&file, None)?, expected);
+
+        let expected = propagate_default(&dir, PathKind::Directory, 0o777)?.unwrap();
+        let subdir = dir.path().join("subdir");
+        std::fs::create_dir(&subdir)?;
+        assert_eq!(getfacl(&subdir, None)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_inherit_default() {
+        let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+        let defaults = vec![
+            AclEntry::allow_user("", rwx, None),
+            AclEntry::allow_group("", rwx, None),
+            AclEntry::allow_other(rwx, None),
+        ];
+
+        // Without a mask, the group entry is limited by the group bits.
+        assert_eq!(
+            inherit_default(&defaults, PathKind::File, 0o640),
+            crate::from_mode(0o640)
+        );
+
+        let mut with_mask = defaults.clone();
+        with_mask.push(AclEntry::allow_user("11501", rwx, None));
+        with_mask.push(AclEntry::allow_mask(rwx, None));
+        let entries = inherit_default(&with_mask, PathKind::File, 0o640);
+        assert_eq!(entries[1].perms, rwx);
+        assert_eq!(entries[3].perms, rwx);
+        assert_eq!(entries[4].perms, Perm::READ);
+
+        // A directory inherits the default ACL itself, too.
+        let entries = inherit_default(&defaults, PathKind::Directory, 0o700);
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[2].perms, Perm::empty());
+        assert_eq!(entries[5], AclEntry::allow_other(rwx, Flag::DEFAULT));
+    }
+
+    #[test]
+    fn test_ensure_default_acl_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert!(ensure_default_acl(&file, template()).is_err());
+    }
+}
@@ -5,7 +5,7 @@
 use log::debug;
 use std::fmt;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Log a message and return an [`io::Error`] with the value of errno.
 pub fn log_err<R, T>(ret: R, func: &str, arg: T) -> io::Error
@@ -23,8 +23,11 @@ pub fn log_from_err<T>(ret: i32, func: &str, arg: T) -> io::Error
 where
     T: fmt::Debug,
 {
-    assert!(ret > 0);
-    let err = io::Error::from_raw_os_error(ret);
+    let err = if ret > 0 {
+        io::Error::from_raw_os_error(ret)
+    } else {
+        io::Error::new(io::ErrorKind::Other, format!("{func} returned {ret}"))
+    };
     debug!("{}({:?}) returned {}, err={}", func, arg, ret, err);
     err
 }
@@ -46,17 +49,93 @@ where
     Err(log_from_err(ret, func, arg))
 }
 
+/// Return an [`io::Result`] for a native function that returned a null
+/// pointer without reporting an error.
+pub fn fail_null<U>(func: &str) -> io::Result<U> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!("{func} returned null"),
+    ))
+}
+
 /// Return a custom [`io::Result`] with the given message.
 pub fn fail_custom<U>(msg: &str) -> io::Result<U> {
     Err(io::Error::new(io::ErrorKind::Other, msg))
 }
 
+/// Return a custom [`io::Result`] with the given message and a `NotFound` kind.
+pub fn fail_not_found<U>(msg: &str) -> io::Result<U> {
+    Err(io::Error::new(io::ErrorKind::NotFound, msg))
+}
+
 /// Return a custom [`io::Error`] that prefixes the given error.
 pub fn custom_err(msg: &str, err: &io::Error) -> io::Error {
     io::Error::new(err.kind(), format!("{msg}: {err}"))
 }
 
 /// Return a custom [`io::Error`] that prefixes the given error with filename.
+///
+/// The error wraps a [`PathError`], which keeps the path and the OS error code.
 pub fn path_err(path: &Path, err: &io::Error) -> io::Error {
-    io::Error::new(err.kind(), format!("File {path:?}: {err}"))
+    let source = match err.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(err.kind(), err.to_string()),
+    };
+
+    io::Error::new(
+        err.kind(),
+        PathError {
+            path: path.to_path_buf(),
+            source,
+        },
+    )
+}
+
+/// Error from an operation on a file, with the path of the file.
+///
+/// Functions that take a path return an [`io::Error`] that wraps a
+/// `PathError`. The path is kept exactly as given, so a path that is not
+/// valid UTF-8 can be used again, and the OS error code is kept even though
+/// the outer error has a custom message.
+///
+/// ```
+/// use exacl::PathError;
+///
+/// let err = exacl::getfacl("/no-such-file", None).unwrap_err();
+/// let path_err = err.get_ref().and_then(|e| e.downcast_ref::<PathError>());
+/// assert_eq!(path_err.unwrap().path(), std::path::Path::new("/no-such-file"));
+/// ```
+#[derive(Debug)]
+pub struct PathError {
+    path: PathBuf,
+    source: io::Error,
 }
+
+impl PathError {
+    /// Return the path of the file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the underlying error, e.g. to check its
+    /// [`raw_os_error`](io::Error::raw_os_error).
+    #[must_use]
+    pub const fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+
+    /// Return the path, consuming the error.
+    #[must_use]
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "File {:?}: {}", self.path, self.source)
+    }
+}
+
+impl std::error::Error for PathError {}
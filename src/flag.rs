@@ -2,54 +2,63 @@
 
 use crate::bititer::{BitIter, BitIterable};
 use crate::format;
+use crate::format::syntax::LIST_SEPARATOR;
 use crate::sys::*;
 
 use bitflags::bitflags;
 #[cfg(feature = "serde")]
 use serde::{de, ser, Deserialize, Serialize};
 use std::fmt;
+#[cfg(not(exacl_core))]
+use std::io;
+#[cfg(not(exacl_core))]
+use std::path::Path;
 
 bitflags! {
     /// Represents ACL entry inheritance flags.
     #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]
     pub struct Flag : acl_flag_t {
         /// ACL entry was inherited.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const INHERITED = np::ACL_ENTRY_INHERITED;
 
         /// Inherit to files.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const FILE_INHERIT = np::ACL_ENTRY_FILE_INHERIT;
 
         /// Inherit to directories.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const DIRECTORY_INHERIT = np::ACL_ENTRY_DIRECTORY_INHERIT;
 
         /// Clear the DIRECTORY_INHERIT flag in the ACL entry that is inherited.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const LIMIT_INHERIT = np::ACL_ENTRY_LIMIT_INHERIT;
 
         /// Don't consider this entry when processing the ACL. Just inherit it.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const ONLY_INHERIT = np::ACL_ENTRY_ONLY_INHERIT;
 
         /// Specifies a default ACL entry on Linux.
-        #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+        #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core, feature = "strict_posix"))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
         const DEFAULT = 1 << 13;
 
-        #[cfg(any(docsrs, target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_nfs4))]
         #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
         /// NFSv4 Specific Flags on FreeBSD.
         const NFS4_SPECIFIC = Self::INHERITED.bits() | Self::FILE_INHERIT.bits() | Self::DIRECTORY_INHERIT.bits() | Self::LIMIT_INHERIT.bits() | Self::ONLY_INHERIT.bits();
     }
 }
 
+/// Prefix of a token that holds flag bits unknown to this version of exacl,
+/// e.g. "raw=0x4000".
+const RAW_PREFIX: &str = "raw=";
+
 impl Flag {
     // On FreeBSD, acl_flag_t is a u16. On Linux and macOS, acl_flag_t is a u32.
     // To appease the linter, provide a helper function to cast Flag to u32.
@@ -57,6 +66,60 @@ impl Flag {
         #[allow(clippy::unnecessary_cast)]
         return self.bits() as u32;
     }
+
+    /// Return the bits that don't correspond to a flag on this platform.
+    const fn unknown(self) -> Flag {
+        self.difference(Flag::all())
+    }
+
+    /// Format the unknown bits as a raw token, e.g. "raw=0x4000".
+    fn raw_token(self) -> Option<String> {
+        let unknown = self.unknown();
+        if unknown.is_empty() {
+            None
+        } else {
+            Some(format!("{RAW_PREFIX}{:#x}", unknown.bits()))
+        }
+    }
+
+    /// Return the flags that ACL entries can use on the file system
+    /// containing `path`.
+    ///
+    /// Unlike [`Flag::all`], this is decided at runtime. `DEFAULT` is only
+    /// supported for directories with Posix.1e ACL's; the inheritance flags
+    /// are only supported by `NFSv4` and macOS extended ACL's. The result is
+    /// empty if the file system does not support ACL's.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure, e.g. if `path` does not exist.
+    #[cfg(not(exacl_core))]
+    pub fn all_supported<P: AsRef<Path>>(path: P) -> io::Result<Flag> {
+        let path = path.as_ref();
+        let caps = crate::volume_capabilities(path)?;
+        if !caps.acl {
+            return Ok(Flag::empty());
+        }
+
+        #[cfg(exacl_nfs4)]
+        if caps.nfs4 {
+            return Ok(Flag::NFS4_SPECIFIC);
+        }
+
+        #[cfg(all(target_os = "macos", exacl_extended))]
+        return Ok(Flag::all());
+
+        // Default ACL's are not supported on macOS.
+        #[cfg(all(target_os = "macos", not(exacl_extended)))]
+        return Ok(Flag::empty());
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        if caps.default_acl && path.is_dir() {
+            Ok(Flag::DEFAULT)
+        } else {
+            Ok(Flag::empty())
+        }
+    }
 }
 
 impl BitIterable for Flag {
@@ -64,20 +127,18 @@ impl BitIterable for Flag {
         if self.is_empty() {
             return None;
         }
-        let low_bit = 1u32 << self.bits().trailing_zeros();
-        Some(Flag::from_bits_retain(low_bit as acl_flag_t))
+        let low_bit: acl_flag_t = 1 << self.bits().trailing_zeros();
+        Some(Flag::from_bits_retain(low_bit))
     }
 
     fn msb(self) -> Option<Self> {
-        // FIXME: Replace computation with `BITS` once it lands in stable.
-        #[allow(clippy::cast_possible_truncation)]
-        const MAX_BITS: u32 = 8 * std::mem::size_of::<Flag>() as u32 - 1;
+        const MAX_BITS: u32 = acl_flag_t::BITS - 1;
 
         if self.is_empty() {
             return None;
         }
-        let high_bit = 1u32 << (MAX_BITS - self.bits().leading_zeros());
-        Some(Flag::from_bits_retain(high_bit as acl_flag_t))
+        let high_bit: acl_flag_t = 1 << (MAX_BITS - self.bits().leading_zeros());
+        Some(Flag::from_bits_retain(high_bit))
     }
 }
 
@@ -88,44 +149,54 @@ impl BitIterable for Flag {
 pub enum FlagName {
     // *N.B.* Update the corresponding table in format/format_no_serde.rs
     // if any of these entries change.
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     inherited = Flag::INHERITED.as_u32(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     file_inherit = Flag::FILE_INHERIT.as_u32(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     directory_inherit = Flag::DIRECTORY_INHERIT.as_u32(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     limit_inherit = Flag::LIMIT_INHERIT.as_u32(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     only_inherit = Flag::ONLY_INHERIT.as_u32(),
 
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    ))]
     default = Flag::DEFAULT.as_u32(),
 }
 
 impl FlagName {
     const fn from_flag(flag: Flag) -> Option<FlagName> {
         match flag {
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Flag::INHERITED => Some(FlagName::inherited),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Flag::FILE_INHERIT => Some(FlagName::file_inherit),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Flag::DIRECTORY_INHERIT => Some(FlagName::directory_inherit),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Flag::LIMIT_INHERIT => Some(FlagName::limit_inherit),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Flag::ONLY_INHERIT => Some(FlagName::only_inherit),
 
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "freebsd",
+                exacl_core,
+                feature = "strict_posix"
+            ))]
             Flag::DEFAULT => Some(FlagName::default),
 
             _ => None,
@@ -144,23 +215,49 @@ impl fmt::Display for FlagName {
 }
 
 impl fmt::Display for Flag {
+    /// Write a comma-separated list of flag names. Bits that are not flags
+    /// on this platform are written as a single raw token, e.g. "raw=0x4000",
+    /// so they survive a round trip through the text format.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut iter = BitIter(*self & Flag::all());
+        let known = *self & Flag::all();
 
-        if let Some(flag) = iter.next() {
-            write!(f, "{}", FlagName::from_flag(flag).unwrap())?;
+        for (i, name) in BitIter(known).filter_map(FlagName::from_flag).enumerate() {
+            if i > 0 {
+                write!(f, "{LIST_SEPARATOR}")?;
+            }
+            write!(f, "{name}")?;
+        }
 
-            for flag in iter {
-                write!(f, ",{}", FlagName::from_flag(flag).unwrap())?;
+        if let Some(raw) = self.raw_token() {
+            if !known.is_empty() {
+                write!(f, "{LIST_SEPARATOR}")?;
             }
+            write!(f, "{raw}")?;
         }
 
         Ok(())
     }
 }
 
+/// Parse a raw token ("raw=0x4000") holding bits that are not known flags.
+fn parse_raw_flag(s: &str) -> Option<Result<Flag, format::Error>> {
+    let value = s.strip_prefix(RAW_PREFIX)?;
+    let result = value
+        .strip_prefix("0x")
+        .and_then(|hex| acl_flag_t::from_str_radix(hex, 16).ok())
+        .map(Flag::from_bits_retain)
+        .ok_or_else(|| format::Error::Message(format!("Invalid raw flag value: `{s}`")));
+
+    Some(result)
+}
+
 /// Parse an abbreviated flag ("d").
-#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "strict_posix"
+))]
 fn parse_flag_abbreviation(s: &str) -> Option<Flag> {
     match s {
         "d" => Some(Flag::DEFAULT),
@@ -168,7 +265,7 @@ fn parse_flag_abbreviation(s: &str) -> Option<Flag> {
     }
 }
 
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "strict_posix")))]
 const fn parse_flag_abbreviation(_s: &str) -> Option<Flag> {
     None
 }
@@ -187,11 +284,13 @@ impl std::str::FromStr for Flag {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result = Flag::empty();
 
-        for item in s.split(',') {
+        for item in s.split(LIST_SEPARATOR) {
             let word = item.trim();
             if !word.is_empty() {
                 if let Some(flag) = parse_flag_abbreviation(word) {
                     result |= flag;
+                } else if let Some(raw) = parse_raw_flag(word) {
+                    result |= raw?;
                 } else {
                     result |= word.parse::<FlagName>()?.to_flag();
                 }
@@ -209,12 +308,21 @@ impl ser::Serialize for Flag {
         S: ser::Serializer,
     {
         use ser::SerializeSeq;
+        let serializer_is_human_readable = serializer.is_human_readable();
         let mut seq = serializer.serialize_seq(None)?;
 
-        for flag in BitIter(*self) {
+        for flag in BitIter(*self & Flag::all()) {
             seq.serialize_element(&FlagName::from_flag(flag))?;
         }
 
+        // Unknown bits are only kept by human-readable formats, where the
+        // raw token can appear alongside the flag names.
+        if serializer_is_human_readable {
+            if let Some(raw) = self.raw_token() {
+                seq.serialize_element(&raw)?;
+            }
+        }
+
         seq.end()
     }
 }
@@ -225,7 +333,9 @@ impl<'de> de::Deserialize<'de> for Flag {
     where
         D: de::Deserializer<'de>,
     {
-        struct FlagVisitor;
+        struct FlagVisitor {
+            human_readable: bool,
+        }
 
         impl<'de> de::Visitor<'de> for FlagVisitor {
             type Value = Flag;
@@ -240,16 +350,25 @@ impl<'de> de::Deserialize<'de> for Flag {
             {
                 let mut flags: Flag = Flag::empty();
 
-                while let Some(value) = seq.next_element()? {
-                    let name: FlagName = value;
-                    flags |= name.to_flag();
+                if self.human_readable {
+                    while let Some(value) = seq.next_element::<String>()? {
+                        flags |= parse_raw_flag(&value)
+                            .unwrap_or_else(|| value.parse::<FlagName>().map(FlagName::to_flag))
+                            .map_err(de::Error::custom)?;
+                    }
+                } else {
+                    while let Some(value) = seq.next_element()? {
+                        let name: FlagName = value;
+                        flags |= name.to_flag();
+                    }
                 }
 
                 Ok(flags)
             }
         }
 
-        deserializer.deserialize_seq(FlagVisitor)
+        let human_readable = deserializer.is_human_readable();
+        deserializer.deserialize_seq(FlagVisitor { human_readable })
     }
 }
 
@@ -259,17 +378,41 @@ impl<'de> de::Deserialize<'de> for Flag {
 mod flag_tests {
     use super::*;
 
+    #[test]
+    #[cfg(not(exacl_core))]
+    fn test_all_supported() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let file_flags = Flag::all_supported(&file).unwrap();
+        let dir_flags = Flag::all_supported(&dir).unwrap();
+
+        #[cfg(all(target_os = "macos", exacl_extended))]
+        assert_eq!((file_flags, dir_flags), (Flag::all(), Flag::all()));
+
+        #[cfg(all(target_os = "macos", not(exacl_extended)))]
+        assert_eq!((file_flags, dir_flags), (Flag::empty(), Flag::empty()));
+
+        #[cfg(target_os = "linux")]
+        assert_eq!((file_flags, dir_flags), (Flag::empty(), Flag::DEFAULT));
+
+        #[cfg(target_os = "freebsd")]
+        assert!(!file_flags.contains(Flag::DEFAULT));
+
+        let err = Flag::all_supported("/non-existant").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
     #[test]
     fn test_flag_display() {
         assert_eq!(Flag::empty().to_string(), "");
 
-        #[cfg(target_os = "macos")]
+        #[cfg(all(target_os = "macos", exacl_extended))]
         {
             let flags = Flag::INHERITED | Flag::FILE_INHERIT;
             assert_eq!(flags.to_string(), "inherited,file_inherit");
 
             let bad_flag = Flag::from_bits_retain(0x0080_0000) | Flag::INHERITED;
-            assert_eq!(bad_flag.to_string(), "inherited");
+            assert_eq!(bad_flag.to_string(), "inherited,raw=0x800000");
 
             assert_eq!(
                 Flag::all().to_string(),
@@ -277,24 +420,24 @@ mod flag_tests {
             );
         }
 
-        #[cfg(target_os = "linux")]
+        #[cfg(not(exacl_extended))]
         {
             let flags = Flag::DEFAULT;
             assert_eq!(flags.to_string(), "default");
 
             let bad_flag = Flag::from_bits_retain(0x8000) | Flag::DEFAULT;
-            assert_eq!(bad_flag.to_string(), "default");
+            assert_eq!(bad_flag.to_string(), "default,raw=0x8000");
 
             assert_eq!(Flag::all().to_string(), "default");
         }
 
-        #[cfg(target_os = "freebsd")]
+        #[cfg(exacl_nfs4)]
         {
             let flags = Flag::DEFAULT;
             assert_eq!(flags.to_string(), "default");
 
             let bad_flag = Flag::from_bits_retain(0x8000) | Flag::DEFAULT;
-            assert_eq!(bad_flag.to_string(), "default");
+            assert_eq!(bad_flag.to_string(), "default,raw=0x8000");
 
             assert_eq!(
                 Flag::all().to_string(),
@@ -305,7 +448,7 @@ mod flag_tests {
 
     #[test]
     fn test_flag_fromstr() {
-        #[cfg(target_os = "macos")]
+        #[cfg(all(target_os = "macos", exacl_extended))]
         {
             assert_eq!(Flag::empty(), "".parse::<Flag>().unwrap());
 
@@ -322,7 +465,7 @@ mod flag_tests {
             assert_eq!("unknown variant `bad_flag`, expected one of `inherited`, `file_inherit`, `directory_inherit`, `limit_inherit`, `only_inherit`", "bad_flag".parse::<Flag>().unwrap_err().to_string());
         }
 
-        #[cfg(target_os = "linux")]
+        #[cfg(not(exacl_extended))]
         {
             assert_eq!(Flag::empty(), "".parse::<Flag>().unwrap());
 
@@ -335,7 +478,7 @@ mod flag_tests {
             );
         }
 
-        #[cfg(target_os = "freebsd")]
+        #[cfg(exacl_nfs4)]
         {
             assert_eq!(Flag::empty(), "".parse::<Flag>().unwrap());
 
@@ -353,4 +496,95 @@ mod flag_tests {
             );
         }
     }
+
+    #[test]
+    fn test_flag_bititer() {
+        assert_eq!(Flag::empty().lsb(), None);
+        assert_eq!(Flag::empty().msb(), None);
+
+        let low = Flag::all().iter().next().unwrap();
+        let high = Flag::from_bits_retain(1 << (acl_flag_t::BITS - 1));
+        assert_eq!((low | high).lsb(), Some(low));
+        assert_eq!((low | high).msb(), Some(high));
+        // Flags are declared in a different order than their bits on
+        // `FreeBSD`.
+        let mut flags = Flag::all().iter().collect::<Vec<_>>();
+        flags.sort();
+        assert_eq!(BitIter(Flag::all()).collect::<Vec<_>>(), flags);
+    }
+
+    #[test]
+    fn test_flag_const() {
+        const EMPTY: Flag = Flag::empty();
+        const ALL: Flag = Flag::from_bits_truncate(acl_flag_t::MAX);
+        assert_eq!(EMPTY.union(ALL), Flag::all());
+        assert_eq!(FlagName::from_flag(ALL.intersection(EMPTY)), None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_flag_serde() {
+        let json = serde_json::to_string(&Flag::all()).unwrap();
+
+        #[cfg(all(target_os = "macos", exacl_extended))]
+        assert_eq!(
+            json,
+            r#"["inherited","file_inherit","directory_inherit","limit_inherit","only_inherit"]"#
+        );
+
+        #[cfg(not(exacl_extended))]
+        assert_eq!(json, r#"["default"]"#);
+
+        #[cfg(exacl_nfs4)]
+        assert_eq!(
+            json,
+            r#"["file_inherit","directory_inherit","limit_inherit","only_inherit","inherited","default"]"#
+        );
+
+        let flags: Flag = serde_json::from_str(&json).unwrap();
+        assert_eq!(flags, Flag::all());
+        assert_eq!(serde_json::to_string(&Flag::empty()).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_flag_raw() {
+        let raw = Flag::from_bits_retain(1 << (acl_flag_t::BITS - 2));
+        let flags = Flag::all() | raw;
+        let text = flags.to_string();
+        assert!(text.ends_with(",raw=0x4000") || text.ends_with(",raw=0x40000000"));
+        assert_eq!(text.parse::<Flag>().unwrap(), flags);
+        assert_eq!(raw.to_string().parse::<Flag>().unwrap(), raw);
+
+        // Known bits given as a raw token are accepted.
+        assert_eq!(
+            format!("raw={:#x}", Flag::all().bits())
+                .parse::<Flag>()
+                .unwrap(),
+            Flag::all()
+        );
+
+        for bad in ["raw=", "raw=40", "raw=0xzz", "raw=0x1ffffffff"] {
+            assert_eq!(
+                bad.parse::<Flag>().unwrap_err().to_string(),
+                format!("Invalid raw flag value: `{bad}`")
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_flag_raw_serde() {
+        let raw = Flag::from_bits_retain(1 << (acl_flag_t::BITS - 2));
+        let json = serde_json::to_string(&raw).unwrap();
+        assert_eq!(json, format!(r#"["raw={:#x}"]"#, raw.bits()));
+
+        let flags: Flag = serde_json::from_str(&json).unwrap();
+        assert_eq!(flags, raw);
+
+        let err = serde_json::from_str::<Flag>(r#"["bad_flag"]"#).unwrap_err();
+        assert!(
+            err.to_string().starts_with("unknown variant `bad_flag`"),
+            "{err}"
+        );
+    }
 }
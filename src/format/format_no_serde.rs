@@ -11,27 +11,29 @@ use crate::perm::PermName;
 const ACLENTRYKINDS: &'static [(AclEntryKind, &'static str)] = &[
     (AclEntryKind::User, "user"),
     (AclEntryKind::Group, "group"),
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     (AclEntryKind::Mask, "mask"),
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     (AclEntryKind::Other, "other"),
-    #[cfg(target_os = "freebsd")]
     (AclEntryKind::Everyone, "everyone"),
     (AclEntryKind::Unknown, "unknown"),
 ];
 
 const FLAGS: &'static [(FlagName, &'static str)] = &[
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (FlagName::inherited, "inherited"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (FlagName::file_inherit, "file_inherit"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (FlagName::directory_inherit, "directory_inherit"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (FlagName::limit_inherit, "limit_inherit"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (FlagName::only_inherit, "only_inherit"),
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    ))]
     (FlagName::default, "default"),
 ];
 
@@ -39,31 +41,31 @@ const PERMS: &'static [(PermName, &'static str)] = &[
     (PermName::read, "read"),
     (PermName::write, "write"),
     (PermName::execute, "execute"),
-    #[cfg(target_os = "freebsd")]
+    #[cfg(exacl_nfs4)]
     (PermName::read_data, "read_data"),
-    #[cfg(target_os = "freebsd")]
+    #[cfg(exacl_nfs4)]
     (PermName::write_data, "write_data"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::delete, "delete"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::append, "append"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::delete_child, "delete_child"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::readattr, "readattr"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::writeattr, "writeattr"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::readextattr, "readextattr"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::writeextattr, "writeextattr"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::readsecurity, "readsecurity"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::writesecurity, "writesecurity"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::chown, "chown"),
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     (PermName::sync, "sync"),
 ];
 
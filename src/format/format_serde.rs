@@ -11,13 +11,9 @@ use crate::flag::FlagName;
 use crate::perm::PermName;
 
 /// Write value of a simple enum as a `serde` serialized string.
-#[allow(clippy::unnecessary_wraps)]
 fn write_enum<T: Serialize>(f: &mut fmt::Formatter, value: T) -> fmt::Result {
     let mut serializer = EnumSerializer(f);
-    value
-        .serialize(&mut serializer)
-        .expect("can't serialize value");
-    Ok(())
+    value.serialize(&mut serializer).map_err(|_| fmt::Error)
 }
 
 // Read value of a simple enum using a stub `serde` deserializer.
@@ -127,8 +123,9 @@ impl<'a, 'b> ser::Serializer for &mut EnumSerializer<'a, 'b> {
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        self.0.write_str(variant).expect("can't format enum");
-        Ok(())
+        self.0
+            .write_str(variant)
+            .map_err(|err| Error::Message(err.to_string()))
     }
 
     fn serialize_bool(self, _v: bool) -> Result<()> {
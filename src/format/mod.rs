@@ -2,6 +2,7 @@
 
 #[cfg(feature = "serde")]
 mod format_serde;
+pub mod syntax;
 
 #[cfg(not(feature = "serde"))]
 mod format_no_serde;
@@ -17,3 +18,34 @@ pub use format_no_serde::{
     read_aclentrykind, read_flagname, read_permname, write_aclentrykind, write_flagname,
     write_permname, Error,
 };
+
+/// Version of the text format written by [`to_writer`](crate::to_writer).
+///
+/// The output of a given format version is guaranteed not to change across
+/// crate versions: fields, separators, the order of flags and permissions
+/// within a field, and escaping rules stay the same. Pin a version with
+/// [`to_writer_with_version`](crate::to_writer_with_version) when ACL's are
+/// stored in version control.
+///
+/// Every version can be read by [`from_reader`](crate::from_reader), with one
+/// exception: `V1` writes names verbatim, so `V1` output may not read back
+/// when a name contains `:`, `#` or white space.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum FormatVersion {
+    /// Format written by exacl 0.12 and earlier. Names are written verbatim.
+    V1,
+
+    /// Names are written with `\`, `:`, `#`, white space and control
+    /// characters escaped as octal sequences, e.g. `\072`. Flag bits that
+    /// are unknown on the platform are written as a raw token, e.g.
+    /// `raw=0x4000`.
+    #[default]
+    V2,
+}
+
+impl FormatVersion {
+    /// The latest format version. This is the version used by
+    /// [`to_writer`](crate::to_writer) and [`to_string`](crate::to_string).
+    pub const LATEST: FormatVersion = FormatVersion::V2;
+}
@@ -0,0 +1,105 @@
+//! Constants for the text format read by [`from_reader`](crate::from_reader)
+//! and written by [`to_writer`](crate::to_writer).
+//!
+//! Tools that read or write the format without this crate, such as syntax
+//! highlighters and linters, can use these values instead of hard-coding
+//! them. They are part of the stable format; see
+//! [`FormatVersion`](crate::FormatVersion).
+//!
+//! An entry is written as `<allow>:<flags>:<kind>:<name>:<perms>`.
+
+/// Separates the fields of an entry.
+pub const FIELD_SEPARATOR: char = ':';
+
+/// Separates the items in the flags and perms fields.
+pub const LIST_SEPARATOR: char = ',';
+
+/// Starts a comment, which runs to the end of the line.
+pub const COMMENT: char = '#';
+
+/// Starts an escape sequence in a name. The backslash is followed by three
+/// octal digits for each UTF-8 byte of the escaped character, e.g. `\072`.
+/// When reading, a backslash that doesn't start an escape sequence stands
+/// for itself.
+pub const ESCAPE: char = '\\';
+
+/// Value of the allow field for an entry that allows access.
+pub const ALLOW: &str = "allow";
+
+/// Value of the allow field for an entry that denies access.
+pub const DENY: &str = "deny";
+
+/// Kind of an entry for a numeric uid.
+pub const NUMERIC_USER: &str = "uid";
+
+/// Kind of an entry for a numeric gid.
+pub const NUMERIC_GROUP: &str = "gid";
+
+/// Abbreviations accepted when reading, as (abbreviation, full name).
+///
+/// Kind abbreviations replace the whole kind field. Permission abbreviations
+/// may be combined, in any order, e.g. `rw` or `r-x`. The `d` flag is
+/// only available where [`Flag::DEFAULT`](crate::Flag::DEFAULT) is.
+pub const ABBREVIATIONS: &[(&str, &str)] = &[
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    ))]
+    ("d", "default"),
+    ("r", "read"),
+    ("w", "write"),
+    ("x", "execute"),
+    ("u", "user"),
+    ("g", "group"),
+    ("o", "other"),
+    ("m", "mask"),
+    ("e", "everyone"),
+];
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod syntax_tests {
+    use super::*;
+    use crate::aclentry::{AclEntry, AclEntryKind};
+    use crate::flag::Flag;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_abbreviations() {
+        for (abbrev, name) in ABBREVIATIONS {
+            if let Ok(kind) = name.parse::<AclEntryKind>() {
+                assert_eq!(abbrev.parse::<AclEntryKind>().unwrap(), kind);
+            } else if let Ok(perm) = name.parse::<Perm>() {
+                assert_eq!(abbrev.parse::<Perm>().unwrap(), perm);
+            } else {
+                assert_eq!(abbrev.parse::<Flag>().unwrap(), name.parse().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_entry_syntax() {
+        let entry = AclEntry::allow_user("a:b", Perm::READ | Perm::WRITE, None);
+        let text = entry.to_string();
+        assert_eq!(
+            text,
+            format!(
+                "{ALLOW}{FIELD_SEPARATOR}{FIELD_SEPARATOR}user{FIELD_SEPARATOR}\
+                 a{ESCAPE}072b{FIELD_SEPARATOR}read{LIST_SEPARATOR}write"
+            )
+        );
+
+        let entry = AclEntry {
+            allow: false,
+            numeric: true,
+            ..AclEntry::allow_group("500", Perm::READ, None)
+        };
+        assert_eq!(
+            entry.to_string(),
+            format!("{DENY}::{NUMERIC_GROUP}:500:read")
+        );
+    }
+}
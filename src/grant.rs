@@ -0,0 +1,202 @@
+//! Implements `grant_group`, which gives a group access to a file or
+//! directory.
+
+use crate::acl::AclOption;
+use crate::aclentry::{AclEntry, AclEntryKind, PathKind};
+use crate::editor::Editor;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::unix::name_to_gid_strict;
+use crate::{getfacl, setfacl};
+
+use std::io;
+use std::path::Path;
+
+/// Level of access granted by [`grant_group`].
+///
+/// Directories always get `EXECUTE` so they can be searched; files only get
+/// `EXECUTE` with [`Role::Full`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Read files and list directories (`r-X`).
+    Reader,
+
+    /// Read and write files, and add and remove files in directories (`rwX`).
+    Writer,
+
+    /// Read, write and execute (`rwx`).
+    Full,
+}
+
+impl Role {
+    /// Return the permissions for this role on an object of the given kind.
+    #[must_use]
+    pub fn perms(self, path_kind: PathKind) -> Perm {
+        let perms = match self {
+            Role::Reader => Perm::READ,
+            Role::Writer => Perm::READ | Perm::WRITE,
+            Role::Full => return Perm::READ | Perm::WRITE | Perm::EXECUTE,
+        };
+
+        match path_kind {
+            PathKind::File => perms,
+            PathKind::Directory => perms | Perm::EXECUTE,
+        }
+    }
+}
+
+/// Grant a group access to a file or directory, like
+/// `setfacl -m g:<group_name>:<perms>`.
+///
+/// The group must be in the group database; a decimal gid is not accepted. An
+/// empty `group_name` refers to the owning group. An existing entry for the
+/// group is replaced, other entries are kept, and the mask is recalculated to
+/// include the new permissions.
+///
+/// With [`AclOption::DEFAULT_ACL`], the group is also added to the default ACL
+/// of a directory, so new files and subdirectories inherit the grant. If the
+/// directory has no default ACL, it starts from the owner, group and other
+/// entries of the access ACL, like `setfacl -d -m`. The option is ignored for
+/// files. [`AclOption::SYMLINK_ACL`] is passed through; other options are
+/// ignored.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{grant_group, AclOption, Role};
+///
+/// grant_group("./tmp/shared", "staff", Role::Writer, AclOption::DEFAULT_ACL)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] with kind `NotFound` if the group does not exist,
+/// or any error from reading or writing the ACL.
+pub fn grant_group<P: AsRef<Path>>(
+    path: P,
+    group_name: &str,
+    role: Role,
+    options: AclOption,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    if !group_name.is_empty() {
+        name_to_gid_strict(group_name)?;
+    }
+
+    let options = options & (AclOption::DEFAULT_ACL | AclOption::SYMLINK_ACL);
+    let read_options = options - AclOption::DEFAULT_ACL;
+    let mut editor = Editor::new(getfacl(path, read_options)?);
+
+    let path_kind = if path.is_dir() {
+        PathKind::Directory
+    } else {
+        PathKind::File
+    };
+    let perms = role.perms(path_kind);
+    editor.set(AclEntry::allow_group(group_name, perms, None));
+
+    if options.contains(AclOption::DEFAULT_ACL) && path_kind == PathKind::Directory {
+        let has_default = editor
+            .entries()
+            .iter()
+            .any(|entry| entry.flags.contains(Flag::DEFAULT));
+        if !has_default {
+            for entry in base_entries(editor.entries()) {
+                editor.set(AclEntry {
+                    flags: Flag::DEFAULT,
+                    ..entry
+                });
+            }
+        }
+        editor.set(AclEntry::allow_group(group_name, perms, Flag::DEFAULT));
+    }
+
+    setfacl(&[path], editor.finish(), read_options)
+}
+
+/// Return the owner, group and other entries of the access ACL.
+fn base_entries(entries: &[AclEntry]) -> Vec<AclEntry> {
+    entries
+        .iter()
+        .filter(|entry| {
+            !entry.flags.contains(Flag::DEFAULT)
+                && entry.name.is_empty()
+                && matches!(
+                    entry.kind,
+                    AclEntryKind::User | AclEntryKind::Group | AclEntryKind::Other
+                )
+        })
+        .cloned()
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod grant_tests {
+    use super::*;
+
+    #[test]
+    fn test_role_perms() {
+        let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+        assert_eq!(Role::Reader.perms(PathKind::File), Perm::READ);
+        assert_eq!(
+            Role::Reader.perms(PathKind::Directory),
+            Perm::READ | Perm::EXECUTE
+        );
+        assert_eq!(Role::Writer.perms(PathKind::File), Perm::READ | Perm::WRITE);
+        assert_eq!(Role::Writer.perms(PathKind::Directory), rwx);
+        assert_eq!(Role::Full.perms(PathKind::File), rwx);
+        assert_eq!(Role::Full.perms(PathKind::Directory), rwx);
+    }
+
+    #[test]
+    fn test_grant_group_file() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        grant_group(&file, "daemon", Role::Writer, AclOption::DEFAULT_ACL)?;
+
+        let entries = getfacl(&file, None)?;
+        let rw = Perm::READ | Perm::WRITE;
+        assert!(entries.contains(&AclEntry::allow_group("daemon", rw, None)));
+        assert!(entries
+            .iter()
+            .any(|e| e.kind == AclEntryKind::Mask && e.perms.contains(rw)));
+        assert!(!entries.iter().any(|e| e.flags.contains(Flag::DEFAULT)));
+
+        // Granting again replaces the entry.
+        grant_group(&file, "daemon", Role::Reader, AclOption::empty())?;
+        let entries = getfacl(&file, None)?;
+        let daemon = entries.iter().filter(|e| e.name == "daemon");
+        assert_eq!(daemon.map(|e| e.perms).collect::<Vec<_>>(), [Perm::READ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_grant_group_default() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        grant_group(&dir, "daemon", Role::Reader, AclOption::DEFAULT_ACL)?;
+
+        let rx = Perm::READ | Perm::EXECUTE;
+        let entries = getfacl(&dir, None)?;
+        assert!(entries.contains(&AclEntry::allow_group("daemon", rx, None)));
+        assert!(entries.contains(&AclEntry::allow_group("daemon", rx, Flag::DEFAULT)));
+
+        let defaults = getfacl(&dir, AclOption::DEFAULT_ACL)?;
+        assert_eq!(defaults.len(), 5);
+        assert!(defaults.iter().any(|e| e.kind == AclEntryKind::Mask));
+        Ok(())
+    }
+
+    #[test]
+    fn test_grant_group_unknown() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let err =
+            grant_group(&file, "no_such_group", Role::Reader, AclOption::empty()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "unknown group name: \"no_such_group\"");
+
+        let err = grant_group(&file, "1234567", Role::Reader, AclOption::empty()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
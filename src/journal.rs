@@ -0,0 +1,392 @@
+//! Writes a journal of ACL changes so a bulk run can be resumed or rolled back.
+//!
+//! [`apply`] reads the current ACL of every path, writes a plan with the old
+//! and new entries to the journal file, and syncs it to disk before changing
+//! anything. As each path is changed, a completion marker is appended. If the
+//! run is interrupted, [`resume`] applies the changes that were not completed,
+//! and [`rollback`] restores the old ACL's.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::{journal, AclEntry, Perm};
+//!
+//! let entries = vec![
+//!     AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+//!     AclEntry::allow_group("", Perm::READ, None),
+//!     AclEntry::allow_other(Perm::empty(), None),
+//! ];
+//! let changes = vec![("./tmp/a", entries.clone()), ("./tmp/b", entries)];
+//!
+//! if let Err(err) = journal::apply("./migration.journal", changes) {
+//!     eprintln!("{err}");
+//!     journal::rollback("./migration.journal")?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The journal is a text file. The first line is a header. Each planned change
+//! is a tab-separated line `acl`, path, old entries and new entries, where the
+//! entries are in the text format of [`to_writer`](crate::to_writer),
+//! separated by spaces. Status lines `done <n>` and `undone <n>` refer to the
+//! n-th planned change, counting from 0. Backslashes, spaces and control
+//! characters in paths are escaped as a backslash and three octal digits.
+
+use crate::aclentry::AclEntry;
+use crate::dump::{escape_path, unescape_path};
+use crate::failx::path_err;
+use crate::{getfacl, setfacl};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// First line of a journal file.
+const HEADER: &str = "exacl-journal 1";
+
+/// Status of a planned change in a journal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// The change has not been applied, or was interrupted.
+    Pending,
+
+    /// The new ACL was written.
+    Done,
+
+    /// The old ACL was restored by [`rollback`].
+    RolledBack,
+}
+
+/// A planned change read from a journal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Record {
+    /// Path of the file or directory.
+    pub path: PathBuf,
+
+    /// Entries before the change.
+    pub old: Vec<AclEntry>,
+
+    /// Entries after the change.
+    pub new: Vec<AclEntry>,
+
+    /// Status of the change.
+    pub status: Status,
+}
+
+/// Change the ACL of each path, recording the changes in a new journal file.
+///
+/// The old ACL's are read and the whole plan is synced to `journal` before
+/// any path is changed. Entries are written with [`setfacl`], including the
+/// default ACL of a directory on Linux and `FreeBSD`.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the journal already exists or can't be written,
+/// if an old ACL can't be read (nothing is changed), or on the first failure
+/// to write an ACL. In the last case, use [`resume`] or [`rollback`].
+pub fn apply<J, I, P>(journal: J, changes: I) -> io::Result<()>
+where
+    J: AsRef<Path>,
+    I: IntoIterator<Item = (P, Vec<AclEntry>)>,
+    P: Into<PathBuf>,
+{
+    let journal = journal.as_ref();
+    let mut records = Vec::new();
+    for (path, new) in changes {
+        let path = path.into();
+        let old = getfacl(&path, None)?;
+        records.push(Record {
+            path,
+            old,
+            new,
+            status: Status::Pending,
+        });
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(journal)
+        .map_err(|err| path_err(journal, &err))?;
+    let mut plan = format!("{HEADER}\n").into_bytes();
+    for record in &records {
+        write_record(&mut plan, record);
+    }
+    file.write_all(&plan)
+        .and_then(|()| file.sync_data())
+        .map_err(|err| path_err(journal, &err))?;
+
+    run(journal, &mut file, &records, Status::Done)?;
+    Ok(())
+}
+
+/// Apply the changes in a journal that are still pending. Return the paths
+/// that were changed.
+///
+/// Changes that are done or rolled back are skipped.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the journal can't be read or updated, or on the
+/// first failure to write an ACL.
+pub fn resume<J: AsRef<Path>>(journal: J) -> io::Result<Vec<PathBuf>> {
+    let journal = journal.as_ref();
+    let records = read(journal)?;
+    let mut file = open_append(journal)?;
+
+    run(journal, &mut file, &records, Status::Done)
+}
+
+/// Restore the old ACL of every change in a journal that is done or pending,
+/// in reverse order. Return the paths that were restored.
+///
+/// Pending changes are restored too, since an interrupted change may have been
+/// partly written. Changes that are already rolled back are skipped, so an
+/// interrupted rollback can be run again.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the journal can't be read or updated, or on the
+/// first failure to write an ACL.
+pub fn rollback<J: AsRef<Path>>(journal: J) -> io::Result<Vec<PathBuf>> {
+    let journal = journal.as_ref();
+    let records = read(journal)?;
+    let mut file = open_append(journal)?;
+
+    run(journal, &mut file, &records, Status::RolledBack)
+}
+
+/// Read the planned changes and their status from a journal.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the journal can't be read, or with kind
+/// `InvalidData` if it is not a valid journal.
+pub fn read<J: AsRef<Path>>(journal: J) -> io::Result<Vec<Record>> {
+    let journal = journal.as_ref();
+    let data = fs::read(journal).map_err(|err| path_err(journal, &err))?;
+    let invalid = |lineno: usize, msg: &str| {
+        let err = io::Error::new(io::ErrorKind::InvalidData, format!("line {lineno}: {msg}"));
+        path_err(journal, &err)
+    };
+
+    // Ignore a partial last line, left by a crash while appending.
+    let end = data.iter().rposition(|&b| b == b'\n').map_or(0, |n| n + 1);
+    let mut lines = data[..end].split(|&b| b == b'\n');
+    if lines.next() != Some(HEADER.as_bytes()) {
+        return Err(invalid(1, "not an exacl journal"));
+    }
+
+    let mut records = Vec::<Record>::new();
+    for (i, line) in lines.enumerate() {
+        let lineno = i + 2;
+        let fields = line.split(|&b| b == b'\t').collect::<Vec<_>>();
+        match fields.as_slice() {
+            [b"acl", path, old, new] => {
+                let entries =
+                    |field: &[u8]| parse_entries(field).map_err(|msg| invalid(lineno, &msg));
+                records.push(Record {
+                    path: unescape_path(path).ok_or_else(|| invalid(lineno, "invalid path"))?,
+                    old: entries(old)?,
+                    new: entries(new)?,
+                    status: Status::Pending,
+                });
+            }
+            [b"done" | b"undone", index] => {
+                let record = std::str::from_utf8(index)
+                    .ok()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .and_then(|n| records.get_mut(n))
+                    .ok_or_else(|| invalid(lineno, "invalid index"))?;
+                record.status = if fields[0] == b"done" {
+                    Status::Done
+                } else {
+                    Status::RolledBack
+                };
+            }
+            // The empty slice after the last newline.
+            [b""] => (),
+            _ => return Err(invalid(lineno, "unknown record")),
+        }
+    }
+
+    Ok(records)
+}
+
+/// Write the new (for `Done`) or old (for `RolledBack`) ACL of each record
+/// that needs it, appending a status line after each.
+fn run(
+    journal: &Path,
+    file: &mut File,
+    records: &[Record],
+    target: Status,
+) -> io::Result<Vec<PathBuf>> {
+    let mut indexes = (0..records.len()).collect::<Vec<_>>();
+    if target == Status::RolledBack {
+        indexes.reverse();
+    }
+
+    let mut changed = Vec::new();
+    for i in indexes {
+        let record = &records[i];
+        let (entries, tag) = match (target, record.status) {
+            (Status::Done, Status::Pending) => (&record.new, "done"),
+            (Status::RolledBack, Status::Pending | Status::Done) => (&record.old, "undone"),
+            _ => continue,
+        };
+
+        setfacl(&[&record.path], entries, None)?;
+        file.write_all(format!("{tag}\t{i}\n").as_bytes())
+            .map_err(|err| path_err(journal, &err))?;
+        changed.push(record.path.clone());
+    }
+
+    file.sync_data().map_err(|err| path_err(journal, &err))?;
+    Ok(changed)
+}
+
+/// Open a journal to append status lines.
+fn open_append(journal: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .append(true)
+        .open(journal)
+        .map_err(|err| path_err(journal, &err))
+}
+
+/// Append an `acl` line for the record.
+fn write_record(buf: &mut Vec<u8>, record: &Record) {
+    let entries = |entries: &[AclEntry]| {
+        entries
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    buf.extend_from_slice(b"acl\t");
+    buf.extend_from_slice(&escape_path(&record.path));
+    buf.extend_from_slice(
+        format!("\t{}\t{}\n", entries(&record.old), entries(&record.new)).as_bytes(),
+    );
+}
+
+/// Parse space-separated entries.
+fn parse_entries(field: &[u8]) -> Result<Vec<AclEntry>, String> {
+    let text = std::str::from_utf8(field).map_err(|_| "invalid UTF-8".to_string())?;
+    text.split(' ')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<AclEntry>().map_err(|err| err.to_string()))
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod journal_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    fn named(path: &Path) -> Vec<String> {
+        getfacl(path, None)
+            .unwrap()
+            .into_iter()
+            .filter(|e| !e.name.is_empty())
+            .map(|e| e.name)
+            .collect()
+    }
+
+    fn with_user(path: &Path, name: &str) -> Vec<AclEntry> {
+        let mut entries = getfacl(path, None).unwrap();
+        entries.push(AclEntry::allow_user(name, Perm::READ, None));
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        entries
+    }
+
+    #[test]
+    fn test_apply_rollback() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let (a, b) = (dir.path().join("a\tx"), dir.path().join("b"));
+        std::fs::write(&a, "")?;
+        std::fs::write(&b, "")?;
+        let journal = dir.path().join("journal");
+
+        let changes = vec![
+            (a.clone(), with_user(&a, "11501")),
+            (b.clone(), with_user(&b, "11502")),
+        ];
+        apply(&journal, changes.clone())?;
+        assert_eq!(named(&a), ["11501"]);
+        assert_eq!(named(&b), ["11502"]);
+
+        let records = read(&journal)?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].path, a);
+        assert_eq!(records[0].new, changes[0].1);
+        assert!(records.iter().all(|r| r.status == Status::Done));
+
+        // The journal is not overwritten.
+        let err = apply(&journal, changes).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        assert!(resume(&journal)?.is_empty());
+        assert_eq!(rollback(&journal)?, vec![b.clone(), a.clone()]);
+        assert!(named(&a).is_empty());
+        assert!(named(&b).is_empty());
+        assert!(read(&journal)?
+            .iter()
+            .all(|r| r.status == Status::RolledBack));
+        assert!(rollback(&journal)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::write(&a, "")?;
+        std::fs::write(&b, "")?;
+        let journal = dir.path().join("journal");
+
+        // Simulate a crash after the first change.
+        let records = [(&a, "11501"), (&b, "11502")].map(|(path, name)| Record {
+            path: path.clone(),
+            old: getfacl(path, None).unwrap(),
+            new: with_user(path, name),
+            status: Status::Pending,
+        });
+        let mut text = format!("{HEADER}\n").into_bytes();
+        records.iter().for_each(|r| write_record(&mut text, r));
+        text.extend_from_slice(b"done\t0\nund");
+        std::fs::write(&journal, text)?;
+
+        let status = read(&journal)?.iter().map(|r| r.status).collect::<Vec<_>>();
+        assert_eq!(status, [Status::Done, Status::Pending]);
+
+        assert_eq!(resume(&journal)?, vec![b.clone()]);
+        assert!(named(&a).is_empty());
+        assert_eq!(named(&b), ["11502"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_invalid() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let journal = dir.path().join("journal");
+
+        for (text, msg) in [
+            ("nope\n", "line 1: not an exacl journal"),
+            ("exacl-journal 1\ndone\t0\n", "line 2: invalid index"),
+            ("exacl-journal 1\nacl\t/a\tbad\t\n", "line 2: "),
+            ("exacl-journal 1\nfoo\n", "line 2: unknown record"),
+        ] {
+            std::fs::write(&journal, text)?;
+            let err = read(&journal).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(msg), "{err}");
+        }
+
+        Ok(())
+    }
+}
@@ -37,7 +37,7 @@
 //! present.
 //!
 //! Both [`getfacl`] and [`setfacl`] work with a `Vec<AclEntry>`. The
-//! [`AclEntry`] structure contains five fields:
+//! [`AclEntry`] structure contains six fields:
 //!
 //! - kind : [`AclEntryKind`] - the kind of entry (User, Group, Other, Mask,
 //!     or Unknown).
@@ -47,36 +47,233 @@
 //! - flags : [`Flag`] - flags indicating whether an entry is inherited, etc.
 //! - allow : [`bool`] - true if entry is allowed; false means deny. Linux only
 //!     supports allow=true.
+//! - numeric : [`bool`] - true if name is an explicit decimal uid/gid that is
+//!     never looked up as a user/group name.
 
 #![warn(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
+// A misbehaving file system or ACL library must not abort the host program, so
+// library code returns errors instead of panicking. Tests may still panic.
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::panic,
+        clippy::panic_in_result_fn,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented
+    )
+)]
 
+#[cfg(not(exacl_core))]
+mod access;
+#[cfg(not(exacl_core))]
 mod acl;
 mod aclentry;
+mod aclset;
+#[cfg(feature = "serde")]
+mod acltext;
+#[cfg(not(exacl_core))]
 mod bindings;
 mod bititer;
+#[cfg(not(exacl_core))]
+mod builder;
+#[cfg(not(exacl_core))]
+mod compiled;
+#[cfg(any(test, feature = "conformance"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "conformance")))]
+pub mod conformance;
+mod consolidate;
+#[cfg(not(exacl_core))]
+mod deadline;
+mod dialect;
+mod document;
+#[cfg(not(exacl_core))]
+mod dump;
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core))]
+mod editor;
+#[cfg(all(
+    any(docsrs, target_os = "linux", target_os = "freebsd"),
+    not(exacl_core)
+))]
+mod ensure;
 mod failx;
 mod flag;
 mod format;
+#[cfg(all(
+    any(docsrs, target_os = "linux", target_os = "freebsd"),
+    not(exacl_core)
+))]
+mod grant;
+#[cfg(not(exacl_core))]
+pub mod journal;
+#[cfg(not(exacl_core))]
+mod link;
+mod lint;
+#[cfg(any(docsrs, target_os = "macos"))]
+#[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+pub mod macos;
+#[cfg(not(exacl_core))]
+mod modify;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod nativetext;
+#[cfg(all(target_os = "linux", exacl_nfs4))]
+mod nfs4;
 mod perm;
+mod platform;
+#[cfg(all(
+    feature = "compat-posix-acl",
+    any(docsrs, target_os = "linux", target_os = "freebsd"),
+    not(exacl_core)
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(
+        feature = "compat-posix-acl",
+        any(target_os = "linux", target_os = "freebsd")
+    )))
+)]
+pub mod posix_acl;
+#[cfg(not(exacl_core))]
 mod qualifier;
+#[cfg(all(feature = "raw", not(exacl_core)))]
+#[cfg_attr(docsrs, doc(cfg(feature = "raw")))]
+pub mod raw;
+mod record;
+mod redact;
+#[cfg(not(exacl_core))]
+mod resolve;
+#[cfg(not(exacl_core))]
+mod selftest;
 mod sys;
-mod unix;
+#[cfg(all(feature = "testing", any(docsrs, target_os = "linux"), not(exacl_core)))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "testing", target_os = "linux"))))]
+pub mod testing;
+#[cfg(not(exacl_core))]
+mod tree;
+#[cfg(not(exacl_core))]
+pub mod unix;
+#[cfg(not(exacl_core))]
 mod util;
+mod validate;
+#[cfg(not(exacl_core))]
+mod volume;
+#[cfg(all(feature = "watch", not(exacl_core)))]
+#[cfg_attr(docsrs, doc(cfg(feature = "watch")))]
+pub mod watch;
+#[cfg(all(feature = "windows", windows, exacl_extended))]
+#[cfg_attr(docsrs, doc(cfg(feature = "windows")))]
+pub mod windows;
+#[cfg(target_os = "linux")]
+mod xattr;
 
-// Export AclOption, AclEntry, AclEntryKind, Flag and Perm.
-pub use acl::AclOption;
-pub use aclentry::{AclEntry, AclEntryKind};
+// Export Acl, AclBuilder, AclOption, AclEntry, AclEntryKind, AclSet, Dialect, Editor, Flag, Lint, Perm and Role.
+#[cfg(not(exacl_core))]
+pub use access::{check_access, rights_matrix, AccessCheck, Principal};
+#[cfg(not(exacl_core))]
+pub use acl::{Acl, AclBrand, AclIter, AclOption};
+#[cfg(not(exacl_core))]
+pub use aclentry::AclEntryRef;
+pub use aclentry::{AclEntry, AclEntryKind, DisplayList, PathKind};
+pub use aclset::AclSet;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub use acltext::AclText;
+#[cfg(not(exacl_core))]
+pub use builder::AclBuilder;
+#[cfg(not(exacl_core))]
+pub use compiled::CompiledAcl;
+pub use consolidate::{consolidate, consolidate_with, Consolidation};
+#[cfg(not(exacl_core))]
+pub use deadline::with_timeout;
+pub use dialect::Dialect;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub use document::AclDocument;
+#[cfg(feature = "json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+pub use document::{from_json, to_json};
+pub use document::{from_reader_format, to_writer_format, Format};
+#[cfg(not(exacl_core))]
+pub use dump::{dump_from_reader, write_dump, write_dump_redacted};
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use editor::Editor;
+#[cfg(all(
+    any(docsrs, target_os = "linux", target_os = "freebsd"),
+    not(exacl_core)
+))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use ensure::{
+    ensure_default_acl, get_default_acl, propagate_default, set_default_acl, DefaultAclStatus,
+};
+pub use failx::PathError;
 pub use flag::Flag;
+pub use format::{syntax, FormatVersion};
+#[cfg(all(
+    any(docsrs, target_os = "linux", target_os = "freebsd"),
+    not(exacl_core)
+))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use grant::{grant_group, Role};
+#[cfg(not(exacl_core))]
+pub use link::{getfacl_both, LinkAcls};
+pub use lint::{lint, Lint};
+#[cfg(not(exacl_core))]
+pub use modify::{add_entry, remove_entry};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "macos"))))]
+pub use nativetext::{from_platform_text, to_platform_text};
 pub use perm::Perm;
+pub use platform::{
+    HAS_DEFAULT_ACL, HAS_DENY_ENTRIES, HAS_INHERIT_FLAGS, HAS_MASK_ENTRIES, HAS_NATIVE_ACL,
+};
+#[cfg(feature = "csv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "csv")))]
+pub use record::write_csv;
+pub use record::AclRecord;
+pub use redact::Redactor;
+#[cfg(not(exacl_core))]
+pub use resolve::{resolve_check, UnresolvedPrincipal};
+#[cfg(not(exacl_core))]
+pub use selftest::{selftest, SelfTestReport};
+#[cfg(not(exacl_core))]
+pub use tree::{
+    apply_grants, find_grants, flatten_tree, revoke_principal_recursive, stats, GrantOp, TreeStats,
+};
+pub use validate::{validate, ValidationError, ValidationMode};
+#[cfg(not(exacl_core))]
+pub use volume::{volume_capabilities, VolumeCapabilities};
+#[cfg(target_os = "linux")]
+#[cfg_attr(docsrs, doc(cfg(target_os = "linux")))]
+pub use xattr::{from_xattr_bytes, to_xattr_bytes, XattrKind};
 
-use acl::Acl;
+#[cfg(any(docsrs, target_os = "linux"))]
+pub use acl::TextOptions;
+
+#[cfg(not(exacl_core))]
 use failx::custom_err;
+#[cfg(not(exacl_core))]
+use std::collections::HashMap;
 use std::io::{self, BufRead};
-use std::path::Path;
+#[cfg(not(exacl_core))]
+use std::os::fd::{AsFd, BorrowedFd};
+#[cfg(not(exacl_core))]
+use std::{
+    borrow::Borrow,
+    path::{Path, PathBuf},
+};
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(not(any(target_os = "macos", exacl_core)))]
 use failx::fail_custom;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    all(target_os = "linux", exacl_nfs4)
+))]
+use log::warn;
 
 /// Get access control list (ACL) for a file or directory.
 ///
@@ -126,35 +323,88 @@ use failx::fail_custom;
 ///
 /// Returns an [`io::Error`] on failure.
 ///
+#[cfg(not(exacl_core))]
 pub fn getfacl<P, O>(path: P, options: O) -> io::Result<Vec<AclEntry>>
 where
     P: AsRef<Path>,
     O: Into<Option<AclOption>>,
 {
-    _getfacl(path.as_ref(), options.into().unwrap_or_default())
+    read_consistent(path.as_ref(), options.into().unwrap_or_default(), _getfacl)
+}
+
+/// Number of times `CONSISTENT_READ` reads an ACL before giving up.
+#[cfg(not(exacl_core))]
+const CONSISTENT_READ_ATTEMPTS: usize = 5;
+
+/// Read the entries with `read`. With `CONSISTENT_READ`, read them again until
+/// the path's change time is the same before and after reading.
+#[cfg(not(exacl_core))]
+fn read_consistent<F>(path: &Path, options: AclOption, mut read: F) -> io::Result<Vec<AclEntry>>
+where
+    F: FnMut(&Path, AclOption) -> io::Result<Vec<AclEntry>>,
+{
+    if !options.contains(AclOption::CONSISTENT_READ) {
+        return read(path, options);
+    }
+
+    let options = options - AclOption::CONSISTENT_READ;
+    for _ in 0..CONSISTENT_READ_ATTEMPTS {
+        let before = change_time(path, options)?;
+        let entries = read(path, options)?;
+        if change_time(path, options)? == before {
+            return Ok(entries);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "{}: ACL changed while reading it {CONSISTENT_READ_ATTEMPTS} times",
+            path.display()
+        ),
+    ))
+}
+
+/// Return the change time of a path, which is updated when its ACL is set.
+#[cfg(not(exacl_core))]
+fn change_time(path: &Path, options: AclOption) -> io::Result<(i64, i64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = if options.contains(AclOption::SYMLINK_ACL) {
+        std::fs::symlink_metadata(path)
+    } else {
+        std::fs::metadata(path)
+    };
+    let metadata = metadata.map_err(|err| failx::path_err(path, &err))?;
+    Ok((metadata.ctime(), metadata.ctime_nsec()))
 }
 
 #[cfg(target_os = "macos")]
 fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
-    Acl::read(path, options)?.entries()
+    Acl::read(path, options)?.entries_with(options)
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(not(any(target_os = "macos", exacl_core)))]
 fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
+    #[cfg(all(target_os = "linux", exacl_nfs4))]
+    if nfs4::is_nfs4(path, options) {
+        return nfs4::read(path, options);
+    }
+
     if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
         fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")
     } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        Acl::read(path, options)?.entries()
+        Acl::read(path, options)?.entries_with(options)
     } else {
         let acl = Acl::read(path, options)?;
-        let mut entries = acl.entries()?;
+        let mut entries = acl.entries_with(options)?;
 
         if acl.is_posix() {
             let mut default = Acl::read(
                 path,
                 options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
             )?
-            .entries()?;
+            .entries_with(options)?;
 
             entries.append(&mut default);
         }
@@ -162,12 +412,129 @@ fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
     }
 }
 
+/// Get access control lists for many paths.
+///
+/// Returns the result of [`getfacl`] for each path, keyed by path. A failure
+/// on one path doesn't stop the others. User and group names are looked up
+/// once per id for the whole batch, which is much faster than separate
+/// `getfacl` calls when many files are owned by the same few users and
+/// groups. Names cached by [`unix::prefetch_ids`] are used too; names looked
+/// up by the batch are forgotten when `getfacl_map` returns.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let paths = ["./tmp/a", "./tmp/b"];
+/// for (path, entries) in exacl::getfacl_map(&paths, None) {
+///     match entries {
+///         Ok(entries) => println!("{}: {} entries", path.display(), entries.len()),
+///         Err(err) => println!("{}: {err}", path.display()),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(not(exacl_core))]
+pub fn getfacl_map<P, O>(paths: &[P], options: O) -> HashMap<PathBuf, io::Result<Vec<AclEntry>>>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let options = options.into().unwrap_or_default();
+    unix::with_batch_cache(|| {
+        paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                (path.to_path_buf(), read_consistent(path, options, _getfacl))
+            })
+            .collect()
+    })
+}
+
+/// Get access control list (ACL) for an open file or directory.
+///
+/// Works like [`getfacl`], but reads the ACL through a file descriptor, such
+/// as a [`std::fs::File`] or a descriptor opened with `O_NOFOLLOW`, so the
+/// path can't be swapped out between opening and reading.
+/// [`AclOption::SYMLINK_ACL`] is not used; the ACL of the open file is read.
+///
+/// On Linux, only the access ACL is returned, since libacl has no file
+/// descriptor API for the default ACL. Use [`AclOption::DEFAULT_ACL`] with
+/// [`getfacl`] instead.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let file = std::fs::File::open("./tmp/foo")?;
+/// let entries = exacl::getfacl_fd(&file, None)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(not(exacl_core))]
+pub fn getfacl_fd<F, O>(fd: F, options: O) -> io::Result<Vec<AclEntry>>
+where
+    F: AsFd,
+    O: Into<Option<AclOption>>,
+{
+    _getfacl_fd(fd.as_fd(), options.into().unwrap_or_default())
+}
+
+#[cfg(target_os = "macos")]
+fn _getfacl_fd(fd: BorrowedFd, options: AclOption) -> io::Result<Vec<AclEntry>> {
+    Acl::read_fd(fd, options)?.entries_with(options)
+}
+
+#[cfg(not(any(target_os = "macos", exacl_core)))]
+fn _getfacl_fd(fd: BorrowedFd, options: AclOption) -> io::Result<Vec<AclEntry>> {
+    if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+        fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")
+    } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+        Acl::read_fd(fd, options)?.entries_with(options)
+    } else {
+        let acl = Acl::read_fd(fd, options)?;
+        #[allow(unused_mut)]
+        let mut entries = acl.entries_with(options)?;
+
+        #[cfg(target_os = "freebsd")]
+        if acl.is_posix() && is_dir_fd(fd)? {
+            let mut default =
+                Acl::read_fd(fd, options | AclOption::DEFAULT_ACL)?.entries_with(options)?;
+            entries.append(&mut default);
+        }
+        Ok(entries)
+    }
+}
+
+/// Return true if the open file is a directory.
+#[cfg(target_os = "freebsd")]
+fn is_dir_fd(fd: BorrowedFd) -> io::Result<bool> {
+    let file = std::fs::File::from(fd.try_clone_to_owned()?);
+    Ok(file.metadata()?.is_dir())
+}
+
 /// Set access control list (ACL) for specified files and directories.
 ///
 /// Sets the ACL for the specified paths using the given access control entries.
 /// The semantics and permissions of the access control list depend on the
 /// underlying platform.
 ///
+/// The entries may be any iterable of `AclEntry` or `&AclEntry`, such as a
+/// `&Vec<AclEntry>` or an iterator that generates entries on the fly. The
+/// entries are consumed once, before any path is modified.
+///
+/// # Durability
+///
+/// By default, a new ACL is visible as soon as `setfacl` returns, but it may
+/// only be in the file system's cache; after a crash or power loss, the file
+/// may have its old ACL. Use the [`AclOption::SYNC_FILES`] and
+/// [`AclOption::SYNC_DIRS`] options to flush the batch to storage before
+/// `setfacl` returns. All paths are written first, then flushed, so a batch
+/// pays for one flush per file rather than interleaving writes and flushes.
+/// Nothing is flushed if writing any path fails.
+///
 /// # macOS
 ///
 /// The ACL contains extended entries beyond the usual mode permission bits.
@@ -228,20 +595,210 @@ fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
 ///
 /// Returns an [`io::Error`] on failure.
 ///
-pub fn setfacl<P, O>(paths: &[P], entries: &[AclEntry], options: O) -> io::Result<()>
+#[cfg(not(exacl_core))]
+pub fn setfacl<P, E, O>(paths: &[P], entries: E, options: O) -> io::Result<()>
 where
     P: AsRef<Path>,
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
     O: Into<Option<AclOption>>,
 {
-    _setfacl(paths, entries, options.into().unwrap_or_default())
+    let options = options.into().unwrap_or_default();
+
+    // On Linux, an NFS mount has an NFSv4 ACL, which is written as an
+    // extended attribute.
+    #[cfg(all(target_os = "linux", exacl_nfs4))]
+    {
+        let is_nfs4 = paths
+            .iter()
+            .map(|path| nfs4::is_nfs4(path.as_ref(), options))
+            .collect::<Vec<_>>();
+        if is_nfs4.contains(&true) {
+            let entries = if options.contains(AclOption::STRIP_INHERITED) {
+                strip_inherited(entries)
+            } else {
+                entries.into_iter().map(|e| e.borrow().clone()).collect()
+            };
+            let path_options = options - (AclOption::SYNC_FILES | AclOption::SYNC_DIRS);
+            for (path, is_nfs4) in paths.iter().zip(is_nfs4) {
+                if is_nfs4 {
+                    nfs4::write(path.as_ref(), &entries, path_options)?;
+                } else {
+                    setfacl(&[path.as_ref()], &entries, path_options)?;
+                }
+            }
+            return sync_batch(paths, options);
+        }
+    }
+
+    // The filled entries depend on each path's current ACL, so write each path
+    // separately.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    if options.contains(AclOption::FILL_REQUIRED) {
+        let entries = entries
+            .into_iter()
+            .map(|entry| entry.borrow().clone())
+            .collect::<Vec<_>>();
+        let path_options =
+            options - (AclOption::FILL_REQUIRED | AclOption::SYNC_FILES | AclOption::SYNC_DIRS);
+        for path in paths {
+            let path = path.as_ref();
+            setfacl(
+                &[path],
+                fill_required(path, &entries, options)?,
+                path_options,
+            )?;
+        }
+        return sync_batch(paths, options);
+    }
+
+    #[cfg(all(any(target_os = "macos", target_os = "freebsd"), exacl_extended))]
+    if options.contains(AclOption::STRIP_INHERITED) {
+        _setfacl(paths, strip_inherited(entries), options)?;
+        return sync_batch(paths, options);
+    }
+
+    _setfacl(paths, entries, options)?;
+    sync_batch(paths, options)
+}
+
+/// Flush the ACL's of a batch written by `setfacl` to storage, as requested
+/// by the `SYNC_FILES` and `SYNC_DIRS` options.
+#[cfg(not(exacl_core))]
+fn sync_batch<P: AsRef<Path>>(paths: &[P], options: AclOption) -> io::Result<()> {
+    if options.contains(AclOption::SYNC_FILES) {
+        for path in paths {
+            let path = path.as_ref();
+            // A symlink can't be opened without following it; its ACL is
+            // flushed with its directory by `SYNC_DIRS`.
+            if options.contains(AclOption::SYMLINK_ACL) && path.is_symlink() {
+                continue;
+            }
+            sync_path(path)?;
+        }
+    }
+
+    if options.contains(AclOption::SYNC_DIRS) {
+        let mut synced = std::collections::BTreeSet::new();
+        for path in paths {
+            let parent = match path.as_ref().parent() {
+                Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+                Some(parent) => parent,
+                None => continue,
+            };
+            if synced.insert(parent) {
+                sync_path(parent)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Flush the data and metadata of a file or directory to storage.
+#[cfg(not(exacl_core))]
+fn sync_path(path: &Path) -> io::Result<()> {
+    std::fs::File::open(path)
+        .and_then(|file| file.sync_all())
+        .map_err(|err| failx::path_err(path, &err))
+}
+
+/// Return the entries with the owner, group and other entries they lack
+/// copied from the current ACL of `path`, as requested by `FILL_REQUIRED`.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn fill_required(
+    path: &Path,
+    entries: &[AclEntry],
+    options: AclOption,
+) -> io::Result<Vec<AclEntry>> {
+    let read_options = options & AclOption::SYMLINK_ACL;
+    let acl = Acl::read(path, read_options)?;
+    if !acl.is_posix() {
+        return Ok(entries.to_vec());
+    }
+
+    let access = acl.entries()?;
+    let default = Acl::read(
+        path,
+        read_options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
+    )?
+    .entries()?;
+
+    let is_default = |entry: &&AclEntry| {
+        options.contains(AclOption::DEFAULT_ACL)
+            || (!options.contains(AclOption::ACCESS_ACL) && entry.flags.contains(Flag::DEFAULT))
+    };
+    let (given_default, given_access): (Vec<_>, Vec<_>) = entries.iter().partition(is_default);
+
+    let mut result = entries.to_vec();
+    result.extend(missing_base(&given_access, &access, Flag::empty()));
+    let source = if default.is_empty() {
+        &access
+    } else {
+        &default
+    };
+    result.extend(missing_base(&given_default, source, Flag::DEFAULT));
+
+    Ok(result)
+}
+
+/// Return the owner, group and other entries in `source` that `given` lacks,
+/// with `flags`. Return nothing if `given` is empty.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn missing_base(given: &[&AclEntry], source: &[AclEntry], flags: Flag) -> Vec<AclEntry> {
+    if given.is_empty() {
+        return Vec::new();
+    }
+
+    [AclEntryKind::User, AclEntryKind::Group, AclEntryKind::Other]
+        .into_iter()
+        .filter(|kind| {
+            !given
+                .iter()
+                .any(|entry| entry.kind == *kind && entry.name.is_empty())
+        })
+        .filter_map(|kind| {
+            source
+                .iter()
+                .find(|entry| entry.kind == kind && entry.name.is_empty())
+        })
+        .map(|entry| AclEntry {
+            flags,
+            ..entry.clone()
+        })
+        .collect()
+}
+
+/// Return a copy of the entries with the `INHERITED` flag removed.
+#[cfg(all(not(exacl_core), exacl_extended))]
+pub(crate) fn strip_inherited<E>(entries: E) -> Vec<AclEntry>
+where
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
+{
+    entries
+        .into_iter()
+        .map(|entry| {
+            let mut entry = entry.borrow().clone();
+            if entry.flags.contains(Flag::INHERITED) {
+                warn!("setfacl: removed inherited flag from entry {entry}");
+                entry.flags.remove(Flag::INHERITED);
+            }
+            entry
+        })
+        .collect()
 }
 
 #[cfg(target_os = "macos")]
-fn _setfacl<P>(paths: &[P], entries: &[AclEntry], options: AclOption) -> io::Result<()>
+fn _setfacl<P, E>(paths: &[P], entries: E, options: AclOption) -> io::Result<()>
 where
     P: AsRef<Path>,
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
 {
-    let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+    let strict = options.contains(AclOption::STRICT_PRINCIPALS);
+    let acl =
+        Acl::from_entries_strict(entries, strict).map_err(|err| custom_err("Invalid ACL", &err))?;
     for path in paths {
         acl.write(path.as_ref(), options)?;
     }
@@ -249,22 +806,26 @@ where
     Ok(())
 }
 
-#[cfg(not(target_os = "macos"))]
-fn _setfacl<P>(paths: &[P], entries: &[AclEntry], options: AclOption) -> io::Result<()>
+#[cfg(not(any(target_os = "macos", exacl_core)))]
+fn _setfacl<P, E>(paths: &[P], entries: E, options: AclOption) -> io::Result<()>
 where
     P: AsRef<Path>,
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
 {
+    let strict = options.contains(AclOption::STRICT_PRINCIPALS);
     if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
         fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")?;
     } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+        let acl = Acl::from_entries_strict(entries, strict)
+            .map_err(|err| custom_err("Invalid ACL", &err))?;
 
         for path in paths {
             acl.write(path.as_ref(), options)?;
         }
     } else {
-        let (access_acl, default_acl) =
-            Acl::from_unified_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+        let (access_acl, default_acl) = Acl::from_unified_entries_strict(entries, strict)
+            .map_err(|err| custom_err("Invalid ACL", &err))?;
 
         if access_acl.is_empty() {
             fail_custom("Invalid ACL: missing required entries")?;
@@ -289,6 +850,174 @@ where
     Ok(())
 }
 
+/// Set access control list (ACL) for an open file or directory.
+///
+/// Works like [`setfacl`] for one path, but writes the ACL through a file
+/// descriptor, so the path can't be swapped out between opening and writing.
+/// Only [`AclOption::ACCESS_ACL`], [`AclOption::DEFAULT_ACL`],
+/// [`AclOption::STRICT_PRINCIPALS`], [`AclOption::STRIP_INHERITED`] and
+/// [`AclOption::SYNC_FILES`] are used; other options are ignored.
+///
+/// On Linux, default entries are rejected, since libacl has no file
+/// descriptor API for the default ACL. Without default entries, the default
+/// ACL of a directory is left unchanged rather than deleted.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclEntry, Perm};
+///
+/// let file = std::fs::File::open("./tmp/foo")?;
+/// let mut entries = exacl::getfacl_fd(&file, None)?;
+/// entries.push(AclEntry::allow_user("some_user", Perm::READ, None));
+/// exacl::setfacl_fd(&file, &entries, None)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(not(exacl_core))]
+pub fn setfacl_fd<F, E, O>(fd: F, entries: E, options: O) -> io::Result<()>
+where
+    F: AsFd,
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
+    O: Into<Option<AclOption>>,
+{
+    let fd = fd.as_fd();
+    let options = options.into().unwrap_or_default();
+
+    #[cfg(all(any(target_os = "macos", target_os = "freebsd"), exacl_extended))]
+    let result = if options.contains(AclOption::STRIP_INHERITED) {
+        _setfacl_fd(fd, strip_inherited(entries), options)
+    } else {
+        _setfacl_fd(fd, entries, options)
+    };
+
+    #[cfg(not(all(any(target_os = "macos", target_os = "freebsd"), exacl_extended)))]
+    let result = _setfacl_fd(fd, entries, options);
+
+    result?;
+    if options.contains(AclOption::SYNC_FILES) {
+        std::fs::File::from(fd.try_clone_to_owned()?).sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn _setfacl_fd<E>(fd: BorrowedFd, entries: E, options: AclOption) -> io::Result<()>
+where
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
+{
+    let strict = options.contains(AclOption::STRICT_PRINCIPALS);
+    let acl =
+        Acl::from_entries_strict(entries, strict).map_err(|err| custom_err("Invalid ACL", &err))?;
+    acl.write_fd(fd, options)
+}
+
+#[cfg(not(any(target_os = "macos", exacl_core)))]
+fn _setfacl_fd<E>(fd: BorrowedFd, entries: E, options: AclOption) -> io::Result<()>
+where
+    E: IntoIterator,
+    E::Item: Borrow<AclEntry>,
+{
+    let strict = options.contains(AclOption::STRICT_PRINCIPALS);
+    if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+        return fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options");
+    } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+        let acl = Acl::from_entries_strict(entries, strict)
+            .map_err(|err| custom_err("Invalid ACL", &err))?;
+        return acl.write_fd(fd, options);
+    }
+
+    let (access_acl, default_acl) = Acl::from_unified_entries_strict(entries, strict)
+        .map_err(|err| custom_err("Invalid ACL", &err))?;
+
+    if access_acl.is_empty() {
+        fail_custom("Invalid ACL: missing required entries")?;
+    }
+
+    // As in `setfacl`, set the default ACL first. An empty default ACL
+    // deletes the directory's default ACL on `FreeBSD`; on Linux it can't be
+    // written through a file descriptor, so it is skipped.
+    #[cfg(target_os = "freebsd")]
+    let write_default = !default_acl.is_empty() || is_dir_fd(fd)?;
+    #[cfg(target_os = "linux")]
+    let write_default = !default_acl.is_empty();
+
+    if access_acl.is_posix() && write_default {
+        default_acl.write_fd(fd, options | AclOption::DEFAULT_ACL)?;
+    }
+    access_acl.write_fd(fd, options)
+}
+
+/// Remove the extended ACL from a file or directory.
+///
+/// On macOS, this removes the ACL entirely, like `chmod -N`. (Calling
+/// `setfacl` with no entries leaves an empty ACL on the file instead.)
+///
+/// On Linux and `FreeBSD`, this removes every entry except the owner, group
+/// and other entries that correspond to the file mode, like `setfacl -b`. The
+/// default ACL of a directory is removed too.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(not(exacl_core))]
+pub fn clear_acl<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    _clear_acl(path.as_ref())
+}
+
+#[cfg(target_os = "macos")]
+fn _clear_acl(path: &Path) -> io::Result<()> {
+    util::xacl_clear_file(path).map_err(|err| failx::path_err(path, &err))
+}
+
+#[cfg(not(any(target_os = "macos", exacl_core)))]
+fn _clear_acl(path: &Path) -> io::Result<()> {
+    let acl = Acl::read(path, AclOption::ACCESS_ACL)?;
+    acl.strip()?.write(path, AclOption::ACCESS_ACL)?;
+
+    if acl.is_posix() {
+        let options = AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR;
+        Acl::from_entries(Vec::<AclEntry>::new())?.write(path, options)?;
+    }
+
+    Ok(())
+}
+
+/// Remove the extended ACL from a file or directory, and keep the permission
+/// bits of its mode.
+///
+/// Unlike [`clear_acl`], which keeps the permissions of the `group::` entry,
+/// this keeps the group bits shown by `ls -l`, which come from the mask when
+/// there is one. On Linux and `FreeBSD`, the ACL is stripped to the owner,
+/// group and other entries, and the previous mode is restored with `chmod`.
+/// On macOS, the mode is independent of the ACL, so this is the same as
+/// `clear_acl`.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(not(exacl_core))]
+pub fn strip_acl<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let permissions = std::fs::metadata(path)
+        .map_err(|err| failx::path_err(path, &err))?
+        .permissions();
+
+    _clear_acl(path)?;
+
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    std::fs::set_permissions(path, permissions).map_err(|err| failx::path_err(path, &err))?;
+    #[cfg(target_os = "macos")]
+    drop(permissions);
+
+    Ok(())
+}
+
 /// Write ACL entries to text.
 ///
 /// Each ACL entry is printed on a separate line. The five fields are separated
@@ -299,25 +1028,90 @@ where
 ///
 ///   <allow> - one of "allow" or "deny"
 ///   <flags> - comma-separated list of flags
-///   <kind>  - one of "user", "group", "other", "mask", "unknown"
+///   <kind>  - one of "user", "group", "other", "mask", "unknown", or
+///             "uid"/"gid" for a numeric entry
 ///   <name>  - user/group name (or decimal id if not known)
 ///   <perms> - comma-separated list of permissions
 /// ```
 ///
 /// Each record, including the last, is terminated by a final newline.
 ///
+/// Within a name, the characters `\`, `:`, `#`, and any white space or
+/// control characters are escaped as a backslash followed by three octal
+/// digits for each UTF-8 byte, e.g. `\072` for `:`. Every entry written by
+/// `to_writer` can be read back by `from_reader`.
+///
 /// # Sample Output
 ///
 /// ```text
 /// allow::group:admin:read,write
 /// ```
 ///
+/// Entries are written in the order given. To produce a canonical ordering,
+/// sort the entries first.
+///
+/// The latest [`FormatVersion`] is used. To keep output stable across crate
+/// upgrades, use [`to_writer_with_version`].
+///
 /// # Errors
 ///
 /// Returns an [`io::Error`] on failure.
-pub fn to_writer<W: io::Write>(mut writer: W, entries: &[AclEntry]) -> io::Result<()> {
+pub fn to_writer<W: io::Write>(writer: W, entries: &[AclEntry]) -> io::Result<()> {
+    to_writer_with_version(writer, entries, FormatVersion::LATEST)
+}
+
+/// Write ACL entries to text using a specific format version.
+///
+/// See `to_writer` for the format. The output for a given `version` is the
+/// same across crate versions.
+///
+/// # Example
+///
+/// ```
+/// use exacl::{AclEntry, FormatVersion, Perm};
+///
+/// let entries = vec![AclEntry::allow_user("a:b", Perm::READ, None)];
+///
+/// let mut buf = Vec::new();
+/// exacl::to_writer_with_version(&mut buf, &entries, FormatVersion::V2).unwrap();
+/// assert_eq!(buf, b"allow::user:a\\072b:read\n");
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn to_writer_with_version<W: io::Write>(
+    mut writer: W,
+    entries: &[AclEntry],
+    version: FormatVersion,
+) -> io::Result<()> {
     for entry in entries {
-        writeln!(writer, "{entry}")?;
+        writeln!(writer, "{}", entry.versioned(version))?;
+    }
+
+    Ok(())
+}
+
+/// Write ACL entries to text with user and group names replaced by
+/// pseudonyms.
+///
+/// See [`Redactor`] for which names are replaced, and `to_writer` for the
+/// format.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn to_writer_redacted<W: io::Write>(
+    mut writer: W,
+    entries: &[AclEntry],
+    redactor: &Redactor,
+) -> io::Result<()> {
+    for entry in entries {
+        writeln!(
+            writer,
+            "{}",
+            redactor.redact(entry).versioned(FormatVersion::LATEST)
+        )?;
     }
 
     Ok(())
@@ -348,6 +1142,8 @@ pub fn to_writer<W: io::Write>(mut writer: W, entries: &[AclEntry]) -> io::Resul
 /// Supported abbreviations:  d = default, r = read, w = write, x = execute,
 /// u = user, g = group, o = other, m = mask
 ///
+/// Names may contain octal escape sequences, as written by `to_writer`.
+///
 /// # Sample Input
 ///
 /// ```text
@@ -361,24 +1157,92 @@ pub fn to_writer<W: io::Write>(mut writer: W, entries: &[AclEntry]) -> io::Resul
 ///
 /// Returns an [`io::Error`] on failure.
 pub fn from_reader<R: io::Read>(reader: R) -> io::Result<Vec<AclEntry>> {
-    let mut result = Vec::<AclEntry>::new();
-    let buf = io::BufReader::new(reader);
+    from_reader_detect(reader).map(|(entries, _)| entries)
+}
+
+/// Read ACL entries from text, one at a time.
+///
+/// This reads the format described in [`from_reader`] without holding all of
+/// the entries in memory, e.g. to restore a very large backup. Linux `getfacl`
+/// output is accepted too, but other dialects are not detected; use
+/// [`from_reader_detect`] for those.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// let input = "user::rw-\ngroup::r--\nother::---\n";
+/// for entry in exacl::entries_from_reader(input.as_bytes()) {
+///     println!("{}", entry?);
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// An item is an [`io::Error`] if reading fails, or with kind `InvalidInput`
+/// and the line number if a line can't be parsed.
+pub fn entries_from_reader<R: io::Read>(reader: R) -> impl Iterator<Item = io::Result<AclEntry>> {
+    io::BufReader::new(reader)
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            let src_line = trim_comment(&line).trim();
+            if src_line.is_empty() {
+                return None;
+            }
+            Some(src_line.parse::<AclEntry>().map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("line {}: {err}", i + 1),
+                )
+            }))
+        })
+}
 
-    for line_result in buf.lines() {
-        let line = line_result?;
+/// Read ACL entries from text in any supported dialect.
+///
+/// Besides the format described in [`from_reader`], this accepts the output
+/// of Linux `getfacl`, macOS `chmod +a` rules (including `ls -le` output) and
+/// `FreeBSD` `NFSv4` entries. The dialect is detected from the first line that
+/// isn't in the native format, and returned along with the entries.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{from_reader_detect, Dialect};
+///
+/// let input = "# file: foo\nuser::rw-\ngroup::r--\nother::---\n";
+/// let (entries, dialect) = from_reader_detect(input.as_bytes())?;
+/// assert_eq!(entries.len(), 3);
+/// assert_eq!(dialect, Dialect::Getfacl);
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn from_reader_detect<R: io::Read>(reader: R) -> io::Result<(Vec<AclEntry>, Dialect)> {
+    let lines = io::BufReader::new(reader)
+        .lines()
+        .collect::<io::Result<Vec<String>>>()?;
+    let dialect = dialect::detect(&lines);
 
-        let src_line = trim_comment(&line).trim();
+    let mut result = Vec::<AclEntry>::new();
+    for line in &lines {
+        let src_line = trim_comment(line).trim();
         if !src_line.is_empty() {
-            result.push(src_line.parse::<AclEntry>()?);
+            result.push(dialect::parse_line(src_line, dialect)?);
         }
     }
 
-    Ok(result)
+    Ok((result, dialect))
 }
 
 /// Return line with end of line comment removed.
 fn trim_comment(line: &str) -> &str {
-    line.find('#').map_or(line, |n| &line[0..n])
+    line.find(syntax::COMMENT).map_or(line, |n| &line[0..n])
 }
 
 /// Write ACL entries to text.
@@ -389,8 +1253,19 @@ fn trim_comment(line: &str) -> &str {
 ///
 /// Returns an [`io::Error`] on failure.
 pub fn to_string(entries: &[AclEntry]) -> io::Result<String> {
+    to_string_with_version(entries, FormatVersion::LATEST)
+}
+
+/// Write ACL entries to text using a specific format version.
+///
+/// See `to_writer_with_version`.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn to_string_with_version(entries: &[AclEntry], version: FormatVersion) -> io::Result<String> {
     let mut buf = Vec::<u8>::with_capacity(128);
-    to_writer(&mut buf, entries)?;
+    to_writer_with_version(&mut buf, entries, version)?;
     String::from_utf8(buf).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
 }
 
@@ -411,7 +1286,7 @@ pub fn from_str(s: &str) -> io::Result<Vec<AclEntry>> {
 /// to the owner/group/other permission bits given in `mode`.
 ///
 /// Extra bits outside the mask 0o777 are ignored.
-#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core))]
 #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
 #[must_use]
 pub fn from_mode(mode: u32) -> Vec<AclEntry> {
@@ -421,3 +1296,211 @@ pub fn from_mode(mode: u32) -> Vec<AclEntry> {
         AclEntry::allow_other(Perm::from_bits_truncate(mode & 7), None),
     ]
 }
+
+/// Return the mode permission bits implied by an ACL.
+///
+/// The owner bits come from the `user::` entry, the group bits from the
+/// `mask::` entry if present (otherwise from the `group::` entry), and the
+/// other bits from the `other::` entry. This is how the kernel reports the
+/// mode of a file with an ACL. Default entries are ignored, and a missing
+/// entry contributes no bits.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+#[must_use]
+pub fn as_permissions(entries: &[AclEntry]) -> std::fs::Permissions {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut user = 0;
+    let mut group = 0;
+    let mut mask = None;
+    let mut other = 0;
+
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) || !entry.name.is_empty() {
+            continue;
+        }
+        let bits = (entry.perms & (Perm::READ | Perm::WRITE | Perm::EXECUTE)).bits();
+        match entry.kind {
+            AclEntryKind::User => user = bits,
+            AclEntryKind::Group => group = bits,
+            AclEntryKind::Mask => mask = Some(bits),
+            AclEntryKind::Other => other = bits,
+            _ => (),
+        }
+    }
+
+    std::fs::Permissions::from_mode(user << 6 | mask.unwrap_or(group) << 3 | other)
+}
+
+/// Return the permission bits of the file mode equivalent to an ACL; the
+/// inverse of [`from_mode`].
+///
+/// The owner bits come from the `user::` entry, the group bits from the
+/// `mask::` entry if present (otherwise from the `group::` entry), and the
+/// other bits from the `other::` entry. On Linux, the mode is computed by
+/// libacl's `acl_equiv_mode`. Default entries are ignored.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclEntry, Perm};
+///
+/// let mut entries = exacl::from_mode(0o640);
+/// assert_eq!(exacl::to_mode(&entries)?, 0o640);
+///
+/// entries.push(AclEntry::allow_user("11501", Perm::WRITE, None));
+/// entries.push(AclEntry::allow_mask(Perm::READ | Perm::WRITE, None));
+/// assert_eq!(exacl::to_mode(&entries)?, 0o660);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the entries are not a valid Posix.1e ACL.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn to_mode(entries: &[AclEntry]) -> io::Result<u32> {
+    let acl = Acl::from_entries(
+        entries
+            .iter()
+            .filter(|entry| !entry.flags.contains(Flag::DEFAULT)),
+    )?;
+    if acl.is_empty() {
+        fail_custom("Invalid ACL: missing required entries")?;
+    }
+    if !acl.is_posix() {
+        fail_custom("ACL has no equivalent mode: not a Posix.1e ACL")?;
+    }
+
+    acl.equiv_mode()
+}
+
+/// Return the permissions of the mask entry that will be added to an ACL, or
+/// None if no mask is needed.
+///
+/// [`setfacl`] adds a mask entry when the ACL has named user or group entries
+/// but no mask. The mask grants the union of the permissions of the named
+/// entries and the `group::` entry. Editors and linters can use this to show
+/// the mask before anything is written. Default entries are ignored; to check
+/// a default ACL, pass its entries without the `DEFAULT` flag.
+///
+/// ```
+/// use exacl::{needs_mask, AclEntry, Perm};
+///
+/// let mut entries = exacl::from_mode(0o640);
+/// assert_eq!(needs_mask(&entries), None);
+///
+/// entries.push(AclEntry::allow_user("bob", Perm::WRITE, None));
+/// assert_eq!(needs_mask(&entries), Some(Perm::READ | Perm::WRITE));
+/// ```
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd", exacl_core))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+#[must_use]
+pub fn needs_mask(entries: &[AclEntry]) -> Option<Perm> {
+    let mut perms = Perm::empty();
+    let mut has_named = false;
+
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) {
+            continue;
+        }
+        match entry.kind {
+            AclEntryKind::Mask => return None,
+            AclEntryKind::User | AclEntryKind::Group if !entry.name.is_empty() => {
+                perms |= entry.perms;
+                has_named = true;
+            }
+            AclEntryKind::Group => perms |= entry.perms,
+            _ => (),
+        }
+    }
+
+    has_named.then_some(perms)
+}
+
+/// Return a copy of ACL entries updated to match the given mode permission
+/// bits.
+///
+/// This is the ACL equivalent of `chmod`. The `user::` and `other::` entries
+/// take the owner and other bits. The group bits are applied to the `mask::`
+/// entry if present, otherwise to the `group::` entry. Named entries and
+/// default entries are left unchanged. Missing `user::`, `group::` or
+/// `other::` entries are added with permissions from `perms`.
+///
+/// On Linux, [`std::fs::set_permissions`] updates the mask entry of a file
+/// with an extended ACL; it does not change the `group::` entry. Use this
+/// function to predict the result, or to update a list of entries before
+/// passing it to [`setfacl`] without clobbering the mask.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+#[must_use]
+pub fn merge_with_permissions(entries: &[AclEntry], perms: &std::fs::Permissions) -> Vec<AclEntry> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = perms.mode();
+    let has_mask = entries
+        .iter()
+        .any(|entry| entry.kind == AclEntryKind::Mask && !entry.flags.contains(Flag::DEFAULT));
+
+    let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+    let mut result = entries.to_vec();
+    let mut missing = from_mode(mode);
+
+    for entry in &mut result {
+        if entry.flags.contains(Flag::DEFAULT) || !entry.name.is_empty() {
+            continue;
+        }
+        missing.retain(|req| req.kind != entry.kind);
+
+        let bits = match entry.kind {
+            AclEntryKind::User => mode >> 6,
+            AclEntryKind::Group if has_mask => continue,
+            AclEntryKind::Group | AclEntryKind::Mask => mode >> 3,
+            AclEntryKind::Other => mode,
+            _ => continue,
+        };
+        entry.perms = (entry.perms - rwx) | Perm::from_bits_truncate(bits & 7);
+    }
+
+    result.append(&mut missing);
+    result
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, not(exacl_core)))]
+mod lib_tests {
+    use super::*;
+
+    #[test]
+    fn test_read_consistent() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let options = AclOption::CONSISTENT_READ;
+
+        // The ACL changes during the first read, so it is read again.
+        let mut reads = 0;
+        let entries = read_consistent(file.path(), options, |path, options| {
+            assert!(!options.contains(AclOption::CONSISTENT_READ));
+            reads += 1;
+            if reads == 1 {
+                setfacl(&[path], from_mode(0o640), None)?;
+            }
+            _getfacl(path, options)
+        })?;
+        assert_eq!(reads, 2);
+        assert_eq!(entries, getfacl(&file, None)?);
+
+        // The ACL changes during every read.
+        let mut mode = 0o640;
+        let err = read_consistent(file.path(), options, |path, options| {
+            mode ^= 0o040;
+            setfacl(&[path], from_mode(mode), None)?;
+            _getfacl(path, options)
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("ACL changed while reading it 5 times"));
+        Ok(())
+    }
+}
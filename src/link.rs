@@ -0,0 +1,134 @@
+//! Implements `getfacl_both`, which reads the ACL's of a symlink and its
+//! target together.
+
+use crate::aclentry::AclEntry;
+use crate::failx::path_err;
+use crate::getfacl;
+#[cfg(target_os = "macos")]
+use crate::{Acl, AclOption};
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// ACL's of a symlink and its target, returned by [`getfacl_both`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LinkAcls {
+    /// ACL of the symlink itself, or None if the path is not a symlink. Only
+    /// macOS has symlink ACL's; this is always None on other platforms.
+    pub link: Option<Vec<AclEntry>>,
+
+    /// ACL of the file or directory the path leads to, or None if the path is
+    /// a broken symlink.
+    pub target: Option<Vec<AclEntry>>,
+}
+
+/// Get the ACL's of a symlink and of its target in one call.
+///
+/// If `path` is not a symlink, only `target` is set, to the same entries as
+/// [`getfacl`](crate::getfacl) returns. On macOS, the target's ACL is read
+/// through an open file where possible. The symlink is checked before and after
+/// reading, so both ACL's belong to the same link even if the path is replaced
+/// concurrently.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let acls = exacl::getfacl_both("./tmp/link")?;
+/// if let Some(link) = &acls.link {
+///     println!("link has {} entries", link.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure, with kind `Interrupted` if the symlink
+/// was replaced while it was read. The call can then be retried.
+pub fn getfacl_both<P: AsRef<Path>>(path: P) -> io::Result<LinkAcls> {
+    let path = path.as_ref();
+    let before = fs::symlink_metadata(path).map_err(|err| path_err(path, &err))?;
+    if !before.file_type().is_symlink() {
+        return Ok(LinkAcls {
+            link: None,
+            target: Some(getfacl(path, None)?),
+        });
+    }
+
+    #[cfg(target_os = "macos")]
+    let link = Some(getfacl(path, AclOption::SYMLINK_ACL)?);
+    #[cfg(not(target_os = "macos"))]
+    let link = None;
+
+    let target = match read_target(path) {
+        Ok(entries) => Some(entries),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err),
+    };
+
+    // A symlink's contents can't change in place, so the same inode means the
+    // same target.
+    let after = fs::symlink_metadata(path).map_err(|err| path_err(path, &err))?;
+    if (before.dev(), before.ino()) != (after.dev(), after.ino()) {
+        let err = io::Error::new(io::ErrorKind::Interrupted, "symlink replaced while reading");
+        return Err(path_err(path, &err));
+    }
+
+    Ok(LinkAcls { link, target })
+}
+
+/// Read the ACL of the file a symlink leads to. Fall back to the path if the
+/// target can't be opened for reading.
+#[cfg(target_os = "macos")]
+fn read_target(path: &Path) -> io::Result<Vec<AclEntry>> {
+    match fs::File::open(path) {
+        Ok(file) => Acl::read_fd(&file, AclOption::empty())?.entries(),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => getfacl(path, None),
+        Err(err) => Err(path_err(path, &err)),
+    }
+}
+
+/// Read the ACL of the file a symlink leads to.
+#[cfg(not(target_os = "macos"))]
+fn read_target(path: &Path) -> io::Result<Vec<AclEntry>> {
+    getfacl(path, None)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod link_tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_getfacl_both() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let file = dir.path().join("file");
+        let link = dir.path().join("link");
+        let broken = dir.path().join("broken");
+        fs::write(&file, "")?;
+        symlink(&file, &link)?;
+        symlink(dir.path().join("missing"), &broken)?;
+
+        let acls = getfacl_both(&file)?;
+        assert_eq!(acls.link, None);
+        assert_eq!(acls.target, Some(getfacl(&file, None)?));
+
+        let acls = getfacl_both(&link)?;
+        assert_eq!(acls.target, Some(getfacl(&file, None)?));
+        #[cfg(target_os = "macos")]
+        assert_eq!(acls.link, Some(getfacl(&link, AclOption::SYMLINK_ACL)?));
+        #[cfg(not(target_os = "macos"))]
+        assert_eq!(acls.link, None);
+
+        let acls = getfacl_both(&broken)?;
+        assert_eq!(acls.target, None);
+
+        let err = getfacl_both(dir.path().join("missing")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        Ok(())
+    }
+}
@@ -0,0 +1,481 @@
+//! Implements `lint` for suspicious ACL entries.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+use std::fmt;
+
+/// Represents a suspicious pattern found in a list of ACL entries.
+///
+/// Each variant records the index of the offending entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Lint {
+    /// Entry grants write access to everyone ("other" or "everyone").
+    WorldWritable(usize),
+
+    /// Group entry grants write access, and the ACL has named user or group
+    /// entries but no mask entry to limit them (Linux and `FreeBSD` only). A
+    /// minimal ACL needs no mask.
+    GroupWriteWithoutMask(usize),
+
+    /// Deny entry follows an allow entry. The kernel evaluates entries in
+    /// order, so the deny may never take effect.
+    DenyAfterAllow {
+        /// Index of the deny entry.
+        index: usize,
+        /// Index of the earlier allow entry.
+        allow_index: usize,
+    },
+
+    /// Entry is `ONLY_INHERIT` but is neither `FILE_INHERIT` nor
+    /// `DIRECTORY_INHERIT`, so it never applies to anything (macOS and
+    /// `FreeBSD` only).
+    InheritOnlyNeverApplies(usize),
+
+    /// Allow entry grants no permissions.
+    EmptyAllow(usize),
+
+    /// Entry never takes effect because earlier entries for the same
+    /// principal already decide all of its permissions (macOS and `FreeBSD`
+    /// `NFSv4` only).
+    Shadowed {
+        /// Index of the shadowed entry.
+        index: usize,
+        /// Indices of the earlier entries that shadow it.
+        shadowed_by: Vec<usize>,
+    },
+
+    /// Named entry has the same kind, name and allow/deny as an earlier
+    /// entry, but different flags and permissions, e.g. an access and a
+    /// default entry for one user that grant different access. This is often
+    /// a duplicated line that was only partly edited.
+    ConflictingDuplicate {
+        /// Index of the later entry.
+        index: usize,
+        /// Index of the earlier entry.
+        duplicate_of: usize,
+    },
+}
+
+impl Lint {
+    /// Return index of the offending entry.
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        match self {
+            Lint::WorldWritable(index)
+            | Lint::GroupWriteWithoutMask(index)
+            | Lint::DenyAfterAllow { index, .. }
+            | Lint::InheritOnlyNeverApplies(index)
+            | Lint::EmptyAllow(index)
+            | Lint::Shadowed { index, .. }
+            | Lint::ConflictingDuplicate { index, .. } => *index,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::WorldWritable(i) => write!(f, "entry {i}: world writable"),
+            Lint::GroupWriteWithoutMask(i) => write!(f, "entry {i}: group write without mask"),
+            Lint::DenyAfterAllow { index, allow_index } => {
+                write!(f, "entry {index}: deny follows allow entry {allow_index}")
+            }
+            Lint::InheritOnlyNeverApplies(i) => {
+                write!(f, "entry {i}: inherit-only entry never applies")
+            }
+            Lint::EmptyAllow(i) => write!(f, "entry {i}: allow entry has no permissions"),
+            Lint::Shadowed { index, shadowed_by } => {
+                write!(f, "entry {index}: shadowed by entry")?;
+                for (n, i) in shadowed_by.iter().enumerate() {
+                    let sep = if n == 0 { " " } else { ", " };
+                    write!(f, "{sep}{i}")?;
+                }
+                Ok(())
+            }
+            Lint::ConflictingDuplicate {
+                index,
+                duplicate_of,
+            } => write!(
+                f,
+                "entry {index}: conflicts with entry {duplicate_of} for the same principal"
+            ),
+        }
+    }
+}
+
+/// Check a list of ACL entries for suspicious patterns.
+///
+/// Returns a (possibly empty) vector of [`Lint`] in entry order. The checks
+/// are advisory; none of these patterns are rejected by `setfacl`.
+#[must_use]
+pub fn lint(entries: &[AclEntry]) -> Vec<Lint> {
+    let mut result = Vec::new();
+
+    // Index of first allow entry, for explicit and inherited entries.
+    let mut first_allow = [None; 2];
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.is_everyone() && entry.allow && entry.perms.contains(Perm::WRITE) {
+            result.push(Lint::WorldWritable(i));
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+        if entry.kind == AclEntryKind::Group
+            && entry.allow
+            && entry.perms.contains(Perm::WRITE)
+            && has_named(entries, entry.flags & Flag::DEFAULT)
+            && !has_mask(entries, entry.flags & Flag::DEFAULT)
+        {
+            result.push(Lint::GroupWriteWithoutMask(i));
+        }
+
+        #[cfg(exacl_extended)]
+        if entry.flags.contains(Flag::ONLY_INHERIT)
+            && !entry
+                .flags
+                .intersects(Flag::FILE_INHERIT | Flag::DIRECTORY_INHERIT)
+        {
+            result.push(Lint::InheritOnlyNeverApplies(i));
+        }
+
+        if entry.allow && entry.perms.is_empty() && !is_required(entry) {
+            result.push(Lint::EmptyAllow(i));
+        }
+
+        if let Some(duplicate_of) = find_conflicting(&entries[..i], entry) {
+            result.push(Lint::ConflictingDuplicate {
+                index: i,
+                duplicate_of,
+            });
+        }
+
+        if !applies_to_object(entry) {
+            continue;
+        }
+
+        let class = usize::from(entry.is_inherited());
+        if entry.allow {
+            first_allow[class].get_or_insert(i);
+        } else if let Some(allow_index) = first_allow[class] {
+            result.push(Lint::DenyAfterAllow {
+                index: i,
+                allow_index,
+            });
+        }
+    }
+
+    // Entries are evaluated in order on macOS and for NFSv4 ACL's.
+    #[cfg(exacl_extended)]
+    if !is_posix(entries) {
+        result.extend(find_shadowed(entries));
+        result.sort_by_key(Lint::index);
+    }
+
+    result
+}
+
+/// Return entries that can never take effect.
+///
+/// Entries are evaluated in order. Each permission bit is decided by the first
+/// entry that matches the principal and mentions the bit, whether it allows or
+/// denies. An entry is shadowed when every one of its permission bits has
+/// already been decided by earlier entries for the same principal (or for
+/// everyone). Group membership is not known, so a group entry does not shadow
+/// a user entry.
+#[cfg_attr(not(exacl_extended), allow(dead_code))]
+fn find_shadowed(entries: &[AclEntry]) -> Vec<Lint> {
+    let mut result = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.perms.is_empty() || !applies_to_object(entry) {
+            continue;
+        }
+
+        let mut remaining = entry.perms;
+        let mut shadowed_by = Vec::new();
+
+        for (j, prev) in entries[..i].iter().enumerate() {
+            if !applies_to_object(prev) || !prev.is_same_principal(entry) && !prev.is_everyone() {
+                continue;
+            }
+            if prev.perms.intersects(remaining) {
+                remaining -= prev.perms;
+                shadowed_by.push(j);
+                if remaining.is_empty() {
+                    result.push(Lint::Shadowed {
+                        index: i,
+                        shadowed_by,
+                    });
+                    break;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Return the index of the first earlier entry for the same named principal
+/// that differs from `entry` in both flags and permissions. Inherited entries
+/// are expected to differ from explicit ones, so they are not compared.
+fn find_conflicting(prev: &[AclEntry], entry: &AclEntry) -> Option<usize> {
+    if entry.name.is_empty() || entry.is_inherited() {
+        return None;
+    }
+
+    prev.iter().position(|prev| {
+        prev.is_same_principal(entry)
+            && prev.allow == entry.allow
+            && prev.flags != entry.flags
+            && prev.perms != entry.perms
+            && !prev.is_inherited()
+    })
+}
+
+/// Return true if the entries are a Posix.1e ACL, which has no order.
+///
+/// `FreeBSD` NFSv4 ACL's have unnamed user and group entries (owner@ and
+/// group@) too, so only the mask and other entries tell them apart.
+#[cfg_attr(all(exacl_core, not(exacl_extended)), allow(dead_code))]
+pub(crate) fn is_posix(entries: &[AclEntry]) -> bool {
+    entries
+        .iter()
+        .any(|entry| matches!(entry.kind, AclEntryKind::Mask | AclEntryKind::Other))
+}
+
+/// Return true if entry is one of the required Posix.1e entries.
+#[allow(clippy::missing_const_for_fn)]
+fn is_required(entry: &AclEntry) -> bool {
+    match entry.kind {
+        #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+        AclEntryKind::Mask | AclEntryKind::Other => true,
+        #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+        AclEntryKind::User | AclEntryKind::Group => entry.name.is_empty(),
+        _ => false,
+    }
+}
+
+/// Return true if there is a named user or group entry in the access or
+/// default ACL.
+#[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+fn has_named(entries: &[AclEntry], default: Flag) -> bool {
+    entries.iter().any(|entry| {
+        matches!(entry.kind, AclEntryKind::User | AclEntryKind::Group)
+            && !entry.name.is_empty()
+            && (entry.flags & Flag::DEFAULT) == default
+    })
+}
+
+/// Return true if there is a mask entry in the access or default ACL.
+#[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+fn has_mask(entries: &[AclEntry], default: Flag) -> bool {
+    entries
+        .iter()
+        .any(|entry| entry.kind == AclEntryKind::Mask && (entry.flags & Flag::DEFAULT) == default)
+}
+
+/// Return true if entry is considered when checking access to the object
+/// itself.
+#[allow(clippy::missing_const_for_fn)]
+fn applies_to_object(entry: &AclEntry) -> bool {
+    #[cfg(exacl_extended)]
+    return !entry.flags.contains(Flag::ONLY_INHERIT);
+
+    #[cfg(not(exacl_extended))]
+    return !entry.flags.contains(Flag::DEFAULT);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod lint_tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_empty() {
+        assert!(lint(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_lint_display() {
+        let lints = [
+            Lint::WorldWritable(1),
+            Lint::GroupWriteWithoutMask(2),
+            Lint::DenyAfterAllow {
+                index: 3,
+                allow_index: 0,
+            },
+            Lint::InheritOnlyNeverApplies(4),
+            Lint::EmptyAllow(5),
+            Lint::ConflictingDuplicate {
+                index: 6,
+                duplicate_of: 2,
+            },
+        ];
+        let msgs = lints.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            msgs,
+            vec![
+                "entry 1: world writable",
+                "entry 2: group write without mask",
+                "entry 3: deny follows allow entry 0",
+                "entry 4: inherit-only entry never applies",
+                "entry 5: allow entry has no permissions",
+                "entry 6: conflicts with entry 2 for the same principal",
+            ]
+        );
+        assert_eq!(lints[2].index(), 3);
+    }
+
+    fn deny_user(name: &str, perms: Perm) -> AclEntry {
+        AclEntry {
+            allow: false,
+            ..AclEntry::allow_user(name, perms, None)
+        }
+    }
+
+    #[test]
+    fn test_find_shadowed() {
+        let rw = Perm::READ | Perm::WRITE;
+        let entries = vec![
+            AclEntry::allow_user("500", Perm::READ, None),
+            deny_user("501", rw),
+            deny_user("500", Perm::WRITE),
+            deny_user("500", rw),
+            AclEntry::allow_user("500", rw | Perm::EXECUTE, None),
+            AclEntry::allow_group("500", rw, None),
+            AclEntry::allow_user("501", Perm::READ, None),
+        ];
+        assert_eq!(
+            find_shadowed(&entries),
+            vec![
+                Lint::Shadowed {
+                    index: 3,
+                    shadowed_by: vec![0, 2]
+                },
+                Lint::Shadowed {
+                    index: 6,
+                    shadowed_by: vec![1]
+                },
+            ]
+        );
+        assert_eq!(
+            find_shadowed(&entries)[0].to_string(),
+            "entry 3: shadowed by entry 0, 2"
+        );
+    }
+
+    #[test]
+    #[cfg(exacl_nfs4)]
+    fn test_lint_nfs4_shadowed() {
+        // owner@ and group@ have no name, like the required Posix.1e entries.
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            deny_user("500", Perm::READ),
+            AclEntry::allow_user("500", Perm::READ, None),
+        ];
+        assert!(lint(&entries).contains(&Lint::Shadowed {
+            index: 3,
+            shadowed_by: vec![2]
+        }));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+    fn test_lint_posix() {
+        let rw = Perm::READ | Perm::WRITE;
+        let mut entries = vec![
+            AclEntry::allow_user("", rw, None),
+            AclEntry::allow_group("", rw, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ];
+
+        // A minimal ACL needs no mask.
+        assert!(lint(&entries).is_empty());
+
+        entries.push(AclEntry::allow_user("500", Perm::empty(), None));
+        assert_eq!(
+            lint(&entries),
+            vec![Lint::GroupWriteWithoutMask(1), Lint::EmptyAllow(3)]
+        );
+
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        entries.push(AclEntry::allow_other(Perm::WRITE, Flag::DEFAULT));
+        assert_eq!(
+            lint(&entries),
+            vec![Lint::EmptyAllow(3), Lint::WorldWritable(5)]
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_os = "macos", exacl_extended))]
+    fn test_lint_macos() {
+        let entries = vec![
+            AclEntry::allow_user("500", Perm::READ, None),
+            AclEntry::deny_user("500", Perm::READ, None),
+            AclEntry::allow_group("everyone", Perm::WRITE, Flag::INHERITED),
+            AclEntry::deny_group("staff", Perm::WRITE, Flag::INHERITED),
+            AclEntry::allow_user("501", Perm::READ, Flag::ONLY_INHERIT),
+            AclEntry::allow_user("502", Perm::empty(), None),
+        ];
+        assert_eq!(
+            lint(&entries),
+            vec![
+                Lint::DenyAfterAllow {
+                    index: 1,
+                    allow_index: 0
+                },
+                Lint::Shadowed {
+                    index: 1,
+                    shadowed_by: vec![0]
+                },
+                Lint::WorldWritable(2),
+                Lint::DenyAfterAllow {
+                    index: 3,
+                    allow_index: 2
+                },
+                Lint::Shadowed {
+                    index: 3,
+                    shadowed_by: vec![2]
+                },
+                Lint::InheritOnlyNeverApplies(4),
+                Lint::EmptyAllow(5),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    ))]
+    fn test_lint_conflicting_duplicate() {
+        let rw = Perm::READ | Perm::WRITE;
+        let entries = vec![
+            AclEntry::allow_user("", rw, None),
+            AclEntry::allow_user("500", Perm::READ, None),
+            AclEntry::allow_user("501", rw, None),
+            AclEntry::allow_user("", Perm::READ, Flag::DEFAULT),
+            AclEntry::allow_user("500", rw, Flag::DEFAULT),
+            AclEntry::allow_user("501", rw, Flag::DEFAULT),
+            AclEntry::allow_group("500", rw, Flag::DEFAULT),
+        ];
+        let lints = lint(&entries)
+            .into_iter()
+            .filter(|lint| matches!(lint, Lint::ConflictingDuplicate { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lints,
+            vec![Lint::ConflictingDuplicate {
+                index: 4,
+                duplicate_of: 1
+            }]
+        );
+    }
+}
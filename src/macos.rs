@@ -0,0 +1,158 @@
+//! Converts between uid/gid and the GUID's used in macOS ACL's (macOS only).
+//!
+//! macOS identifies the user or group in each ACL entry by GUID. These
+//! functions map a uid or gid to its GUID and back, using the membership
+//! API's in `<membership.h>`.
+//!
+//! This module also provides [`SipError`], returned when System Integrity
+//! Protection prevents an ACL change.
+//!
+//! ```ignore
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::macos::{guid_to_id, uid_to_guid};
+//!
+//! let guid = uid_to_guid(501)?;
+//! assert_eq!(guid_to_id(guid)?, (Some(501), None));
+//! # Ok(()) }
+//! ```
+
+#[cfg(target_os = "macos")]
+use crate::sys::{csr_check, CSR_ALLOW_UNRESTRICTED_FS, SF_RESTRICTED};
+use crate::unix;
+use std::fmt;
+use std::io;
+#[cfg(target_os = "macos")]
+use std::os::macos::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+pub use uuid::Uuid;
+
+/// Convert uid to GUID.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn uid_to_guid(uid: u32) -> io::Result<Uuid> {
+    unix::uid_to_guid(uid)
+}
+
+/// Convert gid to GUID.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn gid_to_guid(gid: u32) -> io::Result<Uuid> {
+    unix::gid_to_guid(gid)
+}
+
+/// Convert GUID to uid/gid.
+///
+/// Returns a pair of options `(Option<uid>, Option<gid>)`. At most one option is
+/// set. If neither is set, the GUID was not found.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn guid_to_id(guid: Uuid) -> io::Result<(Option<u32>, Option<u32>)> {
+    unix::guid_to_id(guid)
+}
+
+/// Error returned when System Integrity Protection (SIP) prevents changing
+/// the ACL of a file, even as root.
+///
+/// The error is wrapped in an [`io::Error`] with kind `PermissionDenied`. Use
+/// [`is_sip_error`] to detect it.
+#[derive(Debug)]
+pub struct SipError {
+    path: PathBuf,
+}
+
+impl SipError {
+    /// Return the path of the protected file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Display for SipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "File {:?}: System Integrity Protection prevents changing the ACL",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for SipError {}
+
+/// Return true if `err` is a [`SipError`].
+#[must_use]
+pub fn is_sip_error(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|inner| inner.is::<SipError>())
+}
+
+/// Return a [`SipError`] if SIP protects `path`; otherwise, return `err`.
+///
+/// Only called when writing an ACL fails with a permission error.
+#[cfg(target_os = "macos")]
+pub(crate) fn check_sip(path: &Path, symlink_acl: bool, err: io::Error) -> io::Error {
+    // SIP may be disabled, e.g. in recovery mode.
+    if unsafe { csr_check(CSR_ALLOW_UNRESTRICTED_FS) } == 0 {
+        return err;
+    }
+
+    let metadata = if symlink_acl {
+        path.symlink_metadata()
+    } else {
+        path.metadata()
+    };
+
+    match metadata {
+        Ok(metadata) if metadata.st_flags() & SF_RESTRICTED != 0 => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            SipError {
+                path: path.to_path_buf(),
+            },
+        ),
+        _ => err,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod macos_tests {
+    use super::*;
+
+    #[test]
+    fn test_guid_roundtrip() {
+        let guid = uid_to_guid(1500).unwrap();
+        assert_eq!(guid_to_id(guid).unwrap(), (Some(1500), None));
+
+        let guid = gid_to_guid(20).unwrap();
+        assert_eq!(guid_to_id(guid).unwrap(), (None, Some(20)));
+    }
+
+    #[test]
+    fn test_check_sip() {
+        let eperm = || io::Error::from_raw_os_error(1);
+
+        // /System/Library is restricted, unless SIP is disabled.
+        let err = check_sip(Path::new("/System/Library"), false, eperm());
+        if unsafe { csr_check(CSR_ALLOW_UNRESTRICTED_FS) } != 0 {
+            assert!(is_sip_error(&err));
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            assert_eq!(
+                err.to_string(),
+                "File \"/System/Library\": System Integrity Protection prevents changing the ACL"
+            );
+        }
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let err = check_sip(file.path(), false, eperm());
+        assert!(!is_sip_error(&err));
+        assert_eq!(err.raw_os_error(), Some(1));
+    }
+}
@@ -0,0 +1,345 @@
+//! Implements `add_entry` and `remove_entry`, which change one entry of an
+//! ACL like `chmod +a` and `chmod -a`.
+
+use crate::aclentry::AclEntry;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::editor::Editor;
+use crate::failx::fail_custom;
+#[cfg(exacl_extended)]
+use crate::flag::Flag;
+use crate::lint::is_posix;
+use crate::perm::Perm;
+use crate::{getfacl, setfacl};
+
+use std::io;
+use std::path::Path;
+
+/// Number of times an edit is retried when the ACL changes under it.
+const EDIT_ATTEMPTS: usize = 5;
+
+/// Add an entry to the ACL of a file or directory, like `chmod +a`.
+///
+/// With a `position`, the entry is inserted at that index, like
+/// `chmod +a#`. Without one, the permissions are added to an entry for the
+/// same principal with the same type and flags, if there is one. Otherwise the
+/// entry is inserted in canonical order: explicit deny entries, explicit allow
+/// entries, inherited deny entries, then inherited allow entries.
+///
+/// A Posix.1e ACL has no order, so the entry is set like `setfacl -m` and
+/// the mask is recalculated. A `position` is an error.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{add_entry, AclEntry, Perm};
+///
+/// add_entry("./tmp/foo", &AclEntry::allow_user("bob", Perm::READ, None), None)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the ACL can't be read or written, if the
+/// `position` is out of range, or if the ACL kept changing while it was
+/// edited.
+pub fn add_entry<P, I>(path: P, entry: &AclEntry, position: I) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    I: Into<Option<usize>>,
+{
+    let position = position.into();
+    edit(path.as_ref(), |entries| {
+        insert_entry(entries, entry, position)?;
+        Ok(true)
+    })?;
+    Ok(())
+}
+
+/// Remove permissions from the ACL of a file or directory, like `chmod -a`.
+///
+/// `spec` names the entries to change: entries with the same kind, name,
+/// type and flags (the `INHERITED` flag is ignored). The permissions of
+/// `spec` are removed from each; an entry left without permissions, or any
+/// matching entry if `spec` has no permissions, is removed. Names are
+/// compared as [`getfacl`] returns them.
+///
+/// Return true if the ACL changed. The ACL isn't written if nothing matched.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the ACL can't be read or written, or if the
+/// ACL kept changing while it was edited.
+pub fn remove_entry<P: AsRef<Path>>(path: P, spec: &AclEntry) -> io::Result<bool> {
+    edit(path.as_ref(), |entries| Ok(remove_matching(entries, spec)))
+}
+
+/// Read the entries of `path`, change them with `func` and write them back.
+///
+/// If `func` returns false, nothing is written. Before writing, the entries
+/// are read again; if another writer changed them, the edit starts over.
+fn edit<F>(path: &Path, mut func: F) -> io::Result<bool>
+where
+    F: FnMut(&mut Vec<AclEntry>) -> io::Result<bool>,
+{
+    for _ in 0..EDIT_ATTEMPTS {
+        let current = getfacl(path, None)?;
+        let mut entries = current.clone();
+        if !func(&mut entries)? {
+            return Ok(false);
+        }
+
+        if getfacl(path, None)? == current {
+            setfacl(&[path], &entries, None)?;
+            return Ok(true);
+        }
+    }
+
+    fail_custom(&format!(
+        "{}: ACL changed while editing it {EDIT_ATTEMPTS} times",
+        path.display()
+    ))
+}
+
+/// Return true if `entry` is for the same principal with the same type and
+/// flags as `spec`. The `INHERITED` flag is ignored.
+fn is_match(entry: &AclEntry, spec: &AclEntry) -> bool {
+    #[cfg(exacl_extended)]
+    let same_flags = (entry.flags - Flag::INHERITED) == (spec.flags - Flag::INHERITED);
+    #[cfg(not(exacl_extended))]
+    let same_flags = entry.flags == spec.flags;
+
+    entry.is_same_principal(spec) && entry.allow == spec.allow && same_flags
+}
+
+/// Return the index where `entry` goes in canonical order: after the last
+/// entry of the same group or an earlier one.
+fn canonical_index(entries: &[AclEntry], entry: &AclEntry) -> usize {
+    let rank = |entry: &AclEntry| (entry.is_inherited(), entry.allow);
+    entries
+        .iter()
+        .position(|e| rank(e) > rank(entry))
+        .unwrap_or(entries.len())
+}
+
+fn insert_entry(
+    entries: &mut Vec<AclEntry>,
+    entry: &AclEntry,
+    position: Option<usize>,
+) -> io::Result<()> {
+    if is_posix(entries) {
+        if position.is_some() {
+            return fail_custom("Posix.1e ACL's have no order; position is not supported");
+        }
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        {
+            let mut editor = Editor::new(std::mem::take(entries));
+            editor.set(entry.clone());
+            *entries = editor.finish();
+        }
+        return Ok(());
+    }
+
+    match position {
+        Some(index) if index > entries.len() => fail_custom(&format!(
+            "position {index} is out of range; the ACL has {} entries",
+            entries.len()
+        )),
+        Some(index) => {
+            entries.insert(index, entry.clone());
+            Ok(())
+        }
+        None => {
+            match entries.iter_mut().find(|e| is_match(e, entry)) {
+                Some(existing) => existing.perms |= entry.perms,
+                None => entries.insert(canonical_index(entries, entry), entry.clone()),
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Remove the permissions of `spec` from matching entries. Return true if
+/// anything changed.
+fn remove_matching(entries: &mut Vec<AclEntry>, spec: &AclEntry) -> bool {
+    let remaining = |entry: &AclEntry| {
+        if spec.perms.is_empty() {
+            Perm::empty()
+        } else {
+            entry.perms - spec.perms
+        }
+    };
+
+    let changes = entries
+        .iter()
+        .filter(|entry| is_match(entry, spec))
+        .filter_map(|entry| {
+            let perms = remaining(entry);
+            (perms != entry.perms || perms.is_empty()).then(|| AclEntry {
+                perms,
+                ..entry.clone()
+            })
+        })
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return false;
+    }
+
+    // Use an editor so the mask of a Posix.1e ACL is recalculated.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    if is_posix(entries) {
+        let mut editor = Editor::new(std::mem::take(entries));
+        for change in changes {
+            if change.perms.is_empty() {
+                editor.remove(&change);
+            } else {
+                editor.set(change);
+            }
+        }
+        *entries = editor.finish();
+        return true;
+    }
+
+    entries.retain_mut(|entry| {
+        if !is_match(entry, spec) {
+            return true;
+        }
+        entry.perms = remaining(entry);
+        !entry.perms.is_empty()
+    });
+    true
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod modify_tests {
+    use super::*;
+    use crate::aclentry::AclEntryKind;
+
+    fn deny_user(name: &str, perms: Perm) -> AclEntry {
+        AclEntry {
+            allow: false,
+            ..AclEntry::allow_user(name, perms, None)
+        }
+    }
+
+    #[test]
+    fn test_insert_entry_ordered() -> io::Result<()> {
+        let mut entries = vec![
+            deny_user("11501", Perm::WRITE),
+            AclEntry::allow_user("11501", Perm::READ, None),
+        ];
+
+        // Deny entries go after the other deny entries.
+        insert_entry(&mut entries, &deny_user("11502", Perm::READ), None)?;
+        assert_eq!(entries[1], deny_user("11502", Perm::READ));
+
+        // Allow entries go at the end, or are merged.
+        insert_entry(
+            &mut entries,
+            &AclEntry::allow_group("11502", Perm::READ, None),
+            None,
+        )?;
+        assert_eq!(entries[3], AclEntry::allow_group("11502", Perm::READ, None));
+        insert_entry(
+            &mut entries,
+            &AclEntry::allow_user("11501", Perm::EXECUTE, None),
+            None,
+        )?;
+        assert_eq!(entries[2].perms, Perm::READ | Perm::EXECUTE);
+        assert_eq!(entries.len(), 4);
+
+        // An explicit position is kept.
+        insert_entry(&mut entries, &deny_user("11503", Perm::READ), Some(4))?;
+        assert_eq!(entries[4], deny_user("11503", Perm::READ));
+
+        let err = insert_entry(&mut entries, &deny_user("11503", Perm::READ), Some(9));
+        assert!(err.unwrap_err().to_string().contains("out of range"));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(exacl_extended)]
+    fn test_insert_entry_inherited() -> io::Result<()> {
+        let inherited = AclEntry::allow_user("11501", Perm::READ, Flag::INHERITED);
+        let mut entries = vec![inherited.clone()];
+
+        insert_entry(
+            &mut entries,
+            &AclEntry::allow_user("11502", Perm::READ, None),
+            None,
+        )?;
+        insert_entry(&mut entries, &deny_user("11503", Perm::READ), None)?;
+        assert_eq!(entries[0].name, "11503");
+        assert_eq!(entries[1].name, "11502");
+        assert_eq!(entries[2], inherited);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_matching_ordered() {
+        let mut entries = vec![
+            deny_user("11501", Perm::WRITE),
+            AclEntry::allow_user("11501", Perm::READ | Perm::WRITE, None),
+        ];
+
+        // The type must match.
+        assert!(!remove_matching(
+            &mut entries,
+            &deny_user("11501", Perm::READ)
+        ));
+        assert!(remove_matching(
+            &mut entries,
+            &AclEntry::allow_user("11501", Perm::WRITE, None)
+        ));
+        assert_eq!(entries[1].perms, Perm::READ);
+
+        // No permissions removes the whole entry.
+        assert!(remove_matching(
+            &mut entries,
+            &deny_user("11501", Perm::empty())
+        ));
+        assert_eq!(
+            entries,
+            vec![AclEntry::allow_user("11501", Perm::READ, None)]
+        );
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_add_remove_entry_posix() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let rw = Perm::READ | Perm::WRITE;
+        let entry = AclEntry::allow_user("11501", rw, None);
+        add_entry(&file, &entry, None)?;
+
+        let entries = getfacl(&file, None)?;
+        let added = entries.iter().find(|e| e.name == "11501").unwrap();
+        assert_eq!(added.perms, rw);
+        assert!(entries
+            .iter()
+            .any(|e| e.kind == AclEntryKind::Mask && e.perms.contains(rw)));
+
+        let err = add_entry(&file, &entry, 0).unwrap_err();
+        assert!(err.to_string().contains("position"), "{err}");
+
+        assert!(remove_entry(
+            &file,
+            &AclEntry::allow_user("11501", Perm::WRITE, None)
+        )?);
+        let entries = getfacl(&file, None)?;
+        let changed = entries.iter().find(|e| e.name == "11501").unwrap();
+        assert_eq!(changed.perms, Perm::READ);
+
+        assert!(remove_entry(
+            &file,
+            &AclEntry::allow_user("11501", Perm::empty(), None)
+        )?);
+        assert!(!remove_entry(
+            &file,
+            &AclEntry::allow_user("11501", Perm::empty(), None)
+        )?);
+        assert!(!getfacl(&file, None)?.iter().any(|e| e.name == "11501"));
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+//! Implements `from_platform_text` and `to_platform_text`, which read and
+//! write the text format of the platform's own ACL tools.
+
+#[cfg(target_os = "linux")]
+use crate::acl::{Acl, TextOptions};
+use crate::aclentry::AclEntry;
+#[cfg(target_os = "macos")]
+use crate::aclentry::AclEntryKind;
+#[cfg(target_os = "macos")]
+use crate::dialect::{self, Dialect};
+use crate::failx::fail_custom;
+#[cfg(target_os = "macos")]
+use crate::flag::Flag;
+
+use std::io;
+
+/// Read ACL entries from the text format of the platform's ACL tools.
+///
+/// On Linux, this is the output of `getfacl`, e.g. `user::rwx` and
+/// `default:group:staff:r-x`. Headers such as `# owner:` and `#effective:`
+/// comments are ignored. The text is parsed by `acl_from_text`, so user and
+/// group names must be in the user/group database. Entries prefixed with
+/// `default:` have the `DEFAULT` flag.
+///
+/// On macOS, this is one `chmod +a` rule per line, e.g.
+/// `user:bob allow read,write,file_inherit`, as listed by `ls -le`.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # #[cfg(target_os = "linux")] {
+/// let text = "# owner: root\nuser::rw-\ngroup::r--\nother::---\n";
+/// let entries = exacl::from_platform_text(text)?;
+/// assert_eq!(entries.len(), 3);
+/// # }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry can't be parsed.
+#[cfg(target_os = "linux")]
+pub fn from_platform_text(text: &str) -> io::Result<Vec<AclEntry>> {
+    let mut access = String::new();
+    let mut default = String::new();
+
+    for line in text.lines() {
+        let line = crate::trim_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (text, line) = match line.strip_prefix("default:") {
+            Some(rest) => (&mut default, rest),
+            None => (&mut access, line),
+        };
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    let mut entries = parse_text(&access, false)?;
+    entries.append(&mut parse_text(&default, true)?);
+    Ok(entries)
+}
+
+/// Parse entries with `acl_from_text`. On failure, report the first line that
+/// doesn't parse by itself.
+#[cfg(target_os = "linux")]
+fn parse_text(text: &str, default_acl: bool) -> io::Result<Vec<AclEntry>> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    match Acl::from_text(text, default_acl) {
+        Ok(acl) => acl.entries(),
+        Err(err) => match text
+            .lines()
+            .find(|line| Acl::from_text(line, default_acl).is_err())
+        {
+            Some(line) => fail_custom(&format!("invalid ACL entry: {line:?}")),
+            None => Err(err),
+        },
+    }
+}
+
+/// Read ACL entries from the text format of the platform's ACL tools.
+///
+/// On Linux, this is the output of `getfacl`, e.g. `user::rwx` and
+/// `default:group:staff:r-x`. Headers such as `# owner:` and `#effective:`
+/// comments are ignored. The text is parsed by `acl_from_text`, so user and
+/// group names must be in the user/group database. Entries prefixed with
+/// `default:` have the `DEFAULT` flag.
+///
+/// On macOS, this is one `chmod +a` rule per line, e.g.
+/// `user:bob allow read,write,file_inherit`, as listed by `ls -le`.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry can't be parsed.
+#[cfg(target_os = "macos")]
+pub fn from_platform_text(text: &str) -> io::Result<Vec<AclEntry>> {
+    let mut entries = Vec::new();
+
+    for line in text.lines() {
+        let line = crate::trim_comment(line).trim();
+        if !line.is_empty() {
+            entries.push(dialect::parse_line(line, Dialect::Chmod)?);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Write ACL entries in the text format of the platform's ACL tools.
+///
+/// On Linux, this is the long text form of `acl_to_text`, as printed by
+/// `getfacl` without headers. Entries with the `DEFAULT` flag are written
+/// last with a `default:` prefix. The entries must form a valid ACL.
+///
+/// On macOS, this is one `chmod +a` rule per line.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # #[cfg(target_os = "linux")] {
+/// let entries = exacl::from_mode(0o640);
+/// let text = exacl::to_platform_text(&entries)?;
+/// assert_eq!(text, "user::rw-\ngroup::r--\nother::---\n");
+/// # }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the entries can't be written in this format.
+#[cfg(target_os = "linux")]
+pub fn to_platform_text(entries: &[AclEntry]) -> io::Result<String> {
+    let (access, default) = Acl::from_unified_entries(entries)?;
+    let mut text = String::new();
+
+    // `acl_to_any_text` doesn't end the last entry with a newline.
+    for (acl, prefix) in [(access, ""), (default, "default:")] {
+        if acl.is_empty() {
+            continue;
+        }
+        let options = TextOptions {
+            prefix: prefix.to_string(),
+            ..TextOptions::default()
+        };
+        text.push_str(&acl.to_any_text(&options)?);
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+
+    Ok(text)
+}
+
+/// Write ACL entries in the text format of the platform's ACL tools.
+///
+/// On Linux, this is the long text form of `acl_to_text`, as printed by
+/// `getfacl` without headers. Entries with the `DEFAULT` flag are written
+/// last with a `default:` prefix. The entries must form a valid ACL.
+///
+/// On macOS, this is one `chmod +a` rule per line.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the entries can't be written in this format.
+#[cfg(target_os = "macos")]
+pub fn to_platform_text(entries: &[AclEntry]) -> io::Result<String> {
+    use std::fmt::Write;
+
+    let mut text = String::new();
+    for entry in entries {
+        let kind = match entry.kind {
+            AclEntryKind::User => "user",
+            AclEntryKind::Group => "group",
+            kind => return fail_custom(&format!("unsupported kind in chmod rule: \"{kind}\"")),
+        };
+
+        // `inherited` is written before the type; other flags are listed with
+        // the permissions.
+        let inherited = if entry.flags.contains(Flag::INHERITED) {
+            "inherited "
+        } else {
+            ""
+        };
+        let allow = if entry.allow { "allow" } else { "deny" };
+        let flags = entry.flags - Flag::INHERITED;
+        let sep = if entry.perms.is_empty() || flags.is_empty() {
+            ""
+        } else {
+            ","
+        };
+
+        writeln!(
+            text,
+            "{kind}:{} {inherited}{allow} {}{sep}{flags}",
+            entry.name, entry.perms
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    }
+
+    Ok(text)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod nativetext_tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use crate::flag::Flag;
+    use crate::perm::Perm;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_from_platform_text() -> io::Result<()> {
+        let text = "\
+# file: foo
+# owner: root
+# group: root
+user::rwx
+user:root:r-x\t#effective:r--
+group::r-x
+mask::r--
+other::---
+default:user::rwx
+default:group::r-x
+default:other::---
+";
+        let entries = from_platform_text(text)?;
+        assert_eq!(entries.len(), 8);
+        assert!(entries.contains(&AclEntry::allow_user(
+            "root",
+            Perm::READ | Perm::EXECUTE,
+            None
+        )));
+        assert_eq!(
+            entries[5],
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE | Perm::EXECUTE, Flag::DEFAULT)
+        );
+
+        // Round trip, without the headers and comment.
+        let out = to_platform_text(&entries)?;
+        assert_eq!(from_platform_text(&out)?, entries);
+        assert!(out.ends_with("default:other::---\n"), "{out}");
+
+        let err = from_platform_text("user::rwx\nbogus::rwx\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid ACL entry: \"bogus::rwx\"");
+
+        assert!(from_platform_text("")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_to_platform_text_invalid() {
+        // No other entry.
+        let mut entries = crate::from_mode(0o640);
+        entries.pop();
+        assert!(to_platform_text(&entries).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_platform_text_macos() -> io::Result<()> {
+        let entries = vec![
+            AclEntry::allow_group("staff", Perm::READ | Perm::WRITE, Flag::FILE_INHERIT),
+            AclEntry::deny_user("11501", Perm::DELETE, Flag::INHERITED),
+            AclEntry::allow_group("everyone", Perm::empty(), Flag::ONLY_INHERIT),
+        ];
+
+        let text = to_platform_text(&entries)?;
+        assert_eq!(
+            text,
+            "group:staff allow read,write,file_inherit\n\
+             user:11501 inherited deny delete\n\
+             group:everyone allow only_inherit\n"
+        );
+        assert_eq!(from_platform_text(&text)?, entries);
+        Ok(())
+    }
+}
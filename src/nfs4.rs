@@ -0,0 +1,489 @@
+//! Reads and writes `NFSv4` ACL's on Linux NFS mounts (requires the `nfs4`
+//! feature).
+//!
+//! The Linux NFS client doesn't support the Posix.1e calls. It exposes the
+//! server's ACL as the `system.nfs4_acl` extended attribute instead, in the
+//! XDR encoding of the `fattr4_acl` attribute (RFC 7530, section 6.2.1): a
+//! count of entries, then each entry's type, flags, access mask and `who`
+//! string.
+
+use crate::acl::AclOption;
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::failx::{fail_custom, path_err};
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+
+use std::ffi::{c_void, CString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Name of the extended attribute that holds the ACL.
+const XATTR_NAME: &[u8] = b"system.nfs4_acl\0";
+
+// Entry types.
+const ACE4_ACCESS_ALLOWED_ACE_TYPE: u32 = 0;
+const ACE4_ACCESS_DENIED_ACE_TYPE: u32 = 1;
+
+// Entry flags.
+const ACE4_IDENTIFIER_GROUP: u32 = 0x40;
+
+const FLAGS: [(u32, Flag); 5] = [
+    (0x01, Flag::FILE_INHERIT),
+    (0x02, Flag::DIRECTORY_INHERIT),
+    (0x04, Flag::LIMIT_INHERIT),
+    (0x08, Flag::ONLY_INHERIT),
+    (0x80, Flag::INHERITED),
+];
+
+const PERMS: [(u32, Perm); 14] = [
+    (0x0000_0001, Perm::READ_DATA),
+    (0x0000_0002, Perm::WRITE_DATA),
+    (0x0000_0004, Perm::APPEND),
+    (0x0000_0008, Perm::READEXTATTR),
+    (0x0000_0010, Perm::WRITEEXTATTR),
+    (0x0000_0020, Perm::EXECUTE),
+    (0x0000_0040, Perm::DELETE_CHILD),
+    (0x0000_0080, Perm::READATTR),
+    (0x0000_0100, Perm::WRITEATTR),
+    (0x0001_0000, Perm::DELETE),
+    (0x0002_0000, Perm::READSECURITY),
+    (0x0004_0000, Perm::WRITESECURITY),
+    (0x0008_0000, Perm::CHOWN),
+    (0x0010_0000, Perm::SYNC),
+];
+
+// Special `who` strings.
+const OWNER_WHO: &str = "OWNER@";
+const GROUP_WHO: &str = "GROUP@";
+const EVERYONE_WHO: &str = "EVERYONE@";
+
+/// Return true if the path has an `NFSv4` ACL, i.e. it is on an NFS mount
+/// whose server supports ACL's.
+pub(crate) fn is_nfs4(path: &Path, options: AclOption) -> bool {
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    let symlink = options.contains(AclOption::SYMLINK_ACL);
+    get_xattr(&c_path, symlink, &mut []).is_ok()
+}
+
+/// Read the ACL of a path on an NFS mount. There is no default ACL; with
+/// `DEFAULT_ACL`, no entries are returned.
+pub(crate) fn read(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
+    if options.contains(AclOption::DEFAULT_ACL) {
+        return Ok(Vec::new());
+    }
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let symlink = options.contains(AclOption::SYMLINK_ACL);
+
+    let mut buf = Vec::new();
+    loop {
+        let size = get_xattr(&c_path, symlink, &mut []).map_err(|err| path_err(path, &err))?;
+        buf.resize(size, 0);
+        match get_xattr(&c_path, symlink, &mut buf) {
+            Ok(len) => {
+                buf.truncate(len);
+                break;
+            }
+            // The ACL grew since its size was read.
+            Err(err) if err.raw_os_error() == Some(libc::ERANGE) => continue,
+            Err(err) => return Err(path_err(path, &err)),
+        }
+    }
+
+    decode(&buf).map_err(|err| path_err(path, &err))
+}
+
+/// Write the ACL of a path on an NFS mount.
+pub(crate) fn write(path: &Path, entries: &[AclEntry], options: AclOption) -> io::Result<()> {
+    if options.contains(AclOption::DEFAULT_ACL) {
+        return fail_custom("NFSv4 ACL's don't have a default ACL");
+    }
+
+    let strict = options.contains(AclOption::STRICT_PRINCIPALS);
+    let buf = encode(entries, strict)?;
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    let ret = unsafe {
+        if options.contains(AclOption::SYMLINK_ACL) {
+            libc::lsetxattr(
+                c_path.as_ptr(),
+                XATTR_NAME.as_ptr().cast(),
+                buf.as_ptr().cast::<c_void>(),
+                buf.len(),
+                0,
+            )
+        } else {
+            libc::setxattr(
+                c_path.as_ptr(),
+                XATTR_NAME.as_ptr().cast(),
+                buf.as_ptr().cast::<c_void>(),
+                buf.len(),
+                0,
+            )
+        }
+    };
+    if ret != 0 {
+        return Err(path_err(path, &io::Error::last_os_error()));
+    }
+
+    Ok(())
+}
+
+/// Read the extended attribute into `buf`, or return its size if `buf` is
+/// empty.
+fn get_xattr(c_path: &CString, symlink: bool, buf: &mut [u8]) -> io::Result<usize> {
+    let ret = unsafe {
+        if symlink {
+            libc::lgetxattr(
+                c_path.as_ptr(),
+                XATTR_NAME.as_ptr().cast(),
+                buf.as_mut_ptr().cast::<c_void>(),
+                buf.len(),
+            )
+        } else {
+            libc::getxattr(
+                c_path.as_ptr(),
+                XATTR_NAME.as_ptr().cast(),
+                buf.as_mut_ptr().cast::<c_void>(),
+                buf.len(),
+            )
+        }
+    };
+
+    usize::try_from(ret).map_err(|_| io::Error::last_os_error())
+}
+
+/// Decode the XDR value of the extended attribute.
+fn decode(buf: &[u8]) -> io::Result<Vec<AclEntry>> {
+    let mut reader = Reader(buf);
+    let count = reader.read_u32()?;
+
+    // Each entry takes at least 16 bytes, so don't trust a larger count.
+    let mut entries = Vec::with_capacity(buf.len().min(count as usize) / 16);
+    for _ in 0..count {
+        let ace_type = reader.read_u32()?;
+        let ace_flags = reader.read_u32()?;
+        let mask = reader.read_u32()?;
+        let who = reader.read_string()?;
+        entries.push(decode_entry(ace_type, ace_flags, mask, who)?);
+    }
+
+    if !reader.0.is_empty() {
+        return fail_custom("NFSv4 ACL has trailing data");
+    }
+
+    Ok(entries)
+}
+
+fn decode_entry(ace_type: u32, ace_flags: u32, mask: u32, who: &str) -> io::Result<AclEntry> {
+    let allow = match ace_type {
+        ACE4_ACCESS_ALLOWED_ACE_TYPE => true,
+        ACE4_ACCESS_DENIED_ACE_TYPE => false,
+        _ => return fail_custom(&format!("unsupported NFSv4 entry type: {ace_type}")),
+    };
+
+    let is_group = ace_flags & ACE4_IDENTIFIER_GROUP != 0;
+    let flags = from_bits(ace_flags & !ACE4_IDENTIFIER_GROUP, &FLAGS, "flags")?;
+    let perms = from_bits(mask, &PERMS, "permissions")?;
+
+    let (kind, name) = match who {
+        OWNER_WHO => (AclEntryKind::User, String::new()),
+        GROUP_WHO => (AclEntryKind::Group, String::new()),
+        EVERYONE_WHO => (AclEntryKind::Everyone, String::new()),
+        _ => {
+            let kind = if is_group {
+                AclEntryKind::Group
+            } else {
+                AclEntryKind::User
+            };
+
+            // A name is `user@domain` when the client maps ids to names, and a
+            // decimal id when it doesn't.
+            let local = who.split_once('@').map_or(who, |(local, _)| local);
+            match local.parse::<u32>() {
+                Ok(id) => {
+                    let qualifier = if is_group {
+                        Qualifier::Group(id)
+                    } else {
+                        Qualifier::User(id)
+                    };
+                    (kind, qualifier.name()?)
+                }
+                Err(_) => (kind, local.to_string()),
+            }
+        }
+    };
+
+    Ok(AclEntry {
+        kind,
+        name,
+        perms,
+        flags,
+        allow,
+        numeric: false,
+    })
+}
+
+/// Encode the entries as the XDR value of the extended attribute. Named users
+/// and groups are written as decimal ids, which the NFS client accepts whether
+/// or not it maps ids to names.
+fn encode(entries: &[AclEntry], strict: bool) -> io::Result<Vec<u8>> {
+    let count = u32::try_from(entries.len()).or_else(|_| fail_custom("NFSv4 ACL is too long"))?;
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&count.to_be_bytes());
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) {
+            return fail_custom(&format!("NFSv4 ACL's don't have default entries: {entry}"));
+        }
+
+        let (who, group) = match (entry.kind, entry.qualifier(strict)) {
+            (AclEntryKind::Everyone, _) => (EVERYONE_WHO.to_string(), false),
+            (_, Ok(Qualifier::UserObj)) => (OWNER_WHO.to_string(), false),
+            (_, Ok(Qualifier::GroupObj)) => (GROUP_WHO.to_string(), true),
+            (_, Ok(Qualifier::User(uid))) => (uid.to_string(), false),
+            (_, Ok(Qualifier::Group(gid))) => (gid.to_string(), true),
+            (_, Err(err)) => return Err(err),
+            (kind, Ok(_)) => {
+                return fail_custom(&format!("unsupported kind in NFSv4 ACL: \"{kind}\""));
+            }
+        };
+
+        let ace_type = if entry.allow {
+            ACE4_ACCESS_ALLOWED_ACE_TYPE
+        } else {
+            ACE4_ACCESS_DENIED_ACE_TYPE
+        };
+
+        // `READ` and `WRITE` mean the same as `READ_DATA` and `WRITE_DATA`.
+        let mut perms = entry.perms;
+        if perms.contains(Perm::READ) {
+            perms = (perms - Perm::READ) | Perm::READ_DATA;
+        }
+        if perms.contains(Perm::WRITE) {
+            perms = (perms - Perm::WRITE) | Perm::WRITE_DATA;
+        }
+
+        let mut ace_flags = to_bits(entry.flags, &FLAGS, "flags")?;
+        if group {
+            ace_flags |= ACE4_IDENTIFIER_GROUP;
+        }
+
+        buf.extend_from_slice(&ace_type.to_be_bytes());
+        buf.extend_from_slice(&ace_flags.to_be_bytes());
+        buf.extend_from_slice(&to_bits(perms, &PERMS, "permissions")?.to_be_bytes());
+        write_string(&mut buf, &who);
+    }
+
+    Ok(buf)
+}
+
+/// Convert `NFSv4` bits to exacl flags or permissions.
+fn from_bits<T>(bits: u32, table: &[(u32, T)], what: &str) -> io::Result<T>
+where
+    T: bitflags::Flags + Copy,
+{
+    let known = table.iter().fold(0, |acc, (bit, _)| acc | bit);
+    if bits & !known != 0 {
+        return fail_custom(&format!("unsupported NFSv4 {what}: {:#x}", bits & !known));
+    }
+
+    Ok(table
+        .iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .fold(T::empty(), |acc, (_, value)| acc.union(*value)))
+}
+
+/// Convert exacl flags or permissions to `NFSv4` bits.
+fn to_bits<T>(value: T, table: &[(u32, T)], what: &str) -> io::Result<u32>
+where
+    T: bitflags::Flags + Copy + std::fmt::Display,
+{
+    let known = table
+        .iter()
+        .fold(T::empty(), |acc, (_, value)| acc.union(*value));
+    let unknown = value.difference(known);
+    if !unknown.is_empty() {
+        return fail_custom(&format!("unsupported {what} in NFSv4 ACL: {unknown}"));
+    }
+
+    Ok(table
+        .iter()
+        .filter(|(_, v)| value.contains(*v))
+        .fold(0, |acc, (bit, _)| acc | bit))
+}
+
+/// Append an XDR string: its length, then its bytes padded to a multiple of
+/// 4.
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    buf.extend_from_slice(&(len as u32).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+    buf.resize(buf.len() + (4 - len % 4) % 4, 0);
+}
+
+/// Reads XDR values from a buffer.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return fail_custom("NFSv4 ACL is truncated");
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn read_string(&mut self) -> io::Result<&'a str> {
+        let len = self.read_u32()? as usize;
+        let bytes = self.take(len)?;
+        self.take((4 - len % 4) % 4)?;
+        std::str::from_utf8(bytes).or_else(|_| fail_custom("NFSv4 name is not UTF-8"))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod nfs4_tests {
+    use super::*;
+
+    /// Return the XDR encoding of one entry.
+    fn ace(ace_type: u32, ace_flags: u32, mask: u32, who: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&ace_type.to_be_bytes());
+        buf.extend_from_slice(&ace_flags.to_be_bytes());
+        buf.extend_from_slice(&mask.to_be_bytes());
+        write_string(&mut buf, who);
+        buf
+    }
+
+    fn acl(aces: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = (aces.len() as u32).to_be_bytes().to_vec();
+        for ace in aces {
+            buf.extend_from_slice(ace);
+        }
+        buf
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut aces = vec![
+            ace(1, 0x40, 0x02, "11502"),
+            ace(0, 0x83, 0x0012_00a1, "11501@example.com"),
+            ace(0, 0, 0x0016_01a7, "OWNER@"),
+            ace(0, 0x40, 0x0012_0081, "GROUP@"),
+            ace(0, 0, 0x0012_0081, "EVERYONE@"),
+        ];
+        let entries = decode(&acl(&aces)).unwrap();
+
+        let rx = Perm::READ_DATA | Perm::EXECUTE;
+        let attrs = Perm::READATTR | Perm::READSECURITY | Perm::SYNC;
+        assert_eq!(
+            entries,
+            [
+                AclEntry::deny_group("11502", Perm::WRITE_DATA, None),
+                AclEntry::allow_user(
+                    "11501",
+                    rx | attrs,
+                    Flag::FILE_INHERIT | Flag::DIRECTORY_INHERIT | Flag::INHERITED
+                ),
+                AclEntry::allow_user(
+                    "",
+                    rx | attrs
+                        | Perm::WRITE_DATA
+                        | Perm::APPEND
+                        | Perm::WRITEATTR
+                        | Perm::WRITESECURITY,
+                    None
+                ),
+                AclEntry::allow_group("", Perm::READ_DATA | attrs, None),
+                AclEntry {
+                    kind: AclEntryKind::Everyone,
+                    ..AclEntry::allow_user("", Perm::READ_DATA | attrs, None)
+                },
+            ]
+        );
+
+        // Encoding gives back the same value, without the domain.
+        aces[1] = ace(0, 0x83, 0x0012_00a1, "11501");
+        assert_eq!(encode(&entries, false).unwrap(), acl(&aces));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let err = decode(&acl(&[ace(2, 0, 0x01, "OWNER@")])).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported NFSv4 entry type: 2");
+
+        let err = decode(&acl(&[ace(0, 0x10, 0x01, "OWNER@")])).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported NFSv4 flags: 0x10");
+
+        let err = decode(&acl(&[ace(0, 0, 0x0200, "OWNER@")])).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported NFSv4 permissions: 0x200");
+
+        let mut buf = acl(&[ace(0, 0, 0x01, "OWNER@")]);
+        buf.pop();
+        assert_eq!(
+            decode(&buf).unwrap_err().to_string(),
+            "NFSv4 ACL is truncated"
+        );
+        buf.extend_from_slice(&[0, 0, 0, 0, 0]);
+        assert_eq!(
+            decode(&buf).unwrap_err().to_string(),
+            "NFSv4 ACL has trailing data"
+        );
+
+        // A huge count doesn't allocate a huge vector.
+        assert!(decode(&u32::MAX.to_be_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let entries = [
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::deny_user("11501", Perm::DELETE, Flag::ONLY_INHERIT),
+            AclEntry::allow_group("", Perm::READ_DATA, None),
+        ];
+        assert_eq!(
+            encode(&entries, false).unwrap(),
+            acl(&[
+                ace(0, 0, 0x03, "OWNER@"),
+                ace(1, 0x08, 0x0001_0000, "11501"),
+                ace(0, 0x40, 0x01, "GROUP@"),
+            ])
+        );
+
+        let err = encode(
+            &[AclEntry::allow_user("", Perm::READ, Flag::DEFAULT)],
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "NFSv4 ACL's don't have default entries: allow:default:user::read"
+        );
+
+        let err = encode(&[AclEntry::allow_mask(Perm::READ, None)], false).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported kind in NFSv4 ACL: \"mask\"");
+    }
+
+    #[test]
+    fn test_is_nfs4() {
+        // Local file systems don't have the attribute.
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert!(!is_nfs4(file.path(), AclOption::empty()));
+        assert!(!is_nfs4(Path::new("/non-existent"), AclOption::empty()));
+    }
+}
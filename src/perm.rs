@@ -2,12 +2,17 @@
 
 use crate::bititer::{BitIter, BitIterable};
 use crate::format;
+use crate::format::syntax::LIST_SEPARATOR;
 use crate::sys::*;
 
 use bitflags::bitflags;
 #[cfg(feature = "serde")]
 use serde::{de, ser, Deserialize, Serialize};
 use std::fmt;
+#[cfg(not(exacl_core))]
+use std::io;
+#[cfg(not(exacl_core))]
+use std::path::Path;
 
 bitflags! {
     /// Represents file access permissions.
@@ -26,78 +31,78 @@ bitflags! {
         const EXECUTE = ACL_EXECUTE;
 
         /// DELETE permission for a file.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const DELETE = np::ACL_DELETE;
 
         /// APPEND_DATA permission for a file.
         /// Same as ADD_SUBDIRECTORY permission for a directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const APPEND = np::ACL_APPEND_DATA;
 
         /// DELETE_CHILD permission for a directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const DELETE_CHILD = np::ACL_DELETE_CHILD;
 
         /// READ_ATTRIBUTES permission for file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const READATTR = np::ACL_READ_ATTRIBUTES;
 
         /// WRITE_ATTRIBUTES permission for a file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const WRITEATTR = np::ACL_WRITE_ATTRIBUTES;
 
         /// READ_EXTATTRIBUTES permission for a file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const READEXTATTR = np::ACL_READ_EXTATTRIBUTES;
 
         /// WRITE_EXTATTRIBUTES permission for a file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const WRITEEXTATTR = np::ACL_WRITE_EXTATTRIBUTES;
 
         /// READ_SECURITY permission for a file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const READSECURITY = np::ACL_READ_SECURITY;
 
         /// WRITE_SECURITY permission for a file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const WRITESECURITY = np::ACL_WRITE_SECURITY;
 
         /// CHANGE_OWNER permission for a file or directory.
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const CHOWN = np::ACL_CHANGE_OWNER;
 
         /// SYNCHRONIZE permission (unsupported).
-        #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_extended))]
         #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
         const SYNC = np::ACL_SYNCHRONIZE;
 
         /// NFSv4 READ_DATA permission.
-        #[cfg(any(docsrs, target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_nfs4))]
         #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
         const READ_DATA = np::ACL_READ_DATA;
 
         /// NFSv4 WRITE_DATA permission.
-        #[cfg(any(docsrs, target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_nfs4))]
         #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
         const WRITE_DATA = np::ACL_WRITE_DATA;
 
         /// Posix specific permissions.
-        #[cfg(any(docsrs, target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_nfs4))]
         #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
         const POSIX_SPECIFIC = Self::READ.bits() | Self::WRITE.bits() | Self::EXECUTE.bits();
 
         /// All NFSv4 specific permissions.
-        #[cfg(any(docsrs, target_os = "freebsd"))]
+        #[cfg(any(docsrs, exacl_nfs4))]
         #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
         const NFS4_SPECIFIC = Self::READ_DATA.bits() | Self::WRITE_DATA.bits()
             | Self::DELETE.bits() | Self::APPEND.bits() | Self::DELETE_CHILD.bits()
@@ -107,7 +112,78 @@ bitflags! {
     }
 }
 
-#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+impl Perm {
+    /// Return the permissions needed to serve an HTTP request `method` on a
+    /// file, or `None` for an unknown method. Matching ignores case.
+    ///
+    /// | Method                 | Permissions                   |
+    /// |------------------------|-------------------------------|
+    /// | `GET`, `HEAD`          | `READ`                        |
+    /// | `PUT`, `POST`, `PATCH` | `WRITE \| APPEND`             |
+    /// | `DELETE`               | `DELETE \| DELETE_CHILD`      |
+    ///
+    /// Where `APPEND`, `DELETE` and `DELETE_CHILD` are not available (Linux,
+    /// or the `strict_posix` feature), writes map to `WRITE` alone and
+    /// `DELETE` maps to `WRITE`, which a Posix.1e ACL requires on the parent
+    /// directory to remove a file.
+    ///
+    /// ```
+    /// use exacl::Perm;
+    ///
+    /// assert_eq!(Perm::from_http_method("get"), Some(Perm::READ));
+    /// assert!(Perm::from_http_method("PUT").unwrap().contains(Perm::WRITE));
+    /// assert_eq!(Perm::from_http_method("CONNECT"), None);
+    /// ```
+    #[must_use]
+    pub fn from_http_method(method: &str) -> Option<Perm> {
+        #[cfg(exacl_extended)]
+        let (write, delete) = (
+            Perm::WRITE | Perm::APPEND,
+            Perm::DELETE | Perm::DELETE_CHILD,
+        );
+        #[cfg(not(exacl_extended))]
+        let (write, delete) = (Perm::WRITE, Perm::WRITE);
+
+        let method = method.to_ascii_uppercase();
+        match method.as_str() {
+            "GET" | "HEAD" => Some(Perm::READ),
+            "PUT" | "POST" | "PATCH" => Some(write),
+            "DELETE" => Some(delete),
+            _ => None,
+        }
+    }
+
+    /// Return the permissions that ACL entries can use on the file system
+    /// containing `path`.
+    ///
+    /// Unlike [`Perm::all`], this is decided at runtime. A Posix.1e ACL only
+    /// supports read, write and execute, even on `FreeBSD`. The result is empty
+    /// if the file system does not support ACL's.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure, e.g. if `path` does not exist.
+    #[cfg(not(exacl_core))]
+    pub fn all_supported<P: AsRef<Path>>(path: P) -> io::Result<Perm> {
+        let caps = crate::volume_capabilities(path)?;
+        if !caps.acl {
+            return Ok(Perm::empty());
+        }
+
+        #[cfg(exacl_nfs4)]
+        if caps.nfs4 {
+            return Ok(Perm::NFS4_SPECIFIC | Perm::EXECUTE);
+        }
+
+        #[cfg(target_os = "macos")]
+        return Ok(Perm::all());
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        Ok(Perm::READ | Perm::WRITE | Perm::EXECUTE)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
 type RevPermIter = std::iter::Rev<BitIter<Perm>>;
 
 // `perm_iter` returns a platform-specific iterator over the permission bits.
@@ -117,12 +193,12 @@ impl Perm {
         BitIter(self & Perm::all())
     }
 
-    #[cfg(target_os = "linux")]
+    #[cfg(not(any(target_os = "macos", exacl_nfs4)))]
     fn perm_iter(self) -> RevPermIter {
         BitIter(self & Perm::all()).rev()
     }
 
-    #[cfg(target_os = "freebsd")]
+    #[cfg(exacl_nfs4)]
     fn perm_iter(self) -> std::iter::Chain<RevPermIter, BitIter<Perm>> {
         BitIter(self & Perm::POSIX_SPECIFIC)
             .rev()
@@ -164,43 +240,43 @@ pub enum PermName {
 
     execute = Perm::EXECUTE.bits(),
 
-    #[cfg(target_os = "freebsd")]
+    #[cfg(exacl_nfs4)]
     read_data = Perm::READ_DATA.bits(),
 
-    #[cfg(target_os = "freebsd")]
+    #[cfg(exacl_nfs4)]
     write_data = Perm::WRITE_DATA.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     delete = Perm::DELETE.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     append = Perm::APPEND.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     delete_child = Perm::DELETE_CHILD.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     readattr = Perm::READATTR.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     writeattr = Perm::WRITEATTR.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     readextattr = Perm::READEXTATTR.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     writeextattr = Perm::WRITEEXTATTR.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     readsecurity = Perm::READSECURITY.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     writesecurity = Perm::WRITESECURITY.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     chown = Perm::CHOWN.bits(),
 
-    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    #[cfg(exacl_extended)]
     sync = Perm::SYNC.bits(),
 }
 
@@ -213,43 +289,43 @@ impl PermName {
 
             Perm::EXECUTE => Some(PermName::execute),
 
-            #[cfg(target_os = "freebsd")]
+            #[cfg(exacl_nfs4)]
             Perm::READ_DATA => Some(PermName::read_data),
 
-            #[cfg(target_os = "freebsd")]
+            #[cfg(exacl_nfs4)]
             Perm::WRITE_DATA => Some(PermName::write_data),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::DELETE => Some(PermName::delete),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::APPEND => Some(PermName::append),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::DELETE_CHILD => Some(PermName::delete_child),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::READATTR => Some(PermName::readattr),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::WRITEATTR => Some(PermName::writeattr),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::READEXTATTR => Some(PermName::readextattr),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::WRITEEXTATTR => Some(PermName::writeextattr),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::READSECURITY => Some(PermName::readsecurity),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::WRITESECURITY => Some(PermName::writesecurity),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::CHOWN => Some(PermName::chown),
 
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+            #[cfg(exacl_extended)]
             Perm::SYNC => Some(PermName::sync),
 
             _ => None,
@@ -269,13 +345,13 @@ impl fmt::Display for PermName {
 
 impl fmt::Display for Perm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut iter = self.perm_iter();
+        let mut iter = self.perm_iter().filter_map(PermName::from_perm);
 
-        if let Some(perm) = iter.next() {
-            write!(f, "{}", PermName::from_perm(perm).unwrap())?;
+        if let Some(name) = iter.next() {
+            write!(f, "{name}")?;
 
-            for perm in iter {
-                write!(f, ",{}", PermName::from_perm(perm).unwrap())?;
+            for name in iter {
+                write!(f, "{LIST_SEPARATOR}{name}")?;
             }
         }
 
@@ -316,7 +392,7 @@ impl std::str::FromStr for Perm {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result = Perm::empty();
 
-        for item in s.split(',') {
+        for item in s.split(LIST_SEPARATOR) {
             let word = item.trim();
             if !word.is_empty() {
                 if let Some(perms) = parse_perm_abbreviation(word) {
@@ -397,6 +473,25 @@ mod perm_tests {
         assert_eq!(acl_perm_t_ACL_APPEND_DATA, acl_perm_t_ACL_ADD_SUBDIRECTORY);
     }
 
+    #[test]
+    #[cfg(not(exacl_core))]
+    fn test_all_supported() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let perms = Perm::all_supported(&file).unwrap();
+
+        #[cfg(target_os = "macos")]
+        assert_eq!(perms, Perm::all());
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(perms, Perm::READ | Perm::WRITE | Perm::EXECUTE);
+
+        #[cfg(target_os = "freebsd")]
+        assert!(perms.contains(Perm::EXECUTE));
+
+        let err = Perm::all_supported("/non-existant").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
     #[test]
     fn test_perm_display() {
         assert_eq!(Perm::empty().to_string(), "");
@@ -407,13 +502,13 @@ mod perm_tests {
         let bad_perm = Perm::from_bits_retain(0x0080_0000) | Perm::READ;
         assert_eq!(bad_perm.to_string(), "read");
 
-        #[cfg(target_os = "macos")]
+        #[cfg(all(target_os = "macos", exacl_extended))]
         assert_eq!(Perm::all().to_string(), "read,write,execute,delete,append,delete_child,readattr,writeattr,readextattr,writeextattr,readsecurity,writesecurity,chown,sync");
 
-        #[cfg(target_os = "linux")]
+        #[cfg(not(exacl_extended))]
         assert_eq!(Perm::all().to_string(), "read,write,execute");
 
-        #[cfg(target_os = "freebsd")]
+        #[cfg(exacl_nfs4)]
         assert_eq!(Perm::all().to_string(), "read,write,execute,read_data,write_data,append,readextattr,writeextattr,delete_child,readattr,writeattr,delete,readsecurity,writesecurity,chown,sync");
     }
 
@@ -431,14 +526,14 @@ mod perm_tests {
         // Duplicate abbreviations not supported.
         assert!("rr".parse::<Perm>().is_err());
 
-        #[cfg(target_os = "macos")]
+        #[cfg(all(target_os = "macos", exacl_extended))]
         {
             assert_eq!("unknown variant `q`, expected one of `read`, `write`, `execute`, `delete`, `append`, `delete_child`, `readattr`, `writeattr`, `readextattr`, `writeextattr`, `readsecurity`, `writesecurity`, `chown`, `sync`", " ,q ".parse::<Perm>().unwrap_err().to_string());
 
             assert_eq!(Perm::all(), "read,write,execute,delete,append,delete_child,readattr,writeattr,readextattr,writeextattr,readsecurity,writesecurity,chown,sync".parse().unwrap());
         }
 
-        #[cfg(target_os = "linux")]
+        #[cfg(not(exacl_extended))]
         {
             assert_eq!(
                 "unknown variant `qq`, expected one of `read`, `write`, `execute`",
@@ -448,7 +543,7 @@ mod perm_tests {
             assert_eq!(Perm::all(), "read,write,execute".parse().unwrap());
         }
 
-        #[cfg(target_os = "freebsd")]
+        #[cfg(exacl_nfs4)]
         {
             assert_eq!(
                 "unknown variant `qq`, expected one of `read`, `write`, `execute`, `read_data`, `write_data`, `delete`, `append`, `delete_child`, `readattr`, `writeattr`, `readextattr`, `writeextattr`, `readsecurity`, `writesecurity`, `chown`, `sync`",
@@ -460,7 +555,7 @@ mod perm_tests {
     }
 
     #[test]
-    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
     fn test_perm_unix_permission() {
         // Test that READ, WRITE, EXECUTE constant correspond to the same bits
         // as the permissions in unix mode.
@@ -475,6 +570,33 @@ mod perm_tests {
         );
     }
 
+    #[test]
+    fn test_from_http_method() {
+        assert_eq!(Perm::from_http_method("GET"), Some(Perm::READ));
+        assert_eq!(Perm::from_http_method("head"), Some(Perm::READ));
+        assert_eq!(Perm::from_http_method("OPTIONS"), None);
+        assert_eq!(Perm::from_http_method(""), None);
+
+        let put = Perm::from_http_method("PUT");
+        assert_eq!(Perm::from_http_method("Post"), put);
+        assert_eq!(Perm::from_http_method("PATCH"), put);
+
+        #[cfg(exacl_extended)]
+        {
+            assert_eq!(put, Some(Perm::WRITE | Perm::APPEND));
+            assert_eq!(
+                Perm::from_http_method("DELETE"),
+                Some(Perm::DELETE | Perm::DELETE_CHILD)
+            );
+        }
+
+        #[cfg(not(exacl_extended))]
+        {
+            assert_eq!(put, Some(Perm::WRITE));
+            assert_eq!(Perm::from_http_method("DELETE"), Some(Perm::WRITE));
+        }
+    }
+
     #[test]
     fn test_perm_comparisons() {
         // Test that Perm has backward-compatible Ord/PartialOrd traits.
@@ -486,7 +608,7 @@ mod perm_tests {
         assert!(Perm::READ < Perm::WRITE);
         #[cfg(target_os = "linux")]
         assert!(Perm::READ > Perm::WRITE);
-        #[cfg(target_os = "freebsd")]
+        #[cfg(exacl_nfs4)]
         assert!(Perm::READ > Perm::WRITE);
     }
 }
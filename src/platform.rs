@@ -0,0 +1,244 @@
+//! Implements constants and macros that describe the ACL features of the
+//! compile target.
+//!
+//! The values follow the target OS and the `strict_posix` feature, so
+//! downstream crates can gate code on them without repeating exacl's `cfg`
+//! conditions.
+
+/// True if the crate has a native ACL API for the target, i.e. `getfacl`,
+/// `setfacl` and `Acl` are available. On other targets, only the portable
+/// types like [`AclEntry`] are built.
+///
+/// [`AclEntry`]: crate::AclEntry
+pub const HAS_NATIVE_ACL: bool = cfg!(not(exacl_core));
+
+/// True if entries can deny access, i.e. `AclEntry::deny_user` and
+/// `AclEntry::deny_group` are available (macOS, `FreeBSD`, and Linux with the
+/// `nfs4` feature).
+pub const HAS_DENY_ENTRIES: bool = cfg!(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "nfs4"
+));
+
+/// True if directories can have a default ACL, i.e. `Flag::DEFAULT` is
+/// available (Linux and `FreeBSD`).
+pub const HAS_DEFAULT_ACL: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "strict_posix"
+));
+
+/// True if entries have inheritance flags, such as `Flag::FILE_INHERIT`
+/// (macOS, `FreeBSD`, and Linux with the `nfs4` feature).
+pub const HAS_INHERIT_FLAGS: bool = cfg!(exacl_extended);
+
+/// True if ACL's have a mask entry, i.e. `AclEntry::allow_mask` is
+/// available (Linux and `FreeBSD`).
+pub const HAS_MASK_ENTRIES: bool =
+    cfg!(any(target_os = "linux", target_os = "freebsd", exacl_core));
+
+/// Expand the items only if [`HAS_NATIVE_ACL`] is true.
+///
+/// ```
+/// exacl::cfg_native_acl! {
+///     fn read_acl(path: &str) -> std::io::Result<Vec<exacl::AclEntry>> {
+///         exacl::getfacl(path, None)
+///     }
+/// }
+/// ```
+#[cfg(not(exacl_core))]
+#[macro_export]
+macro_rules! cfg_native_acl {
+    ($($item:item)*) => { $($item)* };
+}
+
+/// Expand the items only if [`HAS_NATIVE_ACL`] is true. It is false on this target.
+#[cfg(exacl_core)]
+#[macro_export]
+macro_rules! cfg_native_acl {
+    ($($item:item)*) => {};
+}
+
+/// Expand the items only if [`HAS_DENY_ENTRIES`] is true.
+///
+/// ```
+/// exacl::cfg_deny_entries! {
+///     fn deny_guest() -> exacl::AclEntry {
+///         exacl::AclEntry::deny_user("guest", exacl::Perm::WRITE, None)
+///     }
+/// }
+/// ```
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "nfs4"
+))]
+#[macro_export]
+macro_rules! cfg_deny_entries {
+    ($($item:item)*) => { $($item)* };
+}
+
+/// Expand the items only if [`HAS_DENY_ENTRIES`] is true. It is false on this target.
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "nfs4"
+)))]
+#[macro_export]
+macro_rules! cfg_deny_entries {
+    ($($item:item)*) => {};
+}
+
+/// Expand the items only if [`HAS_DEFAULT_ACL`] is true.
+///
+/// ```
+/// exacl::cfg_default_acl! {
+///     fn default_other() -> exacl::AclEntry {
+///         exacl::AclEntry::allow_other(exacl::Perm::READ, exacl::Flag::DEFAULT)
+///     }
+/// }
+/// ```
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "strict_posix"
+))]
+#[macro_export]
+macro_rules! cfg_default_acl {
+    ($($item:item)*) => { $($item)* };
+}
+
+/// Expand the items only if [`HAS_DEFAULT_ACL`] is true. It is false on this target.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    exacl_core,
+    feature = "strict_posix"
+)))]
+#[macro_export]
+macro_rules! cfg_default_acl {
+    ($($item:item)*) => {};
+}
+
+/// Expand the items only if [`HAS_INHERIT_FLAGS`] is true.
+///
+/// ```
+/// exacl::cfg_inherit_flags! {
+///     const INHERIT: exacl::Flag = exacl::Flag::FILE_INHERIT;
+/// }
+/// ```
+#[cfg(exacl_extended)]
+#[macro_export]
+macro_rules! cfg_inherit_flags {
+    ($($item:item)*) => { $($item)* };
+}
+
+/// Expand the items only if [`HAS_INHERIT_FLAGS`] is true. It is false on this target.
+#[cfg(not(exacl_extended))]
+#[macro_export]
+macro_rules! cfg_inherit_flags {
+    ($($item:item)*) => {};
+}
+
+/// Expand the items only if [`HAS_MASK_ENTRIES`] is true.
+///
+/// ```
+/// exacl::cfg_mask_entries! {
+///     fn mask() -> exacl::AclEntry {
+///         exacl::AclEntry::allow_mask(exacl::Perm::READ, None)
+///     }
+/// }
+/// ```
+#[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+#[macro_export]
+macro_rules! cfg_mask_entries {
+    ($($item:item)*) => { $($item)* };
+}
+
+/// Expand the items only if [`HAS_MASK_ENTRIES`] is true. It is false on this target.
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", exacl_core)))]
+#[macro_export]
+macro_rules! cfg_mask_entries {
+    ($($item:item)*) => {};
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod platform_tests {
+    use super::*;
+
+    #[test]
+    fn test_platform() {
+        let caps = (
+            HAS_NATIVE_ACL,
+            HAS_DENY_ENTRIES,
+            HAS_DEFAULT_ACL,
+            HAS_INHERIT_FLAGS,
+            HAS_MASK_ENTRIES,
+        );
+
+        #[cfg(all(target_os = "linux", not(exacl_extended)))]
+        assert_eq!(caps, (true, cfg!(feature = "nfs4"), true, false, true));
+
+        #[cfg(all(target_os = "linux", exacl_extended))]
+        assert_eq!(caps, (true, true, true, true, true));
+
+        #[cfg(all(target_os = "macos", not(feature = "strict_posix")))]
+        assert_eq!(caps, (true, true, false, true, false));
+
+        #[cfg(all(target_os = "macos", feature = "strict_posix"))]
+        assert_eq!(caps, (true, true, true, false, false));
+
+        #[cfg(all(target_os = "freebsd", not(feature = "strict_posix")))]
+        assert_eq!(caps, (true, true, true, true, true));
+
+        #[cfg(exacl_core)]
+        assert_eq!(caps, (false, true, true, cfg!(exacl_extended), true));
+    }
+
+    // Values used when a macro does not expand its items; some are unused on
+    // any given target.
+    #[allow(dead_code)]
+    const NATIVE_ACL: bool = false;
+    #[allow(dead_code)]
+    const DENY_ENTRIES: bool = false;
+    #[allow(dead_code)]
+    const DEFAULT_ACL: bool = false;
+    #[allow(dead_code)]
+    const INHERIT_FLAGS: bool = false;
+    #[allow(dead_code)]
+    const MASK_ENTRIES: bool = false;
+
+    #[test]
+    fn test_platform_macros() {
+        // Each macro shadows the value above only if it expands its items.
+        cfg_native_acl! {
+            const NATIVE_ACL: bool = true;
+        }
+        cfg_deny_entries! {
+            const DENY_ENTRIES: bool = true;
+        }
+        cfg_default_acl! {
+            const DEFAULT_ACL: bool = true;
+        }
+        cfg_inherit_flags! {
+            const INHERIT_FLAGS: bool = true;
+        }
+        cfg_mask_entries! {
+            const MASK_ENTRIES: bool = true;
+        }
+
+        assert_eq!(NATIVE_ACL, HAS_NATIVE_ACL);
+        assert_eq!(DENY_ENTRIES, HAS_DENY_ENTRIES);
+        assert_eq!(DEFAULT_ACL, HAS_DEFAULT_ACL);
+        assert_eq!(INHERIT_FLAGS, HAS_INHERIT_FLAGS);
+        assert_eq!(MASK_ENTRIES, HAS_MASK_ENTRIES);
+    }
+}
@@ -0,0 +1,437 @@
+//! Adapter with the API of the `posix-acl` crate (requires the
+//! `compat-posix-acl` feature).
+//!
+//! Projects that use `posix-acl` can switch to exacl by changing their imports
+//! from `posix_acl::` to `exacl::posix_acl::`. The types and functions mirror
+//! `posix-acl`, and are implemented with [`getfacl`](crate::getfacl) and
+//! [`setfacl`](crate::setfacl). Use [`PosixACL::to_entries`] to move on to
+//! [`AclEntry`] one call site at a time.
+//!
+//! Differences from `posix-acl`:
+//!
+//! - [`ACLError`] is an [`io::Error`].
+//! - [`PosixACL::as_text`] writes numeric uids and gids, rather than names.
+//!
+//! Posix.1e ACL's are only available on Linux and `FreeBSD`.
+
+use crate::acl::AclOption;
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::failx::fail_custom;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier as NativeQualifier;
+use crate::{getfacl, setfacl};
+
+use std::io;
+use std::path::Path;
+
+/// Read permission.
+pub const ACL_READ: u32 = 0x04;
+
+/// Write permission.
+pub const ACL_WRITE: u32 = 0x02;
+
+/// Execute permission.
+pub const ACL_EXECUTE: u32 = 0x01;
+
+/// Read, write and execute permissions.
+pub const ACL_RWX: u32 = ACL_READ | ACL_WRITE | ACL_EXECUTE;
+
+/// Error returned by [`PosixACL`].
+pub type ACLError = io::Error;
+
+/// The principal of an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Qualifier {
+    /// Entry that doesn't have a Posix.1e qualifier.
+    Undefined,
+
+    /// Owner of the file.
+    UserObj,
+
+    /// Owning group of the file.
+    GroupObj,
+
+    /// Everyone else.
+    Other,
+
+    /// Named user, by uid.
+    User(u32),
+
+    /// Named group, by gid.
+    Group(u32),
+
+    /// Maximum permissions of the group class.
+    Mask,
+}
+
+/// One entry of a [`PosixACL`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ACLEntry {
+    /// Principal of the entry.
+    pub qual: Qualifier,
+
+    /// Permissions, a combination of `ACL_READ`, `ACL_WRITE` and
+    /// `ACL_EXECUTE`.
+    pub perm: u32,
+}
+
+/// A Posix.1e access or default ACL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PosixACL {
+    entries: Vec<ACLEntry>,
+}
+
+impl PosixACL {
+    /// Construct the minimal ACL for a file mode, e.g. `0o640`.
+    #[must_use]
+    pub fn new(file_mode: u32) -> PosixACL {
+        PosixACL {
+            entries: vec![
+                ACLEntry {
+                    qual: Qualifier::UserObj,
+                    perm: (file_mode >> 6) & ACL_RWX,
+                },
+                ACLEntry {
+                    qual: Qualifier::GroupObj,
+                    perm: (file_mode >> 3) & ACL_RWX,
+                },
+                ACLEntry {
+                    qual: Qualifier::Other,
+                    perm: file_mode & ACL_RWX,
+                },
+            ],
+        }
+    }
+
+    /// Construct an ACL with no entries.
+    #[must_use]
+    pub const fn empty() -> PosixACL {
+        PosixACL {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Read the access ACL of a file or directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ACLError`] if the ACL can't be read.
+    pub fn read_acl<P: AsRef<Path>>(path: P) -> Result<PosixACL, ACLError> {
+        PosixACL::from_entries(&getfacl(path, AclOption::ACCESS_ACL)?)
+    }
+
+    /// Read the default ACL of a directory. A directory without a default ACL
+    /// has an empty one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ACLError`] if the ACL can't be read.
+    pub fn read_default_acl<P: AsRef<Path>>(path: P) -> Result<PosixACL, ACLError> {
+        PosixACL::from_entries(&getfacl(path, AclOption::DEFAULT_ACL)?)
+    }
+
+    /// Write the access ACL of a file or directory. The mask is recalculated
+    /// first with [`PosixACL::fix_mask`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ACLError`] if the ACL is invalid or can't be written.
+    pub fn write_acl<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ACLError> {
+        self.fix_mask();
+        self.validate()?;
+        setfacl(&[path], self.to_entries(), AclOption::ACCESS_ACL)
+    }
+
+    /// Write the default ACL of a directory. The mask is recalculated first
+    /// with [`PosixACL::fix_mask`]. An empty ACL removes the default ACL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ACLError`] if the ACL is invalid or can't be written.
+    pub fn write_default_acl<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ACLError> {
+        let mut entries = Vec::new();
+        if !self.entries.is_empty() {
+            self.fix_mask();
+            self.validate()?;
+            entries = self.to_entries();
+            for entry in &mut entries {
+                entry.flags = Flag::DEFAULT;
+            }
+        }
+        setfacl(&[path], entries, AclOption::DEFAULT_ACL)
+    }
+
+    /// Set the permissions of the entry for a principal, adding the entry if
+    /// needed. The mask is not changed.
+    pub fn set(&mut self, qual: Qualifier, perm: u32) {
+        match self.entries.iter_mut().find(|entry| entry.qual == qual) {
+            Some(entry) => entry.perm = perm,
+            None => self.entries.push(ACLEntry { qual, perm }),
+        }
+    }
+
+    /// Return the permissions of the entry for a principal.
+    #[must_use]
+    pub fn get(&self, qual: Qualifier) -> Option<u32> {
+        self.entries
+            .iter()
+            .find(|entry| entry.qual == qual)
+            .map(|entry| entry.perm)
+    }
+
+    /// Remove the entry for a principal. Return its permissions.
+    pub fn remove(&mut self, qual: Qualifier) -> Option<u32> {
+        let index = self.entries.iter().position(|entry| entry.qual == qual)?;
+        Some(self.entries.remove(index).perm)
+    }
+
+    /// Return the entries.
+    #[must_use]
+    pub fn entries(&self) -> Vec<ACLEntry> {
+        self.entries.clone()
+    }
+
+    /// Set the mask to the union of the permissions of the owning group and
+    /// the named users and groups. A mask is only added if there are named
+    /// entries.
+    pub fn fix_mask(&mut self) {
+        let has_named = self
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.qual, Qualifier::User(_) | Qualifier::Group(_)));
+        if !has_named && self.get(Qualifier::Mask).is_none() {
+            return;
+        }
+
+        let perm = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry.qual,
+                    Qualifier::GroupObj | Qualifier::User(_) | Qualifier::Group(_)
+                )
+            })
+            .fold(0, |acc, entry| acc | entry.perm);
+        self.set(Qualifier::Mask, perm);
+    }
+
+    /// Return the ACL in the long text form of `getfacl`, one entry per line,
+    /// e.g. `user::rw-`.
+    #[must_use]
+    pub fn as_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            let tag = match entry.qual {
+                Qualifier::Undefined => continue,
+                Qualifier::UserObj => "user:".to_string(),
+                Qualifier::GroupObj => "group:".to_string(),
+                Qualifier::Other => "other:".to_string(),
+                Qualifier::Mask => "mask:".to_string(),
+                Qualifier::User(uid) => format!("user:{uid}"),
+                Qualifier::Group(gid) => format!("group:{gid}"),
+            };
+            let perm = |bit, ch| if entry.perm & bit != 0 { ch } else { '-' };
+            text.push_str(&format!(
+                "{tag}:{}{}{}\n",
+                perm(ACL_READ, 'r'),
+                perm(ACL_WRITE, 'w'),
+                perm(ACL_EXECUTE, 'x')
+            ));
+        }
+
+        text
+    }
+
+    /// Check that the ACL has the owner, owning group and other entries, a
+    /// mask if it has named entries, and no duplicate or undefined entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ACLError`] describing the first problem found.
+    pub fn validate(&self) -> Result<(), ACLError> {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.qual == Qualifier::Undefined {
+                return fail_custom("Invalid ACL: undefined entry");
+            }
+            if self.entries[..i].iter().any(|e| e.qual == entry.qual) {
+                return fail_custom(&format!("Invalid ACL: duplicate entry {:?}", entry.qual));
+            }
+        }
+
+        for qual in [Qualifier::UserObj, Qualifier::GroupObj, Qualifier::Other] {
+            if self.get(qual).is_none() {
+                return fail_custom(&format!("Invalid ACL: missing entry {qual:?}"));
+            }
+        }
+
+        let has_named = self
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.qual, Qualifier::User(_) | Qualifier::Group(_)));
+        if has_named && self.get(Qualifier::Mask).is_none() {
+            return fail_custom("Invalid ACL: missing entry Mask");
+        }
+
+        Ok(())
+    }
+
+    /// Construct an ACL from exacl entries, e.g. the result of
+    /// [`getfacl`](crate::getfacl). Entries are converted as one ACL, so the
+    /// `DEFAULT` flag is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ACLError`] if a user or group name can't be resolved.
+    pub fn from_entries(entries: &[AclEntry]) -> Result<PosixACL, ACLError> {
+        let mut acl = PosixACL::empty();
+        for entry in entries {
+            let qual = match entry.qualifier(false)? {
+                NativeQualifier::UserObj => Qualifier::UserObj,
+                NativeQualifier::GroupObj => Qualifier::GroupObj,
+                NativeQualifier::Other => Qualifier::Other,
+                NativeQualifier::Mask => Qualifier::Mask,
+                NativeQualifier::User(uid) => Qualifier::User(uid),
+                NativeQualifier::Group(gid) => Qualifier::Group(gid),
+                _ => Qualifier::Undefined,
+            };
+            acl.entries.push(ACLEntry {
+                qual,
+                perm: perm_bits(entry.perms),
+            });
+        }
+
+        Ok(acl)
+    }
+
+    /// Return the ACL as exacl entries. Named users and groups are numeric
+    /// entries, and undefined entries are left out.
+    #[must_use]
+    pub fn to_entries(&self) -> Vec<AclEntry> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let perms = perms_from_bits(entry.perm);
+                let numeric = |kind, id: u32| AclEntry {
+                    kind,
+                    numeric: true,
+                    ..AclEntry::allow_user(&id.to_string(), perms, None)
+                };
+                let result = match entry.qual {
+                    Qualifier::Undefined => return None,
+                    Qualifier::UserObj => AclEntry::allow_user("", perms, None),
+                    Qualifier::GroupObj => AclEntry::allow_group("", perms, None),
+                    Qualifier::Other => AclEntry::allow_other(perms, None),
+                    Qualifier::Mask => AclEntry::allow_mask(perms, None),
+                    Qualifier::User(uid) => numeric(AclEntryKind::User, uid),
+                    Qualifier::Group(gid) => numeric(AclEntryKind::Group, gid),
+                };
+                Some(result)
+            })
+            .collect()
+    }
+}
+
+/// Convert permissions to `ACL_READ`, `ACL_WRITE` and `ACL_EXECUTE` bits.
+fn perm_bits(perms: Perm) -> u32 {
+    [
+        (Perm::READ, ACL_READ),
+        (Perm::WRITE, ACL_WRITE),
+        (Perm::EXECUTE, ACL_EXECUTE),
+    ]
+    .iter()
+    .filter(|(perm, _)| perms.contains(*perm))
+    .fold(0, |acc, (_, bit)| acc | bit)
+}
+
+/// Convert `ACL_READ`, `ACL_WRITE` and `ACL_EXECUTE` bits to permissions.
+fn perms_from_bits(bits: u32) -> Perm {
+    [
+        (Perm::READ, ACL_READ),
+        (Perm::WRITE, ACL_WRITE),
+        (Perm::EXECUTE, ACL_EXECUTE),
+    ]
+    .iter()
+    .filter(|(_, bit)| bits & bit != 0)
+    .fold(Perm::empty(), |acc, (perm, _)| acc | *perm)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod posix_acl_tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let acl = PosixACL::new(0o750);
+        assert_eq!(acl.get(Qualifier::UserObj), Some(ACL_RWX));
+        assert_eq!(acl.get(Qualifier::GroupObj), Some(ACL_READ | ACL_EXECUTE));
+        assert_eq!(acl.get(Qualifier::Other), Some(0));
+        assert_eq!(acl.as_text(), "user::rwx\ngroup::r-x\nother::---\n");
+        assert!(acl.validate().is_ok());
+        assert!(PosixACL::empty().entries().is_empty());
+    }
+
+    #[test]
+    fn test_set_remove() {
+        let mut acl = PosixACL::new(0o640);
+        acl.set(Qualifier::User(11501), ACL_READ | ACL_WRITE);
+        acl.set(Qualifier::User(11501), ACL_READ);
+        assert_eq!(acl.entries().len(), 4);
+        assert!(acl.validate().is_err());
+
+        acl.fix_mask();
+        assert_eq!(acl.get(Qualifier::Mask), Some(ACL_READ));
+        assert!(acl.validate().is_ok());
+        assert_eq!(
+            acl.as_text(),
+            "user::rw-\ngroup::r--\nother::---\nuser:11501:r--\nmask::r--\n"
+        );
+
+        assert_eq!(acl.remove(Qualifier::User(11501)), Some(ACL_READ));
+        assert_eq!(acl.remove(Qualifier::User(11501)), None);
+        acl.remove(Qualifier::Other);
+        assert!(acl.validate().is_err());
+    }
+
+    #[test]
+    fn test_entries_roundtrip() -> io::Result<()> {
+        let mut acl = PosixACL::new(0o754);
+        acl.set(Qualifier::Group(11502), ACL_WRITE);
+        acl.fix_mask();
+
+        let entries = acl.to_entries();
+        assert_eq!(entries[3].name, "11502");
+        assert!(entries[3].numeric);
+        assert_eq!(PosixACL::from_entries(&entries)?, acl);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_write() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path();
+
+        let mut acl = PosixACL::read_acl(path)?;
+        acl.set(Qualifier::User(11501), ACL_READ);
+        acl.write_acl(path)?;
+
+        let acl = PosixACL::read_acl(path)?;
+        assert_eq!(acl.get(Qualifier::User(11501)), Some(ACL_READ));
+        assert!(acl.get(Qualifier::Mask).is_some());
+
+        assert!(PosixACL::read_default_acl(path)?.entries().is_empty());
+        let mut default = PosixACL::new(0o750);
+        default.set(Qualifier::Group(11502), ACL_READ);
+        default.write_default_acl(path)?;
+        let read = PosixACL::read_default_acl(path)?;
+        assert_eq!(read.get(Qualifier::Group(11502)), Some(ACL_READ));
+        assert_eq!(read.get(Qualifier::Mask), Some(ACL_READ | ACL_EXECUTE));
+
+        PosixACL::empty().write_default_acl(path)?;
+        assert!(PosixACL::read_default_acl(path)?.entries().is_empty());
+        Ok(())
+    }
+}
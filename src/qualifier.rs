@@ -48,17 +48,21 @@ impl Qualifier {
             (Some(uid), None) => Qualifier::User(uid),
             (None, Some(gid)) => Qualifier::Group(gid),
             (None, None) => Qualifier::Guid(guid),
-            _ => unreachable!("guid_to_id bug"),
+            (Some(_), Some(_)) => return fail_custom("guid_to_id returned a uid and a gid"),
         };
 
         Ok(qualifier)
     }
 
     /// Create qualifier object from a user name.
+    ///
+    /// If `strict` is true, the name must be in the user database; it is not
+    /// parsed as a decimal uid or GUID.
     #[cfg(target_os = "macos")]
-    pub fn user_named(name: &str) -> io::Result<Qualifier> {
-        match unix::name_to_uid(name) {
+    pub fn user_named(name: &str, strict: bool) -> io::Result<Qualifier> {
+        match lookup_uid(name, strict) {
             Ok(uid) => Ok(Qualifier::User(uid)),
+            Err(err) if strict => Err(err),
             Err(err) => {
                 // Try to parse name as a GUID.
                 Uuid::parse_str(name).map_or(Err(err), Qualifier::from_guid)
@@ -67,11 +71,14 @@ impl Qualifier {
     }
 
     /// Create qualifier object from a user name.
+    ///
+    /// If `strict` is true, the name must be in the user database; it is not
+    /// parsed as a decimal uid.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    pub fn user_named(name: &str) -> io::Result<Qualifier> {
+    pub fn user_named(name: &str, strict: bool) -> io::Result<Qualifier> {
         match name {
             OWNER_NAME => Ok(Qualifier::UserObj),
-            s => match unix::name_to_uid(s) {
+            s => match lookup_uid(s, strict) {
                 Ok(uid) => Ok(Qualifier::User(uid)),
                 Err(err) => Err(err),
             },
@@ -79,20 +86,27 @@ impl Qualifier {
     }
 
     /// Create qualifier object from a group name.
+    ///
+    /// If `strict` is true, the name must be in the group database; it is not
+    /// parsed as a decimal gid or GUID.
     #[cfg(target_os = "macos")]
-    pub fn group_named(name: &str) -> io::Result<Qualifier> {
-        match unix::name_to_gid(name) {
+    pub fn group_named(name: &str, strict: bool) -> io::Result<Qualifier> {
+        match lookup_gid(name, strict) {
             Ok(gid) => Ok(Qualifier::Group(gid)),
+            Err(err) if strict => Err(err),
             Err(err) => Uuid::parse_str(name).map_or(Err(err), Qualifier::from_guid),
         }
     }
 
     /// Create qualifier object from a group name.
+    ///
+    /// If `strict` is true, the name must be in the group database; it is not
+    /// parsed as a decimal gid.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    pub fn group_named(name: &str) -> io::Result<Qualifier> {
+    pub fn group_named(name: &str, strict: bool) -> io::Result<Qualifier> {
         match name {
             OWNER_NAME => Ok(Qualifier::GroupObj),
-            s => match unix::name_to_gid(s) {
+            s => match lookup_gid(s, strict) {
                 Ok(gid) => Ok(Qualifier::Group(gid)),
                 Err(err) => Err(err),
             },
@@ -160,6 +174,22 @@ impl Qualifier {
     }
 }
 
+fn lookup_uid(name: &str, strict: bool) -> io::Result<unix::uid_t> {
+    if strict {
+        unix::name_to_uid_strict(name)
+    } else {
+        unix::name_to_uid(name)
+    }
+}
+
+fn lookup_gid(name: &str, strict: bool) -> io::Result<unix::gid_t> {
+    if strict {
+        unix::name_to_gid_strict(name)
+    } else {
+        unix::name_to_gid(name)
+    }
+}
+
 impl fmt::Display for Qualifier {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -227,44 +257,53 @@ mod qualifier_tests {
 
     #[test]
     fn test_user_named() {
-        let user = Qualifier::user_named("89").ok();
+        let user = Qualifier::user_named("89", false).ok();
         assert_eq!(user, Some(Qualifier::User(89)));
 
+        let err = Qualifier::user_named("1234567", true).unwrap_err();
+        assert_eq!(err.to_string(), "unknown user name: \"1234567\"");
+
         #[cfg(target_os = "macos")]
         {
-            let user = Qualifier::user_named("_spotlight").ok();
+            let user = Qualifier::user_named("_spotlight", false).ok();
             assert_eq!(user, Some(Qualifier::User(89)));
 
-            let user = Qualifier::user_named("ffffeeee-dddd-cccc-bbbb-aaaa00000059").ok();
+            let user = Qualifier::user_named("ffffeeee-dddd-cccc-bbbb-aaaa00000059", false).ok();
             assert_eq!(user, Some(Qualifier::User(89)));
+
+            let user = Qualifier::user_named("ffffeeee-dddd-cccc-bbbb-aaaa00000059", true);
+            assert!(user.is_err());
         }
 
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         {
             let (user_id, _) = getent("daemon");
-            let user = Qualifier::user_named("daemon").ok();
+            let user = Qualifier::user_named("daemon", false).ok();
             assert_eq!(user, Some(Qualifier::User(user_id)));
         }
     }
 
     #[test]
     fn test_group_named() {
-        let group = Qualifier::group_named("89").ok();
+        let group = Qualifier::group_named("89", false).ok();
         assert_eq!(group, Some(Qualifier::Group(89)));
 
+        let err = Qualifier::group_named("1234567", true).unwrap_err();
+        assert_eq!(err.to_string(), "unknown group name: \"1234567\"");
+
         #[cfg(target_os = "macos")]
         {
-            let group = Qualifier::group_named("_spotlight").ok();
+            let group = Qualifier::group_named("_spotlight", false).ok();
             assert_eq!(group, Some(Qualifier::Group(89)));
 
-            let group = Qualifier::group_named("abcdefab-cdef-abcd-efab-cdef00000059").ok();
+            let group = Qualifier::group_named("abcdefab-cdef-abcd-efab-cdef00000059", false).ok();
             assert_eq!(group, Some(Qualifier::Group(89)));
         }
 
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         {
             let (_, group_id) = getent("daemon");
-            let group = Qualifier::group_named("daemon").ok();
+            let group = Qualifier::group_named("daemon", false).ok();
             assert_eq!(group, Some(Qualifier::Group(group_id)));
         }
     }
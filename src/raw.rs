@@ -0,0 +1,132 @@
+//! Builds native ACL's with arbitrary tag values, for research and testing
+//! (requires the `raw` feature).
+//!
+//! [`setfacl`](crate::setfacl) and [`Acl::from_entries`] only produce ACL's
+//! that the platform accepts. A [`RawAclBuilder`] passes raw tag values, ids
+//! and permissions straight to the native ACL library instead, so a test can
+//! reproduce a corrupt ACL or a fuzzer can probe how the library and kernel
+//! handle unexpected input.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::raw::RawAclBuilder;
+//! use exacl::{AclOption, Perm};
+//!
+//! // A Linux ACL with a named user (tag 0x02) but no mask entry.
+//! let mut builder = unsafe { RawAclBuilder::new()? };
+//! builder.push(0x01, None, Perm::READ | Perm::WRITE)?;
+//! builder.push(0x02, Some(1000), Perm::READ)?;
+//! builder.push(0x04, None, Perm::READ)?;
+//! builder.push(0x20, None, Perm::empty())?;
+//!
+//! let acl = builder.finish();
+//! let result = acl.write("./tmp/foo".as_ref(), AclOption::empty());
+//! assert!(result.is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::acl::Acl;
+use crate::perm::Perm;
+
+use std::io;
+
+/// Builds a native ACL entry by entry, without checking the entries.
+///
+/// The resulting [`Acl`] is written with [`Acl::write`] or [`Acl::write_fd`],
+/// which hand it to the kernel as is.
+pub struct RawAclBuilder {
+    acl: Acl,
+}
+
+impl RawAclBuilder {
+    /// Start an empty ACL.
+    ///
+    /// # Safety
+    ///
+    /// The entries are passed to the native ACL library without any checks.
+    /// The library and the kernel are not designed for arbitrary tags and
+    /// qualifiers, and their handling of them is not guaranteed to be memory
+    /// safe. Only use a `RawAclBuilder` on systems set aside for testing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the native ACL can't be allocated.
+    pub unsafe fn new() -> io::Result<RawAclBuilder> {
+        Ok(RawAclBuilder { acl: Acl::empty()? })
+    }
+
+    /// Append an entry with a raw tag value, an optional qualifier and
+    /// permissions.
+    ///
+    /// The tag is the platform's `acl_tag_t` value, as a bit pattern. On Linux
+    /// and `FreeBSD`, `id` is the uid or gid of the entry; on macOS, it is a
+    /// uid, which is converted to a GUID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the native library rejects the tag, id or
+    /// permissions. The ACL keeps a partly filled entry in that case.
+    pub fn push(&mut self, tag: u32, id: Option<u32>, perms: Perm) -> io::Result<()> {
+        self.acl.add_raw_entry(tag, id, perms)
+    }
+
+    /// Return the ACL.
+    #[must_use]
+    pub fn finish(self) -> Acl {
+        self.acl
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod raw_tests {
+    use super::*;
+    use crate::acl::AclOption;
+
+    // Linux tag values.
+    #[cfg(target_os = "linux")]
+    const USER_OBJ: u32 = 0x01;
+    #[cfg(target_os = "linux")]
+    const USER: u32 = 0x02;
+    #[cfg(target_os = "linux")]
+    const GROUP_OBJ: u32 = 0x04;
+    #[cfg(target_os = "linux")]
+    const OTHER: u32 = 0x20;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_raw_acl() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+
+        let mut builder = unsafe { RawAclBuilder::new()? };
+        builder.push(USER_OBJ, None, Perm::READ | Perm::WRITE)?;
+        builder.push(GROUP_OBJ, None, Perm::READ)?;
+        builder.push(OTHER, None, Perm::empty())?;
+        builder.finish().write(file.path(), AclOption::empty())?;
+        assert_eq!(crate::getfacl(&file, None)?, crate::from_mode(0o640));
+
+        // A named user without a mask entry is rejected by the kernel, not
+        // by exacl.
+        let mut builder = unsafe { RawAclBuilder::new()? };
+        builder.push(USER_OBJ, None, Perm::READ | Perm::WRITE)?;
+        builder.push(USER, Some(11501), Perm::READ)?;
+        builder.push(GROUP_OBJ, None, Perm::READ)?;
+        builder.push(OTHER, None, Perm::empty())?;
+        let acl = builder.finish();
+        assert_eq!(acl.entries()?.len(), 4);
+        let err = acl.write(file.path(), AclOption::empty()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_raw_acl_unknown_tag() {
+        // libacl itself rejects tags it doesn't know.
+        let mut builder = unsafe { RawAclBuilder::new().unwrap() };
+        let err = builder.push(0x40, None, Perm::READ).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,196 @@
+//! Implements `AclRecord`, which flattens ACL entries for analytics tools.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "csv")]
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One ACL entry of one path, flattened into columns.
+///
+/// A list of records loads into a table with one row per entry, e.g. in a
+/// spreadsheet or a data frame. The column names in [`AclRecord::COLUMNS`] are
+/// the field names and will not change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub struct AclRecord {
+    /// Path of the file or directory.
+    pub path: PathBuf,
+
+    /// Kind of entry (User, Group, Other, Mask, or Unknown).
+    pub kind: AclEntryKind,
+
+    /// Name of the principal, or empty.
+    pub name: String,
+
+    /// Permission bits, as in [`Perm::bits`](crate::Perm::bits).
+    pub perms: u32,
+
+    /// Permission names, separated by commas, e.g. "read,write".
+    pub perm_names: String,
+
+    /// Flag names, separated by commas, e.g. "default".
+    pub flags: String,
+
+    /// True if the entry allows access; false if it denies access.
+    pub allow: bool,
+
+    /// True if the entry belongs to the default ACL.
+    pub default: bool,
+}
+
+impl AclRecord {
+    /// Names of the columns, in order.
+    pub const COLUMNS: [&'static str; 8] = [
+        "path",
+        "kind",
+        "name",
+        "perms",
+        "perm_names",
+        "flags",
+        "allow",
+        "default",
+    ];
+
+    /// Construct a record for an entry of the given path.
+    #[must_use]
+    pub fn new(path: &Path, entry: &AclEntry) -> AclRecord {
+        AclRecord {
+            path: path.to_path_buf(),
+            kind: entry.kind,
+            name: entry.name.clone(),
+            perms: entry.perms.bits(),
+            perm_names: entry.perms.to_string(),
+            flags: entry.flags.to_string(),
+            allow: entry.allow,
+            default: entry.is_default(),
+        }
+    }
+}
+
+/// Write records as CSV, with a header row of [`AclRecord::COLUMNS`]
+/// (requires the `csv` feature).
+///
+/// Fields that contain a comma, quote or line break are quoted, and quotes are
+/// doubled, as in RFC 4180. Paths that are not valid UTF-8 are written lossily.
+/// Each row ends with CRLF.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclEntry, AclRecord, Perm};
+/// use std::path::Path;
+///
+/// let entry = AclEntry::allow_user("bob", Perm::READ | Perm::WRITE, None);
+/// let record = AclRecord::new(Path::new("/tmp/a"), &entry);
+///
+/// let mut csv = Vec::new();
+/// exacl::write_csv(&mut csv, &[record])?;
+/// assert!(String::from_utf8_lossy(&csv).contains("\"read,write\""));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if writing fails.
+#[cfg(feature = "csv")]
+pub fn write_csv<W: io::Write>(mut writer: W, records: &[AclRecord]) -> io::Result<()> {
+    write_row(&mut writer, &AclRecord::COLUMNS)?;
+
+    for record in records {
+        write_row(
+            &mut writer,
+            &[
+                &record.path.to_string_lossy(),
+                &record.kind.to_string(),
+                &record.name,
+                &record.perms.to_string(),
+                &record.perm_names,
+                &record.flags,
+                &record.allow.to_string(),
+                &record.default.to_string(),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write one CSV row.
+#[cfg(feature = "csv")]
+fn write_row<W: io::Write>(writer: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+
+    writer.write_all(b"\r\n")
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod record_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_record_new() {
+        let entry = AclEntry::allow_group("staff", Perm::READ | Perm::EXECUTE, None);
+        let record = AclRecord::new(Path::new("/a"), &entry);
+        assert_eq!(record.path, Path::new("/a"));
+        assert_eq!(record.kind, AclEntryKind::Group);
+        assert_eq!(record.name, "staff");
+        assert_eq!(record.perms, (Perm::READ | Perm::EXECUTE).bits());
+        assert_eq!(record.perm_names, "read,execute");
+        assert_eq!(record.flags, "");
+        assert!(record.allow);
+        assert!(!record.default);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_record_default() {
+        let entry = AclEntry::allow_other(Perm::empty(), crate::Flag::DEFAULT);
+        let record = AclRecord::new(Path::new("/a"), &entry);
+        assert_eq!(record.flags, "default");
+        assert!(record.default);
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn test_write_csv() {
+        let records = vec![
+            AclRecord::new(
+                Path::new("/a"),
+                &AclEntry::allow_user("bob", Perm::READ, None),
+            ),
+            AclRecord::new(
+                Path::new("/b,\"c\""),
+                &AclEntry::allow_group("x y", Perm::READ | Perm::WRITE, None),
+            ),
+        ];
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &records).unwrap();
+        let read = Perm::READ.bits();
+        let read_write = (Perm::READ | Perm::WRITE).bits();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "path,kind,name,perms,perm_names,flags,allow,default\r\n\
+                 /a,user,bob,{read},read,,true,false\r\n\
+                 \"/b,\"\"c\"\"\",group,x y,{read_write},\"read,write\",,true,false\r\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Implements `Redactor`, which replaces user and group names with stable
+//! pseudonyms in text output.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+
+/// Replaces the names of users and groups with pseudonyms, so ACL dumps can
+/// be shared without revealing who has access.
+///
+/// A pseudonym is a keyed hash (`SipHash-2-4`) of the name, so the same name
+/// always gets the same pseudonym for the same salt, and the structure of a
+/// dump is kept: which entries belong to the same principal, and which
+/// principals appear in many files. Keep the salt secret; anyone who knows it
+/// can check guessed names against the pseudonyms.
+///
+/// Named users become `user-<hex>` and named groups `group-<hex>`. A numeric
+/// uid or gid becomes another decimal id. Entries without a name, such as the
+/// owner and `other::` entries, and the macOS `everyone` group are kept as
+/// is.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{AclEntry, Perm, Redactor};
+///
+/// let entries = vec![
+///     AclEntry::allow_user("alice", Perm::READ, None),
+///     AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+/// ];
+///
+/// let redactor = Redactor::new(b"secret salt");
+/// let mut buf = Vec::new();
+/// exacl::to_writer_redacted(&mut buf, &entries, &redactor)?;
+///
+/// let text = String::from_utf8(buf).unwrap();
+/// assert!(!text.contains("alice"));
+/// assert!(text.contains(&redactor.pseudonym(&entries[0])));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Redactor {
+    key: (u64, u64),
+}
+
+impl Redactor {
+    /// Construct a redactor keyed by a caller-provided salt.
+    #[must_use]
+    pub fn new(salt: &[u8]) -> Redactor {
+        // Derive a 128-bit key from a salt of any length.
+        Redactor {
+            key: (siphash((0, 0), salt), siphash((0, 1), salt)),
+        }
+    }
+
+    /// Return the pseudonym for the principal of an entry, or its name if the
+    /// entry is not redacted.
+    #[must_use]
+    pub fn pseudonym(&self, entry: &AclEntry) -> String {
+        if !is_redacted(entry) {
+            return entry.name.clone();
+        }
+
+        let hash = siphash(self.key, entry.name.as_bytes());
+        match (entry.numeric, entry.kind) {
+            // Keep clear of the low ids used by system accounts.
+            (true, _) => (10_000 + hash % 2_000_000_000).to_string(),
+            (false, AclEntryKind::User) => format!("user-{hash:016x}"),
+            (false, _) => format!("group-{hash:016x}"),
+        }
+    }
+
+    /// Return a copy of the entry with its name replaced by a pseudonym.
+    #[must_use]
+    pub fn redact(&self, entry: &AclEntry) -> AclEntry {
+        AclEntry {
+            name: self.pseudonym(entry),
+            ..entry.clone()
+        }
+    }
+}
+
+impl std::fmt::Debug for Redactor {
+    /// Hide the key.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Redactor").finish_non_exhaustive()
+    }
+}
+
+/// Return true if the entry names a user or group.
+fn is_redacted(entry: &AclEntry) -> bool {
+    matches!(entry.kind, AclEntryKind::User | AclEntryKind::Group)
+        && !entry.name.is_empty()
+        && !entry.is_everyone()
+}
+
+/// Return the `SipHash-2-4` of `data`.
+///
+/// The hasher in the standard library is not guaranteed to stay the same
+/// across Rust versions, and pseudonyms must be stable.
+fn siphash(key: (u64, u64), data: &[u8]) -> u64 {
+    let mut v = [
+        key.0 ^ 0x736f_6d65_7073_6575,
+        key.1 ^ 0x646f_7261_6e64_6f6d,
+        key.0 ^ 0x6c79_6765_6e65_7261,
+        key.1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        let m = u64::from_le_bytes(word);
+        v[3] ^= m;
+        sipround(&mut v);
+        sipround(&mut v);
+        v[0] ^= m;
+    }
+
+    // The last word holds the remaining bytes and the length.
+    let mut word = [0; 8];
+    word[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        word[7] = data.len() as u8;
+    }
+    let m = u64::from_le_bytes(word);
+    v[3] ^= m;
+    sipround(&mut v);
+    sipround(&mut v);
+    v[0] ^= m;
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sipround(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn sipround(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13) ^ v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16) ^ v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21) ^ v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17) ^ v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod redact_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_siphash() {
+        // Test vectors from the SipHash paper: key 00..0f, message 00..n-1.
+        let key = (0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908);
+        assert_eq!(siphash(key, b""), 0x726f_db47_dd0e_0e31);
+        let data = (0..15).collect::<Vec<u8>>();
+        assert_eq!(siphash(key, &data), 0xa129_ca61_49be_45e5);
+        assert_eq!(siphash(key, &data[..8]), 0x93f5_f579_9a93_2462);
+    }
+
+    #[test]
+    fn test_redact() {
+        let redactor = Redactor::new(b"salt");
+        let alice = AclEntry::allow_user("alice", Perm::READ, None);
+        let staff = AclEntry::allow_group("alice", Perm::READ, None);
+
+        let pseudonym = redactor.pseudonym(&alice);
+        assert!(pseudonym.starts_with("user-"), "{pseudonym}");
+        assert_eq!(pseudonym.len(), 21);
+        assert_eq!(redactor.pseudonym(&alice), pseudonym);
+        assert!(redactor.pseudonym(&staff).starts_with("group-"));
+
+        // A different salt gives a different pseudonym.
+        assert_ne!(Redactor::new(b"pepper").pseudonym(&alice), pseudonym);
+
+        let redacted = redactor.redact(&alice);
+        assert_eq!(redacted.name, pseudonym);
+        assert_eq!(redacted.perms, Perm::READ);
+
+        // Entries without a principal name are kept.
+        let owner = AclEntry::allow_user("", Perm::READ, None);
+        assert_eq!(redactor.redact(&owner), owner);
+        let other = AclEntry::allow_other(Perm::READ, None);
+        assert_eq!(redactor.redact(&other), other);
+
+        // Numeric ids stay numeric.
+        let numeric = AclEntry {
+            numeric: true,
+            ..AclEntry::allow_user("11501", Perm::READ, None)
+        };
+        let redacted = redactor.redact(&numeric);
+        assert!(redacted.numeric);
+        assert!(redacted.name.parse::<u32>().is_ok(), "{}", redacted.name);
+        assert_ne!(redacted.name, "11501");
+
+        assert_eq!(format!("{redactor:?}"), "Redactor { .. }");
+    }
+}
@@ -0,0 +1,113 @@
+//! Implements `resolve_check` for user and group names.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+
+use std::fmt;
+use std::io;
+
+/// A user or group name that is not in the user/group database.
+///
+/// Returned by [`resolve_check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UnresolvedPrincipal {
+    /// Index of the entry.
+    pub index: usize,
+
+    /// Kind of entry (User or Group).
+    pub kind: AclEntryKind,
+
+    /// Name that did not resolve.
+    pub name: String,
+}
+
+impl UnresolvedPrincipal {
+    /// Return an `UnresolvedPrincipal` for the entry at `index`.
+    pub(crate) fn new(index: usize, entry: &AclEntry) -> UnresolvedPrincipal {
+        UnresolvedPrincipal {
+            index,
+            kind: entry.kind,
+            name: entry.name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for UnresolvedPrincipal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "entry {} ({} {:?})", self.index, self.kind, self.name)
+    }
+}
+
+/// Check that every user and group name in a list of ACL entries is in the
+/// user/group database.
+///
+/// This is the same check that [`setfacl`](crate::setfacl) makes with
+/// [`AclOption::STRICT_PRINCIPALS`](crate::AclOption::STRICT_PRINCIPALS), but
+/// it reports all unknown names at once without touching any files. Decimal
+/// names (and GUID's on macOS) are reported unless the entry is `numeric`.
+/// Entries for the file owner or group are always resolved.
+#[must_use]
+pub fn resolve_check(entries: &[AclEntry]) -> Vec<UnresolvedPrincipal> {
+    let mut result = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        if let Err(err) = entry.qualifier(true) {
+            if err.kind() == io::ErrorKind::NotFound {
+                result.push(UnresolvedPrincipal::new(index, entry));
+            }
+        }
+    }
+
+    result
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod resolve_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_resolve_check() {
+        let entries = vec![
+            AclEntry::allow_user("root", Perm::READ, None),
+            AclEntry::allow_user("no_such_user", Perm::READ, None),
+            AclEntry::allow_group("1234567", Perm::READ, None),
+            AclEntry {
+                numeric: true,
+                ..AclEntry::allow_user("1234567", Perm::READ, None)
+            },
+            AclEntry {
+                kind: AclEntryKind::Unknown,
+                ..AclEntry::allow_user("no_such_user", Perm::READ, None)
+            },
+        ];
+
+        let unresolved = resolve_check(&entries);
+        let messages = unresolved
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "entry 1 (user \"no_such_user\")",
+                "entry 2 (group \"1234567\")",
+            ]
+        );
+        assert_eq!(unresolved[0].kind, AclEntryKind::User);
+        assert_eq!(unresolved[1].name, "1234567");
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_resolve_check_owner() {
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            AclEntry::allow_other(Perm::READ, None),
+        ];
+        assert!(resolve_check(&entries).is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+//! Implements `selftest`, which probes the ACL features of a directory.
+
+use crate::aclentry::AclEntry;
+use crate::failx::path_err;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::volume::{volume_capabilities, VolumeCapabilities};
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::AclOption;
+use crate::{getfacl, setfacl};
+
+use scopeguard::defer;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Upper limit on the number of entries tried when measuring `max_entries`.
+const MAX_PROBE_ENTRIES: usize = 65536;
+
+/// First numeric uid used for the entries that measure `max_entries`.
+const PROBE_UID: u32 = 100_000;
+
+/// Result of [`selftest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SelfTestReport {
+    /// Capabilities reported by the file system, without probing.
+    pub volume: VolumeCapabilities,
+
+    /// An entry for a named user can be written and read back.
+    pub acl: bool,
+
+    /// A deny entry can be written.
+    pub deny: bool,
+
+    /// A default ACL can be written to a directory (Posix.1e only).
+    pub default_acl: bool,
+
+    /// An entry with inheritance flags can be written to a directory.
+    pub inherit: bool,
+
+    /// Largest number of entries written to a file and read back, or None if
+    /// no ACL could be written.
+    pub max_entries: Option<usize>,
+}
+
+/// Probe the ACL features supported in `dir` by trying them.
+///
+/// Unlike [`volume_capabilities`], which asks the file system, this creates a
+/// temporary file and directory in `dir` and writes ACL's to them: a named
+/// entry, a deny entry, a default ACL and an entry with inheritance flags. It
+/// also finds the largest ACL that can be written, which takes a few dozen
+/// writes. The temporary files are removed before returning.
+///
+/// Each probe that fails is reported as false; the error is not returned.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the temporary files can't be created, e.g. if
+/// `dir` does not exist or isn't writable.
+pub fn selftest<P: AsRef<Path>>(dir: P) -> io::Result<SelfTestReport> {
+    let dir = dir.as_ref();
+    let volume = volume_capabilities(dir)?;
+
+    let probe_dir = dir.join(format!(".exacl-selftest-{}", std::process::id()));
+    std::fs::create_dir(&probe_dir).map_err(|err| path_err(&probe_dir, &err))?;
+    defer! { let _ = std::fs::remove_dir_all(&probe_dir); }
+
+    let probe = Probe::new(&probe_dir)?;
+
+    let mut report = SelfTestReport {
+        volume,
+        acl: probe.try_entry(&probe.file, probe.allow(Perm::READ))?,
+        deny: false,
+        default_acl: false,
+        inherit: false,
+        max_entries: None,
+    };
+
+    if report.acl {
+        report.deny = probe.try_entry(&probe.file, probe.deny(Perm::WRITE))?;
+        report.default_acl = probe.try_default_acl()?;
+        report.inherit = probe.try_inherit()?;
+        report.max_entries = probe.max_entries()?;
+    }
+
+    Ok(report)
+}
+
+/// Temporary file and directory used by the probes.
+struct Probe {
+    file: PathBuf,
+    dir: PathBuf,
+    uid: u32,
+}
+
+impl Probe {
+    fn new(probe_dir: &Path) -> io::Result<Probe> {
+        let file = probe_dir.join("file");
+        let dir = probe_dir.join("dir");
+        std::fs::File::create(&file).map_err(|err| path_err(&file, &err))?;
+        std::fs::create_dir(&dir).map_err(|err| path_err(&dir, &err))?;
+        let uid = std::fs::metadata(&file)?.uid();
+
+        Ok(Probe { file, dir, uid })
+    }
+
+    /// Return a numeric entry for the owner of the probe files.
+    fn allow(&self, perms: Perm) -> AclEntry {
+        numeric_entry(self.uid, perms, true)
+    }
+
+    fn deny(&self, perms: Perm) -> AclEntry {
+        numeric_entry(self.uid, perms, false)
+    }
+
+    /// Add an entry to the existing ACL of `path`. Return true if it was
+    /// written and read back.
+    fn try_entry(&self, path: &Path, entry: AclEntry) -> io::Result<bool> {
+        let mut entries = getfacl(path, None)?;
+        entries.push(entry.clone());
+        let ok = setfacl(&[path], &entries, None).is_ok()
+            && getfacl(path, None)?
+                .iter()
+                .any(|e| is_same_probe(e, &entry));
+
+        // Restore the original ACL.
+        entries.pop();
+        let _ = setfacl(&[path], &entries, None);
+
+        Ok(ok)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn try_default_acl(&self) -> io::Result<bool> {
+        let entries = [
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE | Perm::EXECUTE, Flag::DEFAULT),
+            AclEntry::allow_group("", Perm::READ, Flag::DEFAULT),
+            AclEntry::allow_other(Perm::empty(), Flag::DEFAULT),
+        ];
+        let ok = setfacl(&[&self.dir], &entries, AclOption::DEFAULT_ACL).is_ok();
+        let _ = setfacl(&[&self.dir], &[], AclOption::DEFAULT_ACL);
+
+        Ok(ok)
+    }
+
+    #[cfg(target_os = "macos")]
+    #[allow(clippy::unnecessary_wraps)]
+    fn try_default_acl(&self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    #[cfg(exacl_extended)]
+    fn try_inherit(&self) -> io::Result<bool> {
+        let mut entry = self.allow(Perm::READ);
+        entry.flags = Flag::FILE_INHERIT | Flag::DIRECTORY_INHERIT;
+        self.try_entry(&self.dir, entry)
+    }
+
+    #[cfg(not(exacl_extended))]
+    #[allow(clippy::unnecessary_wraps)]
+    fn try_inherit(&self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Find the largest ACL that can be written to the probe file, by
+    /// doubling the number of named entries, then searching between the
+    /// last size that worked and the first that failed.
+    fn max_entries(&self) -> io::Result<Option<usize>> {
+        let base = getfacl(&self.file, None)?;
+        let try_named = |count: usize| -> io::Result<Option<usize>> {
+            let mut entries = base.clone();
+            entries.extend(
+                (PROBE_UID..)
+                    .take(count)
+                    .map(|uid| numeric_entry(uid, Perm::READ, true)),
+            );
+            if setfacl(&[&self.file], &entries, None).is_err() {
+                return Ok(None);
+            }
+            Ok(Some(getfacl(&self.file, None)?.len()))
+        };
+
+        let mut best = None;
+        let mut good = 0;
+        let mut bad = None;
+        let mut count = 1;
+        while count <= MAX_PROBE_ENTRIES {
+            match try_named(count)? {
+                Some(len) => {
+                    best = Some(len);
+                    good = count;
+                    count *= 2;
+                }
+                None => {
+                    bad = Some(count);
+                    break;
+                }
+            }
+        }
+
+        if let Some(mut bad) = bad {
+            while bad - good > 1 {
+                let mid = good + (bad - good) / 2;
+                match try_named(mid)? {
+                    Some(len) => {
+                        best = Some(len);
+                        good = mid;
+                    }
+                    None => bad = mid,
+                }
+            }
+        }
+
+        let _ = setfacl(&[&self.file], &base, None);
+
+        Ok(best)
+    }
+}
+
+/// Return true if `entry` was read back from `probe`. The name may read back
+/// as a user name instead of a uid.
+fn is_same_probe(entry: &AclEntry, probe: &AclEntry) -> bool {
+    entry.kind == probe.kind
+        && !entry.name.is_empty()
+        && entry.allow == probe.allow
+        && entry.perms == probe.perms
+        && entry.flags == probe.flags
+}
+
+/// Return an entry for a numeric uid, which is not looked up.
+fn numeric_entry(uid: u32, perms: Perm, allow: bool) -> AclEntry {
+    AclEntry {
+        allow,
+        numeric: true,
+        ..AclEntry::allow_user(&uid.to_string(), perms, None)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod selftest_tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = selftest(&dir).unwrap();
+        assert_eq!(report.volume, volume_capabilities(&dir).unwrap());
+        assert!(report.acl);
+        assert!(report.max_entries.unwrap() > 4);
+
+        // Temporary files are removed.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            (report.deny, report.default_acl, report.inherit),
+            (false, true, false)
+        );
+
+        #[cfg(target_os = "macos")]
+        {
+            assert_eq!(
+                (report.deny, report.default_acl, report.inherit),
+                (true, false, cfg!(exacl_extended))
+            );
+            assert_eq!(report.max_entries, report.volume.max_entries);
+        }
+
+        let err = selftest("/non-existant").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
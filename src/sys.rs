@@ -2,8 +2,45 @@
 
 #![allow(dead_code, non_camel_case_types)] // constant is never used
 
+#[cfg(not(exacl_core))]
 pub use crate::bindings::*;
 
+#[cfg(exacl_core)]
+pub use portable::*;
+
+/// Portable constants for targets without a native ACL API. The values match
+/// `FreeBSD`, whose model is a superset of Posix.1e and NFSv4.
+#[cfg(exacl_core)]
+mod portable {
+    pub type acl_perm_t = u32;
+    pub type acl_flag_t = u16;
+
+    pub const ACL_EXECUTE: u32 = 1;
+    pub const ACL_WRITE: u32 = 2;
+    pub const ACL_READ: u32 = 4;
+    pub const ACL_READ_DATA: u32 = 8;
+    pub const ACL_WRITE_DATA: u32 = 16;
+    pub const ACL_APPEND_DATA: u32 = 32;
+    pub const ACL_READ_NAMED_ATTRS: u32 = 64;
+    pub const ACL_WRITE_NAMED_ATTRS: u32 = 128;
+    pub const ACL_DELETE_CHILD: u32 = 256;
+    pub const ACL_READ_ATTRIBUTES: u32 = 512;
+    pub const ACL_WRITE_ATTRIBUTES: u32 = 1024;
+    pub const ACL_DELETE: u32 = 2048;
+    pub const ACL_READ_ACL: u32 = 4096;
+    pub const ACL_WRITE_ACL: u32 = 8192;
+    pub const ACL_WRITE_OWNER: u32 = 16384;
+    pub const ACL_SYNCHRONIZE: u32 = 32768;
+
+    pub const ACL_ENTRY_FILE_INHERIT: u32 = 1;
+    pub const ACL_ENTRY_DIRECTORY_INHERIT: u32 = 2;
+    pub const ACL_ENTRY_NO_PROPAGATE_INHERIT: u32 = 4;
+    pub const ACL_ENTRY_INHERIT_ONLY: u32 = 8;
+    pub const ACL_ENTRY_SUCCESSFUL_ACCESS: u32 = 16;
+    pub const ACL_ENTRY_FAILED_ACCESS: u32 = 32;
+    pub const ACL_ENTRY_INHERITED: u32 = 128;
+}
+
 // Demangle some MacOS constants. Linux provides these as-is.
 
 #[cfg(target_os = "macos")]
@@ -24,6 +61,42 @@ pub type acl_flag_t = u32;
 #[cfg(target_os = "linux")]
 pub const ACL_MAX_ENTRIES: u32 = 2_000_000_000;
 
+// System Integrity Protection from <sys/csr.h> and <sys/stat.h> (macOS only).
+#[cfg(target_os = "macos")]
+pub const CSR_ALLOW_UNRESTRICTED_FS: u32 = 1 << 1;
+
+#[cfg(target_os = "macos")]
+pub const SF_RESTRICTED: u32 = 0x0008_0000;
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    pub fn csr_check(mask: u32) -> ::std::os::raw::c_int;
+}
+
+// File security API from <sys/acl.h> and <sys/stat.h>, used to remove an
+// extended ACL (macOS only).
+#[cfg(target_os = "macos")]
+pub type filesec_t = *mut ::std::os::raw::c_void;
+
+#[cfg(target_os = "macos")]
+pub const FILESEC_ACL: ::std::os::raw::c_uint = 5;
+
+#[cfg(target_os = "macos")]
+pub const _FILESEC_REMOVE_ACL: *const ::std::os::raw::c_void = 1 as *const _;
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    pub fn filesec_init() -> filesec_t;
+    pub fn filesec_free(fsec: filesec_t);
+    pub fn filesec_set_property(
+        fsec: filesec_t,
+        property: ::std::os::raw::c_uint,
+        propptr: *const ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+    pub fn chmodx_np(path: *const ::std::os::raw::c_char, fsec: filesec_t)
+        -> ::std::os::raw::c_int;
+}
+
 // MacOS and FreeBSD use acl_get_perm_np().
 #[cfg(any(target_os = "macos", target_os = "freebsd"))]
 pub unsafe fn acl_get_perm(permset_d: acl_permset_t, perm: acl_perm_t) -> ::std::os::raw::c_int {
@@ -56,8 +129,8 @@ pub mod np {
     pub const ACL_ENTRY_ONLY_INHERIT: acl_flag_t = acl_flag_t_ACL_ENTRY_ONLY_INHERIT;
 }
 
-/// Non-portable ACL Permissions & Flags (`FreeBSD` only)
-#[cfg(all(target_os = "freebsd", not(docsrs)))]
+/// Non-portable ACL Permissions & Flags (`FreeBSD` and portable core)
+#[cfg(all(any(target_os = "freebsd", exacl_core), not(docsrs)))]
 pub mod np {
     use super::{acl_flag_t, acl_perm_t};
 
@@ -88,6 +161,34 @@ pub mod np {
     pub const ACL_ENTRY_FAILED_ACCESS: acl_flag_t = super::ACL_ENTRY_FAILED_ACCESS as acl_flag_t;
 }
 
+/// Non-portable ACL Permissions & Flags (Linux with the `nfs4` feature). Linux
+/// has no native values, so these match `FreeBSD`; the bits of the
+/// `system.nfs4_acl` attribute are converted in the `nfs4` module.
+#[cfg(all(target_os = "linux", exacl_nfs4, not(docsrs)))]
+pub mod np {
+    use super::{acl_flag_t, acl_perm_t};
+
+    pub const ACL_READ_DATA: acl_perm_t = 1 << 3;
+    pub const ACL_WRITE_DATA: acl_perm_t = 1 << 4;
+    pub const ACL_APPEND_DATA: acl_perm_t = 1 << 5;
+    pub const ACL_READ_EXTATTRIBUTES: acl_perm_t = 1 << 6;
+    pub const ACL_WRITE_EXTATTRIBUTES: acl_perm_t = 1 << 7;
+    pub const ACL_DELETE_CHILD: acl_perm_t = 1 << 8;
+    pub const ACL_READ_ATTRIBUTES: acl_perm_t = 1 << 9;
+    pub const ACL_WRITE_ATTRIBUTES: acl_perm_t = 1 << 10;
+    pub const ACL_DELETE: acl_perm_t = 1 << 11;
+    pub const ACL_READ_SECURITY: acl_perm_t = 1 << 12;
+    pub const ACL_WRITE_SECURITY: acl_perm_t = 1 << 13;
+    pub const ACL_CHANGE_OWNER: acl_perm_t = 1 << 14;
+    pub const ACL_SYNCHRONIZE: acl_perm_t = 1 << 15;
+
+    pub const ACL_ENTRY_FILE_INHERIT: acl_flag_t = 1 << 0;
+    pub const ACL_ENTRY_DIRECTORY_INHERIT: acl_flag_t = 1 << 1;
+    pub const ACL_ENTRY_LIMIT_INHERIT: acl_flag_t = 1 << 2;
+    pub const ACL_ENTRY_ONLY_INHERIT: acl_flag_t = 1 << 3;
+    pub const ACL_ENTRY_INHERITED: acl_flag_t = 1 << 7;
+}
+
 /// Non-portable ACL Permissions (Docs only). These are fabricated constants to
 /// make it possible for docs to be built on macOS and Linux.
 #[cfg(docsrs)]
@@ -120,6 +221,7 @@ pub mod np {
 // Convenience constants where the API expects a signed i32 type, but bindgen
 // provides u32. (FIXME: Replace with bindgen ParseCallbacks::int_macro?)
 
+#[cfg(not(exacl_core))]
 pub mod sg {
     #![allow(clippy::cast_possible_wrap)]
 
@@ -190,8 +292,23 @@ pub mod sg {
     #[cfg(target_os = "macos")]
     pub const ID_TYPE_GID: i32 = super::ID_TYPE_GID as i32;
 
+    #[cfg(target_os = "linux")]
+    pub const TEXT_SOME_EFFECTIVE: i32 = super::TEXT_SOME_EFFECTIVE as i32;
+    #[cfg(target_os = "linux")]
+    pub const TEXT_ALL_EFFECTIVE: i32 = super::TEXT_ALL_EFFECTIVE as i32;
+    #[cfg(target_os = "linux")]
+    pub const TEXT_SMART_INDENT: i32 = super::TEXT_SMART_INDENT as i32;
+    #[cfg(target_os = "linux")]
+    pub const TEXT_NUMERIC_IDS: i32 = super::TEXT_NUMERIC_IDS as i32;
+    #[cfg(target_os = "linux")]
+    pub const TEXT_ABBREVIATE: i32 = super::TEXT_ABBREVIATE as i32;
+
     #[cfg(target_os = "freebsd")]
     pub const PC_ACL_NFS4: i32 = super::_PC_ACL_NFS4 as i32;
+    #[cfg(target_os = "freebsd")]
+    pub const PC_ACL_EXTENDED: i32 = super::_PC_ACL_EXTENDED as i32;
+    #[cfg(target_os = "macos")]
+    pub const PC_EXTENDED_SECURITY_NP: i32 = super::_PC_EXTENDED_SECURITY_NP as i32;
 
     #[test]
     fn test_signed() {
@@ -0,0 +1,546 @@
+//! Creates files on a specific file system type, and checks their ACL
+//! behavior against known values (requires the `testing` feature).
+//!
+//! ACL limits depend on the file system: the number of entries that fit,
+//! whether default ACL's are supported, and so on. Tests that create files with
+//! [`tempfile`] get whatever file system holds the temporary directory. These
+//! helpers pin down the file system type so tests can check its limits
+//! deterministically.
+//!
+//! - [`memfd`] creates an anonymous file in memory (tmpfs).
+//! - [`tmpfs_dir`] creates a temporary directory on tmpfs.
+//! - [`ImageMount`] mounts a tmpfs, or loop-mounts an ext4, XFS or ZFS image,
+//!   which requires root.
+//! - [`golden_test`] checks a directory against the [`Golden`] values of its
+//!   [`FileSystem`], and [`for_each_file_system`] runs a check on every file
+//!   system that can be mounted, e.g. as root in a CI container or VM.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::testing::{for_each_file_system, golden_test, ImageMount};
+//!
+//! let mount = ImageMount::ext4(16)?;
+//! let report = exacl::selftest(mount.path())?;
+//! assert!(report.default_acl);
+//!
+//! let tested = for_each_file_system(64, golden_test)?;
+//! println!("tested {tested:?}");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::acl::{Acl, AclOption};
+use crate::failx::{fail_err, path_err};
+use crate::{from_mode, getfacl, selftest, setfacl};
+
+use std::ffi::CString;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::RangeInclusive;
+use std::os::fd::FromRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+/// Directory where [`tmpfs_dir`] creates temporary directories.
+const TMPFS_ROOT: &str = "/dev/shm";
+
+/// Extended attribute that holds a Posix.1e access ACL on Linux.
+const ACCESS_ACL_XATTR: &[u8] = b"system.posix_acl_access\0";
+
+/// Create an anonymous file in memory, using `memfd_create`.
+///
+/// The file lives on an internal tmpfs mount and has no path; use
+/// [`Acl::read_fd`](crate::Acl::read_fd) and
+/// [`Acl::write_fd`](crate::Acl::write_fd) to access its ACL.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `name` contains a NUL byte or the file can't be
+/// created.
+pub fn memfd(name: &str) -> io::Result<File> {
+    let c_name = CString::new(name)?;
+    let fd = unsafe { libc::memfd_create(c_name.as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return fail_err(fd, "memfd_create", &c_name);
+    }
+
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Create a temporary directory on tmpfs. The directory is removed when the
+/// returned [`TempDir`] is dropped.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `/dev/shm` is not a tmpfs mount or the
+/// directory can't be created.
+pub fn tmpfs_dir() -> io::Result<TempDir> {
+    let root = Path::new(TMPFS_ROOT);
+    if fs_type(root)? != libc::TMPFS_MAGIC {
+        let err = io::Error::new(io::ErrorKind::Unsupported, "not a tmpfs mount");
+        return Err(path_err(root, &err));
+    }
+
+    tempfile::Builder::new()
+        .prefix("exacl-")
+        .tempdir_in(root)
+        .map_err(|err| path_err(root, &err))
+}
+
+/// A file system type that [`ImageMount`] can mount.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FileSystem {
+    /// Memory file system. Needs no image.
+    Tmpfs,
+
+    /// ext4 image, made with `mkfs.ext4`.
+    Ext4,
+
+    /// XFS image, made with `mkfs.xfs`.
+    Xfs,
+
+    /// ZFS pool on an image, made with `zpool` and `posixacl` enabled.
+    Zfs,
+}
+
+impl FileSystem {
+    /// Every file system type, in the order [`for_each_file_system`] tries
+    /// them.
+    pub const ALL: [FileSystem; 4] = [
+        FileSystem::Tmpfs,
+        FileSystem::Ext4,
+        FileSystem::Xfs,
+        FileSystem::Zfs,
+    ];
+
+    /// Return the known ACL behavior of the file system type.
+    #[must_use]
+    pub const fn golden(self) -> Golden {
+        // Each Posix.1e entry takes 8 bytes of an extended attribute, which
+        // is at most 64 KiB. ext4 stores the attribute in one 4 KiB block,
+        // and XFS stores 12 bytes per entry. The ZFS limit depends on the
+        // pool's settings.
+        let max_entries = match self {
+            FileSystem::Tmpfs => 8000..=8191,
+            FileSystem::Ext4 => 400..=600,
+            FileSystem::Xfs => 5000..=5461,
+            FileSystem::Zfs => 32..=8191,
+        };
+
+        Golden {
+            default_acl: true,
+            deny: false,
+            max_entries,
+        }
+    }
+
+    /// Return the `statfs` magic number of the file system type.
+    const fn magic(self) -> libc::c_long {
+        match self {
+            FileSystem::Tmpfs => 0x0102_1994,
+            FileSystem::Ext4 => 0xef53,
+            FileSystem::Xfs => 0x5846_5342,
+            FileSystem::Zfs => 0x2fc1_2fc1,
+        }
+    }
+}
+
+impl fmt::Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FileSystem::Tmpfs => "tmpfs",
+            FileSystem::Ext4 => "ext4",
+            FileSystem::Xfs => "xfs",
+            FileSystem::Zfs => "zfs",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Known ACL behavior of a file system type, checked by [`golden_test`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Golden {
+    /// A default ACL can be written to a directory.
+    pub default_acl: bool,
+
+    /// A deny entry can be written.
+    pub deny: bool,
+
+    /// Range that the largest number of entries measured by
+    /// [`selftest`](crate::selftest) must fall in.
+    pub max_entries: RangeInclusive<usize>,
+}
+
+/// A file system mounted on a temporary directory.
+///
+/// The file system is unmounted and its image removed when this is dropped.
+/// Mounting requires root. Images are attached to a loop device; the `mount`
+/// and `umount` commands, and `mkfs.ext4`, `mkfs.xfs` or `zpool` for the file
+/// system type, must be in the `PATH`.
+#[derive(Debug)]
+pub struct ImageMount {
+    file_system: FileSystem,
+    mount_point: PathBuf,
+    // Name of the ZFS pool, which is destroyed instead of unmounted.
+    pool: Option<String>,
+    // Holds the image and the mount point. Dropped after `umount`.
+    _temp: TempDir,
+}
+
+impl ImageMount {
+    /// Create an ext4 image of `size_mb` megabytes and mount it with ACL's
+    /// enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] with kind `PermissionDenied` if not running as
+    /// root, or any error from creating or mounting the image.
+    pub fn ext4(size_mb: u64) -> io::Result<ImageMount> {
+        ImageMount::new(FileSystem::Ext4, size_mb)
+    }
+
+    /// Mount a file system of `size_mb` megabytes with Posix.1e ACL's
+    /// enabled. ZFS needs at least 64 megabytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] with kind `PermissionDenied` if not running as
+    /// root, or any error from creating or mounting the file system.
+    pub fn new(file_system: FileSystem, size_mb: u64) -> io::Result<ImageMount> {
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "mounting an image requires root",
+            ));
+        }
+
+        let temp = tempfile::Builder::new().prefix("exacl-").tempdir()?;
+        let image = temp.path().join("fs.img");
+        let mount_point = temp.path().join("mnt");
+        std::fs::create_dir(&mount_point).map_err(|err| path_err(&mount_point, &err))?;
+
+        if file_system != FileSystem::Tmpfs {
+            File::create(&image)
+                .and_then(|file| file.set_len(size_mb * 1024 * 1024))
+                .map_err(|err| path_err(&image, &err))?;
+        }
+
+        let mut pool = None;
+        match file_system {
+            FileSystem::Tmpfs => run(Command::new("mount")
+                .args(["-t", "tmpfs", "-o"])
+                .arg(format!("size={size_mb}m"))
+                .arg("tmpfs")
+                .arg(&mount_point))?,
+            FileSystem::Ext4 => {
+                // Small images default to 1 KiB blocks; use 4 KiB blocks like
+                // a typical disk, so the ACL size limit is the same.
+                run(Command::new("mkfs.ext4")
+                    .args(["-q", "-F", "-b", "4096"])
+                    .arg(&image))?;
+                run(Command::new("mount")
+                    .args(["-t", "ext4", "-o", "loop,acl"])
+                    .arg(&image)
+                    .arg(&mount_point))?;
+            }
+            FileSystem::Xfs => {
+                // XFS always supports ACL's; there is no mount option.
+                run(Command::new("mkfs.xfs").arg("-q").arg("-f").arg(&image))?;
+                run(Command::new("mount")
+                    .args(["-t", "xfs", "-o", "loop"])
+                    .arg(&image)
+                    .arg(&mount_point))?;
+            }
+            FileSystem::Zfs => {
+                let name = pool_name();
+                run(Command::new("zpool")
+                    .args(["create", "-O", "acltype=posixacl", "-O", "xattr=sa", "-m"])
+                    .arg(&mount_point)
+                    .arg(&name)
+                    .arg(&image))?;
+                pool = Some(name);
+            }
+        }
+
+        Ok(ImageMount {
+            file_system,
+            mount_point,
+            pool,
+            _temp: temp,
+        })
+    }
+
+    /// Return the type of the mounted file system.
+    #[must_use]
+    pub const fn file_system(&self) -> FileSystem {
+        self.file_system
+    }
+
+    /// Return the path of the mounted file system.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.mount_point
+    }
+}
+
+impl Drop for ImageMount {
+    fn drop(&mut self) {
+        let result = match &self.pool {
+            Some(pool) => run(Command::new("zpool").arg("destroy").arg(pool)),
+            None => run(Command::new("umount").arg(&self.mount_point)),
+        };
+        if let Err(err) = result {
+            log::warn!("ImageMount: {}", err);
+        }
+    }
+}
+
+/// Return a ZFS pool name that is unique on this system.
+fn pool_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "exacl-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Mount each file system type in turn and call `check` with its type and the
+/// path of the mount.
+///
+/// A file system that can't be mounted, e.g. because its tools are not
+/// installed, is skipped with a warning. Returns the file system types that
+/// were checked, so a test can assert that the ones it relies on were.
+///
+/// # Errors
+///
+/// Returns the first error from `check`, with the file system type prepended
+/// to the message.
+pub fn for_each_file_system<F>(size_mb: u64, mut check: F) -> io::Result<Vec<FileSystem>>
+where
+    F: FnMut(FileSystem, &Path) -> io::Result<()>,
+{
+    let mut checked = Vec::new();
+    for file_system in FileSystem::ALL {
+        let mount = match ImageMount::new(file_system, size_mb) {
+            Ok(mount) => mount,
+            Err(err) => {
+                log::warn!("for_each_file_system: {} skipped: {}", file_system, err);
+                continue;
+            }
+        };
+        check(file_system, mount.path())
+            .map_err(|err| io::Error::new(err.kind(), format!("{file_system}: {err}")))?;
+        checked.push(file_system);
+    }
+
+    Ok(checked)
+}
+
+/// Check the ACL behavior of a directory against the [`Golden`] values of its
+/// file system type.
+///
+/// The checks are:
+///
+/// - `dir` is on a file system of the given type.
+/// - A new file's ACL matches its mode, and a new directory has no default
+///   ACL.
+/// - An ACL that only mirrors the mode is not stored: reading the
+///   `system.posix_acl_access` attribute fails with `ENODATA`.
+/// - [`selftest`](crate::selftest) finds the expected features and a number
+///   of entries in the expected range.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] with kind `InvalidData` that lists every check
+/// that failed, or any error from creating the test files.
+pub fn golden_test(file_system: FileSystem, dir: &Path) -> io::Result<()> {
+    let golden = file_system.golden();
+    let mut failed = Vec::new();
+
+    let actual = fs_type(dir)?;
+    if actual != file_system.magic() {
+        failed.push(format!("file system type is {actual:#x}"));
+    }
+
+    let file = tempfile::NamedTempFile::new_in(dir)?;
+    let mode = file.as_file().metadata()?.permissions().mode() & 0o777;
+    if getfacl(&file, None)? != from_mode(mode) {
+        failed.push("ACL of new file does not match mode".to_string());
+    }
+    if !Acl::read(file.path(), AclOption::empty())?.is_posix() {
+        failed.push("ACL of new file is not Posix.1e".to_string());
+    }
+
+    setfacl(&[file.path()], from_mode(0o640), None)?;
+    match access_acl_errno(file.path()) {
+        Some(libc::ENODATA) => (),
+        errno => failed.push(format!("minimal ACL: expected ENODATA, got {errno:?}")),
+    }
+
+    let subdir = tempfile::tempdir_in(dir)?;
+    if !getfacl(subdir.path(), AclOption::DEFAULT_ACL)?.is_empty() {
+        failed.push("new directory has a default ACL".to_string());
+    }
+
+    let report = selftest(dir)?;
+    if !report.acl {
+        failed.push("named entries not supported".to_string());
+    }
+    if report.default_acl != golden.default_acl {
+        failed.push(format!("default_acl is {}", report.default_acl));
+    }
+    if report.deny != golden.deny {
+        failed.push(format!("deny is {}", report.deny));
+    }
+    match report.max_entries {
+        Some(max) if golden.max_entries.contains(&max) => (),
+        max => failed.push(format!(
+            "max_entries is {max:?}, expected {:?}",
+            golden.max_entries
+        )),
+    }
+
+    if !failed.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", dir.display(), failed.join("; ")),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Return the errno from reading the access ACL attribute of `path`, or None
+/// if it can be read.
+fn access_acl_errno(path: &Path) -> Option<i32> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let ret = unsafe {
+        libc::getxattr(
+            c_path.as_ptr(),
+            ACCESS_ACL_XATTR.as_ptr().cast(),
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret < 0 {
+        return io::Error::last_os_error().raw_os_error();
+    }
+
+    None
+}
+
+/// Run a command and return an error if it doesn't succeed.
+fn run(command: &mut Command) -> io::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("{program}: {err}")))?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{program} failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Return the file system type of `path`, as reported by `statfs`.
+fn fs_type(path: &Path) -> io::Result<libc::c_long> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut buf: libc::statfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::statfs(c_path.as_ptr(), &mut buf) };
+    if ret < 0 {
+        return fail_err(ret, "statfs", &c_path);
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    Ok(buf.f_type as libc::c_long)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod testing_tests {
+    use super::*;
+    use crate::{getfacl, selftest, setfacl, Acl, AclEntry, AclOption, Flag, Perm};
+
+    #[test]
+    fn test_memfd() -> io::Result<()> {
+        let file = memfd("exacl-test")?;
+        let acl = Acl::read_fd(&file, AclOption::empty())?;
+        assert!(acl.entries()?.len() >= 3);
+
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ];
+        Acl::from_entries(&entries)?.write_fd(&file, AclOption::empty())?;
+        assert_eq!(Acl::read_fd(&file, AclOption::empty())?.entries()?, entries);
+
+        assert!(memfd("a\0b").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tmpfs_dir() -> io::Result<()> {
+        let dir = tmpfs_dir()?;
+        assert_eq!(fs_type(dir.path())?, libc::TMPFS_MAGIC);
+
+        // tmpfs allows very large ACL's, so skip `selftest` and check the
+        // default ACL directly.
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE | Perm::EXECUTE, Flag::DEFAULT),
+            AclEntry::allow_group("", Perm::READ, Flag::DEFAULT),
+            AclEntry::allow_other(Perm::empty(), Flag::DEFAULT),
+        ];
+        setfacl(&[dir.path()], &entries, AclOption::DEFAULT_ACL)?;
+        assert_eq!(getfacl(dir.path(), AclOption::DEFAULT_ACL)?, entries);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_system() {
+        let names = FileSystem::ALL.map(|fs| fs.to_string());
+        assert_eq!(names, ["tmpfs", "ext4", "xfs", "zfs"]);
+        assert_eq!(FileSystem::Tmpfs.magic(), libc::TMPFS_MAGIC);
+        assert_eq!(FileSystem::Ext4.magic(), libc::EXT4_SUPER_MAGIC);
+        assert!(FileSystem::ALL.iter().all(|fs| fs.golden().default_acl));
+    }
+
+    #[test]
+    fn test_image_mount() -> io::Result<()> {
+        let mount = match ImageMount::ext4(8) {
+            Ok(mount) => mount,
+            Err(err) => {
+                // Not root, or no loop device in this environment.
+                log::warn!("test_image_mount skipped: {}", err);
+                return Ok(());
+            }
+        };
+        assert_eq!(fs_type(mount.path())?, libc::EXT4_SUPER_MAGIC);
+
+        let report = selftest(mount.path())?;
+        assert!(report.acl);
+        assert!(report.default_acl);
+        assert!(report.max_entries.unwrap() > 4);
+
+        let mount_point = mount.path().to_path_buf();
+        drop(mount);
+        assert!(!mount_point.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,895 @@
+//! Implements operations on the ACL's of many files: walking a directory tree
+//! and applying grants in bulk.
+
+use crate::acl::AclOption;
+use crate::aclentry::{AclEntry, AclEntryKind};
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::editor::Editor;
+use crate::failx::{fail_custom, path_err};
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::record::AclRecord;
+use crate::{getfacl, setfacl};
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Result of [`stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TreeStats {
+    /// Number of files and directories scanned.
+    pub paths: usize,
+
+    /// Number of paths with an extended ACL, i.e. with any entry besides the
+    /// owner, group and other entries that mirror the file mode.
+    pub extended: usize,
+
+    /// Number of entries for each named user or group, keyed by kind and
+    /// name. Unknown ids are reported by their decimal name.
+    pub principals: BTreeMap<(AclEntryKind, String), usize>,
+
+    /// Number of paths with each ACL size, keyed by number of entries.
+    pub entry_counts: BTreeMap<usize, usize>,
+
+    /// Greatest depth below the root of a path with an entry that has flags
+    /// (inheritance flags, or a default entry), or None if there is none. The
+    /// root itself is at depth 0.
+    pub max_flag_depth: Option<usize>,
+
+    /// Paths skipped because permission was denied, with
+    /// [`AclOption::SKIP_ACCESS_DENIED`], in walk order. A directory that was
+    /// read but couldn't be listed is also counted in `paths`; the number of
+    /// paths denied is the length.
+    pub access_denied: Vec<PathBuf>,
+}
+
+/// Summarize the ACL's of `root` and all files and directories below it.
+///
+/// This is useful to estimate the scope of a migration before changing
+/// anything. Symbolic links below `root` are not followed, unless `options`
+/// contains [`AclOption::FOLLOW_DIR_SYMLINKS`]; with
+/// [`AclOption::SKIP_ACCESS_DENIED`], paths that can't be read are skipped
+/// instead of failing. Other options are ignored.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let stats = exacl::stats("./tmp/shared", None)?;
+/// println!("{} of {} paths have extended ACL's", stats.extended, stats.paths);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on the first failure to read a directory or an
+/// ACL.
+pub fn stats<P, O>(root: P, options: O) -> io::Result<TreeStats>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let root = root.as_ref();
+    let mut result = TreeStats::default();
+
+    let denied = walk(
+        root,
+        options.into().unwrap_or_default(),
+        &mut |path, kind| {
+            let entries = getfacl(path, kind)?;
+            result.paths += 1;
+            *result.entry_counts.entry(entries.len()).or_default() += 1;
+            if entries.iter().any(|entry| !is_base_entry(entry)) {
+                result.extended += 1;
+            }
+
+            for entry in &entries {
+                if !entry.name.is_empty()
+                    && matches!(entry.kind, AclEntryKind::User | AclEntryKind::Group)
+                {
+                    let key = (entry.kind, entry.name.clone());
+                    *result.principals.entry(key).or_default() += 1;
+                }
+            }
+
+            if entries.iter().any(|entry| !entry.flags.is_empty()) {
+                let depth = path
+                    .strip_prefix(root)
+                    .map_or(0, |p| p.components().count());
+                result.max_flag_depth = result.max_flag_depth.max(Some(depth));
+            }
+
+            Ok(())
+        },
+    )?;
+
+    result.access_denied = denied;
+    Ok(result)
+}
+
+/// Return true if the entry is an owner, group or other entry that mirrors
+/// the file mode.
+fn is_base_entry(entry: &AclEntry) -> bool {
+    entry.allow
+        && entry.name.is_empty()
+        && entry.flags.is_empty()
+        && matches!(
+            entry.kind,
+            AclEntryKind::User | AclEntryKind::Group | AclEntryKind::Other
+        )
+}
+
+/// Remove every entry for a user or group from the ACL's of `root` and all
+/// files and directories below it. Return the paths whose ACL changed.
+///
+/// `kind` must be [`AclEntryKind::User`] or [`AclEntryKind::Group`]. The name
+/// may be a decimal uid/gid, which also matches entries for a user or group
+/// that has been removed from the database. Entries are removed from both the
+/// access and the default ACL. On Linux and `FreeBSD`, the mask is
+/// recalculated, like `setfacl -x`.
+///
+/// Symbolic links below `root` are not followed, unless `options` contains
+/// [`AclOption::FOLLOW_DIR_SYMLINKS`]; with
+/// [`AclOption::SKIP_ACCESS_DENIED`], paths that can't be read are skipped
+/// instead of failing. Other options are ignored.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{revoke_principal_recursive, AclEntryKind};
+///
+/// for path in revoke_principal_recursive("./tmp/shared", AclEntryKind::User, "bob", None)? {
+///     println!("revoked access to {}", path.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the name can't be resolved, or on the first
+/// failure to read a directory or to read or write an ACL. Paths changed before
+/// the error keep their new ACL.
+pub fn revoke_principal_recursive<P, O>(
+    root: P,
+    kind: AclEntryKind,
+    name: &str,
+    options: O,
+) -> io::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let principal = Principal::new(kind, name)?;
+    let mut changed = Vec::new();
+
+    walk(
+        root.as_ref(),
+        options.into().unwrap_or_default(),
+        &mut |path, kind| {
+            let entries = getfacl(path, kind)?;
+            if !entries.iter().any(|entry| principal.matches(entry)) {
+                return Ok(());
+            }
+
+            setfacl(&[path], principal.remove_from(entries), kind)?;
+            changed.push(path.to_path_buf());
+            Ok(())
+        },
+    )?;
+
+    Ok(changed)
+}
+
+/// Return every entry for a user or group in the ACL's of `root` and all files
+/// and directories below it, with the path of each.
+///
+/// `kind` and `name` identify the principal as in
+/// [`revoke_principal_recursive`]. If `perms` is given, only entries that
+/// include at least one of those permissions are returned. Both allow and deny
+/// entries are returned, from both the access and the default ACL.
+///
+/// Symbolic links below `root` are not followed, unless `options` contains
+/// [`AclOption::FOLLOW_DIR_SYMLINKS`]; with
+/// [`AclOption::SKIP_ACCESS_DENIED`], paths that can't be read are skipped
+/// instead of failing. Other options are ignored.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{find_grants, AclEntryKind, Perm};
+///
+/// for (path, entry) in find_grants("./tmp/shared", AclEntryKind::User, "bob", Perm::WRITE, None)? {
+///     println!("{}: {entry}", path.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the name can't be resolved, or on the first
+/// failure to read a directory or an ACL.
+pub fn find_grants<P, Q, O>(
+    root: P,
+    kind: AclEntryKind,
+    name: &str,
+    perms: Q,
+    options: O,
+) -> io::Result<Vec<(PathBuf, AclEntry)>>
+where
+    P: AsRef<Path>,
+    Q: Into<Option<Perm>>,
+    O: Into<Option<AclOption>>,
+{
+    let principal = Principal::new(kind, name)?;
+    let perms = perms.into();
+    let mut result = Vec::new();
+
+    walk(
+        root.as_ref(),
+        options.into().unwrap_or_default(),
+        &mut |path, kind| {
+            for entry in getfacl(path, kind)? {
+                if principal.matches(&entry) && perms.map_or(true, |p| entry.perms.intersects(p)) {
+                    result.push((path.to_path_buf(), entry));
+                }
+            }
+            Ok(())
+        },
+    )?;
+
+    Ok(result)
+}
+
+/// Return one [`AclRecord`] for each entry in the ACL's of `root` and all files
+/// and directories below it.
+///
+/// Symbolic links below `root` are not followed, unless `options` contains
+/// [`AclOption::FOLLOW_DIR_SYMLINKS`]; with
+/// [`AclOption::SKIP_ACCESS_DENIED`], paths that can't be read are skipped
+/// instead of failing. Other options are ignored. To save the
+/// records as CSV, use `write_csv` (requires the `csv` feature).
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on the first failure to read a directory or an
+/// ACL.
+pub fn flatten_tree<P, O>(root: P, options: O) -> io::Result<Vec<AclRecord>>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let mut result = Vec::new();
+
+    walk(
+        root.as_ref(),
+        options.into().unwrap_or_default(),
+        &mut |path, kind| {
+            for entry in getfacl(path, kind)? {
+                result.push(AclRecord::new(path, &entry));
+            }
+            Ok(())
+        },
+    )?;
+
+    Ok(result)
+}
+
+/// Operation applied to an entry by [`apply_grants`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GrantOp {
+    /// Add the entry, or replace the permissions of an existing entry with the
+    /// same kind, name and flags.
+    Grant,
+
+    /// Remove the entry with the same kind, name and flags.
+    Revoke,
+}
+
+/// Apply a stream of grant and revoke records. Return the paths whose ACL
+/// changed, in sorted order.
+///
+/// Records are grouped by path, so each path's ACL is read and written once,
+/// no matter how many records it has. Records for the same path are applied in
+/// order. Entries are matched by kind, name and flags (and allow/deny on
+/// macOS); names are compared as given, so use the same name that
+/// [`getfacl`](crate::getfacl) reports. On Linux and `FreeBSD`, the mask is
+/// recalculated like `setfacl -m`.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{apply_grants, AclEntry, GrantOp, Perm};
+///
+/// let records = vec![
+///     ("./tmp/a", AclEntry::allow_group("staff", Perm::READ, None), GrantOp::Grant),
+///     ("./tmp/b", AclEntry::allow_user("bob", Perm::empty(), None), GrantOp::Revoke),
+/// ];
+/// apply_grants(records)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on the first failure to read or write an ACL.
+/// Paths are processed in sorted order; paths before the error keep their new
+/// ACL.
+pub fn apply_grants<I, P>(records: I) -> io::Result<Vec<PathBuf>>
+where
+    I: IntoIterator<Item = (P, AclEntry, GrantOp)>,
+    P: Into<PathBuf>,
+{
+    let mut by_path = BTreeMap::<PathBuf, Vec<(AclEntry, GrantOp)>>::new();
+    for (path, entry, op) in records {
+        by_path.entry(path.into()).or_default().push((entry, op));
+    }
+
+    let mut changed = Vec::new();
+    for (path, ops) in by_path {
+        let entries = getfacl(&path, None)?;
+        let new_entries = apply_ops(entries.clone(), ops);
+        if new_entries != entries {
+            setfacl(&[&path], new_entries, None)?;
+            changed.push(path);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Apply grant and revoke operations to a list of entries.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn apply_ops(entries: Vec<AclEntry>, ops: Vec<(AclEntry, GrantOp)>) -> Vec<AclEntry> {
+    let mut editor = Editor::new(entries);
+    for (entry, op) in ops {
+        match op {
+            GrantOp::Grant => editor.set(entry),
+            GrantOp::Revoke => {
+                editor.remove(&entry);
+            }
+        }
+    }
+
+    editor.finish()
+}
+
+/// Apply grant and revoke operations to a list of entries.
+#[cfg(target_os = "macos")]
+fn apply_ops(mut entries: Vec<AclEntry>, ops: Vec<(AclEntry, GrantOp)>) -> Vec<AclEntry> {
+    let is_same = |a: &AclEntry, b: &AclEntry| {
+        a.kind == b.kind && a.name == b.name && a.flags == b.flags && a.allow == b.allow
+    };
+
+    for (entry, op) in ops {
+        match op {
+            GrantOp::Grant => match entries.iter_mut().find(|e| is_same(e, &entry)) {
+                Some(existing) => *existing = entry,
+                None => entries.push(entry),
+            },
+            GrantOp::Revoke => entries.retain(|e| !is_same(e, &entry)),
+        }
+    }
+
+    entries
+}
+
+/// A user or group, resolved to a uid/gid for comparison.
+struct Principal {
+    kind: AclEntryKind,
+    qualifier: Qualifier,
+}
+
+impl Principal {
+    fn new(kind: AclEntryKind, name: &str) -> io::Result<Principal> {
+        if !matches!(kind, AclEntryKind::User | AclEntryKind::Group) {
+            return fail_custom(&format!("unsupported kind: \"{kind}\""));
+        }
+
+        let entry = AclEntry {
+            kind,
+            ..AclEntry::allow_user(name, Perm::empty(), None)
+        };
+        let qualifier = entry.qualifier(false)?;
+
+        Ok(Principal { kind, qualifier })
+    }
+
+    /// Return true if the entry is for this principal.
+    fn matches(&self, entry: &AclEntry) -> bool {
+        entry.kind == self.kind
+            && !entry.name.is_empty()
+            && entry
+                .qualifier(false)
+                .map_or(false, |qualifier| qualifier == self.qualifier)
+    }
+
+    /// Return the entries without the ones for this principal.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn remove_from(&self, entries: Vec<AclEntry>) -> Vec<AclEntry> {
+        // NFSv4 ACL's have no mask; don't add one.
+        let has_mask = entries.iter().any(|e| e.kind == AclEntryKind::Mask);
+        let matching = entries
+            .iter()
+            .filter(|entry| self.matches(entry))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut editor = Editor::new(entries);
+        editor.set_recalc_mask(has_mask);
+        for entry in &matching {
+            editor.remove(entry);
+        }
+
+        editor.finish()
+    }
+
+    /// Return the entries without the ones for this principal.
+    #[cfg(target_os = "macos")]
+    fn remove_from(&self, mut entries: Vec<AclEntry>) -> Vec<AclEntry> {
+        entries.retain(|entry| !self.matches(entry));
+        entries
+    }
+}
+
+/// Call `visit` for `root` and every file and directory below it, with a
+/// hidden option that tells [`getfacl`] and [`setfacl`] whether the path is a
+/// directory, which saves a `stat` per file. Symbolic links below `root` are
+/// skipped, except links to directories inside `root` with
+/// `FOLLOW_DIR_SYMLINKS`. Return the paths skipped with `SKIP_ACCESS_DENIED`.
+fn walk(
+    root: &Path,
+    options: AclOption,
+    visit: &mut dyn FnMut(&Path, AclOption) -> io::Result<()>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut walker = Walker {
+        root: None,
+        ancestors: Vec::new(),
+        skip_denied: options.contains(AclOption::SKIP_ACCESS_DENIED),
+        denied: Vec::new(),
+        visit,
+    };
+
+    if options.contains(AclOption::FOLLOW_DIR_SYMLINKS) && root.is_dir() {
+        let real_root = std::fs::canonicalize(root).map_err(|err| path_err(root, &err))?;
+        walker.root = Some(real_root);
+    }
+
+    walker.walk(root)?;
+    Ok(walker.denied)
+}
+
+/// State of a walk.
+struct Walker<'a> {
+    /// Real path of the root, if symbolic links to directories are followed.
+    root: Option<PathBuf>,
+
+    /// Real paths of the directories being walked, if links are followed.
+    ancestors: Vec<PathBuf>,
+
+    /// True if paths that can't be read because permission is denied are
+    /// skipped.
+    skip_denied: bool,
+
+    /// Paths skipped because permission was denied.
+    denied: Vec<PathBuf>,
+
+    visit: &'a mut dyn FnMut(&Path, AclOption) -> io::Result<()>,
+}
+
+impl Walker<'_> {
+    fn walk(&mut self, path: &Path) -> io::Result<()> {
+        crate::deadline::enter(path);
+        let is_dir = path.is_dir();
+
+        // Skip a link back to a directory being walked.
+        if is_dir && self.root.is_some() {
+            let real_path = std::fs::canonicalize(path).map_err(|err| path_err(path, &err))?;
+            if self.ancestors.contains(&real_path) {
+                return Ok(());
+            }
+            self.ancestors.push(real_path);
+        }
+
+        let result = self.visit_path(path, is_dir);
+
+        if is_dir && self.root.is_some() {
+            self.ancestors.pop();
+        }
+
+        result
+    }
+
+    fn visit_path(&mut self, path: &Path, is_dir: bool) -> io::Result<()> {
+        match (self.visit)(path, AclOption::known_kind(is_dir)) {
+            Err(err) if self.skips(&err) => {
+                self.deny(path, &err);
+                return Ok(());
+            }
+            result => result?,
+        }
+
+        if is_dir {
+            self.walk_children(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn walk_children(&mut self, path: &Path) -> io::Result<()> {
+        let dirents = match std::fs::read_dir(path) {
+            Ok(dirents) => dirents,
+            Err(err) if self.skips(&err) => {
+                self.deny(path, &err);
+                return Ok(());
+            }
+            Err(err) => return Err(path_err(path, &err)),
+        };
+
+        let mut children = Vec::new();
+        for dirent in dirents {
+            let dirent = dirent.map_err(|err| path_err(path, &err))?;
+            let file_type = dirent.file_type().map_err(|err| path_err(path, &err))?;
+            if !file_type.is_symlink() || self.follows(&dirent.path()) {
+                children.push(dirent.path());
+            }
+        }
+
+        // Visit children in a stable order.
+        children.sort();
+        for child in children {
+            self.walk(&child)?;
+        }
+
+        Ok(())
+    }
+
+    /// Return true if the error should skip a path rather than end the walk.
+    fn skips(&self, err: &io::Error) -> bool {
+        self.skip_denied && err.kind() == io::ErrorKind::PermissionDenied
+    }
+
+    /// Record a path skipped because permission was denied.
+    fn deny(&mut self, path: &Path, err: &io::Error) {
+        log::warn!("skipped {path:?}: {err}");
+        self.denied.push(path.to_path_buf());
+    }
+
+    /// Return true if `link` should be followed: it leads to a directory
+    /// inside the root. Broken links are not followed.
+    fn follows(&self, link: &Path) -> bool {
+        let Some(root) = &self.root else {
+            return false;
+        };
+
+        std::fs::canonicalize(link).is_ok_and(|target| target.is_dir() && target.starts_with(root))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tree_tests {
+    use super::*;
+    use crate::flag::Flag;
+
+    fn make_tree() -> io::Result<tempfile::TempDir> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("a"), "")?;
+        std::fs::write(dir.path().join("sub/b"), "")?;
+        std::fs::write(dir.path().join("sub/c"), "")?;
+        Ok(dir)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn add_entry(path: &Path, entry: AclEntry) -> io::Result<()> {
+        let mut editor = Editor::new(getfacl(path, None)?);
+        editor.set(entry);
+        setfacl(&[path], editor.finish(), None)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn add_entry(path: &Path, entry: AclEntry) -> io::Result<()> {
+        let mut entries = getfacl(path, None)?;
+        entries.push(entry);
+        setfacl(&[path], entries, None)
+    }
+
+    #[test]
+    fn test_revoke_principal_recursive() -> io::Result<()> {
+        let dir = make_tree()?;
+        let root = dir.path();
+        let rw = Perm::READ | Perm::WRITE;
+        add_entry(root, AclEntry::allow_user("11501", Perm::READ, None))?;
+        add_entry(&root.join("sub/b"), AclEntry::allow_user("11501", rw, None))?;
+        add_entry(&root.join("sub/c"), AclEntry::allow_user("11502", rw, None))?;
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        {
+            let sub = root.join("sub");
+            let mut editor = Editor::new(getfacl(&sub, None)?);
+            for entry in crate::from_mode(0o750) {
+                let flags = Flag::DEFAULT;
+                editor.set(AclEntry { flags, ..entry });
+            }
+            editor.set(AclEntry::allow_user("11501", rw, Flag::DEFAULT));
+            setfacl(&[&sub], editor.finish(), None)?;
+        }
+
+        let changed = revoke_principal_recursive(root, AclEntryKind::User, "11501", None)?;
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let expected = vec![root.to_path_buf(), root.join("sub"), root.join("sub/b")];
+        #[cfg(target_os = "macos")]
+        let expected = vec![root.to_path_buf(), root.join("sub/b")];
+        assert_eq!(changed, expected);
+
+        for path in &changed {
+            let entries = getfacl(path, None)?;
+            assert!(!entries.iter().any(|entry| entry.name == "11501"));
+        }
+        let entries = getfacl(root.join("sub/c"), None)?;
+        assert!(entries.iter().any(|entry| entry.name == "11502"));
+
+        // A second pass changes nothing.
+        let changed = revoke_principal_recursive(root, AclEntryKind::User, "11501", None)?;
+        assert!(changed.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_grants() -> io::Result<()> {
+        let dir = make_tree()?;
+        let root = dir.path();
+        let rw = Perm::READ | Perm::WRITE;
+        add_entry(
+            &root.join("a"),
+            AclEntry::allow_group("11501", Perm::READ, None),
+        )?;
+        add_entry(
+            &root.join("sub/b"),
+            AclEntry::allow_group("11501", rw, None),
+        )?;
+        add_entry(&root.join("sub/c"), AclEntry::allow_user("11501", rw, None))?;
+
+        let grants = find_grants(root, AclEntryKind::Group, "11501", None, None)?;
+        let paths = grants
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec![root.join("a"), root.join("sub/b")]);
+        assert_eq!(grants[1].1.kind, AclEntryKind::Group);
+        assert_eq!(grants[1].1.perms, rw);
+
+        let grants = find_grants(root, AclEntryKind::Group, "11501", Perm::WRITE, None)?;
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].0, root.join("sub/b"));
+
+        let grants = find_grants(root.join("a"), AclEntryKind::User, "11501", None, None)?;
+        assert!(grants.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> io::Result<()> {
+        let dir = make_tree()?;
+        let root = dir.path();
+        add_entry(
+            &root.join("a"),
+            AclEntry::allow_group("11501", Perm::READ, None),
+        )?;
+        add_entry(
+            &root.join("sub/b"),
+            AclEntry::allow_group("11501", Perm::READ, None),
+        )?;
+        add_entry(
+            &root.join("sub/b"),
+            AclEntry::allow_user("11502", Perm::READ, None),
+        )?;
+
+        let stats = stats(root, None)?;
+        assert_eq!(stats.paths, 5);
+        assert_eq!(stats.extended, 2);
+        assert_eq!(
+            stats.principals,
+            BTreeMap::from([
+                ((AclEntryKind::User, "11502".to_string()), 1),
+                ((AclEntryKind::Group, "11501".to_string()), 2),
+            ])
+        );
+        assert_eq!(stats.max_flag_depth, None);
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        assert_eq!(stats.entry_counts, BTreeMap::from([(3, 3), (5, 1), (6, 1)]));
+        #[cfg(target_os = "macos")]
+        assert_eq!(stats.entry_counts, BTreeMap::from([(0, 3), (1, 1), (2, 1)]));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_stats_flag_depth() -> io::Result<()> {
+        let dir = make_tree()?;
+        let sub = dir.path().join("sub");
+        let mut editor = Editor::new(getfacl(&sub, None)?);
+        for entry in crate::from_mode(0o750) {
+            let flags = Flag::DEFAULT;
+            editor.set(AclEntry { flags, ..entry });
+        }
+        setfacl(&[&sub], editor.finish(), None)?;
+
+        let stats = stats(dir.path(), None)?;
+        assert_eq!(stats.max_flag_depth, Some(1));
+        assert_eq!(stats.extended, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten_tree() -> io::Result<()> {
+        let dir = make_tree()?;
+        let root = dir.path();
+        add_entry(
+            &root.join("sub/b"),
+            AclEntry::allow_user("11501", Perm::READ, None),
+        )?;
+
+        let records = flatten_tree(root, None)?;
+        let record = records.iter().find(|r| r.name == "11501").unwrap();
+        assert_eq!(record.path, root.join("sub/b"));
+        assert_eq!(record.kind, AclEntryKind::User);
+        assert_eq!(record.perm_names, "read");
+
+        let count = |path: PathBuf| records.iter().filter(|r| r.path == path).count();
+        assert_eq!(
+            count(root.join("sub/b")),
+            getfacl(root.join("sub/b"), None)?.len()
+        );
+        assert_eq!(count(root.join("a")), getfacl(root.join("a"), None)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_grants() -> io::Result<()> {
+        let dir = make_tree()?;
+        let root = dir.path();
+        let (a, b, c) = (root.join("a"), root.join("sub/b"), root.join("sub/c"));
+        add_entry(&b, AclEntry::allow_user("11501", Perm::READ, None))?;
+        let c_entries = getfacl(&c, None)?;
+
+        let rw = Perm::READ | Perm::WRITE;
+        let records = vec![
+            (
+                b.clone(),
+                AclEntry::allow_user("11501", Perm::empty(), None),
+                GrantOp::Revoke,
+            ),
+            (
+                a.clone(),
+                AclEntry::allow_group("11501", Perm::READ, None),
+                GrantOp::Grant,
+            ),
+            (
+                b.clone(),
+                AclEntry::allow_user("11502", rw, None),
+                GrantOp::Grant,
+            ),
+            (
+                a.clone(),
+                AclEntry::allow_group("11501", rw, None),
+                GrantOp::Grant,
+            ),
+            (
+                c.clone(),
+                AclEntry::allow_user("11503", Perm::READ, None),
+                GrantOp::Revoke,
+            ),
+        ];
+        let changed = apply_grants(records)?;
+        assert_eq!(changed, vec![a.clone(), b.clone()]);
+
+        let named = |path: &Path| -> io::Result<Vec<(String, Perm)>> {
+            Ok(getfacl(path, None)?
+                .into_iter()
+                .filter(|e| !e.name.is_empty())
+                .map(|e| (e.name, e.perms))
+                .collect())
+        };
+        assert_eq!(named(&a)?, vec![("11501".to_string(), rw)]);
+        assert_eq!(named(&b)?, vec![("11502".to_string(), rw)]);
+        assert_eq!(getfacl(&c, None)?, c_entries);
+        Ok(())
+    }
+
+    #[test]
+    fn test_revoke_principal_recursive_errors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let err =
+            revoke_principal_recursive(&dir, AclEntryKind::User, "no_such_user", None).unwrap_err();
+        assert_eq!(err.to_string(), "unknown user name: \"no_such_user\"");
+
+        let err = revoke_principal_recursive(&dir, AclEntryKind::Unknown, "x", None).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported kind: \"unknown\"");
+
+        let err = revoke_principal_recursive("/non-existant", AclEntryKind::User, "11501", None)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_walk_symlinks() -> io::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let dir = make_tree()?;
+        let outside = tempfile::TempDir::new()?;
+        let root = dir.path();
+        symlink(root.join("sub"), root.join("link_sub"))?;
+        symlink(root.join("a"), root.join("link_a"))?;
+        symlink(outside.path(), root.join("link_out"))?;
+        symlink(root, root.join("sub/loop"))?;
+        symlink(root.join("missing"), root.join("broken"))?;
+
+        let paths = |options: AclOption| -> io::Result<Vec<PathBuf>> {
+            let mut result = Vec::new();
+            walk(root, options, &mut |path, _| {
+                result.push(path.strip_prefix(root).unwrap().to_path_buf());
+                Ok(())
+            })?;
+            Ok(result)
+        };
+
+        let expected = ["", "a", "sub", "sub/b", "sub/c"].map(PathBuf::from);
+        assert_eq!(paths(AclOption::empty())?, expected);
+
+        let expected = [
+            "",
+            "a",
+            "link_sub",
+            "link_sub/b",
+            "link_sub/c",
+            "sub",
+            "sub/b",
+            "sub/c",
+        ]
+        .map(PathBuf::from);
+        assert_eq!(paths(AclOption::FOLLOW_DIR_SYMLINKS)?, expected);
+
+        let stats = stats(root, AclOption::FOLLOW_DIR_SYMLINKS)?;
+        assert_eq!(stats.paths, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_access_denied() -> io::Result<()> {
+        let dir = make_tree()?;
+        let root = dir.path();
+        let (a, sub) = (root.join("a"), root.join("sub"));
+
+        // Deny access to `a` and `sub`, as if read by an unprivileged user.
+        let scan = |options: AclOption| -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+            let mut visited = Vec::new();
+            let denied = walk(root, options, &mut |path, _| {
+                if path == a || path == sub {
+                    return Err(path_err(path, &io::ErrorKind::PermissionDenied.into()));
+                }
+                visited.push(path.to_path_buf());
+                Ok(())
+            })?;
+            Ok((visited, denied))
+        };
+
+        let err = scan(AclOption::empty()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let (visited, denied) = scan(AclOption::SKIP_ACCESS_DENIED)?;
+        assert_eq!(visited, [root.to_path_buf()]);
+        assert_eq!(denied, [a.clone(), sub.clone()]);
+
+        // Other errors still end the walk.
+        let mut fail = |_: &Path, _| Err(io::ErrorKind::NotFound.into());
+        assert!(walk(root, AclOption::SKIP_ACCESS_DENIED, &mut fail).is_err());
+
+        let stats = stats(root, AclOption::SKIP_ACCESS_DENIED)?;
+        assert_eq!(stats.paths, 5);
+        assert!(stats.access_denied.is_empty());
+        Ok(())
+    }
+}
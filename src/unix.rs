@@ -1,20 +1,47 @@
-//! Implements utilities for converting user/group names to uid/gid.
+//! Converts between user/group names and uid/gid.
+//!
+//! Each lookup queries the user and group databases, which may involve a
+//! round trip to a directory service (NSS, LDAP, Open Directory). Programs
+//! that resolve many names can warm an internal cache in one pass with
+//! [`prefetch_names`] and [`prefetch_ids`]. Later lookups of the same names or
+//! ids are answered from the cache until [`clear_cache`] is called.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::unix::prefetch_names;
+//!
+//! prefetch_names(&["alice", "bob", "staff"])?;
+//! # Ok(()) }
+//! ```
+//!
+//! To replace the databases, install a [`NameResolver`] with
+//! [`set_resolver`]. Wrap it in a [`CachingResolver`] to remember every
+//! answer:
+//!
+//! ```
+//! use exacl::unix::{set_resolver, CachingResolver, SystemResolver};
+//!
+//! set_resolver(CachingResolver::new(SystemResolver));
+//! ```
 
 use crate::failx::*;
 use crate::sys::{getgrgid_r, getgrnam_r, getpwnam_r, getpwuid_r, group, passwd, sg};
 #[cfg(target_os = "macos")]
-use crate::sys::{id_t, mbr_gid_to_uuid, mbr_uid_to_uuid, mbr_uuid_to_id};
+use crate::sys::{id_t, mbr_check_membership, mbr_gid_to_uuid, mbr_uid_to_uuid, mbr_uuid_to_id};
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::io;
 use std::mem;
 use std::os::raw::c_char;
 use std::ptr;
+use std::sync::{Arc, Mutex, RwLock};
 #[cfg(target_os = "macos")]
 use uuid::Uuid;
 
 // Export uid_t and gid_t.
-pub use crate::sys::{gid_t, uid_t};
+pub(crate) use crate::sys::{gid_t, uid_t};
 
 // Max buffer sizes for getpwnam_r, getgrnam_r, et al. are usually determined
 // by calling sysconf with SC_GETPW_R_SIZE_MAX or SC_GETGR_R_SIZE_MAX. Rather
@@ -27,8 +54,367 @@ pub use crate::sys::{gid_t, uid_t};
 const INITIAL_BUFSIZE: usize = 4096; // 4KB
 const MAX_BUFSIZE: usize = 1_048_576; // 1MB
 
+/// Results of earlier lookups, filled in by `prefetch_names`/`prefetch_ids`.
+#[derive(Default)]
+struct NameCache {
+    uids: HashMap<String, Option<uid_t>>,
+    gids: HashMap<String, Option<gid_t>>,
+    user_names: HashMap<uid_t, String>,
+    group_names: HashMap<gid_t, String>,
+}
+
+static NAME_CACHE: Mutex<Option<NameCache>> = Mutex::new(None);
+
+/// Run `func` on the name cache, if it exists.
+fn with_cache<T>(func: impl FnOnce(&NameCache) -> Option<T>) -> Option<T> {
+    let cache = NAME_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    cache.as_ref().and_then(func)
+}
+
+/// Names of ids looked up by one batch call such as `getfacl_map`.
+#[derive(Default)]
+struct BatchNames {
+    user_names: HashMap<uid_t, String>,
+    group_names: HashMap<gid_t, String>,
+}
+
+thread_local! {
+    /// Names looked up on this thread while `with_batch_cache` is running.
+    static BATCH_NAMES: RefCell<Option<BatchNames>> = const { RefCell::new(None) };
+}
+
+/// Run `func` with a cache of id to name lookups that lasts until it returns.
+///
+/// Unlike the prefetch cache, the batch cache is filled in on demand, so each
+/// id is looked up at most once per batch. Nested calls share the outer
+/// batch.
+pub(crate) fn with_batch_cache<T>(func: impl FnOnce() -> T) -> T {
+    /// Remove the batch cache when the outermost batch ends, even on panic.
+    struct Batch;
+
+    impl Drop for Batch {
+        fn drop(&mut self) {
+            BATCH_NAMES.with(|names| names.borrow_mut().take());
+        }
+    }
+
+    let outermost = BATCH_NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        let outermost = names.is_none();
+        if outermost {
+            *names = Some(BatchNames::default());
+        }
+        outermost
+    });
+    let _batch = if outermost { Some(Batch) } else { None };
+    func()
+}
+
+/// Look up the name of an id in the batch cache, or with `lookup` if it is
+/// not cached. The result is cached if a batch is running.
+fn batch_lookup(
+    id: u32,
+    field: fn(&mut BatchNames) -> &mut HashMap<u32, String>,
+    lookup: fn(u32) -> io::Result<String>,
+) -> io::Result<String> {
+    BATCH_NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        let Some(names) = names.as_mut().map(field) else {
+            return lookup(id);
+        };
+        if let Some(name) = names.get(&id) {
+            return Ok(name.clone());
+        }
+        let name = lookup(id)?;
+        names.insert(id, name.clone());
+        Ok(name)
+    })
+}
+
+/// Look up user and group names and save the results in the cache.
+///
+/// Each name is looked up in both the user and group databases. Names that
+/// are not found are cached too, so later lookups of the same name don't
+/// query the databases again.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a lookup fails. Names resolved before the
+/// failure stay in the cache.
+pub fn prefetch_names<S: AsRef<str>>(names: &[S]) -> io::Result<()> {
+    let mut uids = HashMap::with_capacity(names.len());
+    let mut gids = HashMap::with_capacity(names.len());
+    let mut result = Ok(());
+
+    for name in names {
+        let name = name.as_ref();
+        if uids.contains_key(name) {
+            continue;
+        }
+        match (lookup_uid(name), lookup_gid(name)) {
+            (Ok(uid), Ok(gid)) => {
+                uids.insert(name.to_string(), uid);
+                gids.insert(name.to_string(), gid);
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+
+    let mut cache = NAME_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    let cache = cache.get_or_insert_with(NameCache::default);
+    cache.uids.extend(uids);
+    cache.gids.extend(gids);
+
+    result
+}
+
+/// Look up the user and group names of numeric ids and save the results in
+/// the cache.
+///
+/// Each id is looked up as both a uid and a gid.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a lookup fails. Ids resolved before the
+/// failure stay in the cache.
+pub fn prefetch_ids(ids: &[u32]) -> io::Result<()> {
+    let mut user_names = HashMap::with_capacity(ids.len());
+    let mut group_names = HashMap::with_capacity(ids.len());
+    let mut result = Ok(());
+
+    for &id in ids {
+        if user_names.contains_key(&id) {
+            continue;
+        }
+        match (lookup_user_name(id), lookup_group_name(id)) {
+            (Ok(user), Ok(group)) => {
+                user_names.insert(id, user);
+                group_names.insert(id, group);
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+
+    let mut cache = NAME_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    let cache = cache.get_or_insert_with(NameCache::default);
+    cache.user_names.extend(user_names);
+    cache.group_names.extend(group_names);
+
+    result
+}
+
+/// Discard all names and ids saved by [`prefetch_names`] and [`prefetch_ids`].
+pub fn clear_cache() {
+    let mut cache = NAME_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    *cache = None;
+}
+
+/// Looks up user and group names and ids.
+///
+/// Every method has a default that queries the system databases, so a
+/// resolver only needs to override the lookups it changes.
+pub trait NameResolver: Send + Sync {
+    /// Return the name of a uid, or the uid in decimal if it has no name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    fn user_name(&self, uid: u32) -> io::Result<String> {
+        lookup_user_name(uid)
+    }
+
+    /// Return the name of a gid, or the gid in decimal if it has no name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    fn group_name(&self, gid: u32) -> io::Result<String> {
+        lookup_group_name(gid)
+    }
+
+    /// Return the uid of a user name, or None if there is no such user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    fn user_id(&self, name: &str) -> io::Result<Option<u32>> {
+        lookup_uid(name)
+    }
+
+    /// Return the gid of a group name, or None if there is no such group.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    fn group_id(&self, name: &str) -> io::Result<Option<u32>> {
+        lookup_gid(name)
+    }
+}
+
+/// Resolver that queries the system user and group databases.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemResolver;
+
+impl NameResolver for SystemResolver {}
+
+/// Resolver that names every user and group by its decimal id.
+///
+/// Names are still converted to ids with the system databases.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NumericResolver;
+
+impl NameResolver for NumericResolver {
+    fn user_name(&self, uid: u32) -> io::Result<String> {
+        Ok(uid.to_string())
+    }
+
+    fn group_name(&self, gid: u32) -> io::Result<String> {
+        Ok(gid.to_string())
+    }
+}
+
+/// Resolver that remembers the answers of another resolver.
+///
+/// Failed lookups are not remembered.
+#[derive(Debug, Default)]
+pub struct CachingResolver<R> {
+    inner: R,
+    cache: Mutex<ResolverCache>,
+}
+
+#[derive(Debug, Default)]
+struct ResolverCache {
+    user_names: HashMap<u32, String>,
+    group_names: HashMap<u32, String>,
+    uids: HashMap<String, Option<u32>>,
+    gids: HashMap<String, Option<u32>>,
+}
+
+impl<R: NameResolver> CachingResolver<R> {
+    /// Construct a caching resolver that asks `inner` about each name or id
+    /// once.
+    pub fn new(inner: R) -> CachingResolver<R> {
+        CachingResolver {
+            inner,
+            cache: Mutex::new(ResolverCache::default()),
+        }
+    }
+
+    /// Return the answer in `field` of the cache, or ask `lookup` and save
+    /// the answer.
+    fn cached<K, V>(
+        &self,
+        key: &K,
+        field: fn(&mut ResolverCache) -> &mut HashMap<K::Owned, V>,
+        lookup: impl FnOnce() -> io::Result<V>,
+    ) -> io::Result<V>
+    where
+        K: ToOwned + std::hash::Hash + Eq + ?Sized,
+        K::Owned: std::hash::Hash + Eq + std::borrow::Borrow<K>,
+        V: Clone,
+    {
+        let lock = || self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(value) = field(&mut lock()).get(key) {
+            return Ok(value.clone());
+        }
+
+        // Don't hold the lock during the lookup.
+        let value = lookup()?;
+        field(&mut lock()).insert(key.to_owned(), value.clone());
+        Ok(value)
+    }
+}
+
+impl<R: NameResolver> NameResolver for CachingResolver<R> {
+    fn user_name(&self, uid: u32) -> io::Result<String> {
+        self.cached(&uid, |c| &mut c.user_names, || self.inner.user_name(uid))
+    }
+
+    fn group_name(&self, gid: u32) -> io::Result<String> {
+        self.cached(&gid, |c| &mut c.group_names, || self.inner.group_name(gid))
+    }
+
+    fn user_id(&self, name: &str) -> io::Result<Option<u32>> {
+        self.cached(name, |c| &mut c.uids, || self.inner.user_id(name))
+    }
+
+    fn group_id(&self, name: &str) -> io::Result<Option<u32>> {
+        self.cached(name, |c| &mut c.gids, || self.inner.group_id(name))
+    }
+}
+
+static RESOLVER: RwLock<Option<Arc<dyn NameResolver>>> = RwLock::new(None);
+
+/// Use `resolver` for every user and group lookup in this process, in place
+/// of the system databases.
+///
+/// While a resolver is installed, the cache filled in by [`prefetch_names`]
+/// and [`prefetch_ids`] is not used.
+pub fn set_resolver<R: NameResolver + 'static>(resolver: R) {
+    let mut current = RESOLVER.write().unwrap_or_else(|err| err.into_inner());
+    *current = Some(Arc::new(resolver));
+}
+
+/// Remove the resolver installed by [`set_resolver`], and go back to the
+/// system databases.
+pub fn reset_resolver() {
+    let mut current = RESOLVER.write().unwrap_or_else(|err| err.into_inner());
+    *current = None;
+}
+
+/// Return the installed resolver, if any.
+fn resolver() -> Option<Arc<dyn NameResolver>> {
+    let current = RESOLVER.read().unwrap_or_else(|err| err.into_inner());
+    current.clone()
+}
+
 /// Convert user name to uid.
-pub fn name_to_uid(name: &str) -> io::Result<uid_t> {
+///
+/// If the name is not in the user database, try to parse it as a decimal uid.
+pub(crate) fn name_to_uid(name: &str) -> io::Result<uid_t> {
+    if let Some(uid) = find_uid(name)? {
+        return Ok(uid);
+    }
+
+    // Try to parse name as a decimal user ID.
+    if let Ok(num) = name.parse::<u32>() {
+        return Ok(num);
+    }
+
+    fail_custom(&format!("unknown user name: {name:?}"))
+}
+
+/// Convert user name to uid, without falling back to a decimal uid.
+pub(crate) fn name_to_uid_strict(name: &str) -> io::Result<uid_t> {
+    match find_uid(name)? {
+        Some(uid) => Ok(uid),
+        None => fail_not_found(&format!("unknown user name: {name:?}")),
+    }
+}
+
+/// Look up user name in the user database, without any numeric fallback.
+pub(crate) fn find_uid(name: &str) -> io::Result<Option<uid_t>> {
+    if let Some(resolver) = resolver() {
+        return resolver.user_id(name);
+    }
+    match with_cache(|cache| cache.uids.get(name).copied()) {
+        Some(uid) => Ok(uid),
+        None => lookup_uid(name),
+    }
+}
+
+fn lookup_uid(name: &str) -> io::Result<Option<uid_t>> {
+    Ok(lookup_user(name)?.map(|(uid, _)| uid))
+}
+
+/// Look up the uid and primary gid of a user name in the user database.
+pub(crate) fn lookup_user(name: &str) -> io::Result<Option<(uid_t, gid_t)>> {
     let mut pwd = mem::MaybeUninit::<passwd>::uninit();
     let mut buf = Vec::<c_char>::with_capacity(INITIAL_BUFSIZE);
     let mut result = ptr::null_mut();
@@ -58,21 +444,50 @@ pub fn name_to_uid(name: &str) -> io::Result<uid_t> {
         return fail_err(ret, "getpwnam_r", name);
     }
 
-    if !result.is_null() {
-        let uid = unsafe { pwd.assume_init().pw_uid };
-        return Ok(uid);
+    if result.is_null() {
+        return Ok(None);
     }
 
-    // Try to parse name as a decimal user ID.
+    let pwd = unsafe { pwd.assume_init() };
+    Ok(Some((pwd.pw_uid, pwd.pw_gid)))
+}
+
+/// Convert group name to gid.
+///
+/// If the name is not in the group database, try to parse it as a decimal gid.
+pub(crate) fn name_to_gid(name: &str) -> io::Result<gid_t> {
+    if let Some(gid) = find_gid(name)? {
+        return Ok(gid);
+    }
+
+    // Try to parse name as a decimal group ID.
     if let Ok(num) = name.parse::<u32>() {
         return Ok(num);
     }
 
-    fail_custom(&format!("unknown user name: {name:?}"))
+    fail_custom(&format!("unknown group name: {name:?}"))
 }
 
-/// Convert group name to gid.
-pub fn name_to_gid(name: &str) -> io::Result<gid_t> {
+/// Convert group name to gid, without falling back to a decimal gid.
+pub(crate) fn name_to_gid_strict(name: &str) -> io::Result<gid_t> {
+    match find_gid(name)? {
+        Some(gid) => Ok(gid),
+        None => fail_not_found(&format!("unknown group name: {name:?}")),
+    }
+}
+
+/// Look up group name in the group database, without any numeric fallback.
+pub(crate) fn find_gid(name: &str) -> io::Result<Option<gid_t>> {
+    if let Some(resolver) = resolver() {
+        return resolver.group_id(name);
+    }
+    match with_cache(|cache| cache.gids.get(name).copied()) {
+        Some(gid) => Ok(gid),
+        None => lookup_gid(name),
+    }
+}
+
+fn lookup_gid(name: &str) -> io::Result<Option<gid_t>> {
     let mut grp = mem::MaybeUninit::<group>::uninit();
     let mut buf = Vec::<c_char>::with_capacity(INITIAL_BUFSIZE);
     let mut result = ptr::null_mut();
@@ -102,21 +517,26 @@ pub fn name_to_gid(name: &str) -> io::Result<gid_t> {
         return fail_err(ret, "getgrnam_r", name);
     }
 
-    if !result.is_null() {
-        let gid = unsafe { grp.assume_init().gr_gid };
-        return Ok(gid);
+    if result.is_null() {
+        return Ok(None);
     }
 
-    // Try to parse name as a decimal group ID.
-    if let Ok(num) = name.parse::<u32>() {
-        return Ok(num);
-    }
-
-    fail_custom(&format!("unknown group name: {name:?}"))
+    let gid = unsafe { grp.assume_init().gr_gid };
+    Ok(Some(gid))
 }
 
 /// Convert uid to user name.
-pub fn uid_to_name(uid: uid_t) -> io::Result<String> {
+pub(crate) fn uid_to_name(uid: uid_t) -> io::Result<String> {
+    if let Some(resolver) = resolver() {
+        return resolver.user_name(uid);
+    }
+    match with_cache(|cache| cache.user_names.get(&uid).cloned()) {
+        Some(name) => Ok(name),
+        None => batch_lookup(uid, |names| &mut names.user_names, lookup_user_name),
+    }
+}
+
+fn lookup_user_name(uid: uid_t) -> io::Result<String> {
     let mut pwd = mem::MaybeUninit::<passwd>::uninit();
     let mut buf = Vec::<c_char>::with_capacity(INITIAL_BUFSIZE);
     let mut result = ptr::null_mut();
@@ -154,7 +574,17 @@ pub fn uid_to_name(uid: uid_t) -> io::Result<String> {
 }
 
 /// Convert gid to group name.
-pub fn gid_to_name(gid: gid_t) -> io::Result<String> {
+pub(crate) fn gid_to_name(gid: gid_t) -> io::Result<String> {
+    if let Some(resolver) = resolver() {
+        return resolver.group_name(gid);
+    }
+    match with_cache(|cache| cache.group_names.get(&gid).cloned()) {
+        Some(name) => Ok(name),
+        None => batch_lookup(gid, |names| &mut names.group_names, lookup_group_name),
+    }
+}
+
+fn lookup_group_name(gid: gid_t) -> io::Result<String> {
     let mut grp = mem::MaybeUninit::<group>::uninit();
     let mut buf = Vec::<c_char>::with_capacity(INITIAL_BUFSIZE);
     let mut result = ptr::null_mut();
@@ -193,7 +623,7 @@ pub fn gid_to_name(gid: gid_t) -> io::Result<String> {
 
 /// Convert uid to GUID.
 #[cfg(target_os = "macos")]
-pub fn uid_to_guid(uid: uid_t) -> io::Result<Uuid> {
+pub(crate) fn uid_to_guid(uid: uid_t) -> io::Result<Uuid> {
     let mut bytes = [0u8; 16];
 
     // On error, returns one of {EIO, ENOENT, EAUTH, EINVAL, ENOMEM}.
@@ -207,7 +637,7 @@ pub fn uid_to_guid(uid: uid_t) -> io::Result<Uuid> {
 
 /// Convert gid to GUID.
 #[cfg(target_os = "macos")]
-pub fn gid_to_guid(gid: gid_t) -> io::Result<Uuid> {
+pub(crate) fn gid_to_guid(gid: gid_t) -> io::Result<Uuid> {
     let mut bytes = [0u8; 16];
 
     // On error, returns one of {EIO, ENOENT, EAUTH, EINVAL, ENOMEM}.
@@ -219,12 +649,30 @@ pub fn gid_to_guid(gid: gid_t) -> io::Result<Uuid> {
     Ok(Uuid::from_bytes(bytes))
 }
 
+/// Return true if the user is a member of the group, directly or through
+/// nested groups, the way the kernel checks membership.
+#[cfg(target_os = "macos")]
+pub(crate) fn check_membership(uid: uid_t, gid: gid_t) -> io::Result<bool> {
+    let mut user = uid_to_guid(uid)?.into_bytes();
+    let mut group = gid_to_guid(gid)?.into_bytes();
+    let mut is_member = 0;
+
+    // On error, returns one of {EIO, ENOENT, EAUTH, EINVAL, ENOMEM}.
+    let ret =
+        unsafe { mbr_check_membership(user.as_mut_ptr(), group.as_mut_ptr(), &mut is_member) };
+    if ret != 0 {
+        return fail_from_err(ret, "mbr_check_membership", uid);
+    }
+
+    Ok(is_member != 0)
+}
+
 /// Convert GUID to uid/gid.
 ///
 /// Returns a pair of options (Option[uid], Option[gid]). Either one option must
 /// be set or neither is set. If neither is set, the GUID was not found.
 #[cfg(target_os = "macos")]
-pub fn guid_to_id(guid: Uuid) -> io::Result<(Option<uid_t>, Option<gid_t>)> {
+pub(crate) fn guid_to_id(guid: Uuid) -> io::Result<(Option<uid_t>, Option<gid_t>)> {
     let mut id_c: id_t = 0;
     let mut idtype: i32 = 0;
     let mut bytes = guid.into_bytes();
@@ -299,6 +747,14 @@ mod unix_tests {
         }
     }
 
+    #[test]
+    fn test_name_to_uid_strict() {
+        let msg = name_to_uid_strict("500").unwrap_err().to_string();
+        assert_eq!(msg, "unknown user name: \"500\"");
+        assert_eq!(find_uid("non_existant").ok(), Some(None));
+        assert_eq!(name_to_uid_strict("root").ok(), Some(0));
+    }
+
     #[test]
     fn test_name_to_gid() {
         let msg = name_to_gid("").unwrap_err().to_string();
@@ -347,6 +803,29 @@ mod unix_tests {
         }
     }
 
+    #[test]
+    fn test_prefetch() {
+        prefetch_names(&["root", "non_existant", "root"]).unwrap();
+        assert_eq!(with_cache(|c| c.uids.get("root").copied()), Some(Some(0)));
+        assert_eq!(
+            with_cache(|c| c.gids.get("non_existant").copied()),
+            Some(None)
+        );
+        assert_eq!(name_to_uid("root").ok(), Some(0));
+        assert!(name_to_uid_strict("non_existant").is_err());
+
+        prefetch_ids(&[0, 1500]).unwrap();
+        assert_eq!(
+            with_cache(|c| c.user_names.get(&0).cloned()).as_deref(),
+            Some("root")
+        );
+        assert_eq!(uid_to_name(1500).unwrap(), "1500");
+        assert_eq!(gid_to_name(1500).unwrap(), "1500");
+
+        let msg = prefetch_names(&["a\0b"]).unwrap_err().to_string();
+        assert!(msg.contains("nul byte"), "{msg}");
+    }
+
     #[test]
     #[cfg(target_os = "macos")]
     fn test_uid_to_guid() {
@@ -380,6 +859,15 @@ mod unix_tests {
         );
     }
 
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_check_membership() {
+        // Every user is in the `everyone` group (gid 12) without being listed.
+        assert_eq!(check_membership(89, 12).ok(), Some(true));
+        assert_eq!(check_membership(89, 89).ok(), Some(true));
+        assert_eq!(check_membership(89, 0).ok(), Some(false));
+    }
+
     #[test]
     #[cfg(target_os = "macos")]
     fn test_guid_to_id() {
@@ -410,4 +898,92 @@ mod unix_tests {
 
         assert_eq!(guid_to_id(Uuid::nil()).unwrap(), (None, None));
     }
+
+    #[test]
+    fn test_with_batch_cache() {
+        let cached = |uid| {
+            BATCH_NAMES.with(|names| {
+                names
+                    .borrow()
+                    .as_ref()
+                    .map(|names| names.user_names.contains_key(&uid))
+            })
+        };
+
+        with_batch_cache(|| {
+            assert_eq!(cached(11501), Some(false));
+            assert_eq!(uid_to_name(11501).unwrap(), "11501");
+            assert_eq!(cached(11501), Some(true));
+
+            // A nested batch shares the outer cache.
+            with_batch_cache(|| assert_eq!(cached(11501), Some(true)));
+            assert_eq!(cached(11501), Some(true));
+        });
+        assert_eq!(cached(11501), None);
+    }
+
+    /// Resolver that names uid 11999 and counts its lookups.
+    #[derive(Default)]
+    struct TestResolver {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl NameResolver for TestResolver {
+        fn user_name(&self, uid: u32) -> io::Result<String> {
+            self.calls
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            match uid {
+                11999 => Ok("test11999".to_string()),
+                _ => lookup_user_name(uid),
+            }
+        }
+
+        fn user_id(&self, name: &str) -> io::Result<Option<u32>> {
+            match name {
+                "test11999" => Ok(Some(11999)),
+                _ => lookup_uid(name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_caching_resolver() {
+        let resolver = CachingResolver::new(TestResolver::default());
+        assert_eq!(resolver.user_name(11999).unwrap(), "test11999");
+        assert_eq!(resolver.user_name(11999).unwrap(), "test11999");
+        assert_eq!(resolver.user_name(0).unwrap(), "root");
+        assert_eq!(
+            resolver
+                .inner
+                .calls
+                .load(std::sync::atomic::Ordering::Relaxed),
+            2
+        );
+
+        assert_eq!(resolver.user_id("test11999").unwrap(), Some(11999));
+        assert_eq!(resolver.group_id("non_existant").unwrap(), None);
+        assert_eq!(resolver.group_name(11502).unwrap(), "11502");
+        assert!(resolver.user_id("a\0b").is_err());
+    }
+
+    #[test]
+    fn test_numeric_resolver() {
+        assert_eq!(NumericResolver.user_name(0).unwrap(), "0");
+        assert_eq!(NumericResolver.group_name(0).unwrap(), "0");
+        assert_eq!(NumericResolver.user_id("root").unwrap(), Some(0));
+        assert_eq!(SystemResolver.user_name(0).unwrap(), "root");
+    }
+
+    #[test]
+    fn test_set_resolver() {
+        // Other tests run at the same time, so only uid 11999 changes.
+        set_resolver(TestResolver::default());
+        assert_eq!(uid_to_name(11999).unwrap(), "test11999");
+        assert_eq!(name_to_uid_strict("test11999").unwrap(), 11999);
+        assert_eq!(uid_to_name(0).unwrap(), "root");
+
+        reset_resolver();
+        assert_eq!(uid_to_name(11999).unwrap(), "11999");
+        assert!(name_to_uid_strict("test11999").is_err());
+    }
 }
@@ -9,12 +9,26 @@
 //!    `xacl_free`      - destroy ACL
 //!    `xacl_foreach`   - apply a function to each entry in an ACL
 //!    `xacl_is_empty`  - return true if an ACL is empty
+//!    `xacl_entry_count` - return number of entries in an ACL
 //!    `xacl_is_posix`  - return true if ACL has Posix.1e semantics.
 //!    `xacl_add_entry` - append new entry to an ACL
+//!    `xacl_add_raw_entry` - append entry with an unchecked tag (`raw` feature)
 //!    `xacl_get_entry` - retrieve contents from an ACL entry
+//!    `xacl_get_guid`  - retrieve the unresolved GUID of an ACL entry (macOS)
 //!    `xacl_get_file`  - get ACL from file path
 //!    `xacl_set_file`  - set ACL for file path
+//!    `xacl_get_fd`    - get ACL from open file descriptor
+//!    `xacl_clear_file` - remove extended ACL from file path (macOS)
+//!    `xacl_strip`     - return ACL with only the mode entries (`FreeBSD`)
+//!    `xacl_set_fd`    - set ACL for open file descriptor
 //!    `xacl_is_nfs4`   - return true if file path uses `NFSv4` ACL on `FreeBSD`
+//!    `xacl_to_any_text` - return text representation of ACL on Linux
+//!    `xacl_from_text` - parse text representation of ACL on Linux
+//!    `xacl_check`     - check a Posix.1e ACL and locate the bad entry (Linux)
+//!    `xacl_equiv_mode` - return the file mode equivalent to an ACL (Linux)
+//!    `xacl_is_supported` - return true if file system supports ACL's (Linux, macOS)
+//!    `xacl_get_brand` - return brand of ACL on `FreeBSD`
+//!    `xacl_is_posix1e` - return true if file path uses Posix.1e ACL on `FreeBSD`
 
 mod util_common;
 
@@ -32,18 +46,31 @@ pub use crate::sys::{acl_entry_t, acl_t};
 
 #[cfg(target_os = "freebsd")]
 pub use util_freebsd::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_nfs4, xacl_is_posix, xacl_set_file,
+    xacl_add_entry, xacl_entry_count, xacl_foreach, xacl_free, xacl_get_brand, xacl_get_entry,
+    xacl_get_fd, xacl_get_file, xacl_init, xacl_is_empty, xacl_is_nfs4, xacl_is_posix,
+    xacl_is_posix1e, xacl_next_entry, xacl_set_fd, xacl_set_file, xacl_strip,
 };
 
 #[cfg(target_os = "linux")]
 pub use util_linux::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_posix, xacl_set_file,
+    xacl_add_entry, xacl_check, xacl_entry_count, xacl_equiv_mode, xacl_foreach, xacl_free,
+    xacl_from_text, xacl_get_entry, xacl_get_fd, xacl_get_file, xacl_init, xacl_is_empty,
+    xacl_is_posix, xacl_is_supported, xacl_next_entry, xacl_set_fd, xacl_set_file,
+    xacl_to_any_text,
 };
 
 #[cfg(target_os = "macos")]
 pub use util_macos::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_posix, xacl_set_file,
+    xacl_add_entry, xacl_clear_file, xacl_entry_count, xacl_foreach, xacl_free, xacl_get_entry,
+    xacl_get_fd, xacl_get_file, xacl_get_guid, xacl_init, xacl_is_empty, xacl_is_posix,
+    xacl_is_supported, xacl_next_entry, xacl_set_fd, xacl_set_file,
 };
+
+#[cfg(all(feature = "raw", target_os = "freebsd"))]
+pub use util_freebsd::xacl_add_raw_entry;
+
+#[cfg(all(feature = "raw", target_os = "linux"))]
+pub use util_linux::xacl_add_raw_entry;
+
+#[cfg(all(feature = "raw", target_os = "macos"))]
+pub use util_macos::xacl_add_raw_entry;
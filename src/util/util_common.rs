@@ -7,11 +7,44 @@ use std::ffi::c_void;
 use std::io;
 use std::ptr;
 
+/// Permission bits that `xacl_get_perm` asks the native library about. This
+/// includes the non-portable bits even when the `strict_posix` feature leaves
+/// them out of `Perm`, so they survive a round trip.
+#[cfg(all(target_os = "linux", not(exacl_no_acl_get_perm)))]
+const NATIVE_PERMS: acl_perm_t = ACL_READ | ACL_WRITE | ACL_EXECUTE;
+
+#[cfg(target_os = "macos")]
+const NATIVE_PERMS: acl_perm_t = ACL_READ | ACL_WRITE | ACL_EXECUTE | EXTENDED_PERMS;
+
+#[cfg(target_os = "freebsd")]
+const NATIVE_PERMS: acl_perm_t =
+    ACL_READ | ACL_WRITE | ACL_EXECUTE | np::ACL_READ_DATA | np::ACL_WRITE_DATA | EXTENDED_PERMS;
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+const EXTENDED_PERMS: acl_perm_t = np::ACL_DELETE
+    | np::ACL_APPEND_DATA
+    | np::ACL_DELETE_CHILD
+    | np::ACL_READ_ATTRIBUTES
+    | np::ACL_WRITE_ATTRIBUTES
+    | np::ACL_READ_EXTATTRIBUTES
+    | np::ACL_WRITE_EXTATTRIBUTES
+    | np::ACL_READ_SECURITY
+    | np::ACL_WRITE_SECURITY
+    | np::ACL_CHANGE_OWNER
+    | np::ACL_SYNCHRONIZE;
+
 /// Free memory allocated by native acl_* routines.
+///
+/// A null pointer is ignored. A failure is logged, since there is nothing the
+/// caller can do about it.
 pub fn xacl_free<T>(ptr: *mut T) {
-    assert!(!ptr.is_null());
+    if ptr.is_null() {
+        return;
+    }
     let ret = unsafe { acl_free(ptr.cast::<c_void>()) };
-    assert_eq!(ret, 0);
+    if ret != 0 {
+        log_err(ret, "acl_free", ptr);
+    }
 }
 
 /// Return true if acl is empty.
@@ -21,6 +54,21 @@ pub fn xacl_is_empty(acl: acl_t) -> bool {
     !xacl_get_entry(acl, sg::ACL_FIRST_ENTRY, &mut entry)
 }
 
+/// Return number of entries in acl.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub fn xacl_entry_count(acl: acl_t) -> usize {
+    let mut count = 0;
+    let mut entry: acl_entry_t = ptr::null_mut();
+    let mut entry_id = sg::ACL_FIRST_ENTRY;
+
+    while xacl_get_entry(acl, entry_id, &mut entry) {
+        count += 1;
+        entry_id = sg::ACL_NEXT_ENTRY;
+    }
+
+    count
+}
+
 /// Return next entry in ACL.
 fn xacl_get_entry(acl: acl_t, entry_id: i32, entry_p: *mut acl_entry_t) -> bool {
     let ret = unsafe { acl_get_entry(acl, entry_id, entry_p) };
@@ -39,22 +87,40 @@ pub fn xacl_foreach<F: FnMut(acl_entry_t) -> io::Result<()>>(
     acl: acl_t,
     mut func: F,
 ) -> io::Result<()> {
-    let mut entry: acl_entry_t = ptr::null_mut();
-    let mut entry_id = sg::ACL_FIRST_ENTRY;
-
-    assert!(!acl.is_null());
-    loop {
-        if !xacl_get_entry(acl, entry_id, &mut entry) {
-            break;
-        }
-        assert!(!entry.is_null());
+    let mut first = true;
+    while let Some(entry) = xacl_next_entry(acl, first)? {
         func(entry)?;
-        entry_id = sg::ACL_NEXT_ENTRY;
+        first = false;
     }
 
     Ok(())
 }
 
+/// Return the first or next entry in a native ACL, or None after the last.
+///
+/// The position is kept in the native ACL, so only one walk over an ACL may
+/// be in progress at a time.
+pub fn xacl_next_entry(acl: acl_t, first: bool) -> io::Result<Option<acl_entry_t>> {
+    let mut entry: acl_entry_t = ptr::null_mut();
+    let entry_id = if first {
+        sg::ACL_FIRST_ENTRY
+    } else {
+        sg::ACL_NEXT_ENTRY
+    };
+
+    if acl.is_null() {
+        return fail_custom("null ACL");
+    }
+    if !xacl_get_entry(acl, entry_id, &mut entry) {
+        return Ok(None);
+    }
+    if entry.is_null() {
+        return fail_null("acl_get_entry");
+    }
+
+    Ok(Some(entry))
+}
+
 /// Create a new empty ACL with the given capacity.
 ///
 /// Client must call `xacl_free` when done with result.
@@ -99,6 +165,7 @@ pub fn xacl_get_tag_type(entry: acl_entry_t) -> io::Result<acl_tag_t> {
 }
 
 /// Get permissions from the entry.
+#[cfg(not(all(target_os = "linux", exacl_no_acl_get_perm)))]
 pub fn xacl_get_perm(entry: acl_entry_t) -> io::Result<Perm> {
     let mut permset: acl_permset_t = std::ptr::null_mut();
 
@@ -107,12 +174,16 @@ pub fn xacl_get_perm(entry: acl_entry_t) -> io::Result<Perm> {
         return fail_err(ret, "acl_get_permset", ());
     }
 
-    assert!(!permset.is_null());
+    if permset.is_null() {
+        return fail_null("acl_get_permset");
+    }
 
     let mut perms = Perm::empty();
-    for perm in BitIter(Perm::all()) {
+    for perm in BitIter(Perm::from_bits_retain(NATIVE_PERMS)) {
         let res = unsafe { acl_get_perm(permset, perm.bits()) };
-        debug_assert!((0..=1).contains(&res));
+        if res < 0 {
+            return fail_err(res, "acl_get_perm", perm);
+        }
         if res == 1 {
             perms |= perm;
         }
@@ -140,7 +211,9 @@ pub fn xacl_set_perm(entry: acl_entry_t, perms: Perm) -> io::Result<()> {
         return fail_err(ret_get, "acl_get_permset", ());
     }
 
-    assert!(!permset.is_null());
+    if permset.is_null() {
+        return fail_null("acl_get_permset");
+    }
 
     let ret_clear = unsafe { acl_clear_perms(permset) };
     if ret_clear != 0 {
@@ -149,8 +222,37 @@ pub fn xacl_set_perm(entry: acl_entry_t, perms: Perm) -> io::Result<()> {
 
     for perm in BitIter(perms) {
         let ret = unsafe { acl_add_perm(permset, perm.bits()) };
-        debug_assert!(ret == 0);
+        if ret != 0 {
+            return fail_err(ret, "acl_add_perm", perm);
+        }
     }
 
     Ok(())
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod util_common_tests {
+    use super::*;
+
+    #[test]
+    fn test_null_pointers() {
+        // Freeing a null pointer is ignored.
+        xacl_free(ptr::null_mut::<c_void>());
+
+        let err = xacl_foreach(ptr::null_mut(), |_| Ok(())).unwrap_err();
+        assert_eq!(err.to_string(), "null ACL");
+    }
+
+    #[test]
+    fn test_log_from_err() {
+        let err = log_from_err(sg::ENOENT, "getpwnam_r", "x");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // An error code that isn't an errno is still reported as an error.
+        let err = log_from_err(0, "getpwnam_r", "x");
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.to_string(), "getpwnam_r returned 0");
+    }
+}
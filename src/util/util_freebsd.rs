@@ -10,14 +10,41 @@ use log::debug;
 use scopeguard::defer;
 use std::ffi::{c_void, CString};
 use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::ptr;
 
-pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
+pub use util_common::{
+    xacl_create_entry, xacl_entry_count, xacl_foreach, xacl_free, xacl_init, xacl_is_empty,
+    xacl_next_entry,
+};
 
 use util_common::*;
 
+/// `NFSv4` permission bits. These are raw bits so they are still recognized
+/// when the `strict_posix` feature leaves them out of `Perm`.
+const NFS4_PERMS: acl_perm_t = np::ACL_READ_DATA
+    | np::ACL_WRITE_DATA
+    | np::ACL_DELETE
+    | np::ACL_APPEND_DATA
+    | np::ACL_DELETE_CHILD
+    | np::ACL_READ_ATTRIBUTES
+    | np::ACL_WRITE_ATTRIBUTES
+    | np::ACL_READ_EXTATTRIBUTES
+    | np::ACL_WRITE_EXTATTRIBUTES
+    | np::ACL_READ_SECURITY
+    | np::ACL_WRITE_SECURITY
+    | np::ACL_CHANGE_OWNER
+    | np::ACL_SYNCHRONIZE;
+
+/// `NFSv4` inheritance flag bits.
+const NFS4_FLAGS: acl_flag_t = np::ACL_ENTRY_INHERITED
+    | np::ACL_ENTRY_FILE_INHERIT
+    | np::ACL_ENTRY_DIRECTORY_INHERIT
+    | np::ACL_ENTRY_LIMIT_INHERIT
+    | np::ACL_ENTRY_ONLY_INHERIT;
+
 fn get_acl_type(acl: acl_t, default_acl: bool) -> acl_type_t {
     if !acl.is_null() && !xacl_is_posix(acl) {
         sg::ACL_TYPE_NFS4
@@ -144,11 +171,11 @@ fn xacl_repair_nfs4(acl: acl_t) -> io::Result<()> {
         let orig_perm = perm;
         if perm.intersects(Perm::READ) {
             perm.remove(Perm::READ);
-            perm.insert(Perm::READ_DATA);
+            perm.insert(Perm::from_bits_retain(np::ACL_READ_DATA));
         }
         if perm.intersects(Perm::WRITE) {
             perm.remove(Perm::WRITE);
-            perm.insert(Perm::WRITE_DATA);
+            perm.insert(Perm::from_bits_retain(np::ACL_WRITE_DATA));
         }
         if perm != orig_perm {
             xacl_set_perm(entry, perm)?;
@@ -208,23 +235,107 @@ pub fn xacl_set_file(
     Ok(())
 }
 
-fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
-    let tag = xacl_get_tag_type(entry)?;
+/// Return a new ACL with only the entries that correspond to the file mode.
+pub fn xacl_strip(acl: acl_t) -> io::Result<acl_t> {
+    let stripped = unsafe { acl_strip_np(acl, 0) };
+    if stripped.is_null() {
+        return fail_err("null", "acl_strip_np", ());
+    }
+
+    Ok(stripped)
+}
 
-    let id = if tag == sg::ACL_USER || tag == sg::ACL_GROUP {
-        let id_ptr = unsafe { acl_get_qualifier(entry).cast::<uid_t>() };
-        if id_ptr.is_null() {
-            return fail_err("null", "acl_get_qualifier", ());
+/// Get ACL for an open file descriptor.
+///
+/// Like `xacl_get_link`, this first tries to obtain the Posix.1e ACL. If that's
+/// not appropriate for the file system object, we try the NFS4 ACL.
+pub fn xacl_get_fd(fd: BorrowedFd, default_acl: bool) -> io::Result<acl_t> {
+    let fd = fd.as_raw_fd();
+    let mut acl_type = get_acl_type(ptr::null_mut(), default_acl);
+    let acl = unsafe { acl_get_fd_np(fd, acl_type) };
+
+    if !acl.is_null() {
+        return Ok(acl);
+    }
+
+    // `acl_get_fd_np` returns EINVAL when the ACL type is not appropriate for
+    // the file system object. Retry with NFSv4 type, which has no default ACL.
+    if io::Error::last_os_error().raw_os_error() == Some(sg::EINVAL) && !default_acl {
+        acl_type = sg::ACL_TYPE_NFS4;
+        let nfs_acl = unsafe { acl_get_fd_np(fd, acl_type) };
+        if !nfs_acl.is_null() {
+            return Ok(nfs_acl);
         }
-        defer! { xacl_free(id_ptr) };
-        Some(unsafe { *id_ptr })
-    } else {
-        None
+    }
+
+    // Report acl_type and file descriptor that failed.
+    let func = match acl_type {
+        sg::ACL_TYPE_ACCESS => "acl_get_fd_np/access",
+        sg::ACL_TYPE_DEFAULT => "acl_get_fd_np/default",
+        sg::ACL_TYPE_NFS4 => "acl_get_fd_np/nfs4",
+        _ => "acl_get_fd_np/?",
     };
 
+    fail_err("null", func, fd)
+}
+
+/// Set ACL for an open file descriptor.
+///
+/// The ACL type is chosen from the ACL's brand: an `NFSv4` ACL is written as
+/// `ACL_TYPE_NFS4`, otherwise as `ACL_TYPE_ACCESS` or `ACL_TYPE_DEFAULT`.
+pub fn xacl_set_fd(fd: BorrowedFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    let fd = fd.as_raw_fd();
+    if !xacl_is_posix(acl) {
+        if default_acl {
+            return fail_custom("Default ACL not supported");
+        }
+
+        // FIXME: This mutates the acl, violating the immutable invariant.
+        xacl_repair_nfs4(acl)?;
+    }
+
+    log_brand("xacl_set_fd", acl)?;
+
+    if default_acl && xacl_is_empty(acl) {
+        // Special case to delete the ACL, as in `xacl_set_file`.
+        let ret = unsafe { acl_delete_fd_np(fd, sg::ACL_TYPE_DEFAULT) };
+        if ret != 0 {
+            return fail_err(ret, "acl_delete_fd_np", fd);
+        }
+        return Ok(());
+    }
+
+    let acl_type = get_acl_type(acl, default_acl);
+    let ret = unsafe { acl_set_fd_np(fd, acl, acl_type) };
+    if ret != 0 {
+        let func = match acl_type {
+            sg::ACL_TYPE_ACCESS => "acl_set_fd_np/access",
+            sg::ACL_TYPE_DEFAULT => "acl_set_fd_np/default",
+            sg::ACL_TYPE_NFS4 => "acl_set_fd_np/nfs4",
+            _ => "acl_set_fd_np/?",
+        };
+        return fail_err(ret, func, fd);
+    }
+
+    Ok(())
+}
+
+/// Get the uid or gid of a named user or group entry.
+fn xacl_get_id(entry: acl_entry_t) -> io::Result<uid_t> {
+    let id_ptr = unsafe { acl_get_qualifier(entry).cast::<uid_t>() };
+    if id_ptr.is_null() {
+        return fail_err("null", "acl_get_qualifier", ());
+    }
+    defer! { xacl_free(id_ptr) };
+    Ok(unsafe { *id_ptr })
+}
+
+fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
+    let tag = xacl_get_tag_type(entry)?;
+
     let result = match tag {
-        sg::ACL_USER => Qualifier::User(id.unwrap()),
-        sg::ACL_GROUP => Qualifier::Group(id.unwrap()),
+        sg::ACL_USER => Qualifier::User(xacl_get_id(entry)?),
+        sg::ACL_GROUP => Qualifier::Group(xacl_get_id(entry)?),
         sg::ACL_USER_OBJ => Qualifier::UserObj,
         sg::ACL_GROUP_OBJ => Qualifier::GroupObj,
         sg::ACL_OTHER => Qualifier::Other,
@@ -244,12 +355,8 @@ fn xacl_get_entry_type(entry: acl_entry_t) -> io::Result<acl_entry_type_t> {
         return fail_err(ret, "acl_get_entry_type_np", ());
     }
 
-    // FIXME: AUDIT, ALARM entry types are not supported.
-    debug_assert!(
-        entry_type == 0
-            || entry_type == sg::ACL_ENTRY_TYPE_ALLOW
-            || entry_type == sg::ACL_ENTRY_TYPE_DENY
-    );
+    // FIXME: AUDIT, ALARM entry types are not supported. They are read as
+    // deny entries.
 
     Ok(entry_type)
 }
@@ -277,12 +384,16 @@ fn xacl_get_flags(acl: acl_t, entry: acl_entry_t) -> io::Result<Flag> {
         return fail_err(ret, "acl_get_flagset_np", ());
     }
 
-    assert!(!flagset.is_null());
+    if flagset.is_null() {
+        return fail_null("acl_get_flagset_np");
+    }
 
     let mut flags = Flag::empty();
-    for flag in BitIter(Flag::all() - Flag::DEFAULT) {
+    for flag in BitIter(Flag::from_bits_retain(NFS4_FLAGS)) {
         let res = unsafe { acl_get_flag_np(flagset, flag.bits()) };
-        debug_assert!((0..=1).contains(&res));
+        if res < 0 {
+            return fail_err(res, "acl_get_flag_np", flag);
+        }
         if res == 1 {
             flags |= flag;
         }
@@ -371,7 +482,9 @@ fn xacl_set_flags(entry: acl_entry_t, flags: Flag) -> io::Result<()> {
         return fail_err(ret_get, "acl_get_flagset_np", ());
     }
 
-    assert!(!flagset.is_null());
+    if flagset.is_null() {
+        return fail_null("acl_get_flagset_np");
+    }
 
     let ret_clear = unsafe { acl_clear_flags_np(flagset) };
     if ret_clear != 0 {
@@ -380,7 +493,9 @@ fn xacl_set_flags(entry: acl_entry_t, flags: Flag) -> io::Result<()> {
 
     for flag in BitIter(flags) {
         let ret = unsafe { acl_add_flag_np(flagset, flag.bits()) };
-        debug_assert!(ret == 0);
+        if ret != 0 {
+            return fail_err(ret, "acl_add_flag_np", flag);
+        }
     }
 
     Ok(())
@@ -393,8 +508,7 @@ pub fn xacl_add_entry(
     perms: Perm,
     flags: Flag,
 ) -> io::Result<acl_entry_t> {
-    let nfs4_specific =
-        perms.intersects(Perm::NFS4_SPECIFIC) || flags.intersects(Flag::NFS4_SPECIFIC);
+    let nfs4_specific = perms.bits() & NFS4_PERMS != 0 || flags.bits() & NFS4_FLAGS != 0;
 
     if allow && xacl_is_posix(*acl) && !nfs4_specific {
         // Check for duplicates already in the list.
@@ -426,7 +540,26 @@ pub fn xacl_add_entry(
     Ok(entry)
 }
 
-fn xacl_get_brand(acl: acl_t) -> io::Result<i32> {
+/// Append an entry with a raw tag value, without checking the tag or the
+/// other entries.
+#[cfg(feature = "raw")]
+pub fn xacl_add_raw_entry(
+    acl: &mut acl_t,
+    tag: u32,
+    id: Option<uid_t>,
+    perms: Perm,
+) -> io::Result<acl_entry_t> {
+    let entry = xacl_create_entry(acl)?;
+    xacl_set_tag_type(entry, tag)?;
+    if let Some(id) = id {
+        xacl_set_qualifier(entry, id)?;
+    }
+    xacl_set_perm(entry, perms)?;
+
+    Ok(entry)
+}
+
+pub fn xacl_get_brand(acl: acl_t) -> io::Result<i32> {
     let mut brand: i32 = 0;
     let ret = unsafe { acl_get_brand_np(acl, &mut brand) };
     if ret != 0 {
@@ -437,15 +570,11 @@ fn xacl_get_brand(acl: acl_t) -> io::Result<i32> {
 }
 
 pub fn xacl_is_posix(acl: acl_t) -> bool {
-    let brand = xacl_get_brand(acl).expect("xacl_get_brand failed");
-    debug_assert!(
-        brand == sg::ACL_BRAND_UNKNOWN
-            || brand == sg::ACL_BRAND_POSIX
-            || brand == sg::ACL_BRAND_NFS4
-    );
-
-    // Treat an Unknown branded ACL as Posix.
-    brand == sg::ACL_BRAND_POSIX || brand == sg::ACL_BRAND_UNKNOWN
+    // Treat an Unknown branded ACL as Posix. So is an ACL whose brand can't
+    // be read; the failure is logged by `xacl_get_brand`.
+    xacl_get_brand(acl).map_or(true, |brand| {
+        brand == sg::ACL_BRAND_POSIX || brand == sg::ACL_BRAND_UNKNOWN
+    })
 }
 
 fn log_brand(func: &str, acl: acl_t) -> io::Result<()> {
@@ -461,22 +590,29 @@ fn log_brand(func: &str, acl: acl_t) -> io::Result<()> {
     Ok(())
 }
 
-pub fn xacl_is_nfs4(path: &Path, symlink: bool) -> io::Result<bool> {
+fn xacl_pathconf_bool(path: &Path, symlink: bool, name: i32) -> io::Result<bool> {
     let c_path = CString::new(path.as_os_str().as_bytes())?;
     let ret = if symlink {
-        unsafe { lpathconf(c_path.as_ptr(), sg::PC_ACL_NFS4) }
+        unsafe { lpathconf(c_path.as_ptr(), name) }
     } else {
-        unsafe { pathconf(c_path.as_ptr(), sg::PC_ACL_NFS4) }
+        unsafe { pathconf(c_path.as_ptr(), name) }
     };
 
     if ret < 0 {
         return fail_err(ret, "pathconf", symlink);
     }
 
-    assert!(ret == 0 || ret == 1);
     Ok(ret == 1)
 }
 
+pub fn xacl_is_nfs4(path: &Path, symlink: bool) -> io::Result<bool> {
+    xacl_pathconf_bool(path, symlink, sg::PC_ACL_NFS4)
+}
+
+pub fn xacl_is_posix1e(path: &Path, symlink: bool) -> io::Result<bool> {
+    xacl_pathconf_bool(path, symlink, sg::PC_ACL_EXTENDED)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
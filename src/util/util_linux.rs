@@ -6,12 +6,15 @@ use crate::sys::*;
 use crate::util::util_common;
 
 use scopeguard::defer;
-use std::ffi::{c_void, CString};
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
 use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 
-pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
+pub use util_common::{
+    xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty, xacl_next_entry,
+};
 
 use util_common::*;
 
@@ -69,23 +72,54 @@ pub fn xacl_set_file(
     Ok(())
 }
 
+/// Get the access ACL for an open file descriptor.
+///
+/// libacl has no file descriptor API for the default ACL.
+pub fn xacl_get_fd(fd: BorrowedFd, default_acl: bool) -> io::Result<acl_t> {
+    let fd = fd.as_raw_fd();
+    if default_acl {
+        return fail_custom("Linux does not support default ACL's with file descriptors");
+    }
+
+    let acl = unsafe { acl_get_fd(fd) };
+    if acl.is_null() {
+        return fail_err("null", "acl_get_fd", fd);
+    }
+
+    Ok(acl)
+}
+
+/// Set the access ACL for an open file descriptor.
+pub fn xacl_set_fd(fd: BorrowedFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    let fd = fd.as_raw_fd();
+    if default_acl {
+        return fail_custom("Linux does not support default ACL's with file descriptors");
+    }
+
+    let ret = unsafe { acl_set_fd(fd, acl) };
+    if ret != 0 {
+        return fail_err(ret, "acl_set_fd", fd);
+    }
+
+    Ok(())
+}
+
+/// Get the uid or gid of a named user or group entry.
+fn xacl_get_id(entry: acl_entry_t) -> io::Result<uid_t> {
+    let id_ptr = unsafe { acl_get_qualifier(entry).cast::<uid_t>() };
+    if id_ptr.is_null() {
+        return fail_err("null", "acl_get_qualifier", ());
+    }
+    defer! { xacl_free(id_ptr) };
+    Ok(unsafe { *id_ptr })
+}
+
 fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
     let tag = xacl_get_tag_type(entry)?;
 
-    let id = if tag == sg::ACL_USER || tag == sg::ACL_GROUP {
-        let id_ptr = unsafe { acl_get_qualifier(entry).cast::<uid_t>() };
-        if id_ptr.is_null() {
-            return fail_err("null", "acl_get_qualifier", ());
-        }
-        defer! { xacl_free(id_ptr) };
-        Some(unsafe { *id_ptr })
-    } else {
-        None
-    };
-
     let result = match tag {
-        sg::ACL_USER => Qualifier::User(id.unwrap()),
-        sg::ACL_GROUP => Qualifier::Group(id.unwrap()),
+        sg::ACL_USER => Qualifier::User(xacl_get_id(entry)?),
+        sg::ACL_GROUP => Qualifier::Group(xacl_get_id(entry)?),
         sg::ACL_USER_OBJ => Qualifier::UserObj,
         sg::ACL_GROUP_OBJ => Qualifier::GroupObj,
         sg::ACL_OTHER => Qualifier::Other,
@@ -201,6 +235,163 @@ pub const fn xacl_is_posix(_acl: acl_t) -> bool {
     true
 }
 
+/// Append an entry with a raw tag value, without checking the tag or the
+/// other entries.
+#[cfg(feature = "raw")]
+pub fn xacl_add_raw_entry(
+    acl: &mut acl_t,
+    tag: u32,
+    id: Option<uid_t>,
+    perms: Perm,
+) -> io::Result<acl_entry_t> {
+    let entry = xacl_create_entry(acl)?;
+    xacl_set_tag_type(entry, tag as acl_tag_t)?;
+    if let Some(id) = id {
+        xacl_set_qualifier(entry, id)?;
+    }
+    xacl_set_perm(entry, perms)?;
+
+    Ok(entry)
+}
+
+pub fn xacl_entry_count(acl: acl_t) -> usize {
+    let ret = unsafe { acl_entries(acl) };
+    usize::try_from(ret).unwrap_or(0)
+}
+
+pub fn xacl_is_supported(path: &Path) -> io::Result<bool> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let ret = unsafe { acl_extended_file(c_path.as_ptr()) };
+    if ret < 0 {
+        let err = log_err(ret, "acl_extended_file", &c_path);
+        if err.raw_os_error() == Some(sg::ENOTSUP) {
+            return Ok(false);
+        }
+        return Err(err);
+    }
+
+    Ok(true)
+}
+
+/// Return the permission bits of the file mode equivalent to the ACL, using
+/// `acl_equiv_mode`. Named entries are reflected in the group bits through the
+/// mask.
+pub fn xacl_equiv_mode(acl: acl_t) -> io::Result<u32> {
+    let mut mode: mode_t = 0;
+    let ret = unsafe { acl_equiv_mode(acl, &mut mode) };
+    if ret < 0 {
+        return fail_err(ret, "acl_equiv_mode", ());
+    }
+
+    Ok(mode)
+}
+
+/// Check a Posix.1e ACL with `acl_check`. Return None if the ACL is valid.
+/// Otherwise, return libacl's description of the error and the position of the
+/// offending entry in `acl`.
+pub fn xacl_check(acl: acl_t) -> io::Result<Option<(String, usize)>> {
+    let mut last: c_int = 0;
+    let ret = unsafe { acl_check(acl, &mut last) };
+    if ret < 0 {
+        return fail_err(ret, "acl_check", ());
+    }
+    if ret == 0 {
+        return Ok(None);
+    }
+
+    let msg_p = unsafe { acl_error(ret) };
+    let msg = if msg_p.is_null() {
+        "Unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(msg_p) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    Ok(Some((
+        format!("{msg} (acl_check returned {ret})"),
+        usize::try_from(last).unwrap_or(0),
+    )))
+}
+
+/// Get permissions from the entry, on systems whose libacl lacks
+/// `acl_get_perm`.
+#[cfg(exacl_no_acl_get_perm)]
+fn xacl_get_perm(entry: acl_entry_t) -> io::Result<Perm> {
+    xacl_get_perm_text(entry)
+}
+
+/// Get permissions from the entry without `acl_get_perm`: copy the entry into
+/// an ACL of its own, and parse the permissions from its text form, e.g.
+/// `user:alice:rw-`.
+#[cfg(any(test, exacl_no_acl_get_perm))]
+fn xacl_get_perm_text(entry: acl_entry_t) -> io::Result<Perm> {
+    let mut acl_p = scopeguard::guard(xacl_init(1)?, xacl_free);
+    let copy = xacl_create_entry(&mut acl_p)?;
+    let ret = unsafe { acl_copy_entry(copy, entry) };
+    if ret != 0 {
+        return fail_err(ret, "acl_copy_entry", ());
+    }
+
+    let text_p = unsafe { acl_to_text(*acl_p, std::ptr::null_mut()) };
+    if text_p.is_null() {
+        return fail_err("null", "acl_to_text", ());
+    }
+    defer! { xacl_free(text_p) };
+
+    let text = unsafe { CStr::from_ptr(text_p) }.to_string_lossy();
+    let perm_text = text.lines().next().and_then(|line| line.rsplit(':').next());
+    let mut perms = Perm::empty();
+    for ch in perm_text.unwrap_or_default().chars() {
+        match ch {
+            'r' => perms |= Perm::READ,
+            'w' => perms |= Perm::WRITE,
+            'x' => perms |= Perm::EXECUTE,
+            '-' => (),
+            _ => return fail_custom(&format!("unexpected ACL text: {text:?}")),
+        }
+    }
+
+    Ok(perms)
+}
+
+pub fn xacl_to_any_text(
+    acl: acl_t,
+    prefix: &str,
+    separator: u8,
+    options: i32,
+) -> io::Result<String> {
+    let c_prefix = CString::new(prefix)?;
+    let prefix_p = if prefix.is_empty() {
+        std::ptr::null()
+    } else {
+        c_prefix.as_ptr()
+    };
+
+    let text_p = unsafe { acl_to_any_text(acl, prefix_p, separator as c_char, options) };
+    if text_p.is_null() {
+        return fail_err("null", "acl_to_any_text", options);
+    }
+    defer! { xacl_free(text_p) };
+
+    let text = unsafe { CStr::from_ptr(text_p) };
+    Ok(text.to_string_lossy().into_owned())
+}
+
+/// Parse the long or short text form of an ACL, e.g. `user::rw-`. The ACL is
+/// not checked.
+///
+/// Client must call `xacl_free` when done with result.
+pub fn xacl_from_text(text: &str) -> io::Result<acl_t> {
+    let c_text = CString::new(text)?;
+    let acl = unsafe { acl_from_text(c_text.as_ptr()) };
+    if acl.is_null() {
+        return fail_err("null", "acl_from_text", text);
+    }
+
+    Ok(acl)
+}
+
 #[cfg(test)]
 mod util_linux_test {
     use super::*;
@@ -261,6 +452,23 @@ mod util_linux_test {
         xacl_free(acl);
     }
 
+    #[test]
+    fn test_get_perm_text() {
+        let mut acl = xacl_init(3).unwrap();
+        let cases = [
+            (Qualifier::UserObj, Perm::READ | Perm::WRITE),
+            (Qualifier::User(11501), Perm::EXECUTE),
+            (Qualifier::Other, Perm::empty()),
+        ];
+        for (qualifier, perms) in &cases {
+            let entry = xacl_add_entry(&mut acl, true, qualifier, *perms, Flag::empty()).unwrap();
+            assert_eq!(xacl_get_perm_text(entry).unwrap(), *perms);
+            assert_eq!(xacl_get_perm(entry).unwrap(), *perms);
+        }
+
+        xacl_free(acl);
+    }
+
     #[test]
     fn test_uninitialized_entry() {
         let mut acl = xacl_init(1).unwrap();
@@ -9,14 +9,27 @@ use crate::util::util_common;
 use scopeguard::defer;
 use std::ffi::{c_void, CString};
 use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use uuid::Uuid;
 
-pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
+pub use util_common::{
+    xacl_create_entry, xacl_entry_count, xacl_foreach, xacl_free, xacl_init, xacl_is_empty,
+    xacl_next_entry,
+};
 
 use util_common::*;
 
+/// Inheritance flag bits that `xacl_get_flags_np` asks about. These are raw
+/// bits so they survive a round trip when the `strict_posix` feature leaves
+/// them out of `Flag`.
+const NATIVE_FLAGS: acl_flag_t = np::ACL_ENTRY_INHERITED
+    | np::ACL_ENTRY_FILE_INHERIT
+    | np::ACL_ENTRY_DIRECTORY_INHERIT
+    | np::ACL_ENTRY_LIMIT_INHERIT
+    | np::ACL_ENTRY_ONLY_INHERIT;
+
 /// Return true if path exists, even if it's a symlink to nowhere.
 fn path_exists(path: &Path, symlink_only: bool) -> bool {
     if symlink_only {
@@ -110,10 +123,73 @@ pub fn xacl_set_file(
     Ok(())
 }
 
-/// Get the GUID qualifier and resolve it to a User/Group if possible.
+/// Remove the extended ACL from a file, like `chmod -N`.
+///
+/// Writing an empty ACL with `acl_set_file` leaves an empty ACL on the file.
+pub fn xacl_clear_file(path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    let fsec = unsafe { filesec_init() };
+    if fsec.is_null() {
+        return fail_err("null", "filesec_init", ());
+    }
+    defer! { unsafe { filesec_free(fsec) } }
+
+    let ret = unsafe { filesec_set_property(fsec, FILESEC_ACL, _FILESEC_REMOVE_ACL) };
+    if ret != 0 {
+        return fail_err(ret, "filesec_set_property", ());
+    }
+
+    let ret = unsafe { chmodx_np(c_path.as_ptr(), fsec) };
+    if ret != 0 {
+        return fail_err(ret, "chmodx_np", &c_path);
+    }
+
+    Ok(())
+}
+
+/// Get the extended ACL for an open file descriptor.
+pub fn xacl_get_fd(fd: BorrowedFd, default_acl: bool) -> io::Result<acl_t> {
+    let fd = fd.as_raw_fd();
+    if default_acl {
+        return fail_custom("macOS does not support default ACL");
+    }
+
+    let acl = unsafe { acl_get_fd_np(fd, acl_type_t_ACL_TYPE_EXTENDED) };
+    if acl.is_null() {
+        let err = log_err("null", "acl_get_fd_np", fd);
+
+        // acl_get_fd_np returns NULL (ENOENT) if the file has no ACL. The
+        // file exists, since we have a descriptor for it.
+        if err.raw_os_error() == Some(sg::ENOENT) {
+            return xacl_init(1);
+        }
+
+        return Err(err);
+    }
+
+    Ok(acl)
+}
+
+/// Set the extended ACL for an open file descriptor.
+pub fn xacl_set_fd(fd: BorrowedFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    let fd = fd.as_raw_fd();
+    if default_acl {
+        return fail_custom("macOS does not support default ACL");
+    }
+
+    let ret = unsafe { acl_set_fd_np(fd, acl, acl_type_t_ACL_TYPE_EXTENDED) };
+    if ret != 0 {
+        return fail_err(ret, "acl_set_fd_np", fd);
+    }
+
+    Ok(())
+}
+
+/// Get the GUID qualifier of the entry, without resolving it.
 ///
 /// Only call this function for `ACL_EXTENDED_ALLOW` or `ACL_EXTENDED_DENY`.
-fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
+pub fn xacl_get_guid(entry: acl_entry_t) -> io::Result<Uuid> {
     let uuid_ptr = unsafe { acl_get_qualifier(entry).cast::<Uuid>() };
     if uuid_ptr.is_null() {
         return fail_err("null", "acl_get_qualifier", ());
@@ -121,7 +197,14 @@ fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
     defer! { xacl_free(uuid_ptr) }
 
     let guid = unsafe { *uuid_ptr };
-    Qualifier::from_guid(guid)
+    Ok(guid)
+}
+
+/// Get the GUID qualifier and resolve it to a User/Group if possible.
+///
+/// Only call this function for `ACL_EXTENDED_ALLOW` or `ACL_EXTENDED_DENY`.
+fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
+    Qualifier::from_guid(xacl_get_guid(entry)?)
 }
 
 /// Get tag and qualifier from the entry.
@@ -139,7 +222,9 @@ fn xacl_get_tag_qualifier(_acl: acl_t, entry: acl_entry_t) -> io::Result<(bool,
 
 /// Get flags from the entry.
 fn xacl_get_flags_np(obj: *mut c_void) -> io::Result<Flag> {
-    assert!(!obj.is_null());
+    if obj.is_null() {
+        return fail_custom("null ACL object");
+    }
 
     let mut flagset: acl_flagset_t = std::ptr::null_mut();
     let ret = unsafe { acl_get_flagset_np(obj, &mut flagset) };
@@ -147,12 +232,16 @@ fn xacl_get_flags_np(obj: *mut c_void) -> io::Result<Flag> {
         return fail_err(ret, "acl_get_flagset_np", ());
     }
 
-    assert!(!flagset.is_null());
+    if flagset.is_null() {
+        return fail_null("acl_get_flagset_np");
+    }
 
     let mut flags = Flag::empty();
-    for flag in BitIter(Flag::all()) {
+    for flag in BitIter(Flag::from_bits_retain(NATIVE_FLAGS)) {
         let res = unsafe { acl_get_flag_np(flagset, flag.bits()) };
-        debug_assert!((0..=1).contains(&res));
+        if res < 0 {
+            return fail_err(res, "acl_get_flag_np", flag);
+        }
         if res == 1 {
             flags |= flag;
         }
@@ -194,10 +283,7 @@ fn xacl_set_tag_qualifier(
     allow: bool,
     qualifier: &Qualifier,
 ) -> io::Result<()> {
-    let tag = if let Qualifier::Unknown(_) = qualifier {
-        debug_assert!(!allow);
-        sg::ACL_EXTENDED_DENY
-    } else if allow {
+    let tag = if allow && !matches!(qualifier, Qualifier::Unknown(_)) {
         sg::ACL_EXTENDED_ALLOW
     } else {
         sg::ACL_EXTENDED_DENY
@@ -210,7 +296,9 @@ fn xacl_set_tag_qualifier(
 }
 
 fn xacl_set_flags_np(obj: *mut c_void, flags: Flag) -> io::Result<()> {
-    assert!(!obj.is_null());
+    if obj.is_null() {
+        return fail_custom("null ACL object");
+    }
 
     let mut flagset: acl_flagset_t = std::ptr::null_mut();
     let ret_get = unsafe { acl_get_flagset_np(obj, &mut flagset) };
@@ -218,7 +306,9 @@ fn xacl_set_flags_np(obj: *mut c_void, flags: Flag) -> io::Result<()> {
         return fail_err(ret_get, "acl_get_flagset_np", ());
     }
 
-    assert!(!flagset.is_null());
+    if flagset.is_null() {
+        return fail_null("acl_get_flagset_np");
+    }
 
     let ret_clear = unsafe { acl_clear_flags_np(flagset) };
     if ret_clear != 0 {
@@ -227,7 +317,9 @@ fn xacl_set_flags_np(obj: *mut c_void, flags: Flag) -> io::Result<()> {
 
     for flag in BitIter(flags) {
         let ret = unsafe { acl_add_flag_np(flagset, flag.bits()) };
-        debug_assert!(ret == 0);
+        if ret != 0 {
+            return fail_err(ret, "acl_add_flag_np", flag);
+        }
     }
 
     Ok(())
@@ -244,6 +336,11 @@ pub fn xacl_add_entry(
     perms: Perm,
     flags: Flag,
 ) -> io::Result<acl_entry_t> {
+    #[cfg(feature = "strict_posix")]
+    if flags.contains(Flag::DEFAULT) {
+        return fail_custom("default ACL entries are not supported on macOS");
+    }
+
     let entry = xacl_create_entry(acl)?;
     xacl_set_tag_qualifier(entry, allow, qualifier)?;
     xacl_set_perm(entry, perms)?;
@@ -256,6 +353,35 @@ pub const fn xacl_is_posix(_acl: acl_t) -> bool {
     false
 }
 
+/// Append an entry with a raw tag value, without checking the tag. The id is
+/// a uid, which is converted to a GUID.
+#[cfg(feature = "raw")]
+pub fn xacl_add_raw_entry(
+    acl: &mut acl_t,
+    tag: u32,
+    id: Option<u32>,
+    perms: Perm,
+) -> io::Result<acl_entry_t> {
+    let entry = xacl_create_entry(acl)?;
+    xacl_set_tag_type(entry, tag)?;
+    if let Some(id) = id {
+        xacl_set_qualifier(entry, &Qualifier::User(id))?;
+    }
+    xacl_set_perm(entry, perms)?;
+
+    Ok(entry)
+}
+
+pub fn xacl_is_supported(path: &Path) -> io::Result<bool> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let ret = unsafe { pathconf(c_path.as_ptr(), sg::PC_EXTENDED_SECURITY_NP) };
+    if ret < 0 {
+        return fail_err(ret, "pathconf", &c_path);
+    }
+
+    Ok(ret == 1)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
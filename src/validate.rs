@@ -0,0 +1,450 @@
+//! Implements `validate`, which checks ACL entries without applying them.
+
+#[cfg(all(
+    not(exacl_core),
+    any(target_os = "linux", target_os = "freebsd", target_os = "macos")
+))]
+use crate::acl::Acl;
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+use std::fmt;
+
+/// Maximum number of entries in a macOS ACL.
+const MACOS_MAX_ENTRIES: usize = 128;
+
+/// Kind of ACL that [`validate`] checks entries against.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Posix.1e access and default ACL's, as used on Linux and `FreeBSD`.
+    Posix,
+
+    /// `NFSv4` ACL's, as used on `FreeBSD` and Linux NFS mounts.
+    Nfs4,
+
+    /// macOS extended ACL's.
+    MacOs,
+}
+
+/// Represents a problem that keeps a list of entries from being applied.
+///
+/// Variants that concern one entry record its index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// ACL is missing a required entry, e.g. the `other` entry of a
+    /// Posix.1e ACL. `default` is true for the default ACL.
+    MissingEntry {
+        /// Kind of the missing entry.
+        kind: AclEntryKind,
+        /// True if the default ACL is missing the entry.
+        default: bool,
+    },
+
+    /// Entry has the same kind and name as an earlier entry in the same ACL.
+    Duplicate {
+        /// Index of the later entry.
+        index: usize,
+        /// Index of the earlier entry.
+        duplicate_of: usize,
+    },
+
+    /// Mask or other entry has a name.
+    UnexpectedName(usize),
+
+    /// Entry's kind is not supported by this kind of ACL.
+    UnsupportedKind(usize),
+
+    /// Entry is a deny entry, which this kind of ACL doesn't support.
+    UnsupportedDeny(usize),
+
+    /// Entry has flags that this kind of ACL doesn't support.
+    UnsupportedFlags(usize),
+
+    /// Entry has permissions that this kind of ACL doesn't support.
+    UnsupportedPerms(usize),
+
+    /// ACL has more entries than the platform allows.
+    TooManyEntries {
+        /// Number of entries.
+        count: usize,
+        /// Maximum number of entries.
+        max: usize,
+    },
+
+    /// Platform's own checks (e.g. `acl_check`, or looking up user and
+    /// group names) rejected the entries.
+    Native(String),
+}
+
+impl ValidationError {
+    /// Return index of the offending entry, if the problem concerns one entry.
+    #[must_use]
+    pub const fn index(&self) -> Option<usize> {
+        match self {
+            ValidationError::Duplicate { index, .. }
+            | ValidationError::UnexpectedName(index)
+            | ValidationError::UnsupportedKind(index)
+            | ValidationError::UnsupportedDeny(index)
+            | ValidationError::UnsupportedFlags(index)
+            | ValidationError::UnsupportedPerms(index) => Some(*index),
+            ValidationError::MissingEntry { .. }
+            | ValidationError::TooManyEntries { .. }
+            | ValidationError::Native(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::MissingEntry { kind, default } => {
+                let acl_name = if *default { "default" } else { "access" };
+                write!(f, "{acl_name} ACL: missing required entry \"{kind}\"")
+            }
+            ValidationError::Duplicate {
+                index,
+                duplicate_of,
+            } => write!(f, "entry {index}: duplicate of entry {duplicate_of}"),
+            ValidationError::UnexpectedName(i) => write!(f, "entry {i}: unexpected name"),
+            ValidationError::UnsupportedKind(i) => write!(f, "entry {i}: unsupported kind"),
+            ValidationError::UnsupportedDeny(i) => write!(f, "entry {i}: deny not supported"),
+            ValidationError::UnsupportedFlags(i) => write!(f, "entry {i}: unsupported flags"),
+            ValidationError::UnsupportedPerms(i) => {
+                write!(f, "entry {i}: unsupported permissions")
+            }
+            ValidationError::TooManyEntries { count, max } => {
+                write!(f, "too many ACL entries: {count} (at most {max})")
+            }
+            ValidationError::Native(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Check whether a list of entries can be applied as an ACL of the given
+/// kind, without touching the file system.
+///
+/// Return every problem found, in order; an empty list means the entries
+/// are valid. The checks match those of [`Acl::from_entries`] and
+/// [`Acl::from_unified_entries`]: required entries, duplicates, and
+/// unsupported kinds, flags and permissions. A missing mask entry is not a
+/// problem; one is computed when the entries are applied.
+///
+/// If those checks pass and `mode` is the platform's own kind of ACL, the
+/// entries are also converted to a native ACL, so the platform's checks
+/// (`acl_check` on Linux) and user/group name lookups run too.
+///
+/// ```
+/// use exacl::{validate, AclEntry, Perm, ValidationError, ValidationMode};
+///
+/// let entries = vec![AclEntry::allow_user("", Perm::READ, None)];
+/// let errors = validate(&entries, ValidationMode::Posix);
+/// assert!(errors.contains(&ValidationError::MissingEntry {
+///     kind: exacl::AclEntryKind::Other,
+///     default: false,
+/// }));
+/// ```
+///
+/// [`Acl::from_entries`]: crate::Acl::from_entries
+/// [`Acl::from_unified_entries`]: crate::Acl::from_unified_entries
+#[must_use]
+pub fn validate(entries: &[AclEntry], mode: ValidationMode) -> Vec<ValidationError> {
+    let mut errors = match mode {
+        ValidationMode::Posix => check_posix(entries),
+        ValidationMode::Nfs4 => check_extended(entries, &[AclEntryKind::Everyone]),
+        ValidationMode::MacOs => check_extended(entries, &[]),
+    };
+
+    if mode == ValidationMode::MacOs && entries.len() > MACOS_MAX_ENTRIES {
+        errors.push(ValidationError::TooManyEntries {
+            count: entries.len(),
+            max: MACOS_MAX_ENTRIES,
+        });
+    }
+
+    if errors.is_empty() {
+        if let Err(err) = check_native(entries, mode) {
+            errors.push(ValidationError::Native(err));
+        }
+    }
+
+    errors
+}
+
+/// Return the flags a Posix.1e entry may have.
+const fn posix_flags() -> Flag {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    ))]
+    return Flag::DEFAULT;
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        exacl_core,
+        feature = "strict_posix"
+    )))]
+    return Flag::empty();
+}
+
+fn check_posix(entries: &[AclEntry]) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+
+    for (i, entry) in entries.iter().enumerate() {
+        match entry.kind {
+            AclEntryKind::User | AclEntryKind::Group => (),
+            AclEntryKind::Mask | AclEntryKind::Other if !entry.name.is_empty() => {
+                errors.push(ValidationError::UnexpectedName(i));
+            }
+            AclEntryKind::Mask | AclEntryKind::Other => (),
+            AclEntryKind::Everyone | AclEntryKind::Unknown => {
+                errors.push(ValidationError::UnsupportedKind(i));
+            }
+        }
+        if !entry.allow {
+            errors.push(ValidationError::UnsupportedDeny(i));
+        }
+        if !posix_flags().contains(entry.flags) {
+            errors.push(ValidationError::UnsupportedFlags(i));
+        }
+        if !rwx.contains(entry.perms) {
+            errors.push(ValidationError::UnsupportedPerms(i));
+        }
+
+        let duplicate_of = entries[..i].iter().position(|prev| {
+            prev.kind == entry.kind
+                && prev.name == entry.name
+                && prev.is_default() == entry.is_default()
+        });
+        if let Some(duplicate_of) = duplicate_of {
+            errors.push(ValidationError::Duplicate {
+                index: i,
+                duplicate_of,
+            });
+        }
+    }
+
+    for default in [false, true] {
+        let acl = entries
+            .iter()
+            .filter(|entry| entry.is_default() == default)
+            .collect::<Vec<_>>();
+        if acl.is_empty() {
+            continue;
+        }
+
+        for kind in [AclEntryKind::User, AclEntryKind::Group, AclEntryKind::Other] {
+            if !acl.iter().any(|e| e.kind == kind && e.name.is_empty()) {
+                errors.push(ValidationError::MissingEntry { kind, default });
+            }
+        }
+    }
+
+    errors
+}
+
+/// Check entries of an ordered ACL (`NFSv4` or macOS). Besides users and
+/// groups, entries may have one of the `extra_kinds`.
+fn check_extended(entries: &[AclEntry], extra_kinds: &[AclEntryKind]) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let supported = matches!(entry.kind, AclEntryKind::User | AclEntryKind::Group)
+            || extra_kinds.contains(&entry.kind);
+        if !supported {
+            errors.push(ValidationError::UnsupportedKind(i));
+        }
+        if entry.is_default() {
+            errors.push(ValidationError::UnsupportedFlags(i));
+        }
+    }
+
+    errors
+}
+
+/// Convert the entries to a native ACL, if `mode` is the platform's kind of
+/// ACL, and return the error message if that fails.
+#[allow(clippy::unnecessary_wraps)]
+fn check_native(entries: &[AclEntry], mode: ValidationMode) -> Result<(), String> {
+    #[cfg(all(not(exacl_core), any(target_os = "linux", target_os = "freebsd")))]
+    if mode == ValidationMode::Posix {
+        return Acl::from_unified_entries(entries)
+            .map(|_| ())
+            .map_err(|err| err.to_string());
+    }
+
+    #[cfg(all(not(exacl_core), target_os = "freebsd"))]
+    if mode == ValidationMode::Nfs4 {
+        return Acl::from_entries(entries)
+            .map(|_| ())
+            .map_err(|err| err.to_string());
+    }
+
+    #[cfg(all(not(exacl_core), target_os = "macos"))]
+    if mode == ValidationMode::MacOs {
+        return Acl::from_entries(entries)
+            .map(|_| ())
+            .map_err(|err| err.to_string());
+    }
+
+    let _ = (entries, mode);
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_posix() {
+        let entries = crate::from_mode(0o640);
+        assert_eq!(validate(&entries, ValidationMode::Posix), vec![]);
+
+        let mut entries = vec![
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_user("11501", Perm::READ, None),
+            AclEntry::allow_user("11501", Perm::WRITE, None),
+            AclEntry::allow_mask(Perm::READ, None),
+        ];
+        entries[3].name = "x".to_string();
+        assert_eq!(
+            validate(&entries, ValidationMode::Posix),
+            vec![
+                ValidationError::Duplicate {
+                    index: 2,
+                    duplicate_of: 1
+                },
+                ValidationError::UnexpectedName(3),
+                ValidationError::MissingEntry {
+                    kind: AclEntryKind::Group,
+                    default: false
+                },
+                ValidationError::MissingEntry {
+                    kind: AclEntryKind::Other,
+                    default: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_posix_unsupported() {
+        let mut entries = crate::from_mode(0o640);
+        entries[0].allow = false;
+        entries[1].kind = AclEntryKind::Everyone;
+
+        let errors = validate(&entries, ValidationMode::Posix);
+        assert_eq!(
+            errors[..2],
+            [
+                ValidationError::UnsupportedDeny(0),
+                ValidationError::UnsupportedKind(1),
+            ]
+        );
+        assert_eq!(errors[0].index(), Some(0));
+        assert_eq!(errors[1].to_string(), "entry 1: unsupported kind");
+        assert_eq!(
+            errors[2].to_string(),
+            "access ACL: missing required entry \"group\""
+        );
+    }
+
+    #[test]
+    #[cfg(exacl_extended)]
+    fn test_validate_posix_perms() {
+        let mut entries = crate::from_mode(0o640);
+        entries[2].perms |= Perm::DELETE;
+
+        assert_eq!(
+            validate(&entries, ValidationMode::Posix),
+            vec![ValidationError::UnsupportedPerms(2)]
+        );
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", exacl_core))]
+    fn test_validate_posix_default() {
+        let mut entries = crate::from_mode(0o640);
+        entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
+
+        assert_eq!(
+            validate(&entries, ValidationMode::Posix),
+            vec![
+                ValidationError::MissingEntry {
+                    kind: AclEntryKind::Group,
+                    default: true
+                },
+                ValidationError::MissingEntry {
+                    kind: AclEntryKind::Other,
+                    default: true
+                },
+            ]
+        );
+
+        // Default entries are not supported by ordered ACL's.
+        assert_eq!(
+            validate(&entries[3..], ValidationMode::Nfs4),
+            vec![ValidationError::UnsupportedFlags(0)]
+        );
+    }
+
+    #[test]
+    fn test_validate_extended() {
+        let entries = vec![
+            AclEntry::allow_user("11501", Perm::READ, None),
+            AclEntry::allow_group("11501", Perm::READ, None),
+            AclEntry::allow_other(Perm::READ, None),
+            AclEntry::allow_group("11501", Perm::READ, None),
+            AclEntry {
+                kind: AclEntryKind::Everyone,
+                ..AclEntry::allow_user("", Perm::READ, None)
+            },
+        ];
+
+        // Duplicates are allowed in ordered ACL's.
+        assert_eq!(
+            validate(&entries[..2], ValidationMode::MacOs),
+            vec![],
+            "{entries:?}"
+        );
+        assert_eq!(
+            validate(&entries, ValidationMode::Nfs4),
+            vec![ValidationError::UnsupportedKind(2)]
+        );
+        assert_eq!(
+            validate(&entries, ValidationMode::MacOs),
+            vec![
+                ValidationError::UnsupportedKind(2),
+                ValidationError::UnsupportedKind(4),
+            ]
+        );
+
+        let entries = vec![AclEntry::allow_user("11501", Perm::READ, None); 129];
+        assert_eq!(
+            validate(&entries, ValidationMode::MacOs),
+            vec![ValidationError::TooManyEntries {
+                count: 129,
+                max: 128
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_validate_native() {
+        let mut entries = crate::from_mode(0o640);
+        entries.push(AclEntry::allow_user("no-such-user-11501", Perm::READ, None));
+
+        let errors = validate(&entries, ValidationMode::Posix);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(&errors[0], ValidationError::Native(msg) if msg.contains("no-such-user")));
+        assert_eq!(errors[0].index(), None);
+    }
+}
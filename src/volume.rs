@@ -0,0 +1,161 @@
+//! Implements `volume_capabilities`.
+
+use crate::failx::path_err;
+use crate::util::*;
+
+use std::io;
+use std::path::Path;
+
+/// Describes the ACL support of the file system containing a path.
+///
+/// Returned by [`volume_capabilities`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VolumeCapabilities {
+    /// File system supports ACL's.
+    pub acl: bool,
+
+    /// File system supports default ACL's on directories (Posix.1e only).
+    pub default_acl: bool,
+
+    /// File system uses `NFSv4` ACL semantics (macOS extended ACL's, `FreeBSD`
+    /// `NFSv4` ACL's, and Linux NFS mounts with the `nfs4` feature).
+    pub nfs4: bool,
+
+    /// File system supports deny entries.
+    pub deny: bool,
+
+    /// Maximum number of entries in an ACL, if known. On macOS, this is 128;
+    /// [`Acl::from_entries`](crate::Acl::from_entries) rejects longer lists.
+    pub max_entries: Option<usize>,
+}
+
+impl VolumeCapabilities {
+    /// Capabilities of a file system that does not support ACL's.
+    const NONE: VolumeCapabilities = VolumeCapabilities {
+        acl: false,
+        default_acl: false,
+        nfs4: false,
+        deny: false,
+        max_entries: None,
+    };
+}
+
+/// Return the ACL capabilities of the file system containing `path`.
+///
+/// On Linux, the maximum number of entries depends on the file system and is
+/// not reported.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure, e.g. if `path` does not exist.
+pub fn volume_capabilities<P: AsRef<Path>>(path: P) -> io::Result<VolumeCapabilities> {
+    let path = path.as_ref();
+    _volume_capabilities(path).map_err(|err| path_err(path, &err))
+}
+
+#[cfg(target_os = "linux")]
+fn _volume_capabilities(path: &Path) -> io::Result<VolumeCapabilities> {
+    #[cfg(exacl_nfs4)]
+    if crate::nfs4::is_nfs4(path, crate::AclOption::empty()) {
+        return Ok(VolumeCapabilities {
+            acl: true,
+            default_acl: false,
+            nfs4: true,
+            deny: true,
+            max_entries: None,
+        });
+    }
+
+    if !xacl_is_supported(path)? {
+        return Ok(VolumeCapabilities::NONE);
+    }
+
+    Ok(VolumeCapabilities {
+        acl: true,
+        default_acl: true,
+        nfs4: false,
+        deny: false,
+        max_entries: None,
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn _volume_capabilities(path: &Path) -> io::Result<VolumeCapabilities> {
+    if !xacl_is_supported(path)? {
+        return Ok(VolumeCapabilities::NONE);
+    }
+
+    Ok(VolumeCapabilities {
+        acl: true,
+        default_acl: false,
+        nfs4: true,
+        deny: true,
+        max_entries: Some(crate::sys::ACL_MAX_ENTRIES as usize),
+    })
+}
+
+#[cfg(target_os = "freebsd")]
+fn _volume_capabilities(path: &Path) -> io::Result<VolumeCapabilities> {
+    let nfs4 = xacl_is_nfs4(path, false)?;
+    let posix = !nfs4 && xacl_is_posix1e(path, false)?;
+    if !nfs4 && !posix {
+        return Ok(VolumeCapabilities::NONE);
+    }
+
+    Ok(VolumeCapabilities {
+        acl: true,
+        default_acl: posix,
+        nfs4,
+        deny: nfs4,
+        max_entries: Some(crate::sys::ACL_MAX_ENTRIES as usize),
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod volume_tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_capabilities() {
+        let dir = tempfile::tempdir().unwrap();
+        let caps = volume_capabilities(&dir).unwrap();
+        assert!(caps.acl);
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            caps,
+            VolumeCapabilities {
+                acl: true,
+                default_acl: true,
+                nfs4: false,
+                deny: false,
+                max_entries: None,
+            }
+        );
+
+        #[cfg(target_os = "macos")]
+        assert_eq!(caps.max_entries, Some(128));
+
+        #[cfg(target_os = "freebsd")]
+        assert_eq!(caps.default_acl, !caps.nfs4);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_volume_capabilities_unsupported() {
+        let caps = volume_capabilities("/proc/self/status").unwrap();
+        assert_eq!(caps, VolumeCapabilities::NONE);
+    }
+
+    #[test]
+    fn test_volume_capabilities_missing() {
+        let err = volume_capabilities("/tmp/does-not-exist").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err
+            .to_string()
+            .starts_with("File \"/tmp/does-not-exist\": "));
+    }
+}
@@ -0,0 +1,384 @@
+//! Applies an ACL to new files in a directory (requires the `watch` feature).
+//!
+//! Default ACL's make new files inherit entries from their directory, but some
+//! file systems don't support them (e.g. `NFSv4` without inheritance
+//! configured). [`DirWatcher`] is a user-space fallback: it watches a
+//! directory using inotify (Linux) or kqueue (macOS, `FreeBSD`) and calls
+//! [`setfacl`] on each file or directory created in it.
+//!
+//! The ACL is applied after the file is created, so there is a short window
+//! where the file has only the permissions it was created with. Only the
+//! directory itself is watched, not its subdirectories.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::watch::DirWatcher;
+//! use exacl::{AclEntry, Perm};
+//!
+//! let entries = vec![
+//!     AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+//!     AclEntry::allow_group("", Perm::READ, None),
+//!     AclEntry::allow_group("staff", Perm::READ, None),
+//!     AclEntry::allow_mask(Perm::READ, None),
+//!     AclEntry::allow_other(Perm::empty(), None),
+//! ];
+//!
+//! let mut watcher = DirWatcher::new("./shared", entries, None)?;
+//! loop {
+//!     for path in watcher.wait(None)? {
+//!         println!("{}", path.display());
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::setfacl;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Watches a directory and applies an ACL to each new file in it.
+pub struct DirWatcher {
+    dir: PathBuf,
+    entries: Vec<AclEntry>,
+    options: AclOption,
+    watch: sys_watch::Watch,
+}
+
+impl DirWatcher {
+    /// Start watching `dir` for new files.
+    ///
+    /// `entries` and `options` are passed to [`setfacl`] for each new file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the directory can't be watched.
+    pub fn new<P, O>(dir: P, entries: Vec<AclEntry>, options: O) -> io::Result<DirWatcher>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        let watch = sys_watch::Watch::new(&dir)?;
+
+        Ok(DirWatcher {
+            dir,
+            entries,
+            options: options.into().unwrap_or_default(),
+            watch,
+        })
+    }
+
+    /// Return the directory being watched.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Wait for new files, apply the ACL to them and return their paths.
+    ///
+    /// Waits forever if `timeout` is None. Returns an empty vector if the
+    /// timeout expires, or the wait is interrupted by a signal. Files that are
+    /// removed before the ACL is applied are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if waiting fails, or the ACL can't be applied
+    /// to a new file. The ACL is still applied to the other new files.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        let mut result = Ok(());
+
+        for name in self.watch.wait(&self.dir, timeout)? {
+            let path = self.dir.join(name);
+            match setfacl(&[&path], &self.entries, self.options) {
+                Ok(()) => paths.push(path),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+
+        result.map(|()| paths)
+    }
+}
+
+/// Return the timeout in milliseconds for `poll`, or -1 to wait forever.
+#[cfg(target_os = "linux")]
+fn timeout_millis(timeout: Option<Duration>) -> i32 {
+    timeout.map_or(-1, |timeout| {
+        i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod sys_watch {
+    use super::timeout_millis;
+    use crate::failx::*;
+
+    use std::ffi::{CString, OsStr, OsString};
+    use std::io;
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr;
+    use std::time::Duration;
+
+    const EVENT_SIZE: usize = mem::size_of::<libc::inotify_event>();
+
+    /// Inotify instance watching one directory.
+    pub struct Watch {
+        fd: OwnedFd,
+    }
+
+    impl Watch {
+        pub fn new(dir: &Path) -> io::Result<Watch> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+            if fd < 0 {
+                return fail_err(fd, "inotify_init1", ());
+            }
+            let watch = Watch {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            };
+
+            let c_path = CString::new(dir.as_os_str().as_bytes())?;
+            let mask = libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_ONLYDIR;
+            let ret = unsafe { libc::inotify_add_watch(fd, c_path.as_ptr(), mask) };
+            if ret < 0 {
+                return fail_err(ret, "inotify_add_watch", &c_path);
+            }
+
+            Ok(watch)
+        }
+
+        /// Wait for events and return the names of new files.
+        pub fn wait(
+            &mut self,
+            _dir: &Path,
+            timeout: Option<Duration>,
+        ) -> io::Result<Vec<OsString>> {
+            let fd = self.fd.as_raw_fd();
+            let mut pollfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            let ret = unsafe { libc::poll(&mut pollfd, 1, timeout_millis(timeout)) };
+            if ret < 0 {
+                let err = log_err(ret, "poll", fd);
+                if err.kind() == io::ErrorKind::Interrupted {
+                    return Ok(Vec::new());
+                }
+                return Err(err);
+            }
+
+            let mut names = Vec::new();
+            let mut buf = vec![0u8; 64 * (EVENT_SIZE + 256)];
+            loop {
+                let len = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+                if len < 0 {
+                    let err = log_err(len, "read", fd);
+                    if err.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    }
+                    return Err(err);
+                }
+                if len == 0 {
+                    break;
+                }
+
+                #[allow(clippy::cast_sign_loss)]
+                parse_events(&buf[..len as usize], &mut names);
+            }
+
+            Ok(names)
+        }
+    }
+
+    /// Append the names in a buffer of inotify events to `names`.
+    fn parse_events(buf: &[u8], names: &mut Vec<OsString>) {
+        let mut offset = 0;
+        while offset + EVENT_SIZE <= buf.len() {
+            let event: libc::inotify_event =
+                unsafe { ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+            let start = offset + EVENT_SIZE;
+            let end = (start + event.len as usize).min(buf.len());
+            offset = end;
+
+            if event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) == 0 {
+                continue;
+            }
+
+            // The name is padded with NUL bytes.
+            let name = buf[start..end].split(|b| *b == 0).next().unwrap_or(&[]);
+            if !name.is_empty() {
+                names.push(OsStr::from_bytes(name).to_os_string());
+            }
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+mod sys_watch {
+    use crate::failx::*;
+
+    use std::collections::HashSet;
+    use std::ffi::{CString, OsString};
+    use std::fs;
+    use std::io;
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr;
+    use std::time::Duration;
+
+    #[cfg(target_os = "macos")]
+    const OPEN_FLAGS: libc::c_int = libc::O_EVTONLY;
+    #[cfg(target_os = "freebsd")]
+    const OPEN_FLAGS: libc::c_int = libc::O_RDONLY | libc::O_DIRECTORY;
+
+    /// Kqueue instance watching one directory for writes.
+    ///
+    /// kqueue reports that the directory changed, but not which names were
+    /// added. We keep the set of names in the directory and compare.
+    pub struct Watch {
+        kq: OwnedFd,
+        /// Directory registered with `kq`; kept open while watching.
+        dir_fd: OwnedFd,
+        known: HashSet<OsString>,
+    }
+
+    impl Watch {
+        pub fn new(dir: &Path) -> io::Result<Watch> {
+            let c_path = CString::new(dir.as_os_str().as_bytes())?;
+            let dir_fd = unsafe { libc::open(c_path.as_ptr(), OPEN_FLAGS | libc::O_CLOEXEC) };
+            if dir_fd < 0 {
+                return fail_err(dir_fd, "open", &c_path);
+            }
+            let dir_fd = unsafe { OwnedFd::from_raw_fd(dir_fd) };
+
+            let kq = unsafe { libc::kqueue() };
+            if kq < 0 {
+                return fail_err(kq, "kqueue", ());
+            }
+
+            let watch = Watch {
+                kq: unsafe { OwnedFd::from_raw_fd(kq) },
+                dir_fd,
+                known: list_dir(dir)?,
+            };
+
+            let mut change: libc::kevent = unsafe { mem::zeroed() };
+            #[allow(clippy::cast_sign_loss)]
+            {
+                change.ident = watch.dir_fd.as_raw_fd() as libc::uintptr_t;
+            }
+            change.filter = libc::EVFILT_VNODE;
+            change.flags = libc::EV_ADD | libc::EV_CLEAR;
+            change.fflags = libc::NOTE_WRITE;
+
+            let ret = unsafe { libc::kevent(kq, &change, 1, ptr::null_mut(), 0, ptr::null()) };
+            if ret < 0 {
+                return fail_err(ret, "kevent", &c_path);
+            }
+
+            Ok(watch)
+        }
+
+        /// Wait for the directory to change and return the names of new files.
+        pub fn wait(&mut self, dir: &Path, timeout: Option<Duration>) -> io::Result<Vec<OsString>> {
+            let timespec = timeout.map(|timeout| libc::timespec {
+                tv_sec: libc::time_t::try_from(timeout.as_secs()).unwrap_or(libc::time_t::MAX),
+                tv_nsec: libc::c_long::from(timeout.subsec_nanos()),
+            });
+            let timespec_p = timespec.as_ref().map_or(ptr::null(), |ts| ts as *const _);
+
+            let kq = self.kq.as_raw_fd();
+            let mut event: libc::kevent = unsafe { mem::zeroed() };
+            let ret = unsafe { libc::kevent(kq, ptr::null(), 0, &mut event, 1, timespec_p) };
+            if ret < 0 {
+                let err = log_err(ret, "kevent", kq);
+                if err.kind() == io::ErrorKind::Interrupted {
+                    return Ok(Vec::new());
+                }
+                return Err(err);
+            }
+
+            if ret == 0 {
+                return Ok(Vec::new());
+            }
+
+            let current = list_dir(dir)?;
+            let names = current.difference(&self.known).cloned().collect();
+            self.known = current;
+
+            Ok(names)
+        }
+    }
+
+    /// Return the set of names in a directory.
+    fn list_dir(dir: &Path) -> io::Result<HashSet<OsString>> {
+        fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+    use crate::getfacl;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_dir_watcher() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let entry = AclEntry::allow_user("11501", Perm::READ, None);
+
+        #[cfg(target_os = "macos")]
+        let entries = vec![entry.clone()];
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("", Perm::READ, None),
+            AclEntry::allow_other(Perm::empty(), None),
+            entry.clone(),
+        ];
+
+        let mut watcher = DirWatcher::new(&dir, entries, None)?;
+        assert_eq!(watcher.dir(), dir.path());
+        assert!(watcher.wait(Some(Duration::from_millis(10)))?.is_empty());
+
+        let path = dir.path().join("new_file");
+        std::fs::write(&path, "")?;
+
+        let paths = watcher.wait(Some(Duration::from_secs(5)))?;
+        assert_eq!(paths, vec![path.clone()]);
+
+        let acl = getfacl(&path, None)?;
+        assert!(acl
+            .iter()
+            .any(|e| e.name == entry.name && e.perms == entry.perms));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dir_watcher_missing() {
+        let err = DirWatcher::new("/non-existant", vec![], None)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,463 @@
+//! Reads and writes the DACL of a file or directory on Windows (requires the
+//! `windows` feature).
+//!
+//! A Windows DACL is an ordered list of allow and deny entries, like an `NFSv4`
+//! ACL, so it maps onto [`AclEntry`] with the `NFSv4` permissions and
+//! inheritance flags:
+//!
+//! - A principal's name is its SID string, e.g. `S-1-5-32-544`. The kind is
+//!   `User` for user accounts and `Group` for every other SID. `Everyone`
+//!   (`S-1-1-0`) is an [`AclEntryKind::Everyone`] entry, and `OWNER RIGHTS`
+//!   (`S-1-3-4`) is the owner's `User` entry with an empty name.
+//! - `READ` and `WRITE` are written as `READ_DATA` and `WRITE_DATA`. Generic
+//!   rights are read as the file rights they stand for.
+//! - Entries with the `INHERITED` flag are ignored by [`setfacl`]; Windows
+//!   propagates them from the parent directory.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::windows::{getfacl, setfacl};
+//! use exacl::{AclEntry, Perm};
+//!
+//! let mut entries = getfacl("C:\\tmp\\foo.txt")?;
+//! entries.push(AclEntry::allow_group("S-1-5-32-545", Perm::READ_DATA, None));
+//! setfacl("C:\\tmp\\foo.txt", &entries)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::failx::{fail_custom, fail_err, fail_from_err, path_err};
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+use scopeguard::defer;
+use std::ffi::{c_void, OsStr};
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+use windows_sys::Win32::Security::Authorization::{
+    ConvertSidToStringSidW, ConvertStringSidToSidW, GetNamedSecurityInfoW, SetNamedSecurityInfoW,
+    SE_FILE_OBJECT,
+};
+use windows_sys::Win32::Security::{
+    AddAccessAllowedAceEx, AddAccessDeniedAceEx, GetAce, GetLengthSid, InitializeAcl,
+    LookupAccountSidW, SidTypeUser, ACCESS_ALLOWED_ACE, ACE_HEADER, ACL, ACL_REVISION,
+    DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID, SID_NAME_USE,
+    UNPROTECTED_DACL_SECURITY_INFORMATION,
+};
+use windows_sys::Win32::System::SystemServices::{ACCESS_ALLOWED_ACE_TYPE, ACCESS_DENIED_ACE_TYPE};
+
+/// SID of the `Everyone` group.
+const EVERYONE_SID: &str = "S-1-1-0";
+
+/// SID of `OWNER RIGHTS`, which stands for the owner of the object.
+const OWNER_RIGHTS_SID: &str = "S-1-3-4";
+
+const FLAGS: [(u8, Flag); 5] = [
+    (0x01, Flag::FILE_INHERIT),
+    (0x02, Flag::DIRECTORY_INHERIT),
+    (0x04, Flag::LIMIT_INHERIT),
+    (0x08, Flag::ONLY_INHERIT),
+    (0x10, Flag::INHERITED),
+];
+
+const PERMS: [(u32, Perm); 14] = [
+    (0x0000_0001, Perm::READ_DATA),
+    (0x0000_0002, Perm::WRITE_DATA),
+    (0x0000_0004, Perm::APPEND),
+    (0x0000_0008, Perm::READEXTATTR),
+    (0x0000_0010, Perm::WRITEEXTATTR),
+    (0x0000_0020, Perm::EXECUTE),
+    (0x0000_0040, Perm::DELETE_CHILD),
+    (0x0000_0080, Perm::READATTR),
+    (0x0000_0100, Perm::WRITEATTR),
+    (0x0001_0000, Perm::DELETE),
+    (0x0002_0000, Perm::READSECURITY),
+    (0x0004_0000, Perm::WRITESECURITY),
+    (0x0008_0000, Perm::CHOWN),
+    (0x0010_0000, Perm::SYNC),
+];
+
+/// Generic rights and the file rights they stand for (`FILE_GENERIC_READ`,
+/// `FILE_GENERIC_WRITE`, `FILE_GENERIC_EXECUTE` and `FILE_ALL_ACCESS`).
+const GENERIC_RIGHTS: [(u32, u32); 4] = [
+    (0x8000_0000, 0x0012_0089),
+    (0x4000_0000, 0x0012_0116),
+    (0x2000_0000, 0x0012_00a0),
+    (0x1000_0000, 0x001f_01ff),
+];
+
+/// Return the DACL of a file or directory.
+///
+/// A null DACL, which grants everyone full access, is returned as one entry
+/// that allows everyone every permission.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the DACL can't be read, or if it has an entry
+/// type other than allow or deny, e.g. an object or callback entry.
+pub fn getfacl<P: AsRef<Path>>(path: P) -> io::Result<Vec<AclEntry>> {
+    let path = path.as_ref();
+    read_dacl(path).map_err(|err| path_err(path, &err))
+}
+
+/// Replace the DACL of a file or directory.
+///
+/// Entries are written in the order given; Windows expects deny entries
+/// before allow entries. Inheritance from the parent directory is kept.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry can't be represented in a DACL, e.g.
+/// if its name is not a SID string, or if the DACL can't be written.
+pub fn setfacl<P: AsRef<Path>>(path: P, entries: &[AclEntry]) -> io::Result<()> {
+    let path = path.as_ref();
+    write_dacl(path, entries).map_err(|err| path_err(path, &err))
+}
+
+fn read_dacl(path: &Path) -> io::Result<Vec<AclEntry>> {
+    let wide_path = to_wide(path.as_os_str());
+    let mut dacl: *mut ACL = ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+
+    let ret = unsafe {
+        GetNamedSecurityInfoW(
+            wide_path.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut dacl,
+            ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+    if ret != ERROR_SUCCESS {
+        return fail_from_err(ret as i32, "GetNamedSecurityInfoW", path);
+    }
+    defer! { unsafe { LocalFree(descriptor) }; }
+
+    if dacl.is_null() {
+        return Ok(vec![AclEntry {
+            kind: AclEntryKind::Everyone,
+            ..AclEntry::allow_user("", Perm::all(), None)
+        }]);
+    }
+
+    let count = unsafe { (*dacl).AceCount };
+    let mut entries = Vec::with_capacity(usize::from(count));
+    for i in 0..count {
+        let mut ace: *mut c_void = ptr::null_mut();
+        if unsafe { GetAce(dacl, u32::from(i), &mut ace) } == 0 {
+            return fail_err(0, "GetAce", i);
+        }
+        entries.push(unsafe { read_ace(ace) }?);
+    }
+
+    Ok(entries)
+}
+
+/// Convert an allow or deny ACE to an entry.
+///
+/// # Safety
+///
+/// `ace` must point to an ACE returned by `GetAce`.
+unsafe fn read_ace(ace: *const c_void) -> io::Result<AclEntry> {
+    let header = &*ace.cast::<ACE_HEADER>();
+    let allow = match u32::from(header.AceType) {
+        ACCESS_ALLOWED_ACE_TYPE => true,
+        ACCESS_DENIED_ACE_TYPE => false,
+        ace_type => return fail_custom(&format!("unsupported ACE type: {ace_type}")),
+    };
+
+    // Allow and deny ACE's have the same layout.
+    let ace = ace.cast::<ACCESS_ALLOWED_ACE>();
+    let sid: PSID = ptr::addr_of!((*ace).SidStart).cast_mut().cast();
+    let flags = from_bits(header.AceFlags, &FLAGS, "flags")?;
+    let perms = from_bits(expand_generic((*ace).Mask), &PERMS, "permissions")?;
+
+    let sid_string = sid_to_string(sid)?;
+    let (kind, name) = match sid_string.as_str() {
+        EVERYONE_SID => (AclEntryKind::Everyone, String::new()),
+        OWNER_RIGHTS_SID => (AclEntryKind::User, String::new()),
+        _ if is_user(sid) => (AclEntryKind::User, sid_string),
+        _ => (AclEntryKind::Group, sid_string),
+    };
+
+    Ok(AclEntry {
+        kind,
+        name,
+        perms,
+        flags,
+        allow,
+        numeric: false,
+    })
+}
+
+fn write_dacl(path: &Path, entries: &[AclEntry]) -> io::Result<()> {
+    let mut sids = scopeguard::guard(Vec::new(), |sids| {
+        for sid in sids {
+            unsafe { LocalFree(sid) };
+        }
+    });
+
+    // Windows propagates inherited entries from the parent.
+    let entries = entries
+        .iter()
+        .filter(|entry| !entry.flags.contains(Flag::INHERITED))
+        .collect::<Vec<_>>();
+
+    let mut size = std::mem::size_of::<ACL>();
+    for entry in &entries {
+        let sid = string_to_sid(sid_string(entry)?)?;
+        sids.push(sid);
+        size += std::mem::size_of::<ACCESS_ALLOWED_ACE>() - std::mem::size_of::<u32>()
+            + unsafe { GetLengthSid(sid) } as usize;
+    }
+
+    // The ACL must be aligned on a 4-byte boundary.
+    let Ok(acl_size) = u16::try_from(size) else {
+        return fail_custom(&format!("DACL too large: {size} bytes"));
+    };
+    let mut buf = vec![0u32; usize::from(acl_size).div_ceil(4)];
+    let acl = buf.as_mut_ptr().cast::<ACL>();
+    if unsafe { InitializeAcl(acl, u32::from(acl_size), ACL_REVISION) } == 0 {
+        return fail_err(0, "InitializeAcl", acl_size);
+    }
+
+    for (entry, sid) in entries.iter().zip(sids.iter()) {
+        // `READ` and `WRITE` mean the same as `READ_DATA` and `WRITE_DATA`.
+        let mut perms = entry.perms;
+        if perms.contains(Perm::READ) {
+            perms = (perms - Perm::READ) | Perm::READ_DATA;
+        }
+        if perms.contains(Perm::WRITE) {
+            perms = (perms - Perm::WRITE) | Perm::WRITE_DATA;
+        }
+
+        let mask = to_bits(perms, &PERMS, "permissions")?;
+        let flags = u32::from(to_bits(entry.flags, &FLAGS, "flags")?);
+        let ret = unsafe {
+            if entry.allow {
+                AddAccessAllowedAceEx(acl, ACL_REVISION, flags, mask, *sid)
+            } else {
+                AddAccessDeniedAceEx(acl, ACL_REVISION, flags, mask, *sid)
+            }
+        };
+        if ret == 0 {
+            return fail_err(ret, "AddAccessAceEx", entry);
+        }
+    }
+
+    let wide_path = to_wide(path.as_os_str());
+    let ret = unsafe {
+        SetNamedSecurityInfoW(
+            wide_path.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | UNPROTECTED_DACL_SECURITY_INFORMATION,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            acl,
+            ptr::null(),
+        )
+    };
+    if ret != ERROR_SUCCESS {
+        return fail_from_err(ret as i32, "SetNamedSecurityInfoW", path);
+    }
+
+    Ok(())
+}
+
+/// Return the SID string of an entry's principal.
+fn sid_string(entry: &AclEntry) -> io::Result<&str> {
+    match (entry.kind, entry.name.as_str()) {
+        (AclEntryKind::Everyone, _) => Ok(EVERYONE_SID),
+        (AclEntryKind::User, "") => Ok(OWNER_RIGHTS_SID),
+        (AclEntryKind::User | AclEntryKind::Group, name) if name.starts_with("S-") => Ok(name),
+        (AclEntryKind::User | AclEntryKind::Group, name) => {
+            fail_custom(&format!("name is not a SID string: \"{name}\""))
+        }
+        (kind, _) => fail_custom(&format!("unsupported kind in Windows ACL: \"{kind}\"")),
+    }
+}
+
+/// Return true if the SID is a user account.
+fn is_user(sid: PSID) -> bool {
+    let mut name_len = 0;
+    let mut domain_len = 0;
+    let mut sid_use: SID_NAME_USE = 0;
+
+    // Ask for the buffer sizes, then look up the account.
+    unsafe {
+        LookupAccountSidW(
+            ptr::null(),
+            sid,
+            ptr::null_mut(),
+            &mut name_len,
+            ptr::null_mut(),
+            &mut domain_len,
+            &mut sid_use,
+        )
+    };
+    let mut name = vec![0u16; name_len as usize];
+    let mut domain = vec![0u16; domain_len as usize];
+    let ret = unsafe {
+        LookupAccountSidW(
+            ptr::null(),
+            sid,
+            name.as_mut_ptr(),
+            &mut name_len,
+            domain.as_mut_ptr(),
+            &mut domain_len,
+            &mut sid_use,
+        )
+    };
+
+    ret != 0 && sid_use == SidTypeUser
+}
+
+/// Return the string form of a SID.
+fn sid_to_string(sid: PSID) -> io::Result<String> {
+    let mut wide = ptr::null_mut();
+    if unsafe { ConvertSidToStringSidW(sid, &mut wide) } == 0 {
+        return fail_err(0, "ConvertSidToStringSidW", ());
+    }
+    defer! { unsafe { LocalFree(wide.cast()) }; }
+
+    let len = (0..).take_while(|&i| unsafe { *wide.add(i) } != 0).count();
+    let chars = unsafe { std::slice::from_raw_parts(wide, len) };
+    Ok(String::from_utf16_lossy(chars))
+}
+
+/// Return the SID for a SID string. Free it with `LocalFree`.
+fn string_to_sid(s: &str) -> io::Result<PSID> {
+    let wide = to_wide(OsStr::new(s));
+    let mut sid: PSID = ptr::null_mut();
+    if unsafe { ConvertStringSidToSidW(wide.as_ptr(), &mut sid) } == 0 {
+        return fail_err(0, "ConvertStringSidToSidW", s);
+    }
+
+    Ok(sid)
+}
+
+/// Return a NUL-terminated wide string.
+fn to_wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain(Some(0)).collect()
+}
+
+/// Replace generic rights in an access mask with the file rights they stand
+/// for.
+fn expand_generic(mask: u32) -> u32 {
+    GENERIC_RIGHTS
+        .iter()
+        .filter(|(generic, _)| mask & generic != 0)
+        .fold(mask, |acc, (generic, specific)| (acc & !generic) | specific)
+}
+
+/// Convert DACL bits to exacl flags or permissions.
+fn from_bits<B, T>(bits: B, table: &[(B, T)], what: &str) -> io::Result<T>
+where
+    B: Copy + Into<u32>,
+    T: bitflags::Flags + Copy,
+{
+    let bits = bits.into();
+    let known = table.iter().fold(0, |acc, (bit, _)| acc | (*bit).into());
+    if bits & !known != 0 {
+        return fail_custom(&format!("unsupported DACL {what}: {:#x}", bits & !known));
+    }
+
+    Ok(table
+        .iter()
+        .filter(|(bit, _)| bits & (*bit).into() != 0)
+        .fold(T::empty(), |acc, (_, value)| acc.union(*value)))
+}
+
+/// Convert exacl flags or permissions to DACL bits.
+fn to_bits<B, T>(value: T, table: &[(B, T)], what: &str) -> io::Result<B>
+where
+    B: Copy + Default + std::ops::BitOr<Output = B>,
+    T: bitflags::Flags + Copy + std::fmt::Display,
+{
+    let known = table
+        .iter()
+        .fold(T::empty(), |acc, (_, value)| acc.union(*value));
+    let unknown = value.difference(known);
+    if !unknown.is_empty() {
+        return fail_custom(&format!("unsupported {what} in Windows ACL: {unknown}"));
+    }
+
+    Ok(table
+        .iter()
+        .filter(|(_, v)| value.contains(*v))
+        .fold(B::default(), |acc, (bit, _)| acc | *bit))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod windows_tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_generic() {
+        assert_eq!(expand_generic(0x8000_0000), 0x0012_0089);
+        assert_eq!(expand_generic(0x1000_0001), 0x001f_01ff);
+        assert_eq!(expand_generic(0x20), 0x20);
+    }
+
+    #[test]
+    fn test_bits() -> io::Result<()> {
+        let perms = from_bits(0x0012_0089, &PERMS, "permissions")?;
+        assert_eq!(
+            perms,
+            Perm::READ_DATA | Perm::READEXTATTR | Perm::READATTR | Perm::READSECURITY | Perm::SYNC
+        );
+        assert_eq!(to_bits(perms, &PERMS, "permissions")?, 0x0012_0089);
+        assert!(from_bits(0x0100_0000, &PERMS, "permissions").is_err());
+
+        let flags = from_bits(0x13u8, &FLAGS, "flags")?;
+        assert_eq!(
+            flags,
+            Flag::FILE_INHERIT | Flag::DIRECTORY_INHERIT | Flag::INHERITED
+        );
+        assert_eq!(to_bits(flags, &FLAGS, "flags")?, 0x13u8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sid_string() {
+        let everyone = AclEntry {
+            kind: AclEntryKind::Everyone,
+            ..AclEntry::allow_user("", Perm::READ, None)
+        };
+        assert_eq!(sid_string(&everyone).unwrap(), EVERYONE_SID);
+        let owner = AclEntry::allow_user("", Perm::READ, None);
+        assert_eq!(sid_string(&owner).unwrap(), OWNER_RIGHTS_SID);
+        let admins = AclEntry::allow_group("S-1-5-32-544", Perm::READ, None);
+        assert_eq!(sid_string(&admins).unwrap(), "S-1-5-32-544");
+        assert!(sid_string(&AclEntry::allow_user("bob", Perm::READ, None)).is_err());
+    }
+
+    #[test]
+    fn test_getfacl_setfacl() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let mut entries = getfacl(&file)?;
+        entries.insert(
+            0,
+            AclEntry {
+                allow: false,
+                ..AclEntry::allow_group("S-1-5-32-545", Perm::WRITE_DATA, None)
+            },
+        );
+        setfacl(&file, &entries)?;
+
+        let actual = getfacl(&file)?;
+        assert_eq!(actual[0].name, "S-1-5-32-545");
+        assert_eq!(actual[0].kind, AclEntryKind::Group);
+        assert!(!actual[0].allow);
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+//! Implements `to_xattr_bytes` and `from_xattr_bytes`, which convert entries
+//! to and from the value of the Linux `system.posix_acl_access` and
+//! `system.posix_acl_default` extended attributes.
+//!
+//! The value is a little-endian `u32` version (2), followed by one 8-byte
+//! entry per ACL entry: a `u16` tag, a `u16` permission mask and a `u32` id.
+//! Entries are sorted by tag, then id.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::failx::fail_custom;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+
+use std::io;
+
+/// Version of the extended attribute format.
+const XATTR_VERSION: u32 = 2;
+
+// Entry tags.
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+/// Id of entries that have no qualifier.
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+const PERMS: [(u16, Perm); 3] = [
+    (0x04, Perm::READ),
+    (0x02, Perm::WRITE),
+    (0x01, Perm::EXECUTE),
+];
+
+/// Which Posix.1e ACL an extended attribute holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum XattrKind {
+    /// Access ACL (`system.posix_acl_access`).
+    Access,
+
+    /// Default ACL of a directory (`system.posix_acl_default`).
+    Default,
+}
+
+impl XattrKind {
+    /// Return the name of the extended attribute.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            XattrKind::Access => "system.posix_acl_access",
+            XattrKind::Default => "system.posix_acl_default",
+        }
+    }
+}
+
+/// Encode entries as the value of a Posix.1e ACL extended attribute, without
+/// touching the file system.
+///
+/// Only the entries of the given `kind` are encoded: those with the
+/// [`Flag::DEFAULT`] flag for [`XattrKind::Default`], and the others for
+/// [`XattrKind::Access`], so the entries returned by
+/// [`getfacl`](crate::getfacl) for a directory can be passed twice. If there
+/// is no mask entry, one is computed and added, if needed. Named users and
+/// groups are stored by uid/gid.
+///
+/// ```
+/// use exacl::{from_xattr_bytes, to_xattr_bytes, XattrKind};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let entries = exacl::from_mode(0o640);
+/// let bytes = to_xattr_bytes(&entries, XattrKind::Access)?;
+/// assert_eq!(bytes.len(), 4 + 3 * 8);
+/// assert_eq!(from_xattr_bytes(&bytes)?, entries);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry has no Posix.1e equivalent, a name
+/// can't be resolved, a required entry is missing or an entry is duplicated.
+pub fn to_xattr_bytes(entries: &[AclEntry], kind: XattrKind) -> io::Result<Vec<u8>> {
+    let default = kind == XattrKind::Default;
+    let mut raw = Vec::new();
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) == default {
+            raw.push(encode_entry(entry)?);
+        }
+    }
+
+    if !raw.is_empty() {
+        complete(&mut raw)?;
+    }
+
+    let mut buf = Vec::with_capacity(4 + 8 * raw.len());
+    buf.extend_from_slice(&XATTR_VERSION.to_le_bytes());
+    for (tag, perm, id) in raw {
+        buf.extend_from_slice(&tag.to_le_bytes());
+        buf.extend_from_slice(&perm.to_le_bytes());
+        buf.extend_from_slice(&id.to_le_bytes());
+    }
+
+    Ok(buf)
+}
+
+/// Decode the value of a Posix.1e ACL extended attribute, without touching
+/// the file system.
+///
+/// The entries don't have the [`Flag::DEFAULT`] flag, even if `bytes` came
+/// from `system.posix_acl_default`; set it to restore a default ACL with
+/// [`setfacl`](crate::setfacl). Named users and groups are looked up by
+/// uid/gid; ids that aren't in the user/group database are named by their
+/// decimal id.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `bytes` is not a valid attribute value.
+pub fn from_xattr_bytes(bytes: &[u8]) -> io::Result<Vec<AclEntry>> {
+    let Some((version, body)) = bytes.split_first_chunk::<4>() else {
+        return fail_custom("ACL xattr is too short");
+    };
+    if u32::from_le_bytes(*version) != XATTR_VERSION {
+        return fail_custom(&format!(
+            "unsupported ACL xattr version: {}",
+            u32::from_le_bytes(*version)
+        ));
+    }
+    if body.len() % 8 != 0 {
+        return fail_custom("ACL xattr has a partial entry");
+    }
+
+    body.chunks_exact(8)
+        .map(|chunk| {
+            let tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+            let perm = u16::from_le_bytes([chunk[2], chunk[3]]);
+            let id = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            decode_entry(tag, perm, id)
+        })
+        .collect()
+}
+
+/// Return the tag, permission mask and id of an entry.
+fn encode_entry(entry: &AclEntry) -> io::Result<(u16, u16, u32)> {
+    if !entry.allow {
+        return fail_custom(&format!("Posix.1e ACL's have no deny entries: {entry}"));
+    }
+    if !(entry.flags - Flag::DEFAULT).is_empty() {
+        return fail_custom(&format!("unsupported flags in Posix.1e ACL: {entry}"));
+    }
+
+    let (tag, id) = match entry.qualifier(false)? {
+        Qualifier::UserObj => (ACL_USER_OBJ, ACL_UNDEFINED_ID),
+        Qualifier::User(uid) => (ACL_USER, uid),
+        Qualifier::GroupObj => (ACL_GROUP_OBJ, ACL_UNDEFINED_ID),
+        Qualifier::Group(gid) => (ACL_GROUP, gid),
+        Qualifier::Mask => (ACL_MASK, ACL_UNDEFINED_ID),
+        Qualifier::Other => (ACL_OTHER, ACL_UNDEFINED_ID),
+        Qualifier::Unknown(_) => {
+            return fail_custom(&format!("unsupported kind in Posix.1e ACL: {entry}"));
+        }
+    };
+
+    let mut perm = 0;
+    for (bit, flag) in PERMS {
+        if entry.perms.contains(flag) {
+            perm |= bit;
+        }
+    }
+    if entry.perms != perms_from_bits(perm) {
+        return fail_custom(&format!("unsupported permissions in Posix.1e ACL: {entry}"));
+    }
+
+    Ok((tag, perm, id))
+}
+
+/// Sort raw entries, check for missing and duplicate entries, and add a mask
+/// entry if one is needed.
+fn complete(raw: &mut Vec<(u16, u16, u32)>) -> io::Result<()> {
+    raw.sort_unstable_by_key(|&(tag, _, id)| (tag, id));
+
+    if let Some(pair) = raw
+        .windows(2)
+        .find(|pair| (pair[0].0, pair[0].2) == (pair[1].0, pair[1].2))
+    {
+        return fail_custom(&format!(
+            "duplicate entry in Posix.1e ACL: tag {:#x}, id {}",
+            pair[0].0, pair[0].2
+        ));
+    }
+
+    for (tag, kind) in [
+        (ACL_USER_OBJ, AclEntryKind::User),
+        (ACL_GROUP_OBJ, AclEntryKind::Group),
+        (ACL_OTHER, AclEntryKind::Other),
+    ] {
+        if !raw.iter().any(|entry| entry.0 == tag) {
+            return fail_custom(&format!("missing required entry \"{kind}\""));
+        }
+    }
+
+    // Named entries need a mask. Compute it from the group class entries.
+    let has_named = raw
+        .iter()
+        .any(|entry| matches!(entry.0, ACL_USER | ACL_GROUP));
+    if has_named && !raw.iter().any(|entry| entry.0 == ACL_MASK) {
+        let perm = raw
+            .iter()
+            .filter(|entry| matches!(entry.0, ACL_USER | ACL_GROUP_OBJ | ACL_GROUP))
+            .fold(0, |acc, entry| acc | entry.1);
+        let pos = raw.partition_point(|entry| entry.0 < ACL_MASK);
+        raw.insert(pos, (ACL_MASK, perm, ACL_UNDEFINED_ID));
+    }
+
+    Ok(())
+}
+
+fn decode_entry(tag: u16, perm: u16, id: u32) -> io::Result<AclEntry> {
+    let (kind, qualifier) = match tag {
+        ACL_USER_OBJ => (AclEntryKind::User, Qualifier::UserObj),
+        ACL_USER => (AclEntryKind::User, Qualifier::User(id)),
+        ACL_GROUP_OBJ => (AclEntryKind::Group, Qualifier::GroupObj),
+        ACL_GROUP => (AclEntryKind::Group, Qualifier::Group(id)),
+        ACL_MASK => (AclEntryKind::Mask, Qualifier::Mask),
+        ACL_OTHER => (AclEntryKind::Other, Qualifier::Other),
+        _ => return fail_custom(&format!("unknown tag in ACL xattr: {tag:#x}")),
+    };
+
+    let perms = perms_from_bits(perm);
+    if perm & !0x07 != 0 {
+        return fail_custom(&format!("unknown permissions in ACL xattr: {perm:#x}"));
+    }
+
+    Ok(AclEntry {
+        kind,
+        name: qualifier.name()?,
+        perms,
+        flags: Flag::empty(),
+        allow: true,
+        numeric: false,
+    })
+}
+
+fn perms_from_bits(bits: u16) -> Perm {
+    PERMS
+        .iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .fold(Perm::empty(), |acc, (_, perm)| acc | *perm)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod xattr_tests {
+    use super::*;
+
+    #[test]
+    fn test_to_xattr_bytes() -> io::Result<()> {
+        let entries = vec![
+            AclEntry::allow_other(Perm::empty(), None),
+            AclEntry::allow_group("11502", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("", Perm::READ, None),
+        ];
+
+        // Sorted by tag, with a computed mask.
+        let bytes = to_xattr_bytes(&entries, XattrKind::Access)?;
+        #[rustfmt::skip]
+        assert_eq!(bytes, [
+            2, 0, 0, 0,
+            0x01, 0, 6, 0, 0xff, 0xff, 0xff, 0xff,
+            0x04, 0, 4, 0, 0xff, 0xff, 0xff, 0xff,
+            0x08, 0, 6, 0, 0xee, 0x2c, 0, 0,
+            0x10, 0, 6, 0, 0xff, 0xff, 0xff, 0xff,
+            0x20, 0, 0, 0, 0xff, 0xff, 0xff, 0xff,
+        ]);
+
+        let decoded = from_xattr_bytes(&bytes)?;
+        assert_eq!(decoded.len(), 5);
+        assert_eq!(
+            decoded[2],
+            AclEntry::allow_group("11502", Perm::READ | Perm::WRITE, None)
+        );
+        assert_eq!(
+            decoded[3],
+            AclEntry::allow_mask(Perm::READ | Perm::WRITE, None)
+        );
+
+        // There are no default entries.
+        assert_eq!(to_xattr_bytes(&entries, XattrKind::Default)?, [2, 0, 0, 0]);
+        assert!(from_xattr_bytes(&[2, 0, 0, 0])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_xattr_bytes_default() -> io::Result<()> {
+        let mut entries = crate::from_mode(0o750);
+        entries.extend(crate::from_mode(0o700).into_iter().map(|entry| AclEntry {
+            flags: Flag::DEFAULT,
+            ..entry
+        }));
+
+        let access = to_xattr_bytes(&entries, XattrKind::Access)?;
+        let default = to_xattr_bytes(&entries, XattrKind::Default)?;
+        assert_eq!(from_xattr_bytes(&access)?, crate::from_mode(0o750));
+        assert_eq!(from_xattr_bytes(&default)?, crate::from_mode(0o700));
+        assert_eq!(XattrKind::Default.name(), "system.posix_acl_default");
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_xattr_bytes_invalid() {
+        let err = |entries: &[AclEntry]| {
+            to_xattr_bytes(entries, XattrKind::Access)
+                .unwrap_err()
+                .to_string()
+        };
+
+        let mut entries = crate::from_mode(0o640);
+        entries.pop();
+        assert_eq!(err(&entries), "missing required entry \"other\"");
+
+        let mut entries = crate::from_mode(0o640);
+        entries.push(AclEntry::allow_user("", Perm::READ, None));
+        assert_eq!(
+            err(&entries),
+            "duplicate entry in Posix.1e ACL: tag 0x1, id 4294967295"
+        );
+
+        let mut entries = crate::from_mode(0o640);
+        entries[0].allow = false;
+        assert!(err(&entries).contains("no deny entries"));
+    }
+
+    #[test]
+    fn test_from_xattr_bytes_invalid() {
+        let err = |bytes: &[u8]| from_xattr_bytes(bytes).unwrap_err().to_string();
+
+        assert_eq!(err(&[2, 0]), "ACL xattr is too short");
+        assert_eq!(err(&[1, 0, 0, 0]), "unsupported ACL xattr version: 1");
+        assert_eq!(err(&[2, 0, 0, 0, 1]), "ACL xattr has a partial entry");
+        assert_eq!(
+            err(&[2, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0]),
+            "unknown tag in ACL xattr: 0x40"
+        );
+        assert_eq!(
+            err(&[2, 0, 0, 0, 0x01, 0, 8, 0, 0, 0, 0, 0]),
+            "unknown permissions in ACL xattr: 0x8"
+        );
+    }
+}
@@ -1,7 +1,9 @@
 //! API Tests for exacl module.
 
+#![cfg(not(exacl_core))]
+
 use ctor::ctor;
-use exacl::{getfacl, setfacl, AclEntry, AclOption, Perm};
+use exacl::{clear_acl, getfacl, setfacl, AclEntry, AclOption, PathError, Perm};
 use log::debug;
 use std::io;
 
@@ -59,6 +61,82 @@ fn test_getfacl_file() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_getfacl_map() -> io::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let file = tempfile::NamedTempFile::new()?;
+    let missing = dir.path().join("missing");
+
+    let mut entries = getfacl(&file, None)?;
+    entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    entries.push(AclEntry::allow_mask(Perm::READ, None));
+    setfacl(&[&file], &entries, None)?;
+
+    let paths = [dir.path(), file.path(), &missing];
+    let map = exacl::getfacl_map(&paths, None);
+    assert_eq!(map.len(), 3);
+
+    let consistent = exacl::getfacl_map(&paths, AclOption::CONSISTENT_READ);
+    for path in &paths[..2] {
+        assert_eq!(map[*path].as_ref().unwrap(), &getfacl(path, None)?);
+        assert_eq!(consistent[*path].as_ref().unwrap(), &getfacl(path, None)?);
+    }
+    assert!(map[file.path()]
+        .as_ref()
+        .unwrap()
+        .iter()
+        .any(|entry| entry.name == "11501"));
+
+    let err = map[&missing].as_ref().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    Ok(())
+}
+
+#[test]
+fn test_getfacl_setfacl_fd() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+
+    let mut entries = exacl::getfacl_fd(file.as_file(), None)?;
+    assert_eq!(entries, getfacl(&file, None)?);
+
+    entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    entries.push(AclEntry::allow_mask(Perm::READ, None));
+    exacl::setfacl_fd(file.as_file(), &entries, AclOption::SYNC_FILES)?;
+
+    let entries = exacl::getfacl_fd(file.as_file(), None)?;
+    assert!(entries.iter().any(|entry| entry.name == "11501"));
+    assert_eq!(entries, getfacl(&file, None)?);
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_setfacl_fd_default() -> io::Result<()> {
+    use exacl::Flag;
+
+    let dir = tempfile::TempDir::new()?;
+    let dir_file = std::fs::File::open(&dir)?;
+
+    let mut entries = exacl::from_mode(0o750);
+    entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
+    entries.push(AclEntry::allow_group("", Perm::READ, Flag::DEFAULT));
+    entries.push(AclEntry::allow_other(Perm::empty(), Flag::DEFAULT));
+
+    let err = exacl::setfacl_fd(&dir_file, &entries, None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Linux does not support default ACL's with file descriptors"
+    );
+
+    // The default ACL is only read through a path.
+    setfacl(&[&dir], &entries, None)?;
+    assert_eq!(exacl::getfacl_fd(&dir_file, None)?.len(), 3);
+    assert_eq!(getfacl(&dir, None)?.len(), 6);
+    Ok(())
+}
+
 #[test]
 fn test_setfacl_file() -> io::Result<()> {
     let file = tempfile::NamedTempFile::new()?;
@@ -70,6 +148,294 @@ fn test_setfacl_file() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_setfacl_iterator() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+    let entries = getfacl(&file, None)?;
+
+    // Generate entries on the fly, without collecting them first.
+    let grant = |uid: u32| AclEntry {
+        numeric: true,
+        ..AclEntry::allow_user(&uid.to_string(), Perm::READ, None)
+    };
+    setfacl(
+        &[&file],
+        entries.iter().cloned().chain((500..510).map(grant)),
+        None,
+    )?;
+
+    let actual = getfacl(&file, AclOption::NUMERIC_IDS)?;
+    for uid in 500..510 {
+        assert!(actual.contains(&grant(uid)));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_setfacl_sync() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let files = [dir.path().join("a"), dir.path().join("b")];
+    for file in &files {
+        std::fs::write(file, b"")?;
+    }
+
+    let mut entries = getfacl(&files[0], None)?;
+    entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+    setfacl(
+        &files,
+        &entries,
+        AclOption::SYNC_FILES | AclOption::SYNC_DIRS,
+    )?;
+
+    for file in &files {
+        assert!(getfacl(file, None)?
+            .iter()
+            .any(|entry| entry.name == "11501"));
+    }
+
+    // The batch stops before flushing if a path is missing.
+    let missing = [files[0].clone(), dir.path().join("missing")];
+    let err = setfacl(&missing, &entries, AclOption::SYNC_FILES).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_setfacl_fill_required() -> io::Result<()> {
+    use exacl::{AclEntryKind, Flag};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir()?;
+    let files = [dir.path().join("a"), dir.path().join("b")];
+    for (file, mode) in files.iter().zip([0o640, 0o604]) {
+        std::fs::write(file, b"")?;
+        std::fs::set_permissions(file, std::fs::Permissions::from_mode(mode))?;
+    }
+
+    let entries = [AclEntry::allow_user("11501", Perm::READ, None)];
+    let err = setfacl(&files, &entries, None).unwrap_err();
+    assert!(err.to_string().contains("missing required"), "{err}");
+
+    // Each file keeps its own owner, group and other permissions.
+    setfacl(&files, &entries, AclOption::FILL_REQUIRED)?;
+    for (file, mode) in files.iter().zip([0o640, 0o604]) {
+        let acl = getfacl(file, None)?;
+        assert!(acl.iter().any(|entry| entry.name == "11501"));
+        assert_eq!(exacl::as_permissions(&acl).mode() & 0o707, mode & 0o707);
+    }
+
+    // Default entries are filled from the access ACL of the directory.
+    let default = [AclEntry::allow_group("11502", Perm::READ, Flag::DEFAULT)];
+    setfacl(
+        &[dir.path()],
+        &default,
+        AclOption::DEFAULT_ACL | AclOption::FILL_REQUIRED,
+    )?;
+    let acl = getfacl(dir.path(), AclOption::DEFAULT_ACL)?;
+    let kinds = acl
+        .iter()
+        .filter(|entry| entry.name.is_empty())
+        .map(|entry| entry.kind);
+    assert_eq!(
+        kinds.collect::<Vec<_>>(),
+        [
+            AclEntryKind::User,
+            AclEntryKind::Group,
+            AclEntryKind::Mask,
+            AclEntryKind::Other
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_setfacl_numeric() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl(&file, None)?;
+
+    // A numeric entry bypasses the name lookup; it is read back by name.
+    entries.extend(exacl::from_str("uid:0:read")?);
+    setfacl(&[&file], &entries, None)?;
+
+    let actual = getfacl(&file, None)?;
+    assert!(actual.contains(&AclEntry::allow_user("root", Perm::READ, None)));
+
+    Ok(())
+}
+
+#[test]
+fn test_getfacl_numeric_ids() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl(&file, None)?;
+    entries.push(AclEntry::allow_user("root", Perm::READ, None));
+    setfacl(&[&file], &entries, None)?;
+
+    // Users and groups are named by id, and nothing else changes.
+    let root = exacl::from_str("uid:0:read")?;
+    let actual = getfacl(&file, AclOption::NUMERIC_IDS)?;
+    assert!(actual.contains(&root[0]));
+    assert!(!actual.iter().any(|entry| entry.name == "root"));
+    assert_eq!(actual.len(), getfacl(&file, None)?.len());
+
+    let actual = exacl::getfacl_fd(file.as_file(), AclOption::NUMERIC_IDS)?;
+    assert!(actual.contains(&root[0]));
+
+    Ok(())
+}
+
+#[test]
+fn test_getfacl_unnamed_id() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl(&file, None)?;
+    entries.push(AclEntry::allow_user("54321", Perm::READ, None));
+    setfacl(&[&file], &entries, None)?;
+
+    // An id without a name is named by its id, but is only numeric with
+    // `NUMERIC_IDS`.
+    let unnamed = |options| -> io::Result<AclEntry> {
+        let entries = getfacl(&file, options)?;
+        Ok(entries.into_iter().find(|e| e.name == "54321").unwrap())
+    };
+    let entry = unnamed(AclOption::empty())?;
+    assert!(!entry.numeric);
+    assert_eq!(entry.to_string(), "allow::user:54321:read");
+    let entry = unnamed(AclOption::NUMERIC_IDS)?;
+    assert!(entry.numeric);
+    assert_eq!(entry.to_string(), "allow::uid:54321:read");
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_platform_text_getfacl_tool() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut entries = exacl::from_str(
+        "user::rwx\nuser:root:r-x\ngroup::r--\nmask::r-x\nother::---\n\
+         default:user::rwx\ndefault:group::r-x\ndefault:other::---\n",
+    )?;
+    setfacl(&[&dir], &entries, None)?;
+
+    // Skip if the `getfacl` tool isn't installed.
+    let Ok(output) = std::process::Command::new("getfacl")
+        .arg(dir.path())
+        .output()
+    else {
+        return Ok(());
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut actual = exacl::from_platform_text(&text)?;
+
+    actual.sort();
+    entries.sort();
+    assert_eq!(actual, entries);
+
+    Ok(())
+}
+
+#[test]
+fn test_setfacl_strict_principals() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl(&file, None)?;
+    entries.push(AclEntry::allow_user("1234567", Perm::READ, None));
+
+    // By default, a name that is not in the user database is parsed as a uid.
+    setfacl(&[&file], &entries, None)?;
+
+    // In strict mode, every unresolved name is reported.
+    let n = entries.len();
+    let mut strict_entries = entries.clone();
+    strict_entries.push(AclEntry::allow_group("no_such_group", Perm::READ, None));
+    let err = setfacl(&[&file], &strict_entries, AclOption::STRICT_PRINCIPALS).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Invalid ACL: unresolved principals: entry {} (user \"1234567\"), entry {} (group \"no_such_group\")",
+            n - 1,
+            n
+        )
+    );
+
+    // The entry is read back by id, but only `NUMERIC_IDS` marks it as
+    // numeric, which strict mode accepts.
+    let entries = getfacl(&file, None)?;
+    assert!(entries.iter().any(|e| !e.numeric && e.name == "1234567"));
+    assert!(setfacl(&[&file], &entries, AclOption::STRICT_PRINCIPALS).is_err());
+    let entries = getfacl(&file, AclOption::NUMERIC_IDS)?;
+    assert!(entries.iter().any(|e| e.numeric && e.name == "1234567"));
+    setfacl(&[&file], &entries, AclOption::STRICT_PRINCIPALS)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_clear_acl() -> io::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let file = tempfile::NamedTempFile::new_in(&dir)?;
+
+    let mut entries = getfacl(&file, AclOption::ACCESS_ACL)?;
+    let orig_len = entries.len();
+    entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+    setfacl(&[&file], &entries, AclOption::ACCESS_ACL)?;
+    assert!(getfacl(&file, AclOption::ACCESS_ACL)?.len() > orig_len);
+
+    clear_acl(&file)?;
+    assert_eq!(getfacl(&file, AclOption::ACCESS_ACL)?.len(), orig_len);
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut default = exacl::from_mode(0o750);
+        default.push(AclEntry::allow_user("11501", Perm::READ, None));
+        for entry in &mut default {
+            entry.flags = exacl::Flag::DEFAULT;
+        }
+        setfacl(&[&dir], &default, AclOption::DEFAULT_ACL)?;
+        assert!(!getfacl(&dir, AclOption::DEFAULT_ACL)?.is_empty());
+
+        clear_acl(&dir)?;
+        assert!(getfacl(&dir, AclOption::DEFAULT_ACL)?.is_empty());
+    }
+
+    let err = clear_acl("/non-existant").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "macos")]
+fn test_group_entry_from_other_tool() -> io::Result<()> {
+    use exacl::AclEntryKind;
+
+    let file = tempfile::NamedTempFile::new()?;
+
+    // Add group entries with `chmod +a`, which doesn't use exacl.
+    for rule in ["group:staff allow read", "group:everyone deny write"] {
+        let status = std::process::Command::new("chmod")
+            .arg("+a")
+            .arg(rule)
+            .arg(file.path())
+            .status()?;
+        assert!(status.success(), "chmod +a {rule:?}");
+    }
+
+    let entries = getfacl(&file, None)?;
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|e| e.kind == AclEntryKind::Group));
+    assert!(entries.iter().any(|e| e.name == "staff" && e.allow));
+    assert!(entries.iter().any(|e| e.name == "everyone" && !e.allow));
+
+    // Entries read back the same after a round trip through exacl.
+    setfacl(&[&file], &entries, None)?;
+    assert_eq!(getfacl(&file, None)?, entries);
+
+    Ok(())
+}
+
 /// Get the type of filesystem from `df -Th` command output.
 #[cfg(target_os = "linux")]
 fn get_filesystem(path: &std::path::PathBuf) -> String {
@@ -196,6 +562,81 @@ allow::user:ccc:read,execute
     Ok(())
 }
 
+#[test]
+fn test_format_versions() -> io::Result<()> {
+    use exacl::FormatVersion;
+
+    // The output of each format version must never change.
+    let entries = vec![
+        AclEntry::allow_user("a b:c", Perm::READ | Perm::WRITE, None),
+        AclEntry::allow_group("staff", Perm::EXECUTE, None),
+    ];
+
+    assert_eq!(
+        exacl::to_string_with_version(&entries, FormatVersion::V1)?,
+        "allow::user:a b:c:read,write\nallow::group:staff:execute\n"
+    );
+
+    let v2 = "allow::user:a\\040b\\072c:read,write\nallow::group:staff:execute\n";
+    assert_eq!(
+        exacl::to_string_with_version(&entries, FormatVersion::V2)?,
+        v2
+    );
+    assert_eq!(exacl::to_string(&entries)?, v2);
+    assert_eq!(FormatVersion::LATEST, FormatVersion::V2);
+
+    Ok(())
+}
+
+#[test]
+fn test_sort_is_total() {
+    let mut entries = vec![
+        AclEntry::allow_user("a", Perm::WRITE, None),
+        AclEntry::allow_user("a", Perm::READ, None),
+        AclEntry::allow_group("a", Perm::READ, None),
+    ];
+    let mut reversed = entries.clone();
+    reversed.reverse();
+
+    entries.sort();
+    reversed.sort();
+    assert_eq!(entries, reversed);
+}
+
+#[test]
+fn test_reader_multibyte() -> io::Result<()> {
+    // Comments and invalid fields may contain multi-byte UTF-8 sequences.
+    let entries = exacl::from_str("u:aaa:r # caf\u{e9} \u{20ac}\n#\u{e9}\n")?;
+    assert_eq!(exacl::to_string(&entries)?, "allow::user:aaa:read\n");
+
+    let err = exacl::from_str("u:aaa:\u{e9}").unwrap_err();
+    assert!(err.to_string().starts_with("unknown variant"));
+
+    let err = exacl::from_reader(&b"u:aaa:r\xff\n"[..]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    Ok(())
+}
+
+#[test]
+fn test_entries_from_reader() -> io::Result<()> {
+    let input = "u:aaa:r\n\n# comment\ng::rw\nbad\no::-\n";
+    let mut entries = exacl::entries_from_reader(input.as_bytes());
+
+    assert_eq!(entries.next().unwrap()?.to_string(), "allow::user:aaa:read");
+    assert_eq!(
+        entries.next().unwrap()?.to_string(),
+        "allow::group::read,write"
+    );
+    let err = entries.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(err.to_string().starts_with("line 5: "), "{err}");
+    assert_eq!(entries.next().unwrap()?.to_string(), "allow::other::");
+    assert!(entries.next().is_none());
+
+    Ok(())
+}
+
 #[test]
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 fn test_exclusive_acloptions() {
@@ -253,3 +694,336 @@ fn test_from_mode() {
         "allow::user::write,execute\nallow::group::read\nallow::other::read,execute\n"
     );
 }
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_as_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let entries = exacl::from_mode(0o754);
+    assert_eq!(exacl::as_permissions(&entries).mode(), 0o754);
+
+    // Group bits come from the mask when present.
+    let entries = exacl::from_str("u::rw\ng::rwx\nu:500:r\nm::r\no::\nd:o::rwx").unwrap();
+    assert_eq!(exacl::as_permissions(&entries).mode(), 0o640);
+
+    assert_eq!(exacl::as_permissions(&[]).mode(), 0);
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_to_mode() -> io::Result<()> {
+    for mode in [0o000, 0o640, 0o754, 0o777] {
+        assert_eq!(exacl::to_mode(&exacl::from_mode(mode))?, mode);
+    }
+
+    // Group bits come from the mask when present.
+    let entries = exacl::from_str("u::rw\ng::rwx\nu:500:r\nm::r\no::\nd:o::rwx")?;
+    assert_eq!(exacl::to_mode(&entries)?, 0o640);
+
+    assert!(exacl::to_mode(&[]).is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg(not(exacl_core))]
+fn test_strip_acl() -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = exacl::from_mode(0o640);
+    entries.push(AclEntry::allow_user(
+        "11501",
+        Perm::READ | Perm::WRITE,
+        None,
+    ));
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    entries.push(AclEntry::allow_mask(Perm::READ | Perm::WRITE, None));
+    setfacl(&[&file], &entries, None)?;
+    let mode = file.as_file().metadata()?.permissions().mode() & 0o777;
+
+    exacl::strip_acl(&file)?;
+    assert!(!getfacl(&file, None)?
+        .iter()
+        .any(|entry| entry.name == "11501"));
+    assert_eq!(
+        file.as_file().metadata()?.permissions().mode() & 0o777,
+        mode
+    );
+
+    // `clear_acl` keeps the group entry instead of the mask.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    {
+        assert_eq!(mode, 0o660);
+        setfacl(&[&file], &entries, None)?;
+        clear_acl(&file)?;
+        let mode = file.as_file().metadata()?.permissions().mode() & 0o777;
+        assert_eq!(mode, 0o640);
+    }
+
+    let err = exacl::strip_acl("/non-existant").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_needs_mask() -> io::Result<()> {
+    use exacl::needs_mask;
+
+    assert_eq!(needs_mask(&[]), None);
+    assert_eq!(needs_mask(&exacl::from_mode(0o754)), None);
+
+    // The mask covers named entries and the owning group, not the owner.
+    let mut entries = exacl::from_str("u::rwx\ng::r\nu:500:w\no::\nd:g:501:x")?;
+    assert_eq!(needs_mask(&entries), Some(Perm::READ | Perm::WRITE));
+
+    // The mask `setfacl` adds is the one reported.
+    let file = tempfile::NamedTempFile::new()?;
+    entries.retain(|entry| !entry.flags.contains(exacl::Flag::DEFAULT));
+    setfacl(&[&file], &entries, None)?;
+    let actual = getfacl(&file, None)?;
+    let mask = actual
+        .iter()
+        .find(|entry| entry.kind == exacl::AclEntryKind::Mask);
+    assert_eq!(mask.map(|entry| entry.perms), needs_mask(&entries));
+    assert_eq!(needs_mask(&actual), None);
+
+    // Only default entries are named.
+    let entries = exacl::from_str("u::rw\ng::r\no::\nd:u:500:rwx")?;
+    assert_eq!(needs_mask(&entries), None);
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_merge_with_permissions() {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    let merged = exacl::merge_with_permissions(&[], &Permissions::from_mode(0o751));
+    assert_eq!(merged, exacl::from_mode(0o751));
+
+    // With a mask entry, the group bits are applied to the mask.
+    let entries = exacl::from_str("u::rw\ng::rwx\nu:500:rw\nm::rwx\no::\nd:o::rwx").unwrap();
+    let merged = exacl::merge_with_permissions(&entries, &Permissions::from_mode(0o604));
+    assert_eq!(
+        exacl::to_string(&merged).unwrap(),
+        "allow::user::read,write\nallow::group::read,write,execute\nallow::user:500:read,write\nallow::mask::\nallow::other::read\nallow:default:other::read,write,execute\n"
+    );
+    assert_eq!(exacl::as_permissions(&merged).mode(), 0o604);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_set_permissions_interop() -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = tempfile::NamedTempFile::new()?;
+    let entries = exacl::from_str("u::rw\ng::r\nuid:500:rw\nm::rw\no::")?;
+    setfacl(&[&file], &entries, None)?;
+
+    // `set_permissions` changes the mask, not the group entry.
+    let perms = std::fs::Permissions::from_mode(0o640);
+    std::fs::set_permissions(&file, perms.clone())?;
+
+    let mut actual = getfacl(&file, AclOption::NUMERIC_IDS)?;
+    let mut expected = exacl::merge_with_permissions(&entries, &perms);
+    actual.sort();
+    expected.sort();
+    assert_eq!(actual, expected);
+    assert_eq!(exacl::as_permissions(&actual).mode(), 0o640);
+
+    Ok(())
+}
+
+#[test]
+fn test_send_sync() -> io::Result<()> {
+    fn assert_send<T: Send>() {}
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<exacl::AclBuilder>();
+    assert_send_sync::<exacl::AclEntry>();
+    assert_send_sync::<exacl::AclEntryKind>();
+    assert_send_sync::<exacl::AclSet>();
+    assert_send_sync::<exacl::AclOption>();
+    assert_send_sync::<exacl::AclBrand>();
+    assert_send_sync::<exacl::Dialect>();
+    assert_send_sync::<exacl::Flag>();
+    assert_send_sync::<exacl::FormatVersion>();
+    assert_send_sync::<exacl::Lint>();
+    assert_send_sync::<exacl::PathKind>();
+    assert_send_sync::<exacl::Perm>();
+    assert_send_sync::<exacl::Redactor>();
+    assert_send_sync::<exacl::UnresolvedPrincipal>();
+    assert_send_sync::<exacl::VolumeCapabilities>();
+    #[cfg(feature = "serde")]
+    assert_send_sync::<exacl::AclText>();
+    #[cfg(feature = "watch")]
+    assert_send_sync::<exacl::watch::DirWatcher>();
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    assert_send_sync::<exacl::Editor>();
+    #[cfg(target_os = "linux")]
+    assert_send_sync::<exacl::TextOptions>();
+    #[cfg(target_os = "macos")]
+    assert_send_sync::<exacl::macos::SipError>();
+
+    // `Acl` and `CompiledAcl` can be moved to another thread.
+    assert_send::<exacl::Acl>();
+    assert_send::<exacl::CompiledAcl>();
+
+    let file = tempfile::NamedTempFile::new()?;
+    let acl = exacl::Acl::read(file.path(), AclOption::empty())?;
+    let entries = std::thread::spawn(move || acl.entries()).join().unwrap()?;
+    assert_eq!(entries, getfacl(&file, None)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_setfacl_strip_inherited() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+
+    #[cfg(all(target_os = "macos", not(feature = "strict_posix")))]
+    {
+        let entries = vec![AclEntry::allow_group(
+            "staff",
+            Perm::READ,
+            exacl::Flag::INHERITED,
+        )];
+        setfacl(&[&file], &entries, AclOption::STRIP_INHERITED)?;
+
+        let result = getfacl(&file, None)?;
+        assert_eq!(
+            result,
+            vec![AclEntry::allow_group("staff", Perm::READ, None)]
+        );
+    }
+
+    // The option has no effect on Linux, which has no inherited flag.
+    #[cfg(target_os = "linux")]
+    {
+        let entries = getfacl(&file, None)?;
+        setfacl(&[&file], &entries, AclOption::STRIP_INHERITED)?;
+        assert_eq!(getfacl(&file, None)?, entries);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn test_setfacl_unsupported_kind() -> io::Result<()> {
+    use exacl::AclEntryKind;
+
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl(&file, None)?;
+
+    #[cfg(target_os = "macos")]
+    let kind = AclEntryKind::Other;
+    #[cfg(target_os = "linux")]
+    let kind = AclEntryKind::Everyone;
+
+    entries.push(AclEntry {
+        kind,
+        name: String::new(),
+        perms: Perm::READ,
+        flags: exacl::Flag::empty(),
+        allow: true,
+        numeric: false,
+    });
+
+    let err = setfacl(&[&file], &entries, None).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains(&format!("unsupported kind: \"{kind}\"")),
+        "{err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "strict_posix")]
+fn test_strict_posix() {
+    use exacl::Flag;
+
+    // Only the Posix.1e permissions and flags are defined, on every platform.
+    assert_eq!(Perm::all(), Perm::READ | Perm::WRITE | Perm::EXECUTE);
+    assert_eq!(Flag::all(), Flag::DEFAULT);
+
+    assert_eq!(Perm::all().to_string(), "read,write,execute");
+    assert_eq!(Flag::all().to_string(), "default");
+    assert!("delete".parse::<Perm>().is_err());
+    assert!("file_inherit".parse::<Flag>().is_err());
+}
+
+/// Return the `PathError` wrapped in an error from a path function.
+fn path_error(err: &io::Error) -> &PathError {
+    err.get_ref()
+        .and_then(|inner| inner.downcast_ref::<PathError>())
+        .expect("PathError")
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_non_utf8_path() -> io::Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join(OsStr::from_bytes(b"caf\xe9"));
+    std::fs::write(&path, "")?;
+
+    let mut entries = getfacl(&path, None)?;
+    entries.push(AclEntry::allow_user("11501", Perm::READ, None));
+    entries.push(AclEntry::allow_mask(Perm::READ, None));
+    setfacl(&[&path], &entries, None)?;
+    assert!(getfacl(&path, None)?.iter().any(|e| e.name == "11501"));
+
+    // Tree operations report the path unchanged.
+    let records = exacl::flatten_tree(dir.path(), None)?;
+    assert!(records.iter().any(|r| r.path == path));
+
+    // The error for a missing file keeps the path unchanged.
+    let missing = dir.path().join(OsStr::from_bytes(b"missing\xff"));
+    let err = getfacl(&missing, None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(path_error(&err).path(), missing);
+    assert_eq!(path_error(&err).io_error().kind(), io::ErrorKind::NotFound);
+    assert!(path_error(&err).io_error().raw_os_error().is_some());
+
+    let err = setfacl(&[&missing], &entries, None).unwrap_err();
+    assert_eq!(path_error(&err).path(), missing);
+
+    Ok(())
+}
+
+#[test]
+fn test_long_path() -> io::Result<()> {
+    // Nest directories to make a path that is long, but within PATH_MAX on
+    // every platform (1024 on macOS and FreeBSD).
+    let dir = tempfile::tempdir()?;
+    let name = "d".repeat(200);
+    let mut path = dir.path().to_path_buf();
+    while path.as_os_str().len() < 800 {
+        path.push(&name);
+    }
+    std::fs::create_dir_all(&path)?;
+    let file = path.join("f");
+    std::fs::write(&file, "")?;
+
+    let entries = getfacl(&file, None)?;
+    setfacl(&[&file], &entries, None)?;
+    assert_eq!(getfacl(&file, None)?, entries);
+
+    // A path longer than PATH_MAX fails, and the error keeps the whole path.
+    while path.as_os_str().len() < 5000 {
+        path.push(&name);
+    }
+    let err = getfacl(&path, None).unwrap_err();
+    assert_eq!(path_error(&err).path(), path);
+    assert!(err.to_string().contains("File name too long"), "{err}");
+
+    Ok(())
+}
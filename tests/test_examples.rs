@@ -1,5 +1,7 @@
 //! Test example code used in documentation.
 
+#![cfg(not(exacl_core))]
+
 use std::io;
 
 #[test]
@@ -0,0 +1,51 @@
+//! Golden tests of ACL behavior on each file system type.
+//!
+//! Mounting requires root; run these in a CI container or VM with
+//! `cargo test --features testing --test test_golden`. File systems that
+//! can't be mounted are skipped.
+
+#![cfg(all(feature = "testing", target_os = "linux", not(exacl_core)))]
+
+use ctor::ctor;
+use exacl::testing::{for_each_file_system, golden_test, tmpfs_dir, FileSystem, ImageMount};
+use log::debug;
+use std::io;
+
+#[ctor]
+fn init() {
+    env_logger::init();
+}
+
+#[test]
+fn test_golden_tmpfs_dir() -> io::Result<()> {
+    let dir = tmpfs_dir()?;
+    golden_test(FileSystem::Tmpfs, dir.path())
+}
+
+#[test]
+fn test_golden_all() -> io::Result<()> {
+    let checked = for_each_file_system(64, golden_test)?;
+    debug!("test_golden_all checked {:?}", checked);
+
+    // Where one file system can be mounted, tmpfs can be too.
+    if !checked.is_empty() {
+        assert!(checked.contains(&FileSystem::Tmpfs));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_golden_wrong_type() {
+    let Ok(mount) = ImageMount::new(FileSystem::Tmpfs, 8) else {
+        return;
+    };
+    assert_eq!(mount.file_system(), FileSystem::Tmpfs);
+
+    let err = golden_test(FileSystem::Ext4, mount.path()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(
+        err.to_string().contains("file system type is 0x1021994"),
+        "{err}"
+    );
+    assert!(err.to_string().contains("max_entries is"), "{err}");
+}
@@ -0,0 +1,201 @@
+//! Property-based round-trip tests for exacl module.
+
+use exacl::{AclEntry, AclEntryKind, Flag, Perm};
+use proptest::prelude::*;
+
+/// Strategy for the entry kinds supported on this platform.
+fn any_kind() -> impl Strategy<Value = AclEntryKind> {
+    prop::sample::select(vec![
+        AclEntryKind::User,
+        AclEntryKind::Group,
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        AclEntryKind::Mask,
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        AclEntryKind::Other,
+        #[cfg(target_os = "freebsd")]
+        AclEntryKind::Everyone,
+        AclEntryKind::Unknown,
+    ])
+}
+
+/// Strategy for any combination of named permissions.
+fn any_perm() -> impl Strategy<Value = Perm> {
+    let perms = Perm::all().iter().collect::<Vec<_>>();
+    prop::sample::subsequence(perms.clone(), 0..=perms.len())
+        .prop_map(|v| v.into_iter().fold(Perm::empty(), |acc, p| acc | p))
+}
+
+/// Strategy for any combination of named flags.
+fn any_flag() -> impl Strategy<Value = Flag> {
+    let flags = Flag::all().iter().collect::<Vec<_>>();
+    prop::sample::subsequence(flags.clone(), 0..=flags.len())
+        .prop_map(|v| v.into_iter().fold(Flag::empty(), |acc, f| acc | f))
+}
+
+prop_compose! {
+    fn any_entry()(kind in any_kind(), name in any::<String>(), perms in any_perm(), flags in any_flag(), allow in any::<bool>()) -> AclEntry {
+        AclEntry { kind, name, perms, flags, allow, numeric: false }
+    }
+}
+
+prop_compose! {
+    fn numeric_entry()(id in any::<u32>(), is_user in any::<bool>(), perms in any_perm(), flags in any_flag(), allow in any::<bool>()) -> AclEntry {
+        let kind = if is_user { AclEntryKind::User } else { AclEntryKind::Group };
+        AclEntry { kind, name: id.to_string(), perms, flags, allow, numeric: true }
+    }
+}
+
+prop_compose! {
+    /// An entry and its text, with the flags listed in any order.
+    fn shuffled_flags_text()(entry in any_entry())(
+        flags in Just(entry.flags.iter().map(|f| f.to_string()).collect::<Vec<_>>()).prop_shuffle(),
+        entry in Just(entry),
+    ) -> (AclEntry, String) {
+        let text = entry.to_string();
+        let fields = text.splitn(3, ':').collect::<Vec<_>>();
+        let text = format!("{}:{}:{}", fields[0], flags.join(","), fields[2]);
+        (entry, text)
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_entry_text_roundtrip(entry in any_entry()) {
+        let text = entry.to_string();
+        prop_assert!(!text.contains('\n'));
+        prop_assert_eq!(text.parse::<AclEntry>().unwrap(), entry);
+    }
+
+    #[test]
+    fn test_entries_text_roundtrip(entries in prop::collection::vec(any_entry(), 0..16)) {
+        let text = exacl::to_string(&entries).unwrap();
+        prop_assert_eq!(exacl::from_str(&text).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_flag_order_canonical((entry, text) in shuffled_flags_text()) {
+        // Flags may be listed in any order; they are written in one order.
+        let parsed = text.parse::<AclEntry>().unwrap();
+        prop_assert_eq!(parsed.to_string(), entry.to_string());
+        prop_assert_eq!(parsed, entry);
+    }
+
+    #[test]
+    fn test_backslash_name_roundtrip(domain in "[A-Z]{1,8}", user in "[a-z]{1,8}") {
+        // A hand-written `DOMAIN\user` name reads back the same after
+        // formatting, which escapes the backslash.
+        let entry = format!("user:{domain}\\{user}:read").parse::<AclEntry>().unwrap();
+        prop_assert_eq!(&entry.name, &format!("{domain}\\{user}"));
+        prop_assert_eq!(entry.to_string().parse::<AclEntry>().unwrap(), entry);
+    }
+
+    #[test]
+    fn test_numeric_text_roundtrip(entry in numeric_entry()) {
+        prop_assert_eq!(entry.to_string().parse::<AclEntry>().unwrap(), entry);
+    }
+
+    #[test]
+    fn test_text_canonical(entry in any_entry()) {
+        // Formatting a parsed entry reproduces the same text.
+        let text = entry.to_string();
+        prop_assert_eq!(text.parse::<AclEntry>().unwrap().to_string(), text);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_entries_json_roundtrip(entries in prop::collection::vec(prop_oneof![any_entry(), numeric_entry()], 0..16)) {
+        let json = serde_json::to_string(&entries).unwrap();
+        let result: Vec<AclEntry> = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(result, entries);
+    }
+}
+
+/// Strategy for a list of distinct numeric ids that are unlikely to exist in
+/// the user/group database.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn unknown_ids() -> impl Strategy<Value = Vec<u32>> {
+    prop::collection::btree_set(100_000u32..200_000, 0..8).prop_map(|s| s.into_iter().collect())
+}
+
+/// Strategy for any combination of Posix.1e permissions. With the `nfs4`
+/// feature, `Perm::all()` has `NFSv4` permissions too.
+#[cfg(target_os = "linux")]
+fn posix_perm() -> impl Strategy<Value = Perm> {
+    any_perm().prop_map(|perms| perms & (Perm::READ | Perm::WRITE | Perm::EXECUTE))
+}
+
+#[cfg(target_os = "linux")]
+prop_compose! {
+    fn posix_entries()(
+        obj_perms in prop::array::uniform3(posix_perm()),
+        users in unknown_ids(),
+        groups in unknown_ids(),
+        user_perms in posix_perm(),
+        group_perms in posix_perm(),
+        mask in prop::option::of(posix_perm()),
+    ) -> Vec<AclEntry> {
+        let mut entries = vec![
+            AclEntry::allow_user("", obj_perms[0], None),
+            AclEntry::allow_group("", obj_perms[1], None),
+            AclEntry::allow_other(obj_perms[2], None),
+        ];
+        // Unknown ids are read back by id.
+        for uid in users {
+            entries.push(AclEntry::allow_user(&uid.to_string(), user_perms, None));
+        }
+        for gid in groups {
+            entries.push(AclEntry::allow_group(&gid.to_string(), group_perms, None));
+        }
+        if let Some(perms) = mask {
+            entries.push(AclEntry::allow_mask(perms, None));
+        }
+        entries
+    }
+}
+
+#[cfg(target_os = "linux")]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_entries_native_roundtrip(mut entries in posix_entries()) {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        exacl::setfacl(&[&file], &entries, None).unwrap();
+        let mut result = exacl::getfacl(&file, None).unwrap();
+
+        // A mask entry is synthesized if needed.
+        let has_named = entries.iter().any(|e| !e.name.is_empty());
+        let has_mask = entries.iter().any(|e| e.kind == AclEntryKind::Mask);
+        if has_named && !has_mask {
+            let perms = entries
+                .iter()
+                .filter(|e| e.kind == AclEntryKind::Group || !e.name.is_empty())
+                .fold(Perm::empty(), |acc, e| acc | e.perms);
+            entries.push(AclEntry::allow_mask(perms, None));
+        }
+
+        entries.sort();
+        result.sort();
+        prop_assert_eq!(result, entries);
+    }
+}
+
+#[cfg(target_os = "macos")]
+prop_compose! {
+    fn extended_entry()(id in 100_000u32..200_000, is_user in any::<bool>(), perms in any_perm(), flags in any_flag(), allow in any::<bool>()) -> AclEntry {
+        let kind = if is_user { AclEntryKind::User } else { AclEntryKind::Group };
+        AclEntry { kind, name: id.to_string(), perms, flags, allow, numeric: false }
+    }
+}
+
+#[cfg(target_os = "macos")]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_entries_native_roundtrip(entries in prop::collection::vec(extended_entry(), 0..16)) {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        exacl::setfacl(&[&file], &entries, None).unwrap();
+        prop_assert_eq!(exacl::getfacl(&file, None).unwrap(), entries);
+    }
+}
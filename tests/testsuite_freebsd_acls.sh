@@ -209,7 +209,7 @@ testWriteAclToFile1() {
     msg=$(echo "$input" | $EXACL --set $FILE1 2>&1)
     assertEquals "check required entry" 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "$msg"
 
     # Set ACL for current user specifically, with required entries.
@@ -539,7 +539,7 @@ testSetDefault() {
     msg=$(echo "$input" | $EXACL --set --default $DIR1 2>&1)
     assertEquals "set default acl" 1 $?
     assertEquals \
-        'Invalid ACL: entry 3: duplicate default entry for "user"' \
+        'Invalid ACL: entry 3 (allow:default:user::read,write): duplicate default entry for "user"' \
         "$msg"
 
     # Check ACL is updated.
@@ -570,7 +570,7 @@ testMissingFlags() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "${msg//\`/}"
 }
 
@@ -579,7 +579,7 @@ testMissingAllow() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "${msg//\`/}"
 }
 
@@ -589,7 +589,7 @@ testDuplicateEntry() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 4: duplicate entry for "user:501"' \
+        'Invalid ACL: entry 4 (allow::user:501:execute): duplicate entry for "user:501"' \
         "${msg//\`/}"
 }
 
@@ -223,7 +223,7 @@ allow::everyone::readextattr,readattr,readsecurity,sync" \
     msg=$(echo "$input" | $EXACL --set $FILE1 2>&1)
     assertEquals "check required entry" 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "$msg"
 
     # Set ACL for current user specifically, with required entries.
@@ -298,7 +298,7 @@ testWriteAclToDir1() {
     msg=$(echo "$input" | $EXACL --set $DIR1 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: Invalid argument (os error 22)" \
+        "Invalid ACL: entry 0 (allow::mask::read_data): Invalid argument (os error 22)" \
         "$msg"
 
     # Read ACL back.
@@ -414,7 +414,7 @@ testWriteDefaultAcl() {
     msg=$(echo "$input" | $EXACL --set --default $DIR1 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: missing required entry \"user\"" \
+        "Invalid ACL: access ACL (entry 0): missing required entries \"user\", \"group\", \"other\"" \
         "$msg"
 
     # Check ACL again.
@@ -541,7 +541,7 @@ testSetDefault() {
     msg=$(echo "$input" | $EXACL --set --default $DIR1 2>&1)
     assertEquals "set default acl" 1 $?
     assertEquals \
-        'Invalid ACL: entry 3: duplicate default entry for "user"' \
+        'Invalid ACL: entry 3 (allow:default:user::read,write): duplicate default entry for "user"' \
         "$msg"
 
     # Check ACL is updated. (FIXME)
@@ -572,7 +572,7 @@ testMissingFlags() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "${msg//\`/}"
 }
 
@@ -581,7 +581,7 @@ testMissingAllow() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "${msg//\`/}"
 }
 
@@ -591,7 +591,7 @@ testDuplicateEntry() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 4: duplicate entry for "user:501"' \
+        'Invalid ACL: entry 4 (allow::user:501:execute): duplicate entry for "user:501"' \
         "${msg//\`/}"
 }
 
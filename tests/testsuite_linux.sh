@@ -210,7 +210,7 @@ testWriteAclToFile1() {
     msg=$(echo "$input" | $EXACL --set $FILE1 2>&1)
     assertEquals "check failure" 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: allow=false is not supported on Linux" \
+        "Invalid ACL: entry 0 (deny::user:$ME:read): allow=false is not supported on Linux" \
         "$msg"
 
     # Set ACL for current user specifically.
@@ -218,7 +218,7 @@ testWriteAclToFile1() {
     msg=$(echo "$input" | $EXACL --set $FILE1 2>&1)
     assertEquals "check required entry" 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "$msg"
 
     # Set ACL for current user specifically, with required entries.
@@ -273,7 +273,7 @@ testWriteAclToDir1() {
     msg=$(echo "$input" | $EXACL --set $DIR1 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: allow=false is not supported on Linux" \
+        "Invalid ACL: entry 0 (deny::user:$ME:read): allow=false is not supported on Linux" \
         "$msg"
 
     # Set ACL without mask entry.
@@ -553,7 +553,7 @@ testSetDefault() {
     msg=$(echo "$input" | $EXACL --set --default $DIR1 2>&1)
     assertEquals "set default acl" 1 $?
     assertEquals \
-        'Invalid ACL: entry 3: duplicate default entry for "user"' \
+        'Invalid ACL: entry 3 (allow:default:user::read,write): duplicate default entry for "user"' \
         "$msg"
 
     # Set ACL with default entries.
@@ -592,7 +592,7 @@ testMissingFlags() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "${msg//\`/}"
 }
 
@@ -601,7 +601,7 @@ testMissingAllow() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: missing required entry "user"' \
+        'Invalid ACL: access ACL (entry 0): missing required entries "user", "group", "other"' \
         "${msg//\`/}"
 }
 
@@ -611,7 +611,7 @@ testDuplicateEntry() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 4: duplicate entry for "user:501"' \
+        'Invalid ACL: entry 4 (allow::user:501:execute): duplicate entry for "user:501"' \
         "${msg//\`/}"
 
     # daemon is uid 1.
@@ -619,7 +619,7 @@ testDuplicateEntry() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 4: duplicate entry for "user:1"' \
+        'Invalid ACL: entry 4 (allow::user:daemon:read): duplicate entry for "user:1"' \
         "${msg//\`/}"
 
     # Test duplicate entry in default entries.
@@ -627,7 +627,7 @@ testDuplicateEntry() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 6: duplicate default entry for "other"' \
+        'Invalid ACL: entry 6 (allow:default:other::execute): duplicate default entry for "other"' \
         "${msg//\`/}"
 }
 
@@ -59,14 +59,14 @@ testInvalidUser() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown user name: \"non_existant_user\"" \
+        "Invalid ACL: entry 0 (allow::user:non_existant_user:execute): unknown user name: \"non_existant_user\"" \
         "$msg"
 
     input=$(quotifyJson "[{kind:user,name:4294967296,perms:[execute],flags:[],allow:true}]")
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown user name: \"4294967296\"" \
+        "Invalid ACL: entry 0 (allow::user:4294967296:execute): unknown user name: \"4294967296\"" \
         "$msg"
 }
 
@@ -75,14 +75,14 @@ testInvalidGroup() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown group name: \"non_existant_group\"" \
+        "Invalid ACL: entry 0 (allow::group:non_existant_group:execute): unknown group name: \"non_existant_group\"" \
         "$msg"
 
     input=$(quotifyJson "[{kind:group,name:4294967296,perms:[execute],flags:[],allow:true}]")
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown group name: \"4294967296\"" \
+        "Invalid ACL: entry 0 (allow::group:4294967296:execute): unknown group name: \"4294967296\"" \
         "$msg"
 }
 
@@ -91,7 +91,7 @@ testInvalidGUID() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown group name: \"00000000-0000-0000-000-000000000000\"" \
+        "Invalid ACL: entry 0 (allow::group:00000000-0000-0000-000-000000000000:execute): unknown group name: \"00000000-0000-0000-000-000000000000\"" \
         "$msg"
 }
 
@@ -100,7 +100,7 @@ testUnknownKind() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 0: unsupported kind: "unknown"' \
+        'Invalid ACL: entry 0 (allow::unknown:501:execute): unsupported kind: "unknown"' \
         "$msg"
 }
 
@@ -196,7 +196,7 @@ testInterleavedEntryIndex() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        'Invalid ACL: entry 4: unknown user name: "non_existant"' \
+        'Invalid ACL: entry 4 (allow::user:non_existant:): unknown user name: "non_existant"' \
         "$msg"
 }
 
@@ -210,7 +210,7 @@ testInvalidMask() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown mask name: \"invalid\"" \
+        "Invalid ACL: entry 0 (allow::mask:invalid:): unknown mask name: \"invalid\"" \
         "$msg"
 }
 
@@ -224,7 +224,7 @@ testInvalidOther() {
     msg=$(echo "$input" | $EXACL --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Invalid ACL: entry 0: unknown other name: \"invalid\"" \
+        "Invalid ACL: entry 0 (allow::other:invalid:): unknown other name: \"invalid\"" \
         "$msg"
 }
 